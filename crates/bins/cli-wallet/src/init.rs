@@ -2,7 +2,7 @@ use std::io;
 
 use rusqlite::Connection;
 
-use crate::SEED_PHRASE_MANAGER;
+use crate::seed_phrase_manager;
 
 #[derive(Debug, thiserror::Error)]
 pub enum InitError {
@@ -14,8 +14,9 @@ pub enum InitError {
     IO(#[from] io::Error),
 }
 
-pub fn init(db_conn: &Connection, skip_validation: bool) -> Result<(), InitError> {
-    let seed_phrase = wallet::seed_phrase::create_random()?;
+pub fn init(db_conn: &Connection, skip_validation: bool, words: usize) -> Result<(), InitError> {
+    let word_count = wallet::seed_phrase::WordCount::try_from(words)?;
+    let seed_phrase = wallet::seed_phrase::create_random_with_word_count(word_count)?;
 
     println!(
         "Here is your seed phrase:\n->| {} |<-\nWith it your will be able to recover your funds, should you lose access to this device or destroy your local database.\n Make sure to save it somewhere safe.",
@@ -42,7 +43,7 @@ pub fn init(db_conn: &Connection, skip_validation: bool) -> Result<(), InitError
         }
     }
 
-    wallet::wallet::init(SEED_PHRASE_MANAGER, db_conn, &seed_phrase)?;
+    wallet::wallet::init(seed_phrase_manager(), db_conn, &seed_phrase)?;
 
     Ok(())
 }
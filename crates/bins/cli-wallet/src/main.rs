@@ -7,16 +7,18 @@ use parse_asset_amount::parse_asset_amount;
 use primitive_types::U256;
 use r2d2_sqlite::SqliteConnectionManager;
 use rusqlite::Connection;
-use starknet_types::{Asset, STARKNET_STR, Unit, is_valid_starknet_address};
+use starknet_types::{
+    Asset, ChainId, STARKNET_STR, Unit, UnitPreferences, is_valid_starknet_address,
+};
 use starknet_types_core::felt::Felt;
-use std::{fs, path::PathBuf, str::FromStr};
+use std::{fs, path::PathBuf, str::FromStr, sync::OnceLock};
 use sync::display_paid_melt_quote;
 use tracing_subscriber::EnvFilter;
 use wallet::{
     db::balance::Balance,
     melt::wait_for_payment,
     types::{
-        NodeUrl, ProofState, Wad,
+        NodeUrl, Wad,
         compact_wad::{CompactWad, CompactWads},
     },
 };
@@ -25,8 +27,22 @@ mod init;
 mod sync;
 
 const APP_IDENTIFIER: &str = "paynet-cli-wallet";
-const SEED_PHRASE_MANAGER: wallet::wallet::keyring::SeedPhraseManager =
-    wallet::wallet::keyring::SeedPhraseManager::new(APP_IDENTIFIER);
+/// When set, the seed phrase is stored in an encrypted file next to the database
+/// if the OS keyring cannot be used
+const SEED_PASSPHRASE_ENV_VAR: &str = "CLI_WALLET_SEED_PASSPHRASE";
+
+type SeedPhraseManager = wallet::wallet::fallback::SeedPhraseManager<
+    wallet::wallet::keyring::SeedPhraseManager,
+    wallet::wallet::encrypted_file::SeedPhraseManager,
+>;
+static SEED_PHRASE_MANAGER: OnceLock<SeedPhraseManager> = OnceLock::new();
+
+fn seed_phrase_manager() -> SeedPhraseManager {
+    SEED_PHRASE_MANAGER
+        .get()
+        .expect("seed phrase manager is initialized at startup")
+        .clone()
+}
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -44,6 +60,17 @@ struct Cli {
     /// Used to check the node certificate validity when connecting through `https`.
     #[arg(long, value_hint(ValueHint::FilePath))]
     root_ca_cert_path: Option<PathBuf>,
+    /// The path to a `.pem` client certificate
+    ///
+    /// Presented to nodes that require clients to authenticate during the tls handshake.
+    #[arg(long, value_hint(ValueHint::FilePath), requires = "client_key_path")]
+    client_cert_path: Option<PathBuf>,
+    /// The path to the `.pem` private key of `client_cert_path`
+    #[arg(long, value_hint(ValueHint::FilePath), requires = "client_cert_path")]
+    client_key_path: Option<PathBuf>,
+    /// Units to use instead of the default one of their asset
+    #[arg(long, num_args = 1.., value_parser = Unit::from_str)]
+    prefer_units: Vec<Unit>,
 }
 
 #[derive(Subcommand)]
@@ -87,12 +114,55 @@ enum NodeCommands {
     )]
     #[clap(name = "ls")]
     List {},
+    /// Forget a node
+    #[command(
+        about = "Remove a registered node",
+        long_about = "Remove a registered node and all the data stored about it. Refused if the wallet still holds funds at this node."
+    )]
+    #[clap(name = "rm")]
+    Remove {
+        /// Id of the node to remove
+        #[arg(long)]
+        node_id: u32,
+    },
+}
+
+#[derive(Subcommand)]
+enum MaintenanceCommands {
+    /// Delete old data
+    #[command(
+        about = "Delete old spent proofs and wads",
+        long_about = "Delete spent proofs and settled wads older than the retention window. Proofs still referenced by a kept wad are preserved."
+    )]
+    Prune {
+        /// Retention window, in days
+        #[arg(long, default_value_t = 30)]
+        older_than_days: u64,
+        /// Keep all the wads history
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        keep_wad_history: bool,
+        /// Shrink the db file afterward
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        vacuum: bool,
+    },
 }
 
 #[derive(Subcommand)]
 enum Commands {
     #[command(subcommand)]
     Node(NodeCommands),
+    #[command(subcommand)]
+    Maintenance(MaintenanceCommands),
+    /// List the funds left locked by an interrupted operation
+    #[command(
+        about = "List stuck proofs",
+        long_about = "List the proofs that have been pending or reserved for too long, along with what can be done to recover them"
+    )]
+    Audit {
+        /// How long a proof must have stayed pending or reserved to be reported, in minutes
+        #[arg(long, default_value_t = 60)]
+        older_than_minutes: u64,
+    },
     /// Show balance
     #[command(
         about = "Display your balances accross all nodes",
@@ -122,6 +192,9 @@ enum Commands {
         node_id: u32,
         #[arg(long)]
         to: String,
+        /// Chain the node settles on, used to print explorer links for the withdrawal
+        #[arg(long, value_parser = ChainId::from_str)]
+        chain_id: Option<ChainId>,
     },
 
     /// Send tokens
@@ -145,6 +218,10 @@ enum Commands {
         /// File where to save the token wad        
         #[arg(long, short, value_hint(ValueHint::FilePath))]
         output: Option<PathBuf>,
+        /// Take from all nodes proportionally to their balance,
+        /// instead of emptying them one after the other
+        #[arg(long)]
+        balanced: bool,
     },
     /// Receive a wad of proofs
     #[command(
@@ -173,7 +250,11 @@ enum Commands {
         #[arg(long, short, default_value = "20")]
         limit: u32,
     },
-    Sync,
+    Sync {
+        /// Only sync those nodes, instead of all of them
+        #[arg(long, num_args = 1..)]
+        node_ids: Vec<u32>,
+    },
     #[command(
         about = "Generate a new wallet",
         long_about = "Generate a new wallet. This will create a new wallet with a new seed phrase and private key."
@@ -182,6 +263,9 @@ enum Commands {
         /// Skip asking for confirmation of seed phrase saving
         #[arg(short, long, action = clap::ArgAction::SetTrue)]
         yes: bool,
+        /// Number of words of the generated seed phrase: 12, 15, 18, 21 or 24
+        #[arg(long, default_value_t = 12)]
+        words: usize,
     },
     #[command(
         about = "Restore a wallet",
@@ -218,6 +302,21 @@ impl WadArgs {
     }
 }
 
+fn print_wads_summary(wads: &CompactWads<Unit>) {
+    match wads.summary() {
+        Ok(summary) => {
+            println!("Summary:");
+            for s in summary {
+                println!(
+                    "  {}: {} {} ({} proofs)",
+                    s.node_url, s.total, s.unit, s.proof_count
+                );
+            }
+        }
+        Err(_) => println!("sum of all proofs in the wads overflowed"),
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     tracing_subscriber::fmt()
@@ -225,6 +324,12 @@ async fn main() -> Result<()> {
         .init();
 
     let cli = Cli::parse();
+    let unit_preferences = cli
+        .prefer_units
+        .iter()
+        .fold(UnitPreferences::default(), |preferences, unit| {
+            preferences.prefer(*unit)
+        });
     let db_path = cli
         .db_path
         .or(dirs::data_dir().map(|mut dp| {
@@ -263,6 +368,46 @@ async fn main() -> Result<()> {
             },
         )
         .transpose()?;
+    let opt_tls_client_identity = match (
+        cli.client_cert_path.or_else(|| {
+            std::env::var("TLS_CLIENT_CERT_PATH")
+                .ok()
+                .map(PathBuf::from)
+        }),
+        cli.client_key_path
+            .or_else(|| std::env::var("TLS_CLIENT_KEY_PATH").ok().map(PathBuf::from)),
+    ) {
+        (Some(cert_path), Some(key_path)) => {
+            let read = |path: &PathBuf| {
+                std::fs::read(path).map_err(|e| {
+                    anyhow!("Failed to load TLS client identity file {:?}: {}", path, e)
+                })
+            };
+            Some(tonic::transport::Identity::from_pem(
+                read(&cert_path)?,
+                read(&key_path)?,
+            ))
+        }
+        (None, None) => None,
+        _ => {
+            return Err(anyhow!(
+                "the TLS client certificate and key paths must be set together"
+            ));
+        }
+    };
+
+    let encrypted_file_fallback = std::env::var(SEED_PASSPHRASE_ENV_VAR)
+        .ok()
+        .map(|passphrase| {
+            wallet::wallet::encrypted_file::SeedPhraseManager::new(
+                db_path.with_extension("seed"),
+                passphrase,
+            )
+        });
+    let _ = SEED_PHRASE_MANAGER.set(wallet::wallet::fallback::SeedPhraseManager::new(
+        wallet::wallet::keyring::SeedPhraseManager::new(APP_IDENTIFIER),
+        encrypted_file_fallback,
+    ));
 
     let manager = SqliteConnectionManager::file(db_path);
     let pool = r2d2::Pool::new(manager)?;
@@ -290,7 +435,12 @@ async fn main() -> Result<()> {
     match cli.command {
         Commands::Node(NodeCommands::Add { node_url, restore }) => {
             let node_url = wallet::types::NodeUrl::from_str(&node_url)?;
-            let mut node_client = wallet::connect_to_node(&node_url, opt_tls_root_ca_cert).await?;
+            let mut node_client = wallet::connect_to_node_with_client_identity(
+                &node_url,
+                opt_tls_root_ca_cert,
+                opt_tls_client_identity,
+            )
+            .await?;
 
             let tx = db_conn.transaction()?;
             let node_id = wallet::node::register(pool.clone(), &mut node_client, &node_url).await?;
@@ -309,7 +459,7 @@ async fn main() -> Result<()> {
             };
             if should_restore {
                 println!("Restoring proofs");
-                wallet::node::restore(SEED_PHRASE_MANAGER, pool, node_id, node_client).await?;
+                wallet::node::restore(seed_phrase_manager(), pool, node_id, node_client).await?;
                 println!("Restoring done.");
 
                 let balances = wallet::db::balance::get_for_node(&db_conn, node_id)?;
@@ -327,6 +477,11 @@ async fn main() -> Result<()> {
                 println!("{} {}", id, url);
             }
         }
+        Commands::Node(NodeCommands::Remove { node_id }) => {
+            wallet::db::node::delete(&mut db_conn, node_id)?;
+
+            println!("Successfully removed node with id `{}`", node_id);
+        }
         Commands::Balance { node_id } => match node_id {
             Some(node_id) => {
                 let balances = wallet::db::balance::get_for_node(&db_conn, node_id)?;
@@ -356,7 +511,7 @@ async fn main() -> Result<()> {
             let (mut node_client, node_url) = connect_to_node(&mut db_conn, node_id).await?;
             println!("Requesting {} to mint {} {}", &node_url, amount, asset);
 
-            let unit = asset.find_best_unit();
+            let unit = unit_preferences.unit_for(asset);
             let amount = parse_asset_amount(&amount, asset, unit)?;
 
             let mint_quote_response = wallet::mint::create_quote(
@@ -409,6 +564,8 @@ async fn main() -> Result<()> {
                 &mut node_client,
                 STARKNET_STR.to_string(),
                 mint_quote_response.quote.clone(),
+                mint_quote_response.expiry,
+                wallet::mint::PollBackoff::default(),
             )
             .await?
             {
@@ -419,7 +576,7 @@ async fn main() -> Result<()> {
             }
 
             wallet::mint::redeem_quote(
-                SEED_PHRASE_MANAGER,
+                seed_phrase_manager(),
                 pool.clone(),
                 &mut node_client,
                 STARKNET_STR.to_string(),
@@ -427,6 +584,7 @@ async fn main() -> Result<()> {
                 node_id,
                 unit.as_str(),
                 amount,
+                None,
             )
             .await?;
 
@@ -438,12 +596,13 @@ async fn main() -> Result<()> {
             asset,
             node_id,
             to,
+            chain_id,
         } => {
             let (mut node_client, _node_url) = connect_to_node(&mut db_conn, node_id).await?;
 
             println!("Melting {} {} tokens", amount, asset);
 
-            let unit = asset.find_best_unit();
+            let unit = unit_preferences.unit_for(asset);
             let amount = parse_asset_amount(&amount, asset, unit)?;
             let on_chain_amount = unit.convert_amount_into_u256(amount);
 
@@ -471,9 +630,16 @@ async fn main() -> Result<()> {
             )
             .await?;
             println!("Melt quote created!");
+            if let Some(chain_id) = &chain_id {
+                wallet::db::melt_quote::set_chain_id(
+                    &db_conn,
+                    &melt_quote_response.quote,
+                    chain_id.as_str(),
+                )?;
+            }
 
             let melt_response = wallet::melt::pay_quote(
-                SEED_PHRASE_MANAGER,
+                seed_phrase_manager(),
                 pool.clone(),
                 &mut node_client,
                 node_id,
@@ -486,7 +652,11 @@ async fn main() -> Result<()> {
             println!("Melt submited!");
 
             if melt_response.state == node_client::MeltQuoteState::MlqsPaid as i32 {
-                display_paid_melt_quote(melt_quote_response.quote, melt_response.transfer_ids);
+                display_paid_melt_quote(
+                    melt_quote_response.quote,
+                    melt_response.transfer_ids,
+                    chain_id.as_ref(),
+                );
             } else {
                 match wait_for_payment(
                     pool.clone(),
@@ -496,9 +666,11 @@ async fn main() -> Result<()> {
                 )
                 .await?
                 {
-                    Some(transfer_ids) => {
-                        display_paid_melt_quote(melt_quote_response.quote, transfer_ids)
-                    }
+                    Some(transfer_ids) => display_paid_melt_quote(
+                        melt_quote_response.quote,
+                        transfer_ids,
+                        chain_id.as_ref(),
+                    ),
                     None => println!("Melt quote {} has expired", melt_quote_response.quote),
                 }
             }
@@ -509,6 +681,7 @@ async fn main() -> Result<()> {
             node_ids,
             memo,
             output,
+            balanced,
         } => {
             let output = output
                 .map(|output_path| {
@@ -530,28 +703,38 @@ async fn main() -> Result<()> {
                 })
                 .transpose()?;
 
-            let amount = amount
-                .checked_mul(asset.scale_factor())
+            let amount = asset
+                .checked_scale(amount)
                 .ok_or(anyhow!("amount greater than the maximum for this asset"))?;
-            let (total_amount, unit, _remainder) = asset.convert_to_amount_and_unit(amount)?;
+            let (total_amount, unit, _remainder) =
+                asset.convert_to_amount_and_unit(amount, &unit_preferences)?;
 
-            let node_ids_with_amount_to_use =
-                wallet::send::plan_spending(&db_conn, total_amount, unit, &node_ids)?;
+            let strategy = if balanced {
+                wallet::send::SpendingStrategy::Balanced
+            } else {
+                wallet::send::SpendingStrategy::DrainFirst
+            };
+            let spending_plan =
+                wallet::send::plan_spending(&db_conn, total_amount, unit, &node_ids, strategy)?;
 
-            let mut node_and_proofs = Vec::with_capacity(node_ids_with_amount_to_use.len());
-            for (node_id, amount_to_use) in node_ids_with_amount_to_use {
+            let mut node_and_proofs = Vec::with_capacity(spending_plan.len());
+            for wallet::send::NodeSpending {
+                node_id,
+                amount: amount_to_use,
+                ..
+            } in spending_plan
+            {
                 let (mut node_client, node_url) = connect_to_node(&mut db_conn, node_id).await?;
 
                 let proofs_ids = wallet::fetch_inputs_ids_from_db_or_node(
-                    SEED_PHRASE_MANAGER,
+                    seed_phrase_manager(),
                     pool.clone(),
                     &mut node_client,
                     node_id,
                     amount_to_use,
                     unit.as_str(),
                 )
-                .await?
-                .ok_or(anyhow!("not enough funds"))?;
+                .await?;
 
                 println!(
                     "Spending {} {} from node {} ({})",
@@ -560,47 +743,9 @@ async fn main() -> Result<()> {
                 node_and_proofs.push((node_url, proofs_ids));
             }
 
-            let mut wads = Vec::with_capacity(node_and_proofs.len());
-            let mut should_revert = None;
-            for (i, (node_url, proofs_ids)) in node_and_proofs.iter().enumerate() {
-                let proofs = match wallet::load_tokens_from_db(&db_conn, proofs_ids) {
-                    Ok(p) => p,
-                    Err(e) => {
-                        println!(
-                            "Failed to load the following proofs for node {}: {}\nProof ids: {:?}\nReverting now.",
-                            node_url, e, proofs_ids
-                        );
-                        should_revert = Some(i);
-                        break;
-                    }
-                };
-
-                let wad =
-                    wallet::wad::create_from_parts(node_url.clone(), unit, memo.clone(), proofs);
-                wads.push(wad);
-            }
-            if let Some(max_reached) = should_revert {
-                node_and_proofs
-                    .iter()
-                    .map(|(_, pids)| pids)
-                    .take(max_reached)
-                    .for_each(|proofs_id| {
-                        if let Err(e) = wallet::db::proof::set_proofs_to_state(
-                            &db_conn,
-                            proofs_id,
-                            ProofState::Unspent,
-                        ) {
-                            println!(
-                                "failed to revet state of the following proofs: {}\nProofs ids: {:?}",
-                                e, proofs_id
-                            );
-                        }
-                    });
-
-                return Err(anyhow!("wad creation reverted"));
-            };
-
-            let wads = CompactWads::new(wads);
+            let wads = wallet::send::create_wads_atomic(&mut db_conn, unit, &node_and_proofs, memo)
+                .map_err(|e| anyhow!("wad creation reverted: {e}"))?;
+            print_wads_summary(&wads);
 
             match output {
                 Some((output_path, path_str)) => {
@@ -624,10 +769,15 @@ async fn main() -> Result<()> {
             let wads = args.read_wads()?;
 
             for wad in wads {
-                let mut node_client =
-                    wallet::connect_to_node(&wad.node_url, opt_tls_root_ca_cert.clone()).await?;
+                let mut node_client = wallet::connect_to_node_with_client_identity(
+                    &wad.node_url,
+                    opt_tls_root_ca_cert.clone(),
+                    opt_tls_client_identity.clone(),
+                )
+                .await?;
                 let node_id =
-                    wallet::node::register(pool.clone(), &mut node_client, &wad.node_url).await?;
+                    wallet::node::get_or_register(pool.clone(), &mut node_client, &wad.node_url)
+                        .await?;
                 let CompactWad {
                     node_url,
                     unit,
@@ -636,7 +786,7 @@ async fn main() -> Result<()> {
                 } = wad;
 
                 match wallet::receive_wad(
-                    SEED_PHRASE_MANAGER,
+                    seed_phrase_manager(),
                     pool.clone(),
                     &mut node_client,
                     node_id,
@@ -672,9 +822,9 @@ async fn main() -> Result<()> {
                 opt_wad_string,
                 opt_wad_file_path,
             };
-            let wads = args.read_wads()?;
+            let wads = CompactWads::new(args.read_wads()?);
 
-            for wad in wads {
+            for wad in wads.0.iter() {
                 let regular_wad = Wad {
                     node_url: wad.node_url.clone(),
                     proofs: wad.proofs(),
@@ -694,19 +844,77 @@ async fn main() -> Result<()> {
                 println!("\nDetailed Contents:");
                 println!("{}", serde_json::to_string_pretty(&regular_wad)?);
             }
+
+            if wads.0.len() > 1 {
+                print_wads_summary(&wads);
+            }
         }
-        Commands::Sync => {
-            sync::sync_all_pending_operations(pool).await?;
+        Commands::Sync { node_ids } => {
+            let node_ids = (!node_ids.is_empty()).then_some(node_ids.as_slice());
+            let report = sync::sync_all_pending_operations(pool, node_ids).await?;
+            println!("{report}");
         }
-        Commands::Init { yes } => {
-            init::init(&db_conn, yes)?;
+        Commands::Init { yes, words } => {
+            init::init(&db_conn, yes, words)?;
             println!("Wallet saved!");
         }
         Commands::Restore { seed_phrase } => {
             let seed_phrase = wallet::seed_phrase::create_from_str(&seed_phrase)?;
-            wallet::wallet::restore(SEED_PHRASE_MANAGER, &db_conn, seed_phrase)?;
+            wallet::wallet::restore(seed_phrase_manager(), &db_conn, seed_phrase)?;
             println!("Wallet saved!");
         }
+        Commands::Maintenance(MaintenanceCommands::Prune {
+            older_than_days,
+            keep_wad_history,
+            vacuum,
+        }) => {
+            let report = wallet::db::maintenance::prune(
+                &mut db_conn,
+                wallet::db::maintenance::PruneOptions {
+                    spent_proofs_older_than: std::time::Duration::from_secs(
+                        older_than_days * 24 * 60 * 60,
+                    ),
+                    keep_wad_history,
+                    vacuum,
+                },
+            )?;
+            println!(
+                "Deleted {} spent proofs and {} wads",
+                report.deleted_proofs, report.deleted_wads
+            );
+        }
+        Commands::Audit { older_than_minutes } => {
+            let stuck_proofs = wallet::audit::find_stuck_proofs(
+                &db_conn,
+                std::time::Duration::from_secs(older_than_minutes * 60),
+            )?;
+            if stuck_proofs.is_empty() {
+                println!("No stuck proofs found.");
+                return Ok(());
+            }
+
+            for stuck_proof in stuck_proofs {
+                let remediation = match stuck_proof.remediation() {
+                    wallet::audit::Remediation::Reconcile => format!(
+                        "run `sync --node-ids {}` to reconcile it with the node",
+                        stuck_proof.node_id
+                    ),
+                    wallet::audit::Remediation::Release => format!(
+                        "not part of any pending wad, run `sync --node-ids {}` to find out if it was spent before releasing it",
+                        stuck_proof.node_id
+                    ),
+                };
+                println!(
+                    "{} (node {}): amount {}, {:?} for {} minutes, {}",
+                    stuck_proof.y,
+                    stuck_proof.node_id,
+                    stuck_proof.amount,
+                    stuck_proof.state,
+                    stuck_proof.age.as_secs() / 60,
+                    remediation
+                );
+            }
+        }
         Commands::History { limit } => {
             let db_conn = pool.get()?;
 
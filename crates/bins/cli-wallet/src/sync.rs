@@ -1,58 +1,140 @@
+use std::{fmt::Display, str::FromStr};
+
 use anyhow::{Result, anyhow};
 use node_client::NodeClient;
 use nuts::nut04::MintQuoteState;
 use nuts::nut05::MeltQuoteState;
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
+use starknet_types::{ChainId, STARKNET_STR, explorer_tx_url};
 use tonic::transport::Channel;
 use wallet::db::melt_quote::PendingMeltQuote;
 use wallet::db::mint_quote::PendingMintQuote;
+use wallet::sync::ProofsSyncResult;
 use wallet::types::NodeUrl;
 
-use crate::SEED_PHRASE_MANAGER;
+use crate::seed_phrase_manager;
+
+/// What a sync changed in the wallet
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    pub nodes: Vec<NodeSyncReport>,
+    pub wads_updated: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct NodeSyncReport {
+    pub node_id: u32,
+    pub mint_quotes_redeemed: usize,
+    pub mint_quotes_expired: usize,
+    pub melt_quotes_paid: usize,
+    pub melt_quotes_expired: usize,
+    pub proofs: ProofsSyncResult,
+}
+
+impl Display for SyncReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for node in &self.nodes {
+            writeln!(
+                f,
+                "node {}: {} mint quotes redeemed, {} expired; {} melt quotes paid, {} expired; {} proofs marked spent, {} released",
+                node.node_id,
+                node.mint_quotes_redeemed,
+                node.mint_quotes_expired,
+                node.melt_quotes_paid,
+                node.melt_quotes_expired,
+                node.proofs.spent,
+                node.proofs.released,
+            )?;
+        }
+        write!(f, "{} wads updated", self.wads_updated)
+    }
+}
 
-const STARKNET_STR: &str = "starknet";
+/// Final state of a quote observed during a sync
+enum QuoteOutcome {
+    Paid,
+    Expired,
+    Unsettled,
+}
 
-pub async fn sync_all_pending_operations(pool: Pool<SqliteConnectionManager>) -> Result<()> {
-    let db_conn = pool.get()?;
-    let (pending_mint_quotes, pending_melt_quotes) = {
-        let mint_quotes = wallet::db::mint_quote::get_pendings(&db_conn)?;
-        let melt_quotes = wallet::db::melt_quote::get_pendings(&db_conn)?;
-        (mint_quotes, melt_quotes)
+/// Bring every node the wallet knows about up to date
+///
+/// For each node, paid mint quotes are redeemed, melt quotes are finalized
+/// and the Pending or Reserved proofs are reconciled with the node's view of them.
+/// Nodes with nothing to reconcile are not contacted, neither are those missing from `node_ids` when set.
+pub async fn sync_all_pending_operations(
+    pool: Pool<SqliteConnectionManager>,
+    node_ids: Option<&[u32]>,
+) -> Result<SyncReport> {
+    let (nodes, mut pending_mint_quotes, mut pending_melt_quotes) = {
+        let db_conn = pool.get()?;
+        (
+            wallet::sync::select_nodes(&db_conn, node_ids)?,
+            wallet::db::mint_quote::get_pendings(&db_conn)?,
+            wallet::db::melt_quote::get_pendings(&db_conn)?,
+        )
     };
 
-    for (node_id, pending_quotes) in pending_mint_quotes {
-        let node_url = wallet::db::node::get_url_by_id(&db_conn, node_id)?
-            .ok_or(anyhow!("unknown node id: {}", node_id))?;
-        println!("Syncing node {} ({}) mint quotes", node_id, node_url);
+    let mut report = SyncReport::default();
+    for (node_id, node_url) in nodes {
+        let mint_quotes = take_node_quotes(&mut pending_mint_quotes, node_id);
+        let melt_quotes = take_node_quotes(&mut pending_melt_quotes, node_id);
+        let has_unsettled_proofs = {
+            let db_conn = pool.get()?;
+            !wallet::db::proof::get_unsettled_proofs(&db_conn, node_id)?.is_empty()
+        };
+        if mint_quotes.is_empty() && melt_quotes.is_empty() && !has_unsettled_proofs {
+            continue;
+        }
 
+        println!("Syncing node {} ({})", node_id, node_url);
         let (mut node_client, _) = connect_to_node(pool.clone(), node_id).await?;
-        sync_mint_quotes(&pool, &mut node_client, node_id, &pending_quotes).await?;
-    }
-    for (node_id, pending_quotes) in pending_melt_quotes {
-        let node_url = wallet::db::node::get_url_by_id(&db_conn, node_id)?
-            .ok_or(anyhow!("unknown node id: {}", node_id))?;
-        println!("Syncing node {} ({}) melt quotes", node_id, node_url);
+        let mut node_report = NodeSyncReport {
+            node_id,
+            ..Default::default()
+        };
 
-        let (mut node_client, _) = connect_to_node(pool.clone(), node_id).await?;
-        sync_melt_quotes(&pool, &mut node_client, &pending_quotes).await?;
+        sync_mint_quotes(
+            &pool,
+            &mut node_client,
+            node_id,
+            &mint_quotes,
+            &mut node_report,
+        )
+        .await?;
+        sync_melt_quotes(&pool, &mut node_client, &melt_quotes, &mut node_report).await?;
+        node_report.proofs =
+            wallet::sync::unsettled_proofs(pool.clone(), &mut node_client, node_id).await?;
+
+        report.nodes.push(node_report);
     }
 
     // Sync pending WADs using the lib wallet function i
     println!("Syncing pending WADs");
-    let wad_results = wallet::sync::pending_wads(pool, None).await?;
+    let wad_results = wallet::sync::pending_wads(pool, None, node_ids).await?;
 
     for result in wad_results {
         match result.result {
             // No status change
             Ok(None) => {}
-            Ok(Some(status)) => println!("WAD {} updated to status: {:?}", result.wad_id, status),
+            Ok(Some(status)) => {
+                report.wads_updated += 1;
+                println!("WAD {} updated to status: {:?}", result.wad_id, status)
+            }
             Err(e) => eprintln!("Failed to sync WAD {}: {}", result.wad_id, e),
         }
     }
 
-    println!("Sync completed for all nodes");
-    Ok(())
+    println!("Sync completed");
+    Ok(report)
+}
+
+fn take_node_quotes<Q>(quotes_per_node: &mut Vec<(u32, Vec<Q>)>, node_id: u32) -> Vec<Q> {
+    match quotes_per_node.iter().position(|(id, _)| *id == node_id) {
+        Some(p) => quotes_per_node.swap_remove(p).1,
+        None => Vec::new(),
+    }
 }
 
 async fn sync_mint_quotes(
@@ -60,6 +142,7 @@ async fn sync_mint_quotes(
     node_client: &mut NodeClient<Channel>,
     node_id: u32,
     pending_mint_quotes: &[PendingMintQuote],
+    report: &mut NodeSyncReport,
 ) -> Result<()> {
     for pending_mint_quote in pending_mint_quotes {
         let new_state = {
@@ -74,6 +157,7 @@ async fn sync_mint_quotes(
                 Some(new_state) => new_state,
                 None => {
                     println!("Mint quote {} has expired", pending_mint_quote.id);
+                    report.mint_quotes_expired += 1;
                     continue;
                 }
             }
@@ -87,7 +171,7 @@ async fn sync_mint_quotes(
 
             // Redeem the quote
             if let Err(e) = wallet::mint::redeem_quote(
-                SEED_PHRASE_MANAGER,
+                seed_phrase_manager(),
                 pool.clone(),
                 node_client,
                 STARKNET_STR.to_string(),
@@ -95,6 +179,7 @@ async fn sync_mint_quotes(
                 node_id,
                 &pending_mint_quote.unit,
                 pending_mint_quote.amount,
+                None,
             )
             .await
             {
@@ -104,6 +189,7 @@ async fn sync_mint_quotes(
                 );
             } else {
                 println!("Successfully redeemed mint quote {}", pending_mint_quote.id);
+                report.mint_quotes_redeemed += 1;
             }
         }
     }
@@ -115,15 +201,21 @@ async fn sync_melt_quotes(
     pool: &Pool<SqliteConnectionManager>,
     node_client: &mut NodeClient<Channel>,
     pending_melt_quotes: &[PendingMeltQuote],
+    report: &mut NodeSyncReport,
 ) -> Result<()> {
     for pending_melt_quote in pending_melt_quotes {
-        sync_melt_quote(
+        match sync_melt_quote(
             pool.clone(),
             node_client,
             STARKNET_STR.to_string(),
             pending_melt_quote.id.clone(),
         )
-        .await?;
+        .await?
+        {
+            QuoteOutcome::Paid => report.melt_quotes_paid += 1,
+            QuoteOutcome::Expired => report.melt_quotes_expired += 1,
+            QuoteOutcome::Unsettled => {}
+        }
     }
 
     Ok(())
@@ -134,30 +226,40 @@ async fn sync_melt_quote(
     node_client: &mut NodeClient<Channel>,
     method: String,
     quote_id: String,
-) -> Result<bool> {
-    let melt_quote = wallet::sync::melt_quote(pool, node_client, method, quote_id.clone()).await?;
+) -> Result<QuoteOutcome> {
+    let melt_quote =
+        wallet::sync::melt_quote(pool.clone(), node_client, method, quote_id.clone()).await?;
 
-    let is_final = match melt_quote {
+    let outcome = match melt_quote {
         Some((MeltQuoteState::Paid, tx_ids)) => {
-            display_paid_melt_quote(quote_id, tx_ids);
-            true
+            let opt_chain_id = {
+                let db_conn = pool.get()?;
+                wallet::db::melt_quote::get_chain_id(&db_conn, &quote_id)?
+            }
+            .and_then(|s| ChainId::from_str(&s).ok());
+            display_paid_melt_quote(quote_id, tx_ids, opt_chain_id.as_ref());
+            QuoteOutcome::Paid
         }
         None => {
             println!("Melt quote {} has expired", quote_id);
-            true
+            QuoteOutcome::Expired
         }
-        _ => false,
+        _ => QuoteOutcome::Unsettled,
     };
 
-    Ok(is_final)
+    Ok(outcome)
 }
 
-pub fn display_paid_melt_quote(quote_id: String, tx_ids: Vec<String>) {
+pub fn display_paid_melt_quote(
+    quote_id: String,
+    tx_ids: Vec<String>,
+    opt_chain_id: Option<&ChainId>,
+) {
     println!("Melt quote {} completed successfully", quote_id);
     if !tx_ids.is_empty() {
         println!(
             "tx hashes: {}",
-            format_melt_transfers_id_into_term_message(tx_ids)
+            format_melt_transfers_id_into_term_message(tx_ids, opt_chain_id)
         );
     }
 }
@@ -179,17 +281,27 @@ async fn connect_to_node(
     Ok((node_client, node_url))
 }
 
-pub fn format_melt_transfers_id_into_term_message(transfer_ids: Vec<String>) -> String {
+/// Transfers are shown as explorer links when the chain is known to have one, raw hashes otherwise
+pub fn format_melt_transfers_id_into_term_message(
+    transfer_ids: Vec<String>,
+    opt_chain_id: Option<&ChainId>,
+) -> String {
     let mut string_to_print = "Melt done. Withdrawal settled with tx".to_string();
     if transfer_ids.len() != 1 {
         string_to_print.push('s');
     }
     string_to_print.push_str(": ");
-    let mut iterator = transfer_ids.into_iter();
+    let display = |tx_hash: String| {
+        opt_chain_id
+            .and_then(|chain_id| explorer_tx_url(chain_id, &tx_hash))
+            .map(|url| url.to_string())
+            .unwrap_or(tx_hash)
+    };
+    let mut iterator = transfer_ids.into_iter().map(display);
     string_to_print.push_str(&iterator.next().unwrap());
-    for tx_hash in iterator {
+    for tx in iterator {
         string_to_print.push_str(", ");
-        string_to_print.push_str(&tx_hash);
+        string_to_print.push_str(&tx);
     }
 
     string_to_print
@@ -3,12 +3,11 @@ use std::sync::{Arc, atomic::AtomicU64};
 use nuts::{QuoteTTLConfig, nut06::NutsSettings};
 use starknet_types::Unit;
 use tokio::sync::RwLock;
-use tonic::transport::Channel;
 
 use crate::methods::Method;
 
 pub type NutsSettingsState = Arc<RwLock<NutsSettings<Method, Unit, serde_json::Value>>>;
-pub type SignerClient = signer::SignerClient<tower_otel::trace::Grpc<Channel>>;
+pub use crate::signer_client::SignerClient;
 
 /// Quote Time To Live config
 ///
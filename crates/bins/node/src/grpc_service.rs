@@ -1,5 +1,6 @@
+pub mod rate_limit;
+
 use crate::{
-    keyset_cache::CachedKeysetInfo,
     liquidity_sources::LiquiditySources,
     response_cache::{CachedResponse, InMemResponseCache, ResponseCache},
 };
@@ -19,10 +20,11 @@ use nuts::{
     nut06::{ContactInfo, NodeInfo, NodeVersion, NutsSettings},
     nut19::{CacheResponseKey, Route},
 };
+use open_telemetry_tracing::grpc_context::follow_remote_parent;
 use signer::GetRootPubKeyRequest;
 use sqlx::PgPool;
 use starknet_types::Unit;
-use std::{str::FromStr, sync::Arc};
+use std::{str::FromStr, sync::Arc, time::Duration};
 use thiserror::Error;
 use tokio::sync::RwLock;
 use tonic::{Request, Response, Status};
@@ -33,6 +35,7 @@ use crate::{
     app_state::{NutsSettingsState, QuoteTTLConfigState, SignerClient},
     keyset_cache::KeysetCache,
     methods::Method,
+    rpc_metrics::observe_rpc,
 };
 
 #[derive(Debug, Clone)]
@@ -65,6 +68,7 @@ impl GrpcState {
         nuts_settings: NutsSettings<Method, Unit, serde_json::Value>,
         quote_ttl: QuoteTTLConfig,
         liquidity_sources: LiquiditySources<Unit>,
+        response_cache_ttl: Option<Duration>,
     ) -> Self {
         Self {
             pg_pool,
@@ -73,7 +77,7 @@ impl GrpcState {
             quote_ttl: Arc::new(quote_ttl.into()),
             signer: signer_client,
             liquidity_sources,
-            response_cache: Arc::new(InMemResponseCache::new(None)),
+            response_cache: Arc::new(InMemResponseCache::new(response_cache_ttl)),
         }
     }
 
@@ -98,12 +102,10 @@ impl GrpcState {
             let response = response.into_inner();
             let keyset_id = KeysetId::from_bytes(&response.keyset_id)?;
 
+            // The keyset infos are not cached here, the row may already exist with
+            // an operator set fee. They get loaded from db on first use instead.
             insert_keysets_query_builder.add_row(keyset_id, unit, max_order, index);
 
-            self.keyset_cache
-                .insert_info(keyset_id, CachedKeysetInfo::new(true, unit, max_order))
-                .await;
-
             let keys = response
                 .keys
                 .into_iter()
@@ -185,10 +187,11 @@ impl Node for GrpcState {
         let keysets = db_node::keyset::get_keysets(&mut conn)
             .await
             .map_err(|e| Status::internal(e.to_string()))?
-            .map(|(id, unit, active)| Keyset {
+            .map(|(id, unit, active, input_fee_ppk)| Keyset {
                 id: id.to_vec(),
                 unit,
                 active,
+                input_fee_ppk: input_fee_ppk.into(),
             })
             .collect();
 
@@ -224,77 +227,82 @@ impl Node for GrpcState {
         &self,
         swap_request: Request<SwapRequest>,
     ) -> Result<Response<SwapResponse>, Status> {
-        let swap_request = swap_request.into_inner();
-
-        let cache_key = (Route::Swap, hash_swap_request(&swap_request));
-        // Try to get from cache first
-        if let Some(CachedResponse::Swap(swap_response)) = self.get_cached_response(&cache_key) {
-            return Ok(Response::new(swap_response));
-        }
+        follow_remote_parent(swap_request.metadata());
+        observe_rpc("swap", async {
+            let swap_request = swap_request.into_inner();
+
+            let cache_key = (Route::Swap, hash_swap_request(&swap_request));
+            // Try to get from cache first
+            if let Some(CachedResponse::Swap(swap_response)) = self.get_cached_response(&cache_key)
+            {
+                return Ok(Response::new(swap_response));
+            }
 
-        if swap_request.inputs.len() > 64 {
-            return Err(Status::invalid_argument(
-                "Too many inputs: maximum allowed is 64",
-            ));
-        }
-        if swap_request.outputs.len() > 64 {
-            return Err(Status::invalid_argument(
-                "Too many outputs: maximum allowed is 64",
-            ));
-        }
+            if swap_request.inputs.len() > 64 {
+                return Err(Status::invalid_argument(
+                    "Too many inputs: maximum allowed is 64",
+                ));
+            }
+            if swap_request.outputs.len() > 64 {
+                return Err(Status::invalid_argument(
+                    "Too many outputs: maximum allowed is 64",
+                ));
+            }
 
-        if swap_request.inputs.is_empty() {
-            return Err(Status::invalid_argument("Inputs cannot be empty"));
-        }
-        if swap_request.outputs.is_empty() {
-            return Err(Status::invalid_argument("Outputs cannot be empty"));
-        }
+            if swap_request.inputs.is_empty() {
+                return Err(Status::invalid_argument("Inputs cannot be empty"));
+            }
+            if swap_request.outputs.is_empty() {
+                return Err(Status::invalid_argument("Outputs cannot be empty"));
+            }
 
-        let inputs = swap_request
-            .inputs
-            .into_iter()
-            .map(|p| -> Result<Proof, ParseGrpcError> {
-                Ok(Proof {
-                    amount: p.amount.into(),
-                    keyset_id: KeysetId::from_bytes(&p.keyset_id)
-                        .map_err(ParseGrpcError::KeysetId)?,
-                    secret: Secret::new(p.secret).map_err(ParseGrpcError::Secret)?,
-                    c: PublicKey::from_slice(&p.unblind_signature)
-                        .map_err(ParseGrpcError::PublicKey)?,
-                })
-            })
-            .collect::<Result<Vec<_>, _>>()?;
-        let outputs = swap_request
-            .outputs
-            .into_iter()
-            .map(|bm| -> Result<BlindedMessage, ParseGrpcError> {
-                Ok(BlindedMessage {
-                    amount: bm.amount.into(),
-                    keyset_id: KeysetId::from_bytes(&bm.keyset_id)
-                        .map_err(ParseGrpcError::KeysetId)?,
-                    blinded_secret: PublicKey::from_slice(&bm.blinded_secret)
-                        .map_err(ParseGrpcError::PublicKey)?,
+            let inputs = swap_request
+                .inputs
+                .into_iter()
+                .map(|p| -> Result<Proof, ParseGrpcError> {
+                    Ok(Proof {
+                        amount: p.amount.into(),
+                        keyset_id: KeysetId::from_bytes(&p.keyset_id)
+                            .map_err(ParseGrpcError::KeysetId)?,
+                        secret: Secret::new(p.secret).map_err(ParseGrpcError::Secret)?,
+                        c: PublicKey::from_slice(&p.unblind_signature)
+                            .map_err(ParseGrpcError::PublicKey)?,
+                    })
                 })
-            })
-            .collect::<Result<Vec<_>, _>>()?;
-
-        let promises = self.inner_swap(&inputs, &outputs).await?;
-
-        let swap_response = SwapResponse {
-            signatures: promises
-                .iter()
-                .map(|p| node::BlindSignature {
-                    amount: p.amount.into(),
-                    keyset_id: p.keyset_id.to_bytes().to_vec(),
-                    blind_signature: p.c.to_bytes().to_vec(),
+                .collect::<Result<Vec<_>, _>>()?;
+            let outputs = swap_request
+                .outputs
+                .into_iter()
+                .map(|bm| -> Result<BlindedMessage, ParseGrpcError> {
+                    Ok(BlindedMessage {
+                        amount: bm.amount.into(),
+                        keyset_id: KeysetId::from_bytes(&bm.keyset_id)
+                            .map_err(ParseGrpcError::KeysetId)?,
+                        blinded_secret: PublicKey::from_slice(&bm.blinded_secret)
+                            .map_err(ParseGrpcError::PublicKey)?,
+                    })
                 })
-                .collect(),
-        };
-
-        // Store in cache
-        self.cache_response(cache_key, CachedResponse::Swap(swap_response.clone()))?;
+                .collect::<Result<Vec<_>, _>>()?;
 
-        Ok(Response::new(swap_response))
+            let promises = self.inner_swap(&inputs, &outputs).await?;
+
+            let swap_response = SwapResponse {
+                signatures: promises
+                    .iter()
+                    .map(|p| node::BlindSignature {
+                        amount: p.amount.into(),
+                        keyset_id: p.keyset_id.to_bytes().to_vec(),
+                        blind_signature: p.c.to_bytes().to_vec(),
+                    })
+                    .collect(),
+            };
+
+            // Store in cache
+            self.cache_response(cache_key, CachedResponse::Swap(swap_response.clone()))?;
+
+            Ok(Response::new(swap_response))
+        })
+        .await
     }
 
     #[instrument]
@@ -326,60 +334,65 @@ impl Node for GrpcState {
         &self,
         mint_request: Request<MintRequest>,
     ) -> Result<Response<MintResponse>, Status> {
-        let mint_request = mint_request.into_inner();
-
-        let cache_key = (Route::Mint, hash_mint_request(&mint_request));
-        // Try to get from cache first
-        if let Some(CachedResponse::Mint(mint_response)) = self.get_cached_response(&cache_key) {
-            return Ok(Response::new(mint_response));
-        }
+        follow_remote_parent(mint_request.metadata());
+        observe_rpc("mint", async {
+            let mint_request = mint_request.into_inner();
+
+            let cache_key = (Route::Mint, hash_mint_request(&mint_request));
+            // Try to get from cache first
+            if let Some(CachedResponse::Mint(mint_response)) = self.get_cached_response(&cache_key)
+            {
+                return Ok(Response::new(mint_response));
+            }
 
-        if mint_request.outputs.len() > 64 {
-            return Err(Status::invalid_argument(
-                "Too many outputs: maximum allowed is 64",
-            ));
-        }
+            if mint_request.outputs.len() > 64 {
+                return Err(Status::invalid_argument(
+                    "Too many outputs: maximum allowed is 64",
+                ));
+            }
 
-        let method = Method::from_str(&mint_request.method).map_err(ParseGrpcError::Method)?;
+            let method = Method::from_str(&mint_request.method).map_err(ParseGrpcError::Method)?;
 
-        if mint_request.outputs.is_empty() {
-            return Err(Status::invalid_argument("Outputs cannot be empty"));
-        }
+            if mint_request.outputs.is_empty() {
+                return Err(Status::invalid_argument("Outputs cannot be empty"));
+            }
 
-        let quote_id = Uuid::from_str(&mint_request.quote).map_err(ParseGrpcError::Uuid)?;
+            let quote_id = Uuid::from_str(&mint_request.quote).map_err(ParseGrpcError::Uuid)?;
 
-        let outputs = mint_request
-            .outputs
-            .into_iter()
-            .map(|bm| -> Result<BlindedMessage, ParseGrpcError> {
-                Ok(BlindedMessage {
-                    amount: bm.amount.into(),
-                    keyset_id: KeysetId::from_bytes(&bm.keyset_id)
-                        .map_err(ParseGrpcError::KeysetId)?,
-                    blinded_secret: PublicKey::from_slice(&bm.blinded_secret)
-                        .map_err(ParseGrpcError::PublicKey)?,
+            let outputs = mint_request
+                .outputs
+                .into_iter()
+                .map(|bm| -> Result<BlindedMessage, ParseGrpcError> {
+                    Ok(BlindedMessage {
+                        amount: bm.amount.into(),
+                        keyset_id: KeysetId::from_bytes(&bm.keyset_id)
+                            .map_err(ParseGrpcError::KeysetId)?,
+                        blinded_secret: PublicKey::from_slice(&bm.blinded_secret)
+                            .map_err(ParseGrpcError::PublicKey)?,
+                    })
                 })
-            })
-            .collect::<Result<Vec<_>, _>>()?;
+                .collect::<Result<Vec<_>, _>>()?;
 
-        let promises = self.inner_mint(method, quote_id, &outputs).await?;
-        let signatures = promises
-            .iter()
-            .map(|p| node::BlindSignature {
-                amount: p.amount.into(),
-                keyset_id: p.keyset_id.to_bytes().to_vec(),
-                blind_signature: p.c.to_bytes().to_vec(),
-            })
-            .collect::<Vec<_>>();
+            let promises = self.inner_mint(method, quote_id, &outputs).await?;
+            let signatures = promises
+                .iter()
+                .map(|p| node::BlindSignature {
+                    amount: p.amount.into(),
+                    keyset_id: p.keyset_id.to_bytes().to_vec(),
+                    blind_signature: p.c.to_bytes().to_vec(),
+                })
+                .collect::<Vec<_>>();
 
-        let mint_response = MintResponse {
-            signatures: signatures.clone(),
-        };
+            let mint_response = MintResponse {
+                signatures: signatures.clone(),
+            };
 
-        // Store in cache
-        self.cache_response(cache_key, CachedResponse::Mint(mint_response.clone()))?;
+            // Store in cache
+            self.cache_response(cache_key, CachedResponse::Mint(mint_response.clone()))?;
 
-        Ok(Response::new(mint_response))
+            Ok(Response::new(mint_response))
+        })
+        .await
     }
 
     async fn melt_quote(
@@ -410,54 +423,59 @@ impl Node for GrpcState {
         &self,
         melt_request: Request<MeltRequest>,
     ) -> Result<Response<MeltResponse>, Status> {
-        let melt_request = melt_request.into_inner();
+        follow_remote_parent(melt_request.metadata());
+        observe_rpc("melt", async {
+            let melt_request = melt_request.into_inner();
 
-        let cache_key = (Route::Melt, hash_melt_request(&melt_request));
+            let cache_key = (Route::Melt, hash_melt_request(&melt_request));
 
-        // Try to get from cache first
-        if let Some(CachedResponse::Melt(melt_response)) = self.get_cached_response(&cache_key) {
-            return Ok(Response::new(melt_response));
-        }
+            // Try to get from cache first
+            if let Some(CachedResponse::Melt(melt_response)) = self.get_cached_response(&cache_key)
+            {
+                return Ok(Response::new(melt_response));
+            }
 
-        if melt_request.inputs.len() > 64 {
-            return Err(Status::invalid_argument(
-                "Too many inputs: maximum allowed is 64",
-            ));
-        }
+            if melt_request.inputs.len() > 64 {
+                return Err(Status::invalid_argument(
+                    "Too many inputs: maximum allowed is 64",
+                ));
+            }
 
-        if melt_request.inputs.is_empty() {
-            return Err(Status::invalid_argument("Inputs cannot be empty"));
-        }
+            if melt_request.inputs.is_empty() {
+                return Err(Status::invalid_argument("Inputs cannot be empty"));
+            }
 
-        let method = Method::from_str(&melt_request.method).map_err(ParseGrpcError::Method)?;
-        let quote_id = Uuid::from_str(&melt_request.quote).map_err(ParseGrpcError::Uuid)?;
-        let inputs = melt_request
-            .clone()
-            .inputs
-            .into_iter()
-            .map(|p| -> Result<Proof, ParseGrpcError> {
-                Ok(Proof {
-                    amount: p.amount.into(),
-                    keyset_id: KeysetId::from_bytes(&p.keyset_id)
-                        .map_err(ParseGrpcError::KeysetId)?,
-                    secret: Secret::new(p.secret).map_err(ParseGrpcError::Secret)?,
-                    c: PublicKey::from_slice(&p.unblind_signature)
-                        .map_err(ParseGrpcError::PublicKey)?,
+            let method = Method::from_str(&melt_request.method).map_err(ParseGrpcError::Method)?;
+            let quote_id = Uuid::from_str(&melt_request.quote).map_err(ParseGrpcError::Uuid)?;
+            let inputs = melt_request
+                .clone()
+                .inputs
+                .into_iter()
+                .map(|p| -> Result<Proof, ParseGrpcError> {
+                    Ok(Proof {
+                        amount: p.amount.into(),
+                        keyset_id: KeysetId::from_bytes(&p.keyset_id)
+                            .map_err(ParseGrpcError::KeysetId)?,
+                        secret: Secret::new(p.secret).map_err(ParseGrpcError::Secret)?,
+                        c: PublicKey::from_slice(&p.unblind_signature)
+                            .map_err(ParseGrpcError::PublicKey)?,
+                    })
                 })
-            })
-            .collect::<Result<Vec<_>, _>>()?;
+                .collect::<Result<Vec<_>, _>>()?;
 
-        let response = self.inner_melt(method, quote_id, &inputs).await?;
+            let response = self.inner_melt(method, quote_id, &inputs).await?;
 
-        let melt_response = MeltResponse {
-            state: response.state.into(),
-            transfer_ids: response.transfer_ids.unwrap_or_default(),
-        };
+            let melt_response = MeltResponse {
+                state: response.state.into(),
+                transfer_ids: response.transfer_ids.unwrap_or_default(),
+            };
 
-        // Store in cache
-        self.cache_response(cache_key, CachedResponse::Melt(melt_response.clone()))?;
+            // Store in cache
+            self.cache_response(cache_key, CachedResponse::Melt(melt_response.clone()))?;
 
-        Ok(Response::new(melt_response))
+            Ok(Response::new(melt_response))
+        })
+        .await
     }
 
     #[instrument]
@@ -516,7 +534,7 @@ impl Node for GrpcState {
         let pub_key = self
             .signer
             .clone()
-            .get_root_pub_key(Request::new(GetRootPubKeyRequest {}))
+            .get_root_pub_key(GetRootPubKeyRequest {})
             .await?
             .into_inner()
             .root_pubkey;
@@ -0,0 +1,253 @@
+//! Per-client request rate limiting
+//!
+//! Every request creates work for the database and the signer, some create db rows.
+//! A token bucket per peer ip caps what a single client can ask for,
+//! while letting it burst up to the whole limit after being idle.
+//!
+//! Only the node services are wrapped, health checks are never throttled.
+
+use std::{
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use futures::future::{Either, Ready, ready};
+use tonic::{Status, transport::server::TcpConnectInfo};
+use tower::{Layer, Service};
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Allows `max_requests` per `window` to each peer
+#[derive(Debug)]
+pub struct RateLimiter {
+    max_requests: u32,
+    window: Duration,
+    buckets: DashMap<IpAddr, Bucket>,
+    /// When the buckets are next checked for eviction
+    next_eviction: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(max_requests: u32, window: Duration) -> Self {
+        Self {
+            max_requests,
+            window,
+            buckets: DashMap::new(),
+            next_eviction: Mutex::new(Instant::now() + window),
+        }
+    }
+
+    /// Drop the buckets of the peers idle for a whole window, at most once per window
+    ///
+    /// Those are full again, forgetting them changes nothing for their peer.
+    /// Without this, every ip that ever connected would stay in memory.
+    fn evict_idle_buckets(&self, now: Instant) {
+        // Another request is already evicting
+        let Ok(mut next_eviction) = self.next_eviction.try_lock() else {
+            return;
+        };
+        if now < *next_eviction {
+            return;
+        }
+        *next_eviction = now + self.window;
+        drop(next_eviction);
+
+        self.buckets
+            .retain(|_, bucket| now.saturating_duration_since(bucket.last_refill) < self.window);
+    }
+
+    fn try_acquire(&self, peer: IpAddr, now: Instant) -> bool {
+        // Must not run while an entry of the map is held, it would deadlock
+        self.evict_idle_buckets(now);
+
+        let capacity = f64::from(self.max_requests);
+        let refill_per_sec = capacity / self.window.as_secs_f64();
+
+        let mut bucket = self.buckets.entry(peer).or_insert(Bucket {
+            tokens: capacity,
+            last_refill: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.last_refill);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * refill_per_sec).min(capacity);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Remote ip of the connection the request came through
+///
+/// Only known for tcp connections, other requests are let through.
+fn peer_ip<B>(req: &http::Request<B>) -> Option<IpAddr> {
+    let connect_info = req.extensions().get::<TcpConnectInfo>();
+    #[cfg(feature = "tls")]
+    let connect_info = connect_info.or_else(|| {
+        req.extensions()
+            .get::<tonic::transport::server::TlsConnectInfo<TcpConnectInfo>>()
+            .map(|info| info.get_ref())
+    });
+
+    connect_info
+        .and_then(|info| info.remote_addr())
+        .map(|addr| addr.ip())
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimitLayer {
+    /// Requests go through untouched when absent
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+impl RateLimitLayer {
+    pub fn new(limiter: Option<RateLimiter>) -> Self {
+        Self {
+            limiter: limiter.map(Arc::new),
+        }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit {
+            inner,
+            limiter: self.limiter.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for RateLimit<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
+    ResBody: Default,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Either<S::Future, Ready<Result<S::Response, S::Error>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        if let (Some(limiter), Some(peer)) = (&self.limiter, peer_ip(&req)) {
+            if !limiter.try_acquire(peer, Instant::now()) {
+                let status = Status::resource_exhausted(format!(
+                    "rate limit of {} requests per {}s exceeded",
+                    limiter.max_requests,
+                    limiter.window.as_secs()
+                ));
+
+                return Either::Right(ready(Ok(status.into_http())));
+            }
+        }
+
+        Either::Left(self.inner.call(req))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        net::{Ipv4Addr, SocketAddr},
+    };
+
+    use tower::{ServiceExt, service_fn};
+
+    use super::*;
+
+    const PEER: IpAddr = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7));
+    const OTHER_PEER: IpAddr = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 8));
+
+    #[test]
+    fn bucket_refills_over_the_window() {
+        let limiter = RateLimiter::new(3, Duration::from_secs(60));
+        let start = Instant::now();
+
+        assert!((0..3).all(|_| limiter.try_acquire(PEER, start)));
+        assert!(!limiter.try_acquire(PEER, start));
+        // Each peer has its own bucket
+        assert!(limiter.try_acquire(OTHER_PEER, start));
+        // One token every 20s
+        assert!(limiter.try_acquire(PEER, start + Duration::from_secs(20)));
+        assert!(!limiter.try_acquire(PEER, start + Duration::from_secs(21)));
+    }
+
+    #[test]
+    fn idle_buckets_are_evicted() {
+        let limiter = RateLimiter::new(3, Duration::from_secs(60));
+        let start = Instant::now();
+
+        assert!(limiter.try_acquire(PEER, start));
+        assert!(limiter.try_acquire(OTHER_PEER, start + Duration::from_secs(30)));
+        assert_eq!(limiter.buckets.len(), 2);
+
+        // PEER has been idle for a whole window, OTHER_PEER for half of one
+        assert!(limiter.try_acquire(OTHER_PEER, start + Duration::from_secs(61)));
+        assert!(!limiter.buckets.contains_key(&PEER));
+        assert!(limiter.buckets.contains_key(&OTHER_PEER));
+
+        // An evicted peer starts again from a full bucket
+        assert!((0..3).all(|_| limiter.try_acquire(PEER, start + Duration::from_secs(62))));
+        assert!(!limiter.try_acquire(PEER, start + Duration::from_secs(62)));
+    }
+
+    fn request_from(peer: IpAddr) -> http::Request<()> {
+        let mut req = http::Request::new(());
+        req.extensions_mut().insert(TcpConnectInfo {
+            local_addr: None,
+            remote_addr: Some(SocketAddr::new(peer, 40000)),
+        });
+
+        req
+    }
+
+    fn grpc_status(response: &http::Response<tonic::body::Body>) -> Option<&str> {
+        response
+            .headers()
+            .get("grpc-status")
+            .and_then(|v| v.to_str().ok())
+    }
+
+    #[tokio::test]
+    async fn requests_over_the_limit_are_throttled() {
+        let inner = service_fn(|_req: http::Request<()>| async {
+            Ok::<_, Infallible>(http::Response::new(tonic::body::Body::default()))
+        });
+        let service =
+            RateLimitLayer::new(Some(RateLimiter::new(2, Duration::from_secs(60)))).layer(inner);
+
+        for _ in 0..2 {
+            let response = service.clone().oneshot(request_from(PEER)).await.unwrap();
+            assert_eq!(grpc_status(&response), None);
+        }
+        let response = service.clone().oneshot(request_from(PEER)).await.unwrap();
+        assert_eq!(
+            grpc_status(&response),
+            Some((tonic::Code::ResourceExhausted as i32).to_string().as_str())
+        );
+
+        let response = service.oneshot(request_from(OTHER_PEER)).await.unwrap();
+        assert_eq!(grpc_status(&response), None);
+    }
+}
@@ -1,31 +1,42 @@
 #[cfg(feature = "keyset-rotation")]
 use node::KeysetRotationServiceServer;
-use std::{collections::HashSet, net::SocketAddr};
+use std::{collections::HashSet, net::SocketAddr, time::Duration};
 use tonic::transport::Server;
 use tower::ServiceBuilder;
-use tower_otel::trace;
 use tracing::instrument;
 
 use futures::TryFutureExt;
 use node::NodeServer;
 use nuts::QuoteTTLConfig;
-use signer::SignerClient;
 use sqlx::Postgres;
 use starknet_types::Unit;
-use tonic::{service::LayerExt, transport::Channel};
+use tonic::service::LayerExt;
 
-use crate::{grpc_service::GrpcState, liquidity_sources::LiquiditySources};
+use crate::{
+    app_state::SignerClient,
+    grpc_service::{
+        GrpcState,
+        rate_limit::{RateLimitLayer, RateLimiter},
+    },
+    liquidity_sources::LiquiditySources,
+};
 
-use super::{Error, env_variables::EnvVariables};
+use super::config::NodeConfig;
+#[cfg(feature = "tls")]
+use super::config::TlsPaths;
+
+const DEFAULT_RATE_LIMIT_WINDOW_SECS: u64 = 60;
 
-#[instrument]
+// `config` holds secrets, only its redacted summary may be logged
+#[instrument(skip(config, shutdown_signal))]
 pub async fn launch_tonic_server_task(
     pg_pool: sqlx::Pool<Postgres>,
-    signer_client: SignerClient<trace::Grpc<Channel>>,
+    signer_client: SignerClient,
     liquidity_sources: LiquiditySources<Unit>,
-    env_vars: EnvVariables,
+    config: NodeConfig,
+    shutdown_signal: impl Future<Output = ()> + Send + 'static,
 ) -> Result<(SocketAddr, impl Future<Output = Result<(), crate::Error>>), super::Error> {
-    let nuts_settings = super::nuts_settings::nuts_settings();
+    let nuts_settings = super::nuts_settings::nuts_settings(&config.mint_amount_bounds);
     let supported_units: HashSet<_> = nuts_settings
         .nut04
         .methods
@@ -34,20 +45,19 @@ pub async fn launch_tonic_server_task(
         .chain(nuts_settings.nut05.methods.iter().map(|m| m.unit))
         .collect();
 
-    let ttl = env_vars.quote_ttl.unwrap_or(3600);
+    let ttl = config.quote_ttl.unwrap_or(3600);
     let grpc_state = GrpcState::new(
         pg_pool,
-        signer_client,
+        signer_client.clone(),
         nuts_settings,
         QuoteTTLConfig {
             mint_ttl: ttl,
             melt_ttl: ttl,
         },
         liquidity_sources,
+        config.response_cache_ttl.map(Duration::from_secs),
     );
-    let address = format!("[::0]:{}", env_vars.grpc_port)
-        .parse()
-        .map_err(Error::InvalidGrpcAddress)?;
+    let address = config.grpc_address;
 
     // TODO: take into account past keyset rotations
     // init node shared
@@ -59,6 +69,7 @@ pub async fn launch_tonic_server_task(
     let health_service = {
         let (health_reporter, health_service) = tonic_health::server::health_reporter();
         health_reporter.set_serving::<NodeServer<GrpcState>>().await;
+        signer_client.set_health_reporter(health_reporter.clone());
         #[cfg(feature = "keyset-rotation")]
         health_reporter
             .set_serving::<KeysetRotationServiceServer<GrpcState>>()
@@ -67,21 +78,33 @@ pub async fn launch_tonic_server_task(
         health_service
     };
     let optl_layer = tower_otel::trace::GrpcLayer::server(tracing::Level::INFO);
+    let rate_limit_layer = RateLimitLayer::new(config.rate_limit_requests.map(|max_requests| {
+        RateLimiter::new(
+            max_requests,
+            Duration::from_secs(
+                config
+                    .rate_limit_window
+                    .unwrap_or(DEFAULT_RATE_LIMIT_WINDOW_SECS),
+            ),
+        )
+    }));
     let meter = opentelemetry::global::meter(env!("CARGO_PKG_NAME"));
 
     #[cfg(feature = "keyset-rotation")]
     let keyset_rotation_service = ServiceBuilder::new()
         .layer(optl_layer.clone())
+        .layer(rate_limit_layer.clone())
         .named_layer(KeysetRotationServiceServer::new(grpc_state.clone()));
 
     let node_service = ServiceBuilder::new()
         .layer(optl_layer)
+        .layer(rate_limit_layer)
         .named_layer(NodeServer::new(grpc_state.clone()));
 
     let tonic_future = {
         let tonic_server = build_server(
             #[cfg(feature = "tls")]
-            &env_vars,
+            config.tls.as_ref(),
         )
         .map_err(super::Error::BuildServer)?;
         let mut tonic_server = tonic_server.layer(tower_otel::metrics::HttpLayer::server(&meter));
@@ -92,7 +115,11 @@ pub async fn launch_tonic_server_task(
         #[cfg(feature = "keyset-rotation")]
         let router = router.add_service(keyset_rotation_service);
 
-        router.serve(address).map_err(crate::Error::Tonic)
+        // Stop accepting new connections once the signal resolves,
+        // then wait for the in-flight requests to be answered
+        router
+            .serve_with_shutdown(address, shutdown_signal)
+            .map_err(crate::Error::Tonic)
     };
 
     Ok((address, tonic_future))
@@ -106,9 +133,13 @@ pub fn build_server() -> Result<Server, anyhow::Error> {
 }
 
 #[cfg(feature = "tls")]
-pub fn build_server(env_vars: &EnvVariables) -> Result<Server, anyhow::Error> {
-    let key_path = &env_vars.tls_key_path;
-    let cert_path = &env_vars.tls_cert_path;
+pub fn build_server(tls: Option<&TlsPaths>) -> Result<Server, anyhow::Error> {
+    let Some(tls) = tls else {
+        tracing::info!("🚀 Starting gRPC server without TLS...");
+        return Ok(tonic::transport::Server::builder());
+    };
+    let key_path = &tls.key_path;
+    let cert_path = &tls.cert_path;
     // Load TLS certificates
     let cert = match std::fs::read(cert_path) {
         Ok(cert) => {
@@ -154,6 +185,19 @@ pub fn build_server(env_vars: &EnvVariables) -> Result<Server, anyhow::Error> {
     let identity = tonic::transport::Identity::from_pem(cert, key);
     let tls_config = tonic::transport::ServerTlsConfig::new().identity(identity);
 
+    #[cfg(feature = "mtls")]
+    let tls_config = match &tls.client_ca_path {
+        Some(client_ca_path) => {
+            let client_ca = std::fs::read(client_ca_path).map_err(|e| {
+                anyhow::anyhow!("Failed to load TLS client CA {}: {}", client_ca_path, e)
+            })?;
+            tracing::info!("🪪 Client certificates required, CA: {}", client_ca_path);
+
+            tls_config.client_ca_root(tonic::transport::Certificate::from_pem(client_ca))
+        }
+        None => tls_config,
+    };
+
     tracing::info!("🔒 Starting gRPC server with TLS...");
     tracing::info!("📜 Certificate: {}", cert_path);
     tracing::info!("🔑 Private key: {}", key_path);
@@ -162,3 +206,230 @@ pub fn build_server(env_vars: &EnvVariables) -> Result<Server, anyhow::Error> {
 
     Ok(server)
 }
+
+#[cfg(test)]
+mod shutdown_tests {
+    use std::sync::Arc;
+
+    use tokio::{
+        net::TcpListener,
+        sync::{Notify, oneshot},
+    };
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::Channel;
+    use tonic_health::pb::{HealthCheckRequest, health_client::HealthClient};
+    use tower::util::MapFutureLayer;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn in_flight_request_completes_during_shutdown() {
+        let request_started = Arc::new(Notify::new());
+        let release_request = Arc::new(Notify::new());
+        // Hold every request until the test releases it
+        let hold_layer = MapFutureLayer::new({
+            let request_started = request_started.clone();
+            let release_request = release_request.clone();
+            move |response| {
+                let request_started = request_started.clone();
+                let release_request = release_request.clone();
+                async move {
+                    request_started.notify_one();
+                    release_request.notified().await;
+                    response.await
+                }
+            }
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (_, health_service) = tonic_health::server::health_reporter();
+        let (shutdown_sender, shutdown_receiver) = oneshot::channel::<()>();
+        let mut server = tokio::spawn(
+            build_server(
+                #[cfg(feature = "tls")]
+                None,
+            )
+            .unwrap()
+            .layer(hold_layer)
+            .add_service(health_service)
+            .serve_with_incoming_shutdown(
+                TcpListenerStream::new(listener),
+                async move {
+                    let _ = shutdown_receiver.await;
+                },
+            ),
+        );
+
+        let channel = Channel::from_shared(format!("http://{}", address))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let request = tokio::spawn(async move {
+            HealthClient::new(channel)
+                .check(HealthCheckRequest {
+                    service: String::new(),
+                })
+                .await
+        });
+        request_started.notified().await;
+        shutdown_sender.send(()).unwrap();
+
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), &mut server)
+                .await
+                .is_err(),
+            "the server should wait for the in-flight request"
+        );
+        release_request.notify_one();
+
+        request.await.unwrap().unwrap();
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("the server should stop once the request is drained")
+            .unwrap()
+            .unwrap();
+    }
+}
+
+#[cfg(all(test, feature = "tls"))]
+mod tests {
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::{Certificate, Channel, ClientTlsConfig};
+    use tonic_health::pb::{HealthCheckRequest, health_client::HealthClient};
+
+    use super::*;
+
+    fn fixture(name: &str) -> String {
+        format!("{}/tests/certs/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    fn server_tls_paths() -> TlsPaths {
+        TlsPaths {
+            cert_path: fixture("server.pem"),
+            key_path: fixture("server.key"),
+            #[cfg(feature = "mtls")]
+            client_ca_path: None,
+        }
+    }
+
+    fn certificate(name: &str) -> Certificate {
+        Certificate::from_pem(std::fs::read(fixture(name)).unwrap())
+    }
+
+    /// Serve a health service configured with `tls` on a random port, returning that port
+    async fn spawn_server(tls: &TlsPaths) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (_, health_service) = tonic_health::server::health_reporter();
+        let router = build_server(Some(tls)).unwrap().add_service(health_service);
+        tokio::spawn(router.serve_with_incoming(TcpListenerStream::new(listener)));
+
+        port
+    }
+
+    async fn check_health(
+        port: u16,
+        tls_config: ClientTlsConfig,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let channel = Channel::from_shared(format!("https://localhost:{}", port))?
+            .tls_config(tls_config)?
+            .connect()
+            .await?;
+        HealthClient::new(channel)
+            .check(HealthCheckRequest {
+                service: String::new(),
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn tls_handshake_succeeds_with_the_configured_certificate() {
+        let port = spawn_server(&server_tls_paths()).await;
+
+        check_health(
+            port,
+            ClientTlsConfig::new().ca_certificate(certificate("ca.pem")),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn tls_handshake_fails_when_the_server_certificate_is_not_trusted() {
+        let port = spawn_server(&server_tls_paths()).await;
+
+        let result = check_health(
+            port,
+            ClientTlsConfig::new().ca_certificate(certificate("unknown_ca.pem")),
+        )
+        .await;
+
+        assert!(result.is_err());
+    }
+
+    #[cfg(feature = "mtls")]
+    fn mtls_server_tls_paths() -> TlsPaths {
+        TlsPaths {
+            client_ca_path: Some(fixture("ca.pem")),
+            ..server_tls_paths()
+        }
+    }
+
+    #[cfg(feature = "mtls")]
+    fn client_tls_config(client: &str) -> ClientTlsConfig {
+        ClientTlsConfig::new()
+            .ca_certificate(certificate("ca.pem"))
+            .identity(tonic::transport::Identity::from_pem(
+                std::fs::read(fixture(&format!("{}.pem", client))).unwrap(),
+                std::fs::read(fixture(&format!("{}.key", client))).unwrap(),
+            ))
+    }
+
+    #[cfg(feature = "mtls")]
+    #[tokio::test]
+    async fn mtls_accepts_a_client_certificate_signed_by_the_client_ca() {
+        let port = spawn_server(&mtls_server_tls_paths()).await;
+
+        check_health(port, client_tls_config("client"))
+            .await
+            .unwrap();
+    }
+
+    #[cfg(feature = "mtls")]
+    #[tokio::test]
+    async fn mtls_rejects_a_client_without_certificate() {
+        let port = spawn_server(&mtls_server_tls_paths()).await;
+
+        let result = check_health(
+            port,
+            ClientTlsConfig::new().ca_certificate(certificate("ca.pem")),
+        )
+        .await;
+
+        assert!(result.is_err());
+    }
+
+    #[cfg(feature = "mtls")]
+    #[tokio::test]
+    async fn mtls_rejects_a_client_certificate_signed_by_an_unknown_ca() {
+        let port = spawn_server(&mtls_server_tls_paths()).await;
+
+        let result = check_health(port, client_tls_config("unknown_client")).await;
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn missing_certificate_fails_to_build_the_server() {
+        let tls = TlsPaths {
+            cert_path: fixture("missing.pem"),
+            ..server_tls_paths()
+        };
+
+        assert!(build_server(Some(&tls)).is_err());
+    }
+}
@@ -1,10 +1,10 @@
-mod env_variables;
-pub use env_variables::read_env_variables;
+mod config;
+pub use config::{MintAmountBounds, read_node_config};
 mod db;
-mod nuts_settings;
+pub(crate) mod nuts_settings;
 pub use db::connect_to_db_and_run_migrations;
 mod signer_client;
-pub use signer_client::connect_to_signer;
+pub use signer_client::{SignerConnectionRetry, connect_to_signer};
 mod grpc;
 pub use grpc::launch_tonic_server_task;
 
@@ -18,12 +18,33 @@ pub enum Error {
     DbMigrate(#[source] sqlx::migrate::MigrateError),
     #[error("Failed to read environment variable `{0}`: {1}")]
     Env(&'static str, #[source] std::env::VarError),
-    #[error(transparent)]
-    ParseInt(#[from] std::num::ParseIntError),
+    #[error("failed to read config file `{0}`: {1}")]
+    ConfigFile(String, #[source] std::io::Error),
+    #[error("invalid config file: {0}")]
+    ConfigParse(#[from] toml::de::Error),
+    #[error("missing setting `{0}`, set it in the config file or through the env var of that name")]
+    MissingSetting(&'static str),
+    #[error("invalid value for `{0}`: {1}")]
+    InvalidNumber(&'static str, #[source] std::num::ParseIntError),
     #[error("Failed parse the Grpc address")]
     InvalidGrpcAddress(#[from] std::net::AddrParseError),
-    #[error("failed to connect to signer")]
-    SignerConnection(tonic::transport::Error),
+    #[error("the gRPC port must be nonzero")]
+    ZeroGrpcPort,
+    #[error("the rate limit requests and window must be nonzero")]
+    ZeroRateLimit,
+    #[error("mint amount bounds of unit `{0}` have a min above their max")]
+    InvalidMintAmountBounds(starknet_types::Unit),
+    #[cfg(feature = "tls")]
+    #[error("tls certificate and key paths must either be both set or both unset")]
+    IncompleteTlsConfig,
+    #[cfg(feature = "mtls")]
+    #[error("failed to read signer tls file `{0}`: {1}")]
+    SignerTlsFile(String, #[source] std::io::Error),
+    #[cfg(feature = "mtls")]
+    #[error("invalid signer tls config: {0}")]
+    SignerTlsConfig(#[source] tonic::transport::Error),
+    #[error("failed to connect to signer after {0} attempts: {1}")]
+    SignerConnection(u32, #[source] tonic::transport::Error),
     #[error("failed to bind gRPC server to port: {0}")]
     Bind(#[from] std::io::Error),
     #[error("failed to init first keysets: {0}")]
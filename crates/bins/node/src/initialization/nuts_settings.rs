@@ -1,10 +1,33 @@
+use std::collections::HashMap;
+
 use nuts::{Amount, nut04::MintMethodSettings, nut05::MeltMethodSettings, nut06::NutsSettings};
 use starknet_types::Unit;
 
 use crate::methods::Method;
 
+use super::MintAmountBounds;
+
+pub(super) fn nuts_settings(
+    mint_amount_bounds: &HashMap<Unit, MintAmountBounds>,
+) -> NutsSettings<Method, Unit, serde_json::Value> {
+    let mut settings = default_nuts_settings();
+
+    for method_settings in settings.nut04.methods.iter_mut() {
+        if let Some(bounds) = mint_amount_bounds.get(&method_settings.unit) {
+            if let Some(min) = bounds.min {
+                method_settings.min_amount = Some(min.max(Amount::ONE));
+            }
+            if let Some(max) = bounds.max {
+                method_settings.max_amount = Some(max);
+            }
+        }
+    }
+
+    settings
+}
+
 // TODO: make it a compile time const
-pub(super) fn nuts_settings() -> NutsSettings<Method, Unit, serde_json::Value> {
+pub(crate) fn default_nuts_settings() -> NutsSettings<Method, Unit, serde_json::Value> {
     NutsSettings {
         nut04: nuts::nut04::Settings {
             methods: vec![
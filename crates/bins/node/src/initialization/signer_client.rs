@@ -1,19 +1,193 @@
-use tonic::transport::Channel;
-use tower_otel::trace;
-use tracing::Level;
+use std::time::Duration;
+
+use tokio_retry::strategy::ExponentialBackoff;
+use tonic::transport::{Channel, Endpoint};
+use tracing::warn;
 
 use crate::app_state::SignerClient;
 
 use super::Error;
+#[cfg(feature = "mtls")]
+use super::config::SignerTlsPaths;
+
+/// Bounds the attempts made to reach the signer during node startup
+///
+/// The signer is often booted alongside the node (same compose file, same pod),
+/// so it may not be accepting connections yet when we first try to reach it.
+#[derive(Debug, Clone, Copy)]
+pub struct SignerConnectionRetry {
+    pub max_attempts: u32,
+    pub max_delay: Duration,
+}
+
+impl Default for SignerConnectionRetry {
+    fn default() -> Self {
+        Self {
+            max_attempts: 10,
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl SignerConnectionRetry {
+    /// 500ms, 1s, 2s, 4s, ... capped at `max_delay`
+    pub(crate) fn delays(&self) -> impl Iterator<Item = Duration> + use<> {
+        ExponentialBackoff::from_millis(2)
+            .factor(250)
+            .max_delay(self.max_delay)
+            .take(self.max_attempts.saturating_sub(1) as usize)
+    }
+}
 
-pub async fn connect_to_signer(signer_url: String) -> Result<SignerClient, Error> {
-    let channel = Channel::builder(signer_url.parse()?)
-        .connect()
+pub async fn connect_to_signer(
+    signer_url: String,
+    retry: SignerConnectionRetry,
+    #[cfg(feature = "mtls")] signer_tls: Option<&SignerTlsPaths>,
+) -> Result<SignerClient, Error> {
+    let endpoint = Channel::builder(signer_url.parse()?);
+    #[cfg(feature = "mtls")]
+    let endpoint = match signer_tls {
+        Some(signer_tls) => endpoint
+            .tls_config(build_signer_tls_config(signer_tls)?)
+            .map_err(Error::SignerTlsConfig)?,
+        None => endpoint,
+    };
+
+    let channel = connect_with_retry(&endpoint, retry)
         .await
-        .map_err(Error::SignerConnection)?;
-    let channel = tower::ServiceBuilder::new()
-        .layer(trace::GrpcLayer::client(Level::INFO))
-        .service(channel);
+        .map_err(|(attempts, err)| Error::SignerConnection(attempts, err))?;
+
+    Ok(SignerClient::new(endpoint, channel))
+}
+
+/// On failure, returns the number of attempts made alongside the last error
+async fn connect_with_retry(
+    endpoint: &Endpoint,
+    retry: SignerConnectionRetry,
+) -> Result<Channel, (u32, tonic::transport::Error)> {
+    let mut delays = retry.delays();
+    let mut attempt = 1;
+    loop {
+        match endpoint.connect().await {
+            Ok(channel) => return Ok(channel),
+            Err(err) => match delays.next() {
+                Some(delay) => {
+                    warn!(
+                        name: "signer-connection",
+                        attempt,
+                        max_attempts = retry.max_attempts,
+                        retry_in_ms = delay.as_millis() as u64,
+                        error = %err,
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                None => return Err((attempt, err)),
+            },
+        }
+    }
+}
+
+#[cfg(feature = "mtls")]
+fn build_signer_tls_config(
+    signer_tls: &SignerTlsPaths,
+) -> Result<tonic::transport::ClientTlsConfig, Error> {
+    let read =
+        |path: &String| std::fs::read(path).map_err(|e| Error::SignerTlsFile(path.clone(), e));
+
+    let identity = tonic::transport::Identity::from_pem(
+        read(&signer_tls.cert_path)?,
+        read(&signer_tls.key_path)?,
+    );
+    let mut tls_config = tonic::transport::ClientTlsConfig::new().identity(identity);
+    match &signer_tls.ca_path {
+        Some(ca_path) => {
+            tls_config =
+                tls_config.ca_certificate(tonic::transport::Certificate::from_pem(read(ca_path)?));
+        }
+        None => {
+            tls_config = tls_config.with_enabled_roots();
+        }
+    }
+
+    Ok(tls_config)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use super::*;
+
+    fn free_local_address() -> SocketAddr {
+        std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+    }
+
+    #[test]
+    fn delays_grow_exponentially_up_to_the_cap() {
+        let retry = SignerConnectionRetry {
+            max_attempts: 6,
+            max_delay: Duration::from_secs(3),
+        };
+
+        assert_eq!(
+            retry.delays().collect::<Vec<_>>(),
+            vec![
+                Duration::from_millis(500),
+                Duration::from_secs(1),
+                Duration::from_secs(2),
+                Duration::from_secs(3),
+                Duration::from_secs(3),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn eventually_connects_to_a_late_signer() {
+        let address = free_local_address();
+
+        let server = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(800)).await;
+            let (_, health_service) = tonic_health::server::health_reporter();
+            tonic::transport::Server::builder()
+                .add_service(health_service)
+                .serve(address)
+                .await
+        });
+
+        let res = connect_to_signer(
+            format!("http://{address}"),
+            SignerConnectionRetry {
+                max_attempts: 5,
+                max_delay: Duration::from_millis(500),
+            },
+            #[cfg(feature = "mtls")]
+            None,
+        )
+        .await;
+        server.abort();
+
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let address = free_local_address();
+
+        let res = connect_to_signer(
+            format!("http://{address}"),
+            SignerConnectionRetry {
+                max_attempts: 2,
+                max_delay: Duration::from_millis(10),
+            },
+            #[cfg(feature = "mtls")]
+            None,
+        )
+        .await;
 
-    Ok(signer::SignerClient::new(channel))
+        assert!(matches!(res, Err(Error::SignerConnection(2, _))));
+    }
 }
@@ -31,14 +31,17 @@ pub struct CachedKeysetInfo {
     active: bool,
     unit: Unit,
     max_order: u32,
+    input_fee_ppk: u16,
 }
 
 impl CachedKeysetInfo {
-    pub fn new(active: bool, unit: Unit, max_order: u32) -> Self {
+    #[cfg(feature = "keyset-rotation")]
+    pub fn new(active: bool, unit: Unit, max_order: u32, input_fee_ppk: u16) -> Self {
         Self {
             active,
             unit,
             max_order,
+            input_fee_ppk,
         }
     }
 
@@ -53,6 +56,10 @@ impl CachedKeysetInfo {
     pub fn max_order(&self) -> u32 {
         self.max_order
     }
+
+    pub fn input_fee_ppk(&self) -> u16 {
+        self.input_fee_ppk
+    }
 }
 
 #[derive(Debug, Default, Clone)]
@@ -62,6 +69,7 @@ pub struct KeysetCache {
 }
 
 impl KeysetCache {
+    #[cfg(feature = "keyset-rotation")]
     pub async fn insert_info(&self, keyset_id: KeysetId, info: CachedKeysetInfo) {
         let mut write_lock = self.infos.write().await;
 
@@ -87,6 +95,15 @@ impl KeysetCache {
         }
     }
 
+    #[cfg(feature = "keyset-rotation")]
+    pub async fn set_input_fee(&self, keyset_id: KeysetId, input_fee_ppk: u16) {
+        let mut write_lock = self.infos.write().await;
+
+        if let Some(info) = write_lock.get_mut(&keyset_id) {
+            info.input_fee_ppk = input_fee_ppk;
+        }
+    }
+
     pub async fn get_keyset_keys(
         &self,
         conn: &mut PgConnection,
@@ -158,6 +175,7 @@ impl KeysetCache {
             active: db_content.active(),
             unit: db_content.unit(),
             max_order: db_content.max_order().into(),
+            input_fee_ppk: db_content.input_fee_ppk(),
         };
 
         {
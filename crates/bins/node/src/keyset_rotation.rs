@@ -2,7 +2,10 @@ use crate::errors::Error;
 use crate::keyset_cache::CachedKeysetInfo;
 use db_node::keyset::deactivate_keysets;
 use grpc_service::GrpcState;
-use node::{KeysetRotationService, RotateKeysetsRequest, RotateKeysetsResponse};
+use node::{
+    KeysetRotationService, RotateKeysetsRequest, RotateKeysetsResponse,
+    UpdateKeysetInputFeeRequest, UpdateKeysetInputFeeResponse,
+};
 
 use std::str::FromStr;
 use tonic::{Request, Response, Status};
@@ -53,7 +56,11 @@ impl KeysetRotationService for GrpcState {
             insert_keysets_query_builder.add_row(new_keyset_id, unit, max_order, index);
 
             self.keyset_cache
-                .insert_info(new_keyset_id, CachedKeysetInfo::new(true, unit, max_order))
+                // New keysets are inserted with the default fee of the `keyset` table
+                .insert_info(
+                    new_keyset_id,
+                    CachedKeysetInfo::new(true, unit, max_order, 0),
+                )
                 .await;
 
             let keys = response
@@ -99,4 +106,52 @@ impl KeysetRotationService for GrpcState {
 
         Ok(Response::new(RotateKeysetsResponse {}))
     }
+    async fn update_keyset_input_fee(
+        &self,
+        request: Request<UpdateKeysetInputFeeRequest>,
+    ) -> Result<Response<UpdateKeysetInputFeeResponse>, Status> {
+        let request = request.into_inner();
+
+        if request.keyset_id.len() != 8 {
+            return Err(Status::invalid_argument("keyset_id must be 8 bytes long"));
+        }
+        let keyset_id = KeysetId::from_bytes(&request.keyset_id)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let new_fee = u16::try_from(request.input_fee_ppk).map_err(|_| {
+            Status::invalid_argument(format!(
+                "input_fee_ppk must fit in a u16, got {}",
+                request.input_fee_ppk
+            ))
+        })?;
+
+        let mut tx = db_node::begin_db_tx(&self.pg_pool)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        let previous_fee = db_node::keyset::update_input_fee(&mut tx, keyset_id, new_fee)
+            .await
+            .map_err(|e| match e {
+                db_node::Error::Sqlx(sqlx::Error::RowNotFound) => {
+                    Status::not_found(format!("keyset {} not found", keyset_id))
+                }
+                e => Status::internal(e.to_string()),
+            })?;
+
+        tx.commit()
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        self.keyset_cache.set_input_fee(keyset_id, new_fee).await;
+
+        tracing::info!(
+            keyset_id = %keyset_id,
+            previous_fee,
+            new_fee,
+            "keyset input fee updated"
+        );
+
+        Ok(Response::new(UpdateKeysetInputFeeResponse {
+            previous_input_fee_ppk: previous_fee.into(),
+        }))
+    }
 }
@@ -24,11 +24,16 @@ pub enum Error {
 
 impl<U: Unit> LiquiditySources<U> {
     #[allow(unused_variables)]
-    pub async fn init(pg_pool: PgPool) -> Result<LiquiditySources<U>, Error> {
+    pub async fn init(
+        pg_pool: PgPool,
+        starknet_config: Option<starknet_liquidity_source::StarknetCliConfig>,
+    ) -> Result<LiquiditySources<U>, Error> {
         #[cfg(not(feature = "mock"))]
-        let starknet = starknet_liquidity_source::StarknetLiquiditySource::init(pg_pool).await?;
+        let starknet =
+            starknet_liquidity_source::StarknetLiquiditySource::init(pg_pool, starknet_config)
+                .await?;
         #[cfg(feature = "mock")]
-        let starknet = starknet_liquidity_source::StarknetLiquiditySource::new();
+        let starknet = starknet_liquidity_source::StarknetLiquiditySource::new(pg_pool);
 
         Ok(LiquiditySources {
             starknet,
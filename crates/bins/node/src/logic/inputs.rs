@@ -27,6 +27,8 @@ pub enum Error {
     #[error(transparent)]
     Db(#[from] sqlx::Error),
     #[error(transparent)]
+    DbNode(#[from] db_node::Error),
+    #[error(transparent)]
     KeysetCache(#[from] keyset_cache::Error),
     #[error(transparent)]
     Signer(tonic::Status),
@@ -49,7 +51,9 @@ impl From<Error> for Status {
             | Error::TotalFeeTooBig
             | Error::AmountExceedsMaxOrder(_, _, _) => Status::invalid_argument(value.to_string()),
             Error::Db(sqlx::Error::RowNotFound) => Status::not_found(value.to_string()),
-            Error::Db(_) | Error::KeysetCache(_) => Status::internal(value.to_string()),
+            Error::Db(_) | Error::DbNode(_) | Error::KeysetCache(_) => {
+                Status::internal(value.to_string())
+            }
             Error::Signer(status) => status,
             Error::ProofIssues {
                 invalid_crypto_indices,
@@ -102,7 +106,7 @@ fn rename_signer_error_details_field_name(status: tonic::Status) -> tonic::Statu
 pub async fn run_verification_queries(
     conn: &mut PgConnection,
     secrets: HashSet<PublicKey>,
-    mut signer: SignerClient,
+    signer: SignerClient,
     verify_proofs_request: Vec<signer::Proof>,
 ) -> Result<(), Error> {
     let query_signer_future = async {
@@ -1,5 +1,8 @@
 mod outputs;
-pub use outputs::{Error as OutputsError, check_outputs_allow_multiple_units, process_outputs};
+pub use outputs::{
+    Error as OutputsError, check_outputs_allow_multiple_units, ensure_outputs_not_already_signed,
+    process_outputs,
+};
 mod inputs;
 pub use inputs::{
     Error as InputsError, run_verification_queries as run_inputs_verification_queries,
@@ -29,13 +29,15 @@ pub enum Error {
     MultipleUnits,
     #[error("the sum off all the outputs' amount must fit in a u64")]
     TotalAmountTooBig,
-    #[error("blind message is already signed")]
-    AlreadySigned,
+    #[error("blind message {0} is already signed")]
+    AlreadySigned(PublicKey),
     #[error("amount exceeds max order for keyset {0}: amount {1} exceeds max value {2}")]
     AmountExceedsMaxOrder(KeysetId, Amount, u64),
     #[error(transparent)]
     Db(#[from] sqlx::Error),
     #[error(transparent)]
+    DbNode(db_node::Error),
+    #[error(transparent)]
     Signer(tonic::Status),
     #[error(transparent)]
     KeysetCache(#[from] keyset_cache::Error),
@@ -109,15 +111,26 @@ pub async fn check_outputs_allow_multiple_units(
     }
 
     // Make sure those outputs were not already signed
-    if db_node::is_any_blind_message_already_used(conn, blind_secrets.into_iter()).await? {
-        Err(Error::AlreadySigned)?;
-    }
+    ensure_outputs_not_already_signed(conn, blind_secrets.into_iter()).await?;
 
     Ok(total_amounts)
 }
 
+pub async fn ensure_outputs_not_already_signed(
+    conn: &mut PgConnection,
+    blind_secrets: impl Iterator<Item = PublicKey>,
+) -> Result<(), Error> {
+    db_node::ensure_blind_messages_unused(conn, blind_secrets)
+        .await
+        .map_err(|e| match e {
+            db_node::Error::BlindMessageAlreadyUsed { y } => Error::AlreadySigned(y),
+            db_node::Error::Sqlx(e) => Error::Db(e),
+            e => Error::DbNode(e),
+        })
+}
+
 pub async fn process_outputs<'a>(
-    mut signer: SignerClient,
+    signer: SignerClient,
     outputs: &[BlindedMessage],
 ) -> Result<(Vec<BlindSignature>, InsertBlindSignaturesQueryBuilder<'a>), Error> {
     let mut query_builder = InsertBlindSignaturesQueryBuilder::new();
@@ -148,11 +161,13 @@ pub async fn process_outputs<'a>(
                 c: PublicKey::from_slice(&bs).expect("the signer should return valid pubkey"),
             };
 
-            query_builder.add_row(bm.blinded_secret, &blind_signature);
+            query_builder
+                .add_row(bm.blinded_secret, &blind_signature)
+                .map_err(Error::DbNode)?;
 
-            blind_signature
+            Ok(blind_signature)
         })
-        .collect::<Vec<_>>();
+        .collect::<Result<Vec<_>, Error>>()?;
 
     Ok((blind_signatures, query_builder))
 }
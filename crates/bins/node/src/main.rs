@@ -7,10 +7,11 @@ use std::time::Duration;
 use errors::Error;
 use gauge::DbMetricsObserver;
 use initialization::{
-    connect_to_db_and_run_migrations, connect_to_signer, launch_tonic_server_task,
-    read_env_variables,
+    SignerConnectionRetry, connect_to_db_and_run_migrations, connect_to_signer,
+    launch_tonic_server_task, read_node_config,
 };
-use tracing::{info, trace};
+use tracing::{info, trace, warn};
+use withdrawal_reconciliation::WithdrawalReconciler;
 
 mod app_state;
 mod errors;
@@ -25,23 +26,55 @@ mod logic;
 mod methods;
 mod response_cache;
 mod routes;
+mod rpc_metrics;
+mod signer_client;
 mod utils;
+mod withdrawal_reconciliation;
+
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 30;
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
     const PKG_NAME: &str = env!("CARGO_PKG_NAME");
     const PKG_VERSION: &str = env!("CARGO_PKG_VERSION");
+    #[cfg(not(feature = "prometheus"))]
     let (meter_provider, subscriber) = open_telemetry_tracing::init(PKG_NAME, PKG_VERSION);
+    #[cfg(feature = "prometheus")]
+    let (meter_provider, subscriber, prometheus_endpoint) =
+        open_telemetry_tracing::init_with_prometheus(PKG_NAME, PKG_VERSION)?;
 
     tracing::subscriber::set_global_default(subscriber).unwrap();
-    opentelemetry::global::set_meter_provider(meter_provider);
+    opentelemetry::global::set_meter_provider(meter_provider.clone());
+    #[cfg(feature = "prometheus")]
+    if let Some(endpoint) = prometheus_endpoint {
+        info!("Serving Prometheus metrics on {}/metrics", endpoint.address);
+        tokio::spawn(async move {
+            if let Err(err) = endpoint.serve().await {
+                tracing::error!("Prometheus endpoint failed: {}", err);
+            }
+        });
+    }
 
     info!("Initializing node...");
-    // Read args and env
-    let env_variables = read_env_variables()?;
+    // Read config file and env
+    let config = read_node_config()?;
+    // The liquidity source merges its own env vars with the file settings, show what it will use
+    let effective_starknet_config =
+        match starknet_liquidity_source::read_config(config.starknet.clone()) {
+            Ok(starknet_config) => Some(starknet_config),
+            Err(err) => {
+                // Not fatal here, the mock liquidity source doesn't need it
+                warn!("Could not resolve the starknet configuration: {}", err);
+                None
+            }
+        };
+    info!(
+        "Effective configuration: {}",
+        config.redacted_summary(effective_starknet_config.as_ref())
+    );
 
     // Connect to db
-    let pg_pool = connect_to_db_and_run_migrations(&env_variables.pg_url).await?;
+    let pg_pool = connect_to_db_and_run_migrations(&config.pg_url).await?;
     info!("Connected to node database.");
 
     // Lauch the database metrics polling task
@@ -58,32 +91,84 @@ async fn main() -> Result<(), anyhow::Error> {
     ));
 
     // Connect to the signer service
-    let signer_client = connect_to_signer(env_variables.signer_url.clone()).await?;
+    let signer_connection_retry = {
+        let default = SignerConnectionRetry::default();
+        SignerConnectionRetry {
+            max_attempts: config
+                .signer_connect_max_attempts
+                .unwrap_or(default.max_attempts),
+            max_delay: config
+                .signer_connect_max_delay
+                .map(Duration::from_secs)
+                .unwrap_or(default.max_delay),
+        }
+    };
+    let signer_client = connect_to_signer(
+        config.signer_url.clone(),
+        signer_connection_retry,
+        #[cfg(feature = "mtls")]
+        config.signer_tls.as_ref(),
+    )
+    .await?;
     info!("Connected to signer server.");
 
-    let liquidity_sources = liquidity_sources::LiquiditySources::init(pg_pool.clone()).await?;
+    let liquidity_sources =
+        liquidity_sources::LiquiditySources::init(pg_pool.clone(), config.starknet.clone()).await?;
+
+    // Launch the task undoing the melts whose withdrawal failed
+    let _handle = tokio::spawn(withdrawal_reconciliation::run_withdrawal_reconciliation(
+        WithdrawalReconciler::new(pg_pool.clone(), liquidity_sources.clone()),
+        Duration::from_secs(30),
+    ));
+
+    let shutdown_timeout = Duration::from_secs(
+        config
+            .shutdown_timeout
+            .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT_SECS),
+    );
+    let (shutdown_sender, shutdown_receiver) = tokio::sync::oneshot::channel::<()>();
 
     // Launch tonic server task
     let (address, grpc_future) = launch_tonic_server_task(
         pg_pool.clone(),
         signer_client,
         liquidity_sources,
-        env_variables,
+        config,
+        async {
+            let _ = shutdown_receiver.await;
+        },
     )
     .await?;
+    let mut grpc_future = std::pin::pin!(grpc_future);
 
     trace!(name: "grpc-listen", port = address.port());
 
     tokio::select! {
-        grpc_res = grpc_future => match grpc_res {
+        grpc_res = &mut grpc_future => match grpc_res {
             Ok(()) => eprintln!("gRPC task should never return"),
             Err(err) => eprintln!("gRPC task failed: {}", err),
         },
         sig = tokio::signal::ctrl_c() => match sig {
-            Ok(()) => info!("gRPC task terminated"),
+            Ok(()) => {
+                info!("Shutdown signal received, draining in-flight requests...");
+                let _ = shutdown_sender.send(());
+                match tokio::time::timeout(shutdown_timeout, grpc_future).await {
+                    Ok(Ok(())) => info!("gRPC task terminated"),
+                    Ok(Err(err)) => eprintln!("gRPC task failed during shutdown: {}", err),
+                    Err(_) => eprintln!(
+                        "gRPC task did not terminate within {}s, forcing shutdown",
+                        shutdown_timeout.as_secs()
+                    ),
+                }
+            }
             Err(err) => eprintln!("unable to listen for shutdown signal: {}", err)
         }
     };
 
+    // Export whatever metrics are still buffered before the process exits
+    if let Err(err) = meter_provider.shutdown() {
+        eprintln!("failed to flush metrics: {}", err);
+    }
+
     Ok(())
 }
@@ -25,13 +25,9 @@ impl<'de> Deserialize<'de> for Method {
         D: serde::Deserializer<'de>,
     {
         let s = <&str>::deserialize(deserializer)?;
-        match s {
-            STARKNET_STR => Ok(Method::Starknet),
-            _ => Err(serde::de::Error::invalid_value(
-                serde::de::Unexpected::Str(s),
-                &"a supported method",
-            )),
-        }
+        Method::from_str(s).map_err(|_| {
+            serde::de::Error::invalid_value(serde::de::Unexpected::Str(s), &"a supported method")
+        })
     }
 }
 
@@ -44,19 +40,52 @@ impl core::fmt::Display for Method {
 }
 
 #[derive(Debug, thiserror::Error)]
-#[error("bad method")]
-pub struct FromStrError;
+#[error("unsupported method: {0}")]
+pub struct FromStrError(pub String);
 
+/// The only place a method string is turned into a `Method`
+///
+/// Every handler receives a parsed `Method`, so adding a new one is caught
+/// by the exhaustive matches rather than by string comparisons spread around.
 impl FromStr for Method {
     type Err = FromStrError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s == STARKNET_STR {
-            return Ok(Self::Starknet);
-        };
-
-        Err(FromStrError)
+        match s {
+            STARKNET_STR => Ok(Self::Starknet),
+            _ => Err(FromStrError(s.to_string())),
+        }
     }
 }
 
 impl nuts::traits::Method for Method {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_method() {
+        assert_eq!(Method::from_str("starknet").unwrap(), Method::Starknet);
+        assert_eq!(
+            Method::from_str(&Method::Starknet.to_string()).unwrap(),
+            Method::Starknet
+        );
+
+        for invalid in ["", "Starknet", " starknet", "bitcoin"] {
+            let err = Method::from_str(invalid).unwrap_err();
+            assert_eq!(err.0, invalid);
+        }
+    }
+
+    #[test]
+    fn serde_roundtrip() {
+        let json = serde_json::to_string(&Method::Starknet).unwrap();
+        assert_eq!(json, "\"starknet\"");
+        assert_eq!(
+            serde_json::from_str::<Method>(&json).unwrap(),
+            Method::Starknet
+        );
+        assert!(serde_json::from_str::<Method>("\"bitcoin\"").is_err());
+    }
+}
@@ -59,6 +59,9 @@ impl From<Error> for Status {
             | Error::MethodNotSupported(_)
             | Error::InvalidPaymentRequest(_) => Status::invalid_argument(value.to_string()),
             Error::Inputs(error) => error.into(),
+            Error::Db(db_node::Error::ProofAlreadySpent { .. }) => {
+                Status::invalid_argument(value.to_string())
+            }
             Error::Db(error) => Status::internal(error.to_string()),
             Error::MeltDisabled => Status::failed_precondition(value.to_string()),
             Error::LiquiditySource(_) => Status::internal(value.to_string()),
@@ -3,7 +3,7 @@ use starknet_types::Unit;
 use std::collections::HashSet;
 
 use db_node::InsertSpentProofsQueryBuilder;
-use nuts::{Amount, nut00::Proof};
+use nuts::{Amount, nut00::Proof, nut01::PublicKey};
 use sqlx::PgConnection;
 
 use crate::{
@@ -18,7 +18,7 @@ pub async fn process_melt_inputs<'a>(
     keyset_cache: KeysetCache,
     inputs: &'a [Proof],
     expected_unit: Unit,
-) -> Result<(Amount, InsertSpentProofsQueryBuilder<'a>), InputsError> {
+) -> Result<(Amount, Vec<PublicKey>, InsertSpentProofsQueryBuilder<'a>), InputsError> {
     let mut secrets = HashSet::new();
     let mut query_builder = InsertSpentProofsQueryBuilder::new();
     let mut total_amount = Amount::ZERO;
@@ -60,7 +60,7 @@ pub async fn process_melt_inputs<'a>(
             .ok_or(InputsError::TotalAmountTooBig)?;
 
         // Append to insert query
-        query_builder.add_row(&y, proof);
+        query_builder.add_row(&y, proof)?;
 
         // Prepare payload for verification
         verify_proofs_request.push(signer::Proof {
@@ -71,7 +71,8 @@ pub async fn process_melt_inputs<'a>(
         });
     }
 
+    let ys = secrets.iter().copied().collect();
     run_inputs_verification_queries(conn, secrets, signer, verify_proofs_request).await?;
 
-    Ok((total_amount, query_builder))
+    Ok((total_amount, ys, query_builder))
 }
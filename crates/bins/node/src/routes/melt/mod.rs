@@ -6,6 +6,7 @@ use liquidity_source::{LiquiditySource, WithdrawInterface};
 use nuts::Amount;
 use nuts::nut00::Proof;
 use nuts::nut05::{MeltQuoteState, MeltResponse};
+use open_telemetry_tracing::quote_metrics::{QuoteTransition, record_quote_transition};
 use starknet_types::Unit;
 use tracing::{Level, event};
 use uuid::Uuid;
@@ -72,6 +73,7 @@ impl GrpcState {
             expiry,
         )
         .await?;
+        record_quote_transition(QuoteTransition::MeltCreated, unit.as_ref());
 
         Ok(nuts::nut05::MeltQuoteResponse {
             quote: quote_id,
@@ -103,6 +105,7 @@ impl GrpcState {
 
         // Check if quote is still valid
         if expiry < unix_time() {
+            record_quote_transition(QuoteTransition::MeltExpired, unit.as_ref());
             return Err(Error::QuoteExpired(quote_id));
         }
 
@@ -112,7 +115,7 @@ impl GrpcState {
         }
 
         // Process and validate inputs
-        let (total_amount, insert_spent_proof_query) = process_melt_inputs(
+        let (total_amount, ys, insert_spent_proof_query) = process_melt_inputs(
             &mut tx,
             self.signer.clone(),
             self.keyset_cache.clone(),
@@ -128,6 +131,8 @@ impl GrpcState {
 
         // Mark inputs as spent
         insert_spent_proof_query.execute(&mut tx).await?;
+        // Needed to restore them if the withdrawal fails
+        db_node::melt_quote::register_inputs(&mut tx, quote_id, &ys).await?;
         db_node::melt_quote::set_state(&mut tx, quote_id, MeltQuoteState::Pending).await?;
         tx.commit().await?;
 
@@ -153,6 +158,10 @@ impl GrpcState {
 
         // Update quote state and transfer ID
         db_node::melt_quote::set_state(&mut conn, quote_id, state).await?;
+        // Some liquidity sources settle synchronously, the others are picked up by their indexer
+        if state == MeltQuoteState::Paid {
+            record_quote_transition(QuoteTransition::MeltPaid, unit.as_ref());
+        }
 
         let meter = opentelemetry::global::meter("business");
         let n_melt_counter = meter.u64_counter("melt.operation.count").build();
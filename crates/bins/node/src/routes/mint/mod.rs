@@ -5,6 +5,7 @@ use nuts::{
     nut00::{BlindSignature, BlindedMessage},
     nut04::MintQuoteState,
 };
+use open_telemetry_tracing::quote_metrics::{QuoteTransition, record_quote_transition};
 use outputs::check_outputs_allow_single_unit;
 use thiserror::Error;
 use tonic::Status;
@@ -52,12 +53,12 @@ impl From<Error> for Status {
                 | OutputsError::InactiveKeyset(_)
                 | OutputsError::MultipleUnits
                 | OutputsError::TotalAmountTooBig
-                | OutputsError::AlreadySigned
+                | OutputsError::AlreadySigned(_)
                 | OutputsError::AmountExceedsMaxOrder(_, _, _) => {
                     Status::invalid_argument(error.to_string())
                 }
                 OutputsError::Db(sqlx::Error::RowNotFound) => Status::not_found(error.to_string()),
-                OutputsError::Db(_) | OutputsError::KeysetCache(_) => {
+                OutputsError::Db(_) | OutputsError::DbNode(_) | OutputsError::KeysetCache(_) => {
                     Status::internal(error.to_string())
                 }
                 OutputsError::Signer(status) => status,
@@ -89,7 +90,7 @@ impl GrpcState {
             return Err(Error::InvalidQuoteStateAtThisPoint(state));
         }
 
-        let total_amount =
+        let (total_amount, unit) =
             check_outputs_allow_single_unit(&mut tx, &self.keyset_cache, outputs).await?;
 
         if total_amount != expected_amount {
@@ -109,6 +110,9 @@ impl GrpcState {
 
         tx.commit().await?;
 
+        if let Some(unit) = unit {
+            record_quote_transition(QuoteTransition::MintIssued, unit.as_ref());
+        }
         event!(
             name: "mint",
             Level::INFO,
@@ -3,14 +3,18 @@ use std::collections::HashSet;
 
 use nuts::{Amount, nut00::BlindedMessage};
 use sqlx::PgConnection;
+use starknet_types::Unit;
 
-use crate::{keyset_cache::KeysetCache, logic::OutputsError};
+use crate::{
+    keyset_cache::KeysetCache,
+    logic::{OutputsError, ensure_outputs_not_already_signed},
+};
 
 pub async fn check_outputs_allow_single_unit(
     conn: &mut PgConnection,
     keyset_cache: &KeysetCache,
     outputs: &[BlindedMessage],
-) -> Result<Amount, OutputsError> {
+) -> Result<(Amount, Option<Unit>), OutputsError> {
     let mut blind_secrets = HashSet::with_capacity(outputs.len());
     let mut total_amount = Amount::ZERO;
     let mut unit = None;
@@ -43,9 +47,7 @@ pub async fn check_outputs_allow_single_unit(
     }
 
     // Make sure those outputs were not already signed
-    if db_node::is_any_blind_message_already_used(conn, blind_secrets.into_iter()).await? {
-        return Err(OutputsError::AlreadySigned);
-    }
+    ensure_outputs_not_already_signed(conn, blind_secrets.into_iter()).await?;
 
-    Ok(total_amount)
+    Ok((total_amount, unit))
 }
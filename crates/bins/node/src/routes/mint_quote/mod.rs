@@ -4,6 +4,7 @@ use nuts::{
     Amount,
     nut04::{MintQuoteResponse, MintQuoteState},
 };
+use open_telemetry_tracing::quote_metrics::{QuoteTransition, record_quote_transition};
 use sqlx::PgConnection;
 use starknet_types::Unit;
 use thiserror::Error;
@@ -80,16 +81,7 @@ impl GrpcState {
                 .ok_or(Error::UnitNotSupported(unit, method))?
         };
 
-        if let Some(min_amount) = settings.min_amount {
-            if min_amount > amount {
-                Err(Error::AmountTooLow(min_amount, amount))?;
-            }
-        }
-        if let Some(max_amount) = settings.max_amount {
-            if max_amount < amount {
-                Err(Error::AmountTooHigh(max_amount, amount))?;
-            }
-        }
+        check_amount_bounds(settings.min_amount, settings.max_amount, amount)?;
 
         let liquidity_source = self
             .liquidity_sources
@@ -108,6 +100,7 @@ impl GrpcState {
         }
         .await?;
 
+        record_quote_transition(QuoteTransition::MintCreated, unit.as_ref());
         event!(
             name: "mint-quote",
             Level::INFO,
@@ -122,6 +115,27 @@ impl GrpcState {
     }
 }
 
+/// Refuse amounts outside of the unit bounds, and zero whatever the bounds
+///
+/// Done before anything is written, a refused quote leaves no trace in db.
+fn check_amount_bounds(
+    min_amount: Option<Amount>,
+    max_amount: Option<Amount>,
+    amount: Amount,
+) -> Result<(), Error> {
+    let min_amount = min_amount.unwrap_or(Amount::ONE).max(Amount::ONE);
+    if amount < min_amount {
+        return Err(Error::AmountTooLow(min_amount, amount));
+    }
+    if let Some(max_amount) = max_amount {
+        if amount > max_amount {
+            return Err(Error::AmountTooHigh(max_amount, amount));
+        }
+    }
+
+    Ok(())
+}
+
 /// Initialize a new mint quote
 async fn create_new_mint_quote(
     conn: &mut PgConnection,
@@ -173,3 +187,41 @@ async fn create_new_mint_quote(
         expiry,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use tonic::Code;
+
+    use super::*;
+
+    fn cap() -> Amount {
+        Amount::from(1_000_000u64)
+    }
+
+    #[test]
+    fn zero_is_rejected() {
+        let err = check_amount_bounds(None, Some(cap()), Amount::ZERO).unwrap_err();
+
+        assert!(matches!(err, Error::AmountTooLow(min, _) if min == Amount::ONE));
+        assert_eq!(Status::from(err).code(), Code::InvalidArgument);
+        // Even when configured otherwise
+        assert!(check_amount_bounds(Some(Amount::ZERO), None, Amount::ZERO).is_err());
+    }
+
+    #[test]
+    fn amount_within_bounds_is_accepted() {
+        assert!(check_amount_bounds(Some(Amount::ONE), Some(cap()), Amount::from(32u64)).is_ok());
+        assert!(check_amount_bounds(Some(Amount::ONE), Some(cap()), cap()).is_ok());
+    }
+
+    #[test]
+    fn amount_over_cap_is_rejected() {
+        let err = check_amount_bounds(Some(Amount::ONE), Some(cap()), Amount::from(1_000_001u64))
+            .unwrap_err();
+
+        assert!(matches!(err, Error::AmountTooHigh(max, _) if max == cap()));
+        let status = Status::from(err);
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert!(status.message().contains("1000000"));
+    }
+}
@@ -12,15 +12,24 @@ use crate::{
     logic::{InputsError, run_inputs_verification_queries},
 };
 
+/// Total fee and amount of the inputs of one unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnitInputs {
+    pub unit: Unit,
+    pub fee: Amount,
+    pub amount: Amount,
+}
+
 pub async fn process_swap_inputs<'a>(
     conn: &mut PgConnection,
     signer: SignerClient,
     keyset_cache: KeysetCache,
     inputs: &'a [Proof],
-) -> Result<(Vec<(Unit, Amount)>, InsertSpentProofsQueryBuilder<'a>), InputsError> {
+) -> Result<(Vec<UnitInputs>, InsertSpentProofsQueryBuilder<'a>), InputsError> {
     // Input process
     let mut secrets = HashSet::new();
-    let mut amounts_per_unit: Vec<(Unit, Amount)> = Vec::new();
+    // For each unit, the sum of the inputs' fee in parts per thousand, and of their amount
+    let mut fees_and_amounts_per_unit: Vec<(Unit, u64, Amount)> = Vec::new();
     let mut query_builder = InsertSpentProofsQueryBuilder::new();
 
     let mut verify_proofs_request = Vec::with_capacity(inputs.len());
@@ -48,17 +57,24 @@ pub async fn process_swap_inputs<'a>(
             ));
         }
 
-        match amounts_per_unit.iter_mut().find(|(u, _)| *u == keyset_unit) {
-            Some((_, a)) => {
+        let input_fee_ppk = u64::from(keyset_info.input_fee_ppk());
+        match fees_and_amounts_per_unit
+            .iter_mut()
+            .find(|(u, _, _)| *u == keyset_unit)
+        {
+            Some((_, f, a)) => {
+                *f = f
+                    .checked_add(input_fee_ppk)
+                    .ok_or(InputsError::TotalFeeTooBig)?;
                 *a = a
                     .checked_add(&proof.amount)
                     .ok_or(InputsError::TotalAmountTooBig)?;
             }
-            None => amounts_per_unit.push((keyset_unit, proof.amount)),
+            None => fees_and_amounts_per_unit.push((keyset_unit, input_fee_ppk, proof.amount)),
         }
 
         // Append to insert query
-        query_builder.add_row(&y, proof);
+        query_builder.add_row(&y, proof)?;
 
         // Prepare payload for verification
         verify_proofs_request.push(signer::Proof {
@@ -71,5 +87,33 @@ pub async fn process_swap_inputs<'a>(
 
     run_inputs_verification_queries(conn, secrets, signer, verify_proofs_request).await?;
 
-    Ok((amounts_per_unit, query_builder))
+    let inputs_per_unit = fees_and_amounts_per_unit
+        .into_iter()
+        .map(|(unit, fee_ppk, amount)| UnitInputs {
+            unit,
+            fee: total_input_fee(fee_ppk),
+            amount,
+        })
+        .collect();
+
+    Ok((inputs_per_unit, query_builder))
+}
+
+/// NUT-02 fee: the sum of the inputs' fee, in parts per thousand, rounded up
+fn total_input_fee(sum_fee_ppk: u64) -> Amount {
+    Amount::from(sum_fee_ppk.div_ceil(1000))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn total_input_fee_is_rounded_up() {
+        assert_eq!(total_input_fee(0), Amount::ZERO);
+        assert_eq!(total_input_fee(1), Amount::from(1u64));
+        assert_eq!(total_input_fee(1000), Amount::from(1u64));
+        assert_eq!(total_input_fee(1001), Amount::from(2u64));
+        assert_eq!(total_input_fee(3 * 400), Amount::from(2u64));
+    }
 }
@@ -1,6 +1,7 @@
 mod inputs;
 
 use inputs::process_swap_inputs;
+use num_traits::CheckedAdd;
 use nuts::{
     Amount,
     nut00::{BlindSignature, BlindedMessage, Proof},
@@ -25,6 +26,8 @@ pub enum Error {
     TxBegin(#[source] sqlx::Error),
     #[error(transparent)]
     Sqlx(#[from] sqlx::Error),
+    #[error(transparent)]
+    Db(#[from] db_node::Error),
     // Primitive processing errors
     #[error(transparent)]
     Outputs(#[from] OutputsError),
@@ -33,8 +36,8 @@ pub enum Error {
     // Swap specific errors
     #[error("All input units should be present as output")]
     UnbalancedUnits,
-    #[error("For unit {0}, Inputs: `{1}`, Outputs: `{2}`")]
-    TransactionUnbalanced(Unit, Amount, Amount),
+    #[error("For unit {0}, Inputs: `{1}`, Outputs: `{2}`, Fee: `{3}`")]
+    TransactionUnbalanced(Unit, Amount, Amount, Amount),
     #[error("the sum off all the outputs' amount and the fee must fit in a u64")]
     TotalOutputAndFeeTooBig,
 }
@@ -45,16 +48,20 @@ impl From<Error> for Status {
             Error::TxBegin(error) | Error::TxCommit(error) | Error::Sqlx(error) => {
                 Status::internal(error.to_string())
             }
+            Error::Db(db_node::Error::ProofAlreadySpent { .. }) => {
+                Status::invalid_argument(value.to_string())
+            }
+            Error::Db(error) => Status::internal(error.to_string()),
             Error::Outputs(error) => match error {
                 OutputsError::DuplicateOutput
                 | OutputsError::MultipleUnits
                 | OutputsError::TotalAmountTooBig
-                | OutputsError::AlreadySigned
+                | OutputsError::AlreadySigned(_)
                 | OutputsError::AmountExceedsMaxOrder(_, _, _) => {
                     Status::invalid_argument(error.to_string())
                 }
                 OutputsError::Db(sqlx::Error::RowNotFound) => Status::not_found(error.to_string()),
-                OutputsError::Db(_) | OutputsError::KeysetCache(_) => {
+                OutputsError::Db(_) | OutputsError::DbNode(_) | OutputsError::KeysetCache(_) => {
                     Status::internal(error.to_string())
                 }
                 OutputsError::Signer(status) => status,
@@ -70,7 +77,7 @@ impl From<Error> for Status {
             },
             Error::Inputs(error) => error.into(),
             Error::UnbalancedUnits
-            | Error::TransactionUnbalanced(_, _, _)
+            | Error::TransactionUnbalanced(_, _, _, _)
             | Error::TotalOutputAndFeeTooBig => Status::invalid_argument(value.to_string()),
         }
     }
@@ -91,7 +98,7 @@ impl GrpcState {
                 .await
                 .map_err(Error::Outputs)?;
 
-        let (input_fees_and_amount, insert_spent_proofs_query_builder) = process_swap_inputs(
+        let (inputs_per_unit, insert_spent_proofs_query_builder) = process_swap_inputs(
             &mut tx,
             self.signer.clone(),
             self.keyset_cache.clone(),
@@ -100,18 +107,22 @@ impl GrpcState {
         .await
         .map_err(Error::Inputs)?;
 
-        // Amount matching
+        // Amount matching, the inputs have to pay for the outputs and the fee
         for (unit, output_amount) in outputs_amounts.iter() {
-            let &(_, input_amount) = input_fees_and_amount
+            let inputs = inputs_per_unit
                 .iter()
-                .find(|(u, _)| u == unit)
+                .find(|i| i.unit == *unit)
                 .ok_or(Error::UnbalancedUnits)?;
 
-            if input_amount != *output_amount {
+            let output_amount_and_fee = output_amount
+                .checked_add(&inputs.fee)
+                .ok_or(Error::TotalOutputAndFeeTooBig)?;
+            if inputs.amount != output_amount_and_fee {
                 Err(Error::TransactionUnbalanced(
                     *unit,
-                    input_amount,
+                    inputs.amount,
                     *output_amount,
+                    inputs.fee,
                 ))?;
             }
         }
@@ -147,3 +158,169 @@ impl GrpcState {
         Ok(blind_signatures)
     }
 }
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use std::str::FromStr;
+
+    use nuts::{
+        QuoteTTLConfig,
+        dhke::{blind_message, unblind_message},
+        nut00::secret::Secret,
+        nut01::PublicKey,
+        nut02::KeysetId,
+    };
+    use signer::mock::{MockSigner, in_process_channel};
+    use tonic::transport::Endpoint;
+
+    use super::*;
+    use crate::{
+        app_state::SignerClient,
+        initialization::{connect_to_db_and_run_migrations, nuts_settings::default_nuts_settings},
+        liquidity_sources::LiquiditySources,
+    };
+
+    type MockSignerClient = signer::SignerClient<tonic::transport::Channel>;
+
+    /// A node state whose `index` keyset of `Unit::MilliStrk` is declared and stored
+    ///
+    /// The mock derives its keys from a fixed seed, so the keyset is the same on every run.
+    async fn setup(index: u32) -> (GrpcState, MockSignerClient, signer::DeclareKeysetResponse) {
+        let pg_url = std::env::var("PG_URL").expect("PG_URL should be set to run the db tests");
+        let pg_pool = connect_to_db_and_run_migrations(&pg_url).await.unwrap();
+        let channel = in_process_channel(MockSigner::default()).await.unwrap();
+        let state = GrpcState::new(
+            pg_pool.clone(),
+            SignerClient::new(
+                Endpoint::from_static("http://in-process.signer"),
+                channel.clone(),
+            ),
+            default_nuts_settings(),
+            QuoteTTLConfig {
+                mint_ttl: 3600,
+                melt_ttl: 3600,
+            },
+            LiquiditySources::init(pg_pool, None).await.unwrap(),
+            None,
+        );
+        state
+            .init_first_keysets([Unit::MilliStrk].into_iter(), index, 32)
+            .await
+            .unwrap();
+
+        // Declaring again returns the same keyset
+        let mut signer = signer::SignerClient::new(channel);
+        let keyset = signer
+            .declare_keyset(signer::DeclareKeysetRequest {
+                unit: Unit::MilliStrk.to_string(),
+                index,
+                max_order: 32,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+
+        (state, signer, keyset)
+    }
+
+    async fn signed_input(
+        signer: &mut MockSignerClient,
+        keyset: &signer::DeclareKeysetResponse,
+        amount: u64,
+    ) -> Proof {
+        let key = keyset.keys.iter().find(|k| k.amount == amount).unwrap();
+        let key = PublicKey::from_str(&key.pubkey).unwrap();
+
+        let secret = Secret::generate();
+        let (blinded_secret, r) = blind_message(secret.as_bytes(), None).unwrap();
+        let blind_signature = signer
+            .sign_blinded_messages(signer::SignBlindedMessagesRequest {
+                messages: vec![signer::BlindedMessage {
+                    amount,
+                    keyset_id: keyset.keyset_id.clone(),
+                    blinded_secret: blinded_secret.to_bytes().to_vec(),
+                }],
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .signatures
+            .remove(0);
+
+        Proof {
+            amount: Amount::from(amount),
+            keyset_id: KeysetId::from_bytes(&keyset.keyset_id).unwrap(),
+            secret,
+            c: unblind_message(&PublicKey::from_slice(&blind_signature).unwrap(), &r, &key)
+                .unwrap(),
+        }
+    }
+
+    fn outputs(keyset: &signer::DeclareKeysetResponse, amounts: &[u64]) -> Vec<BlindedMessage> {
+        amounts
+            .iter()
+            .map(|&amount| BlindedMessage {
+                amount: Amount::from(amount),
+                keyset_id: KeysetId::from_bytes(&keyset.keyset_id).unwrap(),
+                blinded_secret: blind_message(Secret::generate().as_bytes(), None)
+                    .unwrap()
+                    .0,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn swap_against_mock_signer() {
+        let (state, mut signer, keyset) = setup(0).await;
+        let input = signed_input(&mut signer, &keyset, 8).await;
+
+        let signatures = state
+            .inner_swap(&[input], &outputs(&keyset, &[4, 4]))
+            .await
+            .unwrap();
+
+        assert_eq!(signatures.len(), 2);
+        assert!(signatures.iter().all(|s| s.amount == Amount::from(4u64)));
+    }
+
+    #[cfg(feature = "keyset-rotation")]
+    #[tokio::test]
+    async fn updated_input_fee_is_charged_by_swap() {
+        use node::{KeysetRotationService, UpdateKeysetInputFeeRequest};
+        use tonic::Request;
+
+        // Its own keyset, so that the fee doesn't apply to the other swap tests
+        let (state, mut signer, keyset) = setup(1).await;
+        let update_fee = |input_fee_ppk: u32| {
+            state.update_keyset_input_fee(Request::new(UpdateKeysetInputFeeRequest {
+                keyset_id: keyset.keyset_id.clone(),
+                input_fee_ppk,
+            }))
+        };
+
+        update_fee(0).await.unwrap();
+        let input = signed_input(&mut signer, &keyset, 8).await;
+        state
+            .inner_swap(&[input], &outputs(&keyset, &[4, 4]))
+            .await
+            .unwrap();
+
+        // The keyset infos are cached by now, the update has to reach the cache too
+        update_fee(1000).await.unwrap();
+        let input = signed_input(&mut signer, &keyset, 8).await;
+        let res = state
+            .inner_swap(&[input.clone()], &outputs(&keyset, &[4, 4]))
+            .await;
+        assert!(matches!(
+            res,
+            Err(Error::TransactionUnbalanced(Unit::MilliStrk, i, o, f))
+                if i == Amount::from(8u64) && o == Amount::from(8u64) && f == Amount::from(1u64)
+        ));
+
+        let signatures = state
+            .inner_swap(&[input], &outputs(&keyset, &[4, 2, 1]))
+            .await
+            .unwrap();
+        assert_eq!(signatures.len(), 3);
+    }
+}
@@ -0,0 +1,105 @@
+//! Latency of the node core RPCs
+//!
+//! Tracing spans give the duration of a single request, not its distribution.
+//! The histogram lets us alert on the p99 of each route.
+
+use std::{future::Future, time::Instant};
+
+use opentelemetry::{KeyValue, metrics::Meter};
+use tonic::Status;
+
+pub const RPC_DURATION_HISTOGRAM: &str = "rpc_duration_seconds";
+
+/// Run `handler`, recording its duration under `route`
+///
+/// Failed requests are recorded too, with an `error` outcome,
+/// so that fast rejections don't hide slow successes in the same series.
+/// The outcome is also set on the current span, for the trace export to keep every failure.
+pub async fn observe_rpc<T>(
+    route: &'static str,
+    handler: impl Future<Output = Result<T, Status>>,
+) -> Result<T, Status> {
+    observe_rpc_on(&opentelemetry::global::meter("business"), route, handler).await
+}
+
+async fn observe_rpc_on<T>(
+    meter: &Meter,
+    route: &'static str,
+    handler: impl Future<Output = Result<T, Status>>,
+) -> Result<T, Status> {
+    let start = Instant::now();
+    let result = handler.await;
+    let outcome = if result.is_ok() { "success" } else { "error" };
+    open_telemetry_tracing::outcome_filter::record_outcome(result.is_err());
+
+    meter
+        .f64_histogram(RPC_DURATION_HISTOGRAM)
+        .with_unit("s")
+        .build()
+        .record(
+            start.elapsed().as_secs_f64(),
+            &[
+                KeyValue::new("route", route),
+                KeyValue::new("outcome", outcome),
+            ],
+        );
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::metrics::MeterProvider;
+    use opentelemetry_sdk::metrics::{
+        InMemoryMetricExporter, PeriodicReader, SdkMeterProvider, data::Histogram,
+    };
+
+    use super::*;
+
+    fn observations(exporter: &InMemoryMetricExporter, route: &str, outcome: &str) -> u64 {
+        exporter
+            .get_finished_metrics()
+            .unwrap()
+            .iter()
+            .flat_map(|rm| rm.scope_metrics.iter())
+            .flat_map(|sm| sm.metrics.iter())
+            .filter(|m| m.name == RPC_DURATION_HISTOGRAM)
+            .filter_map(|m| m.data.as_any().downcast_ref::<Histogram<f64>>())
+            .flat_map(|h| h.data_points.iter())
+            .filter(|dp| {
+                let has = |key: &str, value: &str| {
+                    dp.attributes
+                        .iter()
+                        .any(|kv| kv.key.as_str() == key && kv.value.as_str() == value)
+                };
+                has("route", route) && has("outcome", outcome)
+            })
+            .map(|dp| dp.count)
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[tokio::test]
+    async fn each_invocation_is_observed_once() {
+        let exporter = InMemoryMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(exporter.clone()).build())
+            .build();
+        let meter = provider.meter("business");
+
+        observe_rpc_on(&meter, "swap", async { Ok(()) })
+            .await
+            .unwrap();
+        observe_rpc_on(&meter, "melt", async {
+            Err::<(), _>(Status::invalid_argument("Inputs cannot be empty"))
+        })
+        .await
+        .unwrap_err();
+        provider.force_flush().unwrap();
+
+        assert_eq!(observations(&exporter, "swap", "success"), 1);
+        assert_eq!(observations(&exporter, "swap", "error"), 0);
+        assert_eq!(observations(&exporter, "melt", "error"), 1);
+        assert_eq!(observations(&exporter, "mint", "success"), 0);
+    }
+}
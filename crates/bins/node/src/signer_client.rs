@@ -0,0 +1,320 @@
+//! Signer client that survives signer restarts
+//!
+//! A `tonic` channel whose peer went away keeps failing until it is rebuilt.
+//! Without this wrapper, restarting the signer would require restarting the node too.
+//! On a transport failure, the node is reported `NOT_SERVING`, the connection is rebuilt
+//! and the call is attempted one more time.
+use std::{sync::Arc, time::Duration};
+
+use node::NodeServer;
+use signer::{
+    DeclareKeysetRequest, DeclareKeysetResponse, GetRootPubKeyRequest, GetRootPubKeyResponse,
+    SignBlindedMessagesRequest, SignBlindedMessagesResponse, VerifyProofsRequest,
+    VerifyProofsResponse,
+};
+use tokio::sync::RwLock;
+use tonic::{
+    Code, Response, Status,
+    transport::{Channel, Endpoint},
+};
+use tonic_health::server::HealthReporter;
+use tower_otel::trace;
+use tracing::{Level, error, info, warn};
+
+use crate::{grpc_service::GrpcState, initialization::SignerConnectionRetry};
+
+type InnerSignerClient = signer::SignerClient<trace::Grpc<Channel>>;
+
+/// Reconnection happens while a request is waiting, so we keep it short
+const RECONNECT_RETRY: SignerConnectionRetry = SignerConnectionRetry {
+    max_attempts: 3,
+    max_delay: Duration::from_secs(1),
+};
+
+#[derive(Debug)]
+struct Connection {
+    /// Incremented on each reconnection, so that concurrent failing calls only reconnect once
+    generation: u64,
+    client: InnerSignerClient,
+}
+
+#[derive(Debug, Clone)]
+pub struct SignerClient {
+    endpoint: Endpoint,
+    connection: Arc<RwLock<Connection>>,
+    health_reporter: Arc<std::sync::OnceLock<HealthReporter>>,
+}
+
+fn wrap_channel(channel: Channel) -> InnerSignerClient {
+    let channel = tower::ServiceBuilder::new()
+        .layer(trace::GrpcLayer::client(Level::INFO))
+        .service(channel);
+
+    signer::SignerClient::new(channel)
+}
+
+/// Transport failures are surfaced by tonic as `Unavailable`, which the signer never returns itself
+fn is_transport_error(status: &Status) -> bool {
+    status.code() == Code::Unavailable
+}
+
+impl SignerClient {
+    pub fn new(endpoint: Endpoint, channel: Channel) -> Self {
+        Self {
+            endpoint,
+            connection: Arc::new(RwLock::new(Connection {
+                generation: 0,
+                client: wrap_channel(channel),
+            })),
+            health_reporter: Default::default(),
+        }
+    }
+
+    /// Let the signer connection state be reflected in the node health service
+    ///
+    /// Only the first reporter set is used.
+    pub fn set_health_reporter(&self, health_reporter: HealthReporter) {
+        let _ = self.health_reporter.set(health_reporter);
+    }
+
+    async fn report_serving(&self, serving: bool) {
+        if let Some(health_reporter) = self.health_reporter.get() {
+            if serving {
+                health_reporter.set_serving::<NodeServer<GrpcState>>().await;
+            } else {
+                health_reporter
+                    .set_not_serving::<NodeServer<GrpcState>>()
+                    .await;
+            }
+        }
+    }
+
+    /// The connection is only locked during each attempt, not while waiting between two of them,
+    /// so that the other calls are not held up by the backoff
+    async fn reconnect(&self, failed_generation: u64) -> Result<InnerSignerClient, Status> {
+        let mut delays = RECONNECT_RETRY.delays();
+        let mut attempt = 1;
+        loop {
+            let delay = {
+                let mut write_lock = self.connection.write().await;
+
+                // Another call already replaced the broken connection
+                if write_lock.generation != failed_generation {
+                    return Ok(write_lock.client.clone());
+                }
+                if attempt == 1 {
+                    self.report_serving(false).await;
+                }
+
+                match self.endpoint.connect().await {
+                    Ok(channel) => {
+                        write_lock.generation += 1;
+                        write_lock.client = wrap_channel(channel);
+                        info!(name: "signer-reconnection", generation = write_lock.generation);
+                        self.report_serving(true).await;
+
+                        return Ok(write_lock.client.clone());
+                    }
+                    Err(err) => match delays.next() {
+                        Some(delay) => {
+                            warn!(
+                                name: "signer-reconnection",
+                                attempt,
+                                max_attempts = RECONNECT_RETRY.max_attempts,
+                                retry_in_ms = delay.as_millis() as u64,
+                                error = %err,
+                            );
+                            delay
+                        }
+                        None => {
+                            error!(name: "signer-reconnection", attempts = attempt, error = %err);
+                            return Err(Status::unavailable(format!(
+                                "signer unreachable after {attempt} reconnection attempts: {err}"
+                            )));
+                        }
+                    },
+                }
+            };
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    async fn call<Req, Resp, F, Fut>(&self, request: Req, f: F) -> Result<Response<Resp>, Status>
+    where
+        Req: Clone,
+        F: Fn(InnerSignerClient, Req) -> Fut,
+        Fut: Future<Output = Result<Response<Resp>, Status>>,
+    {
+        let (generation, client) = {
+            let read_lock = self.connection.read().await;
+            (read_lock.generation, read_lock.client.clone())
+        };
+
+        match f(client, request.clone()).await {
+            Err(status) if is_transport_error(&status) => {
+                let client = self.reconnect(generation).await?;
+                f(client, request).await
+            }
+            res => res,
+        }
+    }
+
+    pub async fn declare_keyset(
+        &self,
+        request: DeclareKeysetRequest,
+    ) -> Result<Response<DeclareKeysetResponse>, Status> {
+        self.call(request, |mut client, request| async move {
+            client.declare_keyset(request).await
+        })
+        .await
+    }
+
+    pub async fn sign_blinded_messages(
+        &self,
+        request: SignBlindedMessagesRequest,
+    ) -> Result<Response<SignBlindedMessagesResponse>, Status> {
+        self.call(request, |mut client, request| async move {
+            client.sign_blinded_messages(request).await
+        })
+        .await
+    }
+
+    pub async fn verify_proofs(
+        &self,
+        request: VerifyProofsRequest,
+    ) -> Result<Response<VerifyProofsResponse>, Status> {
+        self.call(request, |mut client, request| async move {
+            client.verify_proofs(request).await
+        })
+        .await
+    }
+
+    pub async fn get_root_pub_key(
+        &self,
+        request: GetRootPubKeyRequest,
+    ) -> Result<Response<GetRootPubKeyResponse>, Status> {
+        self.call(request, |mut client, request| async move {
+            client.get_root_pub_key(request).await
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use signer::{ListKeysetsRequest, ListKeysetsResponse, Signer, SignerServer};
+    use tonic::Request;
+
+    use super::*;
+
+    const ROOT_PUBKEY: &str = "root-pubkey";
+
+    struct FakeSigner;
+
+    #[tonic::async_trait]
+    impl Signer for FakeSigner {
+        async fn declare_keyset(
+            &self,
+            _request: Request<DeclareKeysetRequest>,
+        ) -> Result<Response<DeclareKeysetResponse>, Status> {
+            Err(Status::unimplemented("fake"))
+        }
+
+        async fn sign_blinded_messages(
+            &self,
+            _request: Request<SignBlindedMessagesRequest>,
+        ) -> Result<Response<SignBlindedMessagesResponse>, Status> {
+            Err(Status::unimplemented("fake"))
+        }
+
+        async fn verify_proofs(
+            &self,
+            _request: Request<VerifyProofsRequest>,
+        ) -> Result<Response<VerifyProofsResponse>, Status> {
+            Err(Status::unimplemented("fake"))
+        }
+
+        async fn get_root_pub_key(
+            &self,
+            _request: Request<GetRootPubKeyRequest>,
+        ) -> Result<Response<GetRootPubKeyResponse>, Status> {
+            Ok(Response::new(GetRootPubKeyResponse {
+                root_pubkey: ROOT_PUBKEY.to_string(),
+            }))
+        }
+
+        async fn list_keysets(
+            &self,
+            _request: Request<ListKeysetsRequest>,
+        ) -> Result<Response<ListKeysetsResponse>, Status> {
+            Err(Status::unimplemented("fake"))
+        }
+    }
+
+    #[tokio::test]
+    async fn reconnects_once_the_signer_is_back() {
+        let address: SocketAddr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let endpoint = Endpoint::from_shared(format!("http://{address}")).unwrap();
+
+        // Nothing listens yet, so the first call fails at the transport level
+        let client = SignerClient::new(endpoint.clone(), endpoint.connect_lazy());
+        let server = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            tonic::transport::Server::builder()
+                .add_service(SignerServer::new(FakeSigner))
+                .serve(address)
+                .await
+        });
+
+        let res = client.get_root_pub_key(GetRootPubKeyRequest {}).await;
+        server.abort();
+
+        assert_eq!(res.unwrap().into_inner().root_pubkey, ROOT_PUBKEY);
+        assert_eq!(client.connection.read().await.generation, 1);
+    }
+
+    #[tokio::test]
+    async fn connection_is_not_locked_between_reconnection_attempts() {
+        let address: SocketAddr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let endpoint = Endpoint::from_shared(format!("http://{address}")).unwrap();
+        let client = SignerClient::new(endpoint.clone(), endpoint.connect_lazy());
+
+        let call = tokio::spawn({
+            let client = client.clone();
+            async move { client.get_root_pub_key(GetRootPubKeyRequest {}).await }
+        });
+        // The first attempt is refused right away, then we wait 500ms before the next one
+        tokio::time::sleep(Duration::from_millis(250)).await;
+
+        assert!(client.connection.try_write().is_ok());
+        assert!(call.await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn surfaces_unavailable_when_reconnection_fails() {
+        let address: SocketAddr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let endpoint = Endpoint::from_shared(format!("http://{address}")).unwrap();
+        let client = SignerClient::new(endpoint.clone(), endpoint.connect_lazy());
+
+        let status = client
+            .get_root_pub_key(GetRootPubKeyRequest {})
+            .await
+            .unwrap_err();
+
+        assert_eq!(status.code(), Code::Unavailable);
+        assert!(status.message().contains("reconnection attempts"));
+    }
+}
@@ -0,0 +1,160 @@
+//! Follow-up of the withdrawals sent to pay melt quotes
+//!
+//! A melt spends its inputs as soon as the withdrawal is handed to the liquidity source.
+//! If the payment then fails, the user would lose both the proofs and the funds.
+//! The withdrawals of the Pending quotes are checked at a fixed time interval,
+//! the melts whose payment reverted are undone.
+
+use std::time::Duration;
+
+use liquidity_source::{LiquiditySource, WithdrawInterface, WithdrawalStatus};
+use sqlx::PgPool;
+use starknet_types::Unit;
+use tracing::{Level, error, event};
+
+use crate::{liquidity_sources::LiquiditySources, methods::Method};
+
+pub struct WithdrawalReconciler {
+    pool: PgPool,
+    liquidity_sources: LiquiditySources<Unit>,
+}
+
+impl WithdrawalReconciler {
+    pub fn new(pool: PgPool, liquidity_sources: LiquiditySources<Unit>) -> Self {
+        Self {
+            pool,
+            liquidity_sources,
+        }
+    }
+
+    async fn reconcile(&self) -> Result<(), anyhow::Error> {
+        // Starknet is the only method for now, melt quotes don't record theirs
+        let withdrawer = self
+            .liquidity_sources
+            .get_liquidity_source(Method::Starknet)
+            .ok_or_else(|| anyhow::anyhow!("no liquidity source for method {}", Method::Starknet))?
+            .withdrawer();
+
+        let mut conn = self.pool.acquire().await?;
+        let pending_withdrawals = db_node::melt_quote::get_pending_withdrawals(&mut conn).await?;
+
+        for (quote_id, transfer_id) in pending_withdrawals {
+            match should_roll_back(&withdrawer, &transfer_id).await {
+                Ok(false) => continue,
+                Ok(true) => {}
+                Err(err) => {
+                    error!(name: "withdrawal-status", %quote_id, error = %err);
+                    continue;
+                }
+            }
+
+            let mut tx = db_node::start_db_tx_from_conn(&mut conn).await?;
+            let rolled_back =
+                db_node::melt_quote::rollback_withdrawal(&mut tx, quote_id, &transfer_id).await?;
+            tx.commit().await?;
+            // The quote got paid, or moved to another withdrawal, since it was listed
+            if !rolled_back {
+                continue;
+            }
+
+            event!(
+                name: "melt-withdrawal-reverted",
+                Level::WARN,
+                name = "melt-withdrawal-reverted",
+                %quote_id,
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Only a withdrawal known to have failed is undone, a pending one may still succeed
+async fn should_roll_back<W: WithdrawInterface>(
+    withdrawer: &W,
+    transfer_id: &[u8],
+) -> Result<bool, W::Error> {
+    Ok(withdrawer.check_withdrawal_status(transfer_id).await? == WithdrawalStatus::Reverted)
+}
+
+pub async fn run_withdrawal_reconciliation(reconciler: WithdrawalReconciler, interval: Duration) {
+    loop {
+        if let Err(err) = reconciler.reconcile().await {
+            error!(name: "withdrawal-reconciliation", error = %err);
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nuts::{Amount, nut05::MeltQuoteState};
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("stub")]
+    struct StubError;
+
+    /// Reports every withdrawal with the same status
+    struct StubWithdrawer(WithdrawalStatus);
+
+    #[async_trait::async_trait]
+    impl WithdrawInterface for StubWithdrawer {
+        type Error = StubError;
+        type Request = ();
+        type Unit = Unit;
+        type InvoiceId = [u8; 32];
+
+        fn compute_total_amount_expected(
+            &self,
+            _request: (),
+            _unit: Unit,
+            _fee: Amount,
+        ) -> Result<Amount, StubError> {
+            Err(StubError)
+        }
+
+        fn deserialize_payment_request(&self, _raw_json_string: &str) -> Result<(), StubError> {
+            Err(StubError)
+        }
+
+        async fn proceed_to_payment(
+            &mut self,
+            _quote_id: Uuid,
+            _request: (),
+            _expiry: u64,
+        ) -> Result<MeltQuoteState, StubError> {
+            Err(StubError)
+        }
+
+        async fn check_withdrawal_status(
+            &self,
+            _transfer_id: &[u8],
+        ) -> Result<WithdrawalStatus, StubError> {
+            Ok(self.0)
+        }
+    }
+
+    #[tokio::test]
+    async fn reverted_withdrawal_is_rolled_back() {
+        let transfer_id = [1u8; 32];
+
+        assert!(
+            should_roll_back(&StubWithdrawer(WithdrawalStatus::Reverted), &transfer_id)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !should_roll_back(&StubWithdrawer(WithdrawalStatus::Pending), &transfer_id)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !should_roll_back(&StubWithdrawer(WithdrawalStatus::Succeeded), &transfer_id)
+                .await
+                .unwrap()
+        );
+    }
+}
@@ -0,0 +1,34 @@
+#!/usr/bin/env sh
+# Regenerates the self-signed certificates used by the node tls tests.
+# They are test fixtures only, never use them outside of tests.
+set -eu
+cd "$(dirname "$0")"
+
+key() {
+    openssl genpkey -algorithm EC -pkeyopt ec_paramgen_curve:P-256 -out "$1.key"
+}
+
+ca() {
+    key "$1"
+    openssl req -x509 -new -key "$1.key" -out "$1.pem" -days 36500 -subj "/CN=$1" \
+        -addext "basicConstraints=critical,CA:TRUE" \
+        -addext "keyUsage=critical,keyCertSign,cRLSign"
+}
+
+# leaf <name> <signing ca> <extensions>
+leaf() {
+    key "$1"
+    openssl req -new -key "$1.key" -out "$1.csr" -subj "/CN=$1"
+    printf "basicConstraints=critical,CA:FALSE\n%s\n" "$3" > "$1.ext"
+    openssl x509 -req -in "$1.csr" -CA "$2.pem" -CAkey "$2.key" -CAcreateserial \
+        -out "$1.pem" -days 36500 -extfile "$1.ext"
+    rm "$1.csr" "$1.ext"
+}
+
+ca ca
+ca unknown_ca
+leaf server ca "subjectAltName=DNS:localhost,IP:127.0.0.1
+extendedKeyUsage=serverAuth"
+leaf client ca "extendedKeyUsage=clientAuth"
+leaf unknown_client unknown_ca "extendedKeyUsage=clientAuth"
+rm -f ./*.srl ca.key unknown_ca.key
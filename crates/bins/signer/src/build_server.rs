@@ -11,6 +11,8 @@ pub fn build_server() -> Result<Server, anyhow::Error> {
 pub fn build_server() -> Result<Server, anyhow::Error> {
     const CERT_PATH_ENV_VAR: &str = "TLS_CERT_PATH";
     const KEY_PATH_ENV_VAR: &str = "TLS_KEY_PATH";
+    #[cfg(feature = "mtls")]
+    const CLIENT_CA_PATH_ENV_VAR: &str = "TLS_CLIENT_CA_PATH";
 
     // Get certificate and key paths from environment or use defaults
     let cert_path =
@@ -62,6 +64,20 @@ pub fn build_server() -> Result<Server, anyhow::Error> {
     let identity = tonic::transport::Identity::from_pem(cert, key);
     let tls_config = tonic::transport::ServerTlsConfig::new().identity(identity);
 
+    // Only the holders of a certificate signed by this CA (the node) will be able to connect
+    #[cfg(feature = "mtls")]
+    let tls_config = match std::env::var(CLIENT_CA_PATH_ENV_VAR) {
+        Ok(client_ca_path) => {
+            let client_ca = std::fs::read(&client_ca_path).map_err(|e| {
+                anyhow::anyhow!("Failed to load TLS client CA {}: {}", client_ca_path, e)
+            })?;
+            tracing::info!("🪪 Client certificates required, CA: {}", client_ca_path);
+
+            tls_config.client_ca_root(tonic::transport::Certificate::from_pem(client_ca))
+        }
+        Err(_) => tls_config,
+    };
+
     tracing::info!("🔒 Starting gRPC server with TLS...");
     tracing::info!("📜 Certificate: {}", cert_path);
     tracing::info!("🔑 Private key: {}", key_path);
@@ -1,6 +1,9 @@
 mod server_errors;
 pub use server_errors::Error;
 
+#[cfg(feature = "mock")]
+pub mod mock;
+
 pub use proto::bdhke::{BlindSignature, BlindedMessage, Proof};
 pub use proto::signer::signer_client::SignerClient;
 pub use proto::signer::signer_server::{Signer, SignerServer};
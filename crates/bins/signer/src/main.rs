@@ -1,22 +1,24 @@
-use bitcoin::bip32::Xpriv;
+use bitcoin::bip32::{DerivationPath, Xpriv};
 use nuts::{
     Amount,
-    dhke::{sign_message, verify_message},
+    dhke::{BatchError, sign_messages, verify_messages},
     nut01::{PublicKey, SetKeyPairs},
     nut02::{KeysetId, MintKeySet},
 };
 use server_errors::{Error, VerifyProofError, VerifyProofsErrors};
 use signer::{
     DeclareKeysetRequest, DeclareKeysetResponse, GetRootPubKeyRequest, GetRootPubKeyResponse, Key,
-    SignBlindedMessagesRequest, SignBlindedMessagesResponse, SignerServer, VerifyProofsRequest,
-    VerifyProofsResponse,
+    KeysetInfo, ListKeysetsRequest, ListKeysetsResponse, SignBlindedMessagesRequest,
+    SignBlindedMessagesResponse, SignerServer, VerifyProofsRequest, VerifyProofsResponse,
+};
+use state::{
+    DerivationConfig, KeysetMetadata, SharedKeySetCache, SharedKeysetMetadata, SharedRootKey,
 };
-use state::{SharedKeySetCache, SharedRootKey};
 use std::{collections::HashMap, net::SocketAddr, str::FromStr, sync::Arc};
 use tokio::sync::RwLock;
 use tonic::{Request, Response, Status, service::LayerExt};
 use tower::ServiceBuilder;
-use tracing::{instrument, trace};
+use tracing::{Span, instrument, trace};
 
 mod build_server;
 mod server_errors;
@@ -25,17 +27,26 @@ mod state;
 use build_server::build_server;
 
 const ROOT_KEY_ENV_VAR: &str = "ROOT_KEY";
+/// Optional, defaults to `m/0'`. Changing it on a running deployment makes existing keysets unrecoverable.
+const DERIVATION_PATH_PREFIX_ENV_VAR: &str = "DERIVATION_PATH_PREFIX";
 const GRPC_PORT_ENV_VAR: &str = "GRPC_PORT";
+/// Optional, defaults to `DEFAULT_MAX_BATCH_SIZE`
+const MAX_BATCH_SIZE_ENV_VAR: &str = "MAX_BATCH_SIZE";
+/// Well above the 64 outputs or inputs a node accepts per request
+const DEFAULT_MAX_BATCH_SIZE: usize = 1024;
 
 #[derive(Debug)]
 pub struct SignerState {
     root_key: SharedRootKey,
     keyset_cache: SharedKeySetCache,
+    keyset_metadata: SharedKeysetMetadata,
+    /// Bounds the crypto work a single sign or verify request can ask for
+    max_batch_size: usize,
 }
 
 #[tonic::async_trait]
 impl signer::Signer for SignerState {
-    #[instrument]
+    #[instrument(skip_all, fields(keyset_id))]
     async fn declare_keyset(
         &self,
         declare_keyset_request: Request<DeclareKeysetRequest>,
@@ -67,9 +78,20 @@ impl signer::Signer for SignerState {
             self.keyset_cache
                 .insert(keyset.id, keyset.keys.clone())
                 .await;
+            self.keyset_metadata
+                .insert(
+                    keyset.id,
+                    KeysetMetadata {
+                        unit: declare_keyset_request.unit.clone(),
+                        index: declare_keyset_request.index,
+                        max_order: declare_keyset_request.max_order,
+                    },
+                )
+                .await;
 
             keyset
         };
+        Span::current().record("keyset_id", tracing::field::display(keyset.id));
 
         Ok(Response::new(DeclareKeysetResponse {
             keyset_id: keyset.id.to_bytes().to_vec(),
@@ -84,19 +106,34 @@ impl signer::Signer for SignerState {
         }))
     }
 
-    #[instrument]
+    #[instrument(skip_all, fields(keyset_id, batch_size))]
     async fn sign_blinded_messages(
         &self,
         sign_blinded_messages_request: Request<SignBlindedMessagesRequest>,
     ) -> Result<Response<SignBlindedMessagesResponse>, Status> {
         let blinded_messages = sign_blinded_messages_request.into_inner().messages;
+        record_batch_fields(
+            blinded_messages.len(),
+            blinded_messages.first().map(|m| m.keyset_id.as_slice()),
+        );
+        if blinded_messages.len() > self.max_batch_size {
+            return Err(Error::BatchTooLarge(
+                "messages",
+                blinded_messages.len(),
+                self.max_batch_size,
+            ))?;
+        }
 
-        let mut signatures = Vec::with_capacity(blinded_messages.len());
+        let mut keys_and_messages = Vec::with_capacity(blinded_messages.len());
 
         let keyset_cache_read_lock = self.keyset_cache.0.read().await;
 
+        // Validate everything before signing anything
         for (idx, blinded_message) in blinded_messages.into_iter().enumerate() {
             let amount = Amount::from(blinded_message.amount);
+            if blinded_message.amount == 0 {
+                return Err(Error::ZeroAmount(idx))?;
+            }
             if !blinded_message.amount.is_power_of_two() {
                 return Err(Error::AmountNotPowerOfTwo(idx, amount))?;
             }
@@ -128,46 +165,63 @@ impl signer::Signer for SignerState {
             let blind_secret = PublicKey::from_slice(&blinded_message.blinded_secret)
                 .map_err(|e| Error::BadSecret(idx, e))?;
 
-            let c = sign_message(&key_pair.secret_key, &blind_secret)
-                .map_err(|e| Error::CouldNotSignMessage(idx, blind_secret, e))?;
-
-            signatures.push(c.to_bytes().to_vec());
+            keys_and_messages.push((&key_pair.secret_key, blind_secret));
         }
 
+        let signatures = sign_messages(keys_and_messages.iter().map(|(k, b)| (*k, b)))
+            .map_err(|BatchError { index, source }| {
+                Error::CouldNotSignMessage(index, keys_and_messages[index].1, source)
+            })?
+            .into_iter()
+            .map(|c| c.to_bytes().to_vec())
+            .collect();
+
         Ok(Response::new(SignBlindedMessagesResponse { signatures }))
     }
 
-    #[instrument]
+    #[instrument(skip_all, fields(keyset_id, batch_size))]
     async fn verify_proofs(
         &self,
         verify_proofs_request: Request<VerifyProofsRequest>,
     ) -> Result<Response<VerifyProofsResponse>, Status> {
         let proofs = verify_proofs_request.into_inner().proofs;
+        record_batch_fields(proofs.len(), proofs.first().map(|p| p.keyset_id.as_slice()));
+        if proofs.len() > self.max_batch_size {
+            return Err(Error::BatchTooLarge(
+                "proofs",
+                proofs.len(),
+                self.max_batch_size,
+            ))?;
+        }
         let mut validation_errors = Vec::new();
         let mut invalid_proof_indices = Vec::new();
 
         let keyset_cache_read_lock = self.keyset_cache.0.read().await;
 
+        let mut validated_proofs = Vec::with_capacity(proofs.len());
         for (idx, proof) in proofs.into_iter().enumerate() {
             match validate_single_proof(&proof, &keyset_cache_read_lock) {
-                Ok(validated_proof) => {
-                    match verify_message(
-                        &validated_proof.secret_key,
-                        validated_proof.signature,
-                        validated_proof.secret.as_bytes(),
-                    ) {
-                        Ok(false) => invalid_proof_indices.push(idx as u32),
-                        Ok(true) => {}
-                        Err(error) => {
-                            tracing::error!(name: "verify-message", error = %error);
-                            invalid_proof_indices.push(idx as u32)
-                        }
-                    }
-                }
+                Ok(validated_proof) => validated_proofs.push((idx, validated_proof)),
                 Err(validation_error) => validation_errors.push((idx, validation_error)),
             }
         }
 
+        let verification_results = verify_messages(
+            validated_proofs
+                .iter()
+                .map(|(_, p)| (&p.secret_key, p.signature, p.secret.as_bytes())),
+        );
+        for ((idx, _), result) in validated_proofs.iter().zip(verification_results) {
+            match result {
+                Ok(false) => invalid_proof_indices.push(*idx as u32),
+                Ok(true) => {}
+                Err(error) => {
+                    tracing::error!(name: "verify-message", error = %error);
+                    invalid_proof_indices.push(*idx as u32)
+                }
+            }
+        }
+
         if validation_errors.is_empty() {
             Ok(Response::new(VerifyProofsResponse {
                 invalid_proof_indices,
@@ -177,7 +231,7 @@ impl signer::Signer for SignerState {
         }
     }
 
-    #[instrument]
+    #[instrument(skip_all)]
     async fn get_root_pub_key(
         &self,
         _get_root_pub_key_request: tonic::Request<GetRootPubKeyRequest>,
@@ -188,6 +242,41 @@ impl signer::Signer for SignerState {
             root_pubkey: pub_key.to_string(),
         }))
     }
+
+    #[instrument(skip_all)]
+    async fn list_keysets(
+        &self,
+        _list_keysets_request: Request<ListKeysetsRequest>,
+    ) -> Result<Response<ListKeysetsResponse>, Status> {
+        let keysets = self
+            .keyset_metadata
+            .0
+            .read()
+            .await
+            .iter()
+            .map(|(keyset_id, metadata)| KeysetInfo {
+                keyset_id: keyset_id.to_bytes().to_vec(),
+                unit: metadata.unit.clone(),
+                index: metadata.index,
+                max_order: metadata.max_order,
+            })
+            .collect();
+
+        Ok(Response::new(ListKeysetsResponse { keysets }))
+    }
+}
+
+/// Describe the batch being processed on the current span
+///
+/// Spans only get these explicit fields: `self` holds the root key and the keysets secret keys,
+/// none of it may end up in traces.
+/// Batches almost always target a single keyset, so the first item's one is representative.
+fn record_batch_fields(batch_size: usize, first_keyset_id: Option<&[u8]>) {
+    let span = Span::current();
+    span.record("batch_size", batch_size);
+    if let Some(keyset_id) = first_keyset_id.and_then(|id| KeysetId::from_bytes(id).ok()) {
+        span.record("keyset_id", tracing::field::display(keyset_id));
+    }
 }
 
 struct ValidatedProof {
@@ -204,6 +293,9 @@ fn validate_single_proof(
         .map_err(|e| VerifyProofError::BadKeysetId(proof.keyset_id.clone(), e))?;
 
     let amount = Amount::from(proof.amount);
+    if proof.amount == 0 {
+        return Err(VerifyProofError::ZeroAmount);
+    }
     if !proof.amount.is_power_of_two() {
         return Err(VerifyProofError::AmountNotPowerOfTwo(amount));
     }
@@ -242,9 +334,22 @@ fn validate_single_proof(
 async fn main() -> Result<(), anyhow::Error> {
     const PKG_NAME: &str = env!("CARGO_PKG_NAME");
     const PKG_VERSION: &str = env!("CARGO_PKG_VERSION");
+    #[cfg(not(feature = "prometheus"))]
     let (meter_provider, subscriber) = open_telemetry_tracing::init(PKG_NAME, PKG_VERSION);
+    #[cfg(feature = "prometheus")]
+    let (meter_provider, subscriber, prometheus_endpoint) =
+        open_telemetry_tracing::init_with_prometheus(PKG_NAME, PKG_VERSION)?;
     tracing::subscriber::set_global_default(subscriber).unwrap();
     opentelemetry::global::set_meter_provider(meter_provider);
+    #[cfg(feature = "prometheus")]
+    if let Some(endpoint) = prometheus_endpoint {
+        tracing::info!("Serving Prometheus metrics on {}/metrics", endpoint.address);
+        tokio::spawn(async move {
+            if let Err(err) = endpoint.serve().await {
+                tracing::error!("Prometheus endpoint failed: {}", err);
+            }
+        });
+    }
 
     #[cfg(debug_assertions)]
     {
@@ -264,9 +369,25 @@ async fn main() -> Result<(), anyhow::Error> {
             .expect("content of `ROOT_KEY` env var should be a valid private key")
     };
 
+    let derivation_config = match std::env::var(DERIVATION_PATH_PREFIX_ENV_VAR) {
+        Ok(prefix) => DerivationConfig::new(DerivationPath::from_str(&prefix).expect(
+            "content of `DERIVATION_PATH_PREFIX` env var should be a valid derivation path",
+        )),
+        Err(_) => DerivationConfig::default(),
+    };
+
+    let max_batch_size = match std::env::var(MAX_BATCH_SIZE_ENV_VAR) {
+        Ok(max_batch_size) => max_batch_size
+            .parse()
+            .expect("content of `MAX_BATCH_SIZE` env var should be a positive integer"),
+        Err(_) => DEFAULT_MAX_BATCH_SIZE,
+    };
+
     let signer_logic = SignerState {
-        root_key: SharedRootKey(Arc::new(root_private_key)),
+        root_key: SharedRootKey::new(root_private_key, derivation_config),
         keyset_cache: SharedKeySetCache(Arc::new(RwLock::new(HashMap::new()))),
+        keyset_metadata: SharedKeysetMetadata::default(),
+        max_batch_size,
     };
 
     let signer_server_service = ServiceBuilder::new()
@@ -299,3 +420,274 @@ fn create_new_starknet_keyset(
 ) -> MintKeySet<starknet_types::Unit> {
     root_key.generate_keyset(unit, index, max_order)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fmt::{Debug, Write},
+        sync::Mutex,
+    };
+
+    use nuts::dhke::blind_message;
+    use signer::{BlindedMessage, Signer};
+    use tracing::{
+        Subscriber,
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+    };
+    use tracing_subscriber::{Layer, layer::Context, prelude::*};
+
+    use super::*;
+
+    /// Write every span field it sees in a shared buffer
+    #[derive(Clone, Default)]
+    struct SpanFields(Arc<Mutex<String>>);
+
+    impl Visit for SpanFields {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            let _ = write!(self.0.lock().unwrap(), "{}={:?} ", field.name(), value);
+        }
+    }
+
+    impl<S: Subscriber> Layer<S> for SpanFields {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+            attrs.record(&mut self.clone());
+        }
+
+        fn on_record(&self, _id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+            values.record(&mut self.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn spans_hold_batch_info_but_no_key_material() {
+        let root_key = Xpriv::new_master(bitcoin::Network::Bitcoin, &[7u8; 32]).unwrap();
+        let state = SignerState {
+            root_key: SharedRootKey::new(root_key, DerivationConfig::default()),
+            keyset_cache: Default::default(),
+            keyset_metadata: Default::default(),
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+        };
+
+        let span_fields = SpanFields::default();
+        let _guard = tracing_subscriber::registry()
+            .with(span_fields.clone())
+            .set_default();
+
+        let keyset = state
+            .declare_keyset(Request::new(DeclareKeysetRequest {
+                unit: starknet_types::Unit::MilliStrk.as_ref().to_string(),
+                index: 0,
+                max_order: 32,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        let keyset_id = KeysetId::from_bytes(&keyset.keyset_id).unwrap();
+
+        let messages = (0..3)
+            .map(|i| BlindedMessage {
+                amount: 1,
+                keyset_id: keyset.keyset_id.clone(),
+                blinded_secret: blind_message(format!("secret {i}").as_bytes(), None)
+                    .unwrap()
+                    .0
+                    .to_bytes()
+                    .to_vec(),
+            })
+            .collect();
+        state
+            .sign_blinded_messages(Request::new(SignBlindedMessagesRequest { messages }))
+            .await
+            .unwrap();
+
+        let captured = span_fields.0.lock().unwrap().clone();
+        assert!(captured.contains("batch_size=3"));
+        assert!(captured.contains(&keyset_id.to_string()));
+        assert!(!captured.contains(&root_key.to_string()));
+        let keyset_secret_keys = state.keyset_cache.0.read().await[&keyset_id]
+            .values()
+            .map(|key_pair| key_pair.secret_key.to_secret_hex())
+            .collect::<Vec<_>>();
+        assert!(keyset_secret_keys.iter().all(|sk| !captured.contains(sk)));
+    }
+
+    async fn state_with_keyset() -> (SignerState, Vec<u8>) {
+        let root_key = Xpriv::new_master(bitcoin::Network::Bitcoin, &[7u8; 32]).unwrap();
+        let state = SignerState {
+            root_key: SharedRootKey::new(root_key, DerivationConfig::default()),
+            keyset_cache: Default::default(),
+            keyset_metadata: Default::default(),
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+        };
+        let keyset_id = state
+            .declare_keyset(Request::new(DeclareKeysetRequest {
+                unit: starknet_types::Unit::MilliStrk.as_ref().to_string(),
+                index: 0,
+                max_order: 8,
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .keyset_id;
+
+        (state, keyset_id)
+    }
+
+    fn blinded_message(keyset_id: &[u8], amount: u64) -> BlindedMessage {
+        BlindedMessage {
+            amount,
+            keyset_id: keyset_id.to_vec(),
+            blinded_secret: blind_message(b"secret", None)
+                .unwrap()
+                .0
+                .to_bytes()
+                .to_vec(),
+        }
+    }
+
+    async fn sign_amount(
+        state: &SignerState,
+        keyset_id: &[u8],
+        amount: u64,
+    ) -> Result<Response<SignBlindedMessagesResponse>, Status> {
+        state
+            .sign_blinded_messages(Request::new(SignBlindedMessagesRequest {
+                messages: vec![blinded_message(keyset_id, amount)],
+            }))
+            .await
+    }
+
+    #[tokio::test]
+    async fn sign_rejects_zero_and_non_power_of_two_amounts() {
+        let (state, keyset_id) = state_with_keyset().await;
+
+        let status = sign_amount(&state, &keyset_id, 0).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert_eq!(status.message(), "amount is zero");
+
+        let status = sign_amount(&state, &keyset_id, 3).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert_eq!(status.message(), "amount is not a power of two");
+
+        let signatures = sign_amount(&state, &keyset_id, 4)
+            .await
+            .unwrap()
+            .into_inner()
+            .signatures;
+        assert_eq!(signatures.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn verify_rejects_zero_and_non_power_of_two_amounts() {
+        let (state, keyset_id) = state_with_keyset().await;
+        let keyset_cache = state.keyset_cache.0.read().await;
+        let proof = |amount| signer::Proof {
+            amount,
+            keyset_id: keyset_id.clone(),
+            secret: "secret".to_string(),
+            unblind_signature: blind_message(b"signature", None)
+                .unwrap()
+                .0
+                .to_bytes()
+                .to_vec(),
+        };
+
+        assert!(matches!(
+            validate_single_proof(&proof(0), &keyset_cache),
+            Err(VerifyProofError::ZeroAmount)
+        ));
+        assert!(matches!(
+            validate_single_proof(&proof(3), &keyset_cache),
+            Err(VerifyProofError::AmountNotPowerOfTwo(amount)) if amount == Amount::from(3u64)
+        ));
+        assert!(validate_single_proof(&proof(4), &keyset_cache).is_ok());
+    }
+
+    #[tokio::test]
+    async fn batches_over_the_limit_are_rejected() {
+        let (mut state, keyset_id) = state_with_keyset().await;
+        state.max_batch_size = 2;
+
+        let sign = |n: usize| SignBlindedMessagesRequest {
+            messages: (0..n).map(|_| blinded_message(&keyset_id, 1)).collect(),
+        };
+        let signatures = state
+            .sign_blinded_messages(Request::new(sign(2)))
+            .await
+            .unwrap()
+            .into_inner()
+            .signatures;
+        assert_eq!(signatures.len(), 2);
+        let status = state
+            .sign_blinded_messages(Request::new(sign(3)))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert_eq!(status.message(), "batch too large");
+
+        let verify = |n: usize| VerifyProofsRequest {
+            proofs: (0..n)
+                .map(|_| signer::Proof {
+                    amount: 1,
+                    keyset_id: keyset_id.clone(),
+                    secret: "secret".to_string(),
+                    unblind_signature: blind_message(b"signature", None)
+                        .unwrap()
+                        .0
+                        .to_bytes()
+                        .to_vec(),
+                })
+                .collect(),
+        };
+        assert!(state.verify_proofs(Request::new(verify(2))).await.is_ok());
+        let status = state
+            .verify_proofs(Request::new(verify(3)))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert_eq!(status.message(), "batch too large");
+    }
+
+    #[tokio::test]
+    async fn list_keysets_returns_declared_keysets_metadata() {
+        let (state, first_keyset_id) = state_with_keyset().await;
+        let second_keyset_id = state
+            .declare_keyset(Request::new(DeclareKeysetRequest {
+                unit: starknet_types::Unit::MilliStrk.as_ref().to_string(),
+                index: 1,
+                max_order: 16,
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .keyset_id;
+
+        let mut keysets = state
+            .list_keysets(Request::new(ListKeysetsRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .keysets;
+        keysets.sort_by_key(|k| k.index);
+
+        let unit = starknet_types::Unit::MilliStrk.as_ref().to_string();
+        assert_eq!(
+            keysets,
+            vec![
+                KeysetInfo {
+                    keyset_id: first_keyset_id,
+                    unit: unit.clone(),
+                    index: 0,
+                    max_order: 8,
+                },
+                KeysetInfo {
+                    keyset_id: second_keyset_id,
+                    unit,
+                    index: 1,
+                    max_order: 16,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,262 @@
+//! In-process signer for tests
+//!
+//! Running the real signer requires a `ROOT_KEY` and a network port.
+//! `MockSigner` derives its keys from a fixed seed and really signs and verifies,
+//! so the signatures it returns are accepted by wallets and by the node.
+//! It performs none of the request validation of the real signer.
+
+use std::{collections::HashMap, str::FromStr, sync::Arc};
+
+use bitcoin::{
+    bip32::{ChildNumber, DerivationPath},
+    key::Secp256k1,
+};
+use hyper_util::rt::TokioIo;
+use nuts::{
+    Amount,
+    dhke::{sign_message, verify_message},
+    nut01::{PublicKey, SetKeyPairs},
+    nut02::{KeysetId, MintKeySet},
+};
+use tokio::sync::RwLock;
+use tonic::{
+    Request, Response, Status,
+    transport::{Channel, Endpoint, Server, Uri},
+};
+use tower::service_fn;
+
+use crate::{
+    DeclareKeysetRequest, DeclareKeysetResponse, GetRootPubKeyRequest, GetRootPubKeyResponse, Key,
+    KeysetInfo, ListKeysetsRequest, ListKeysetsResponse, SignBlindedMessagesRequest,
+    SignBlindedMessagesResponse, Signer, SignerServer, VerifyProofsRequest, VerifyProofsResponse,
+};
+
+const SEED: [u8; 32] = [42; 32];
+
+#[derive(Debug, Default, Clone)]
+pub struct MockSigner {
+    keysets: Arc<RwLock<HashMap<KeysetId, (KeysetInfo, SetKeyPairs)>>>,
+}
+
+fn invalid_argument(e: impl ToString) -> Status {
+    Status::invalid_argument(e.to_string())
+}
+
+impl MockSigner {
+    async fn secret_key(
+        &self,
+        keyset_id: &[u8],
+        amount: u64,
+    ) -> Result<nuts::nut01::SecretKey, Status> {
+        let keyset_id = KeysetId::from_bytes(keyset_id).map_err(invalid_argument)?;
+        let keysets = self.keysets.read().await;
+        let (_, keys) = keysets
+            .get(&keyset_id)
+            .ok_or_else(|| Status::not_found("keyset not found"))?;
+
+        keys.get(&Amount::from(amount))
+            .map(|key_pair| key_pair.secret_key.clone())
+            .ok_or_else(|| Status::not_found("amount not found"))
+    }
+}
+
+#[tonic::async_trait]
+impl Signer for MockSigner {
+    async fn declare_keyset(
+        &self,
+        request: Request<DeclareKeysetRequest>,
+    ) -> Result<Response<DeclareKeysetResponse>, Status> {
+        let request = request.into_inner();
+        let unit = starknet_types::Unit::from_str(&request.unit).map_err(invalid_argument)?;
+        let max_order = u8::try_from(request.max_order).map_err(invalid_argument)?;
+        let derivation_path = DerivationPath::from(vec![
+            ChildNumber::from_hardened_idx(0).map_err(invalid_argument)?,
+            ChildNumber::from_hardened_idx(unit.into()).map_err(invalid_argument)?,
+            ChildNumber::from_hardened_idx(request.index).map_err(invalid_argument)?,
+        ]);
+        let keyset = MintKeySet::generate_from_seed(
+            &Secp256k1::new(),
+            &SEED,
+            max_order,
+            unit,
+            derivation_path,
+        );
+
+        let response = DeclareKeysetResponse {
+            keyset_id: keyset.id.to_bytes().to_vec(),
+            keys: keyset
+                .keys
+                .iter()
+                .map(|(&amount, key_pair)| Key {
+                    amount: amount.into(),
+                    pubkey: key_pair.public_key.to_string(),
+                })
+                .collect(),
+        };
+        let info = KeysetInfo {
+            keyset_id: response.keyset_id.clone(),
+            unit: request.unit,
+            index: request.index,
+            max_order: request.max_order,
+        };
+        self.keysets
+            .write()
+            .await
+            .insert(keyset.id, (info, keyset.keys));
+
+        Ok(Response::new(response))
+    }
+
+    async fn sign_blinded_messages(
+        &self,
+        request: Request<SignBlindedMessagesRequest>,
+    ) -> Result<Response<SignBlindedMessagesResponse>, Status> {
+        let mut signatures = Vec::new();
+        for message in request.into_inner().messages {
+            let secret_key = self.secret_key(&message.keyset_id, message.amount).await?;
+            let blinded_secret =
+                PublicKey::from_slice(&message.blinded_secret).map_err(invalid_argument)?;
+            let signature = sign_message(&secret_key, &blinded_secret).map_err(invalid_argument)?;
+            signatures.push(signature.to_bytes().to_vec());
+        }
+
+        Ok(Response::new(SignBlindedMessagesResponse { signatures }))
+    }
+
+    async fn verify_proofs(
+        &self,
+        request: Request<VerifyProofsRequest>,
+    ) -> Result<Response<VerifyProofsResponse>, Status> {
+        let mut invalid_proof_indices = Vec::new();
+        for (idx, proof) in request.into_inner().proofs.into_iter().enumerate() {
+            let secret_key = self.secret_key(&proof.keyset_id, proof.amount).await?;
+            let signature =
+                PublicKey::from_slice(&proof.unblind_signature).map_err(invalid_argument)?;
+            if !verify_message(&secret_key, signature, proof.secret.as_bytes()).unwrap_or(false) {
+                invalid_proof_indices.push(idx as u32);
+            }
+        }
+
+        Ok(Response::new(VerifyProofsResponse {
+            invalid_proof_indices,
+        }))
+    }
+
+    async fn get_root_pub_key(
+        &self,
+        _request: Request<GetRootPubKeyRequest>,
+    ) -> Result<Response<GetRootPubKeyResponse>, Status> {
+        let root_key = bitcoin::bip32::Xpriv::new_master(bitcoin::Network::Bitcoin, &SEED)
+            .expect("seed is valid");
+
+        Ok(Response::new(GetRootPubKeyResponse {
+            root_pubkey: root_key
+                .private_key
+                .public_key(&Secp256k1::new())
+                .to_string(),
+        }))
+    }
+
+    async fn list_keysets(
+        &self,
+        _request: Request<ListKeysetsRequest>,
+    ) -> Result<Response<ListKeysetsResponse>, Status> {
+        let keysets = self
+            .keysets
+            .read()
+            .await
+            .values()
+            .map(|(info, _)| info.clone())
+            .collect();
+
+        Ok(Response::new(ListKeysetsResponse { keysets }))
+    }
+}
+
+/// Serve `signer` on an in-memory duplex stream and return a channel connected to it
+///
+/// The server task lives as long as the channel. The connection cannot be re-established once dropped.
+pub async fn in_process_channel(signer: MockSigner) -> Result<Channel, tonic::transport::Error> {
+    let (client, server) = tokio::io::duplex(1024 * 1024);
+
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(SignerServer::new(signer))
+            .serve_with_incoming(tokio_stream::once(Ok::<_, std::io::Error>(server)))
+            .await
+    });
+
+    let mut client = Some(client);
+    Endpoint::from_static("http://in-process.signer")
+        .connect_with_connector(service_fn(move |_: Uri| {
+            let client = client.take();
+            async move {
+                client
+                    .map(TokioIo::new)
+                    .ok_or_else(|| std::io::Error::other("in-process signer stream already taken"))
+            }
+        }))
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use nuts::{
+        dhke::{blind_message, unblind_message},
+        nut00::secret::Secret,
+    };
+
+    use super::*;
+    use crate::{BlindedMessage, Proof, SignerClient};
+
+    #[tokio::test]
+    async fn signatures_verify_through_the_channel() {
+        let mut client =
+            SignerClient::new(in_process_channel(MockSigner::default()).await.unwrap());
+        let keyset = client
+            .declare_keyset(DeclareKeysetRequest {
+                unit: starknet_types::Unit::MilliStrk.as_ref().to_string(),
+                index: 0,
+                max_order: 8,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let pubkey = PublicKey::from_str(&keyset.keys[2].pubkey).unwrap();
+        let amount = keyset.keys[2].amount;
+
+        let secret = Secret::generate();
+        let (blinded_secret, r) = blind_message(secret.as_bytes(), None).unwrap();
+        let signature = client
+            .sign_blinded_messages(SignBlindedMessagesRequest {
+                messages: vec![BlindedMessage {
+                    amount,
+                    keyset_id: keyset.keyset_id.clone(),
+                    blinded_secret: blinded_secret.to_bytes().to_vec(),
+                }],
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .signatures
+            .remove(0);
+        let unblinded =
+            unblind_message(&PublicKey::from_slice(&signature).unwrap(), &r, &pubkey).unwrap();
+
+        let proof = |unblind_signature: PublicKey| Proof {
+            amount,
+            keyset_id: keyset.keyset_id.clone(),
+            secret: secret.to_string(),
+            unblind_signature: unblind_signature.to_bytes().to_vec(),
+        };
+        let invalid_proof_indices = client
+            .verify_proofs(VerifyProofsRequest {
+                proofs: vec![proof(unblinded), proof(pubkey)],
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .invalid_proof_indices;
+        assert_eq!(invalid_proof_indices, vec![1]);
+    }
+}
@@ -10,6 +10,7 @@ use tonic_types::{ErrorDetails, FieldViolation, StatusExt};
 #[derive(Debug)]
 pub enum Error<'a> {
     AmountGreaterThanMax(usize, Amount, Amount),
+    ZeroAmount(usize),
     AmountNotPowerOfTwo(usize, Amount),
     UnknownUnit(&'a str),
     MaxOrderTooBig(u32),
@@ -20,6 +21,7 @@ pub enum Error<'a> {
     AmountNotFound(usize, KeysetId, Amount),
     BadSecret(usize, nut01::Error),
     InvalidSignature(usize, nut01::Error),
+    BatchTooLarge(&'a str, usize, usize),
 }
 
 impl<'a> From<Error<'a>> for Status {
@@ -35,6 +37,14 @@ impl<'a> From<Error<'a>> for Status {
                     ),
                 )]),
             ),
+            Error::ZeroAmount(idx) => Status::with_error_details(
+                Code::InvalidArgument,
+                "amount is zero",
+                ErrorDetails::with_bad_request(vec![FieldViolation::new(
+                    format!("messages[{idx}].amount"),
+                    "the provided amount is zero, there is no key to sign it",
+                )]),
+            ),
             Error::AmountNotPowerOfTwo(idx, amount) => Status::with_error_details(
                 Code::InvalidArgument,
                 "amount is not a power of two",
@@ -130,6 +140,14 @@ impl<'a> From<Error<'a>> for Status {
                     format!("the provided signature is invalid: {}", error),
                 )]),
             ),
+            Error::BatchTooLarge(field, len, max) => Status::with_error_details(
+                Code::InvalidArgument,
+                "batch too large",
+                ErrorDetails::with_bad_request(vec![FieldViolation::new(
+                    field,
+                    format!("the batch holds {len} items, at most {max} are accepted"),
+                )]),
+            ),
         }
     }
 }
@@ -140,6 +158,7 @@ pub struct VerifyProofsErrors(pub Vec<(usize, VerifyProofError)>);
 #[derive(Debug)]
 pub enum VerifyProofError {
     BadKeysetId(Vec<u8>, nut02::Error),
+    ZeroAmount,
     AmountNotPowerOfTwo(Amount),
     KeysetNotFound(KeysetId),
     AmountNotFound(KeysetId, Amount),
@@ -154,6 +173,9 @@ impl VerifyProofError {
                 format!("proofs[{}].keyset_id", proof_index),
                 format!("invalid keyset id format: {}", e),
             ),
+            VerifyProofError::ZeroAmount => {
+                FieldViolation::new(format!("proofs[{}].amount", proof_index), "amount is zero")
+            }
             VerifyProofError::AmountNotPowerOfTwo(amount) => FieldViolation::new(
                 format!("proofs[{}].amount", proof_index),
                 format!("amount {} is not a power of two", amount),
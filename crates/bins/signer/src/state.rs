@@ -1,4 +1,7 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
 
 use bitcoin::{
     bip32::{ChildNumber, DerivationPath, Xpriv},
@@ -11,22 +14,61 @@ use nuts::{
 };
 use tokio::sync::RwLock;
 
+/// Leading components of the path keysets are derived at, followed by `unit'/index'`
+///
+/// The default, `m/0'`, is the path used by every signer released so far.
+/// Any other prefix derives entirely different keys from the same root key:
+/// the keysets already declared to a node can only be regenerated with the config they were created with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DerivationConfig {
+    prefix: DerivationPath,
+}
+
+impl Default for DerivationConfig {
+    fn default() -> Self {
+        Self {
+            prefix: DerivationPath::from(vec![
+                ChildNumber::from_hardened_idx(0).expect("0 is a valid index"),
+            ]),
+        }
+    }
+}
+
+impl DerivationConfig {
+    pub fn new(prefix: DerivationPath) -> Self {
+        Self { prefix }
+    }
+
+    pub fn keyset_path(&self, unit_idx: u32, index: u32) -> DerivationPath {
+        self.prefix.extend([
+            ChildNumber::from_hardened_idx(unit_idx).expect("should be a valid index"),
+            ChildNumber::from_hardened_idx(index).expect("should be a valid index"),
+        ])
+    }
+}
+
 #[derive(Debug, Clone)]
-pub struct SharedRootKey(pub Arc<Xpriv>);
+pub struct SharedRootKey {
+    root_key: Arc<Xpriv>,
+    derivation: Arc<DerivationConfig>,
+}
 
 impl SharedRootKey {
+    pub fn new(root_key: Xpriv, derivation: DerivationConfig) -> Self {
+        Self {
+            root_key: Arc::new(root_key),
+            derivation: Arc::new(derivation),
+        }
+    }
+
     pub fn generate_keyset<U: Unit>(&self, unit: U, index: u32, max_order: u8) -> MintKeySet<U> {
         let unit_idx = unit.into();
         let secp_ctx = Secp256k1::new();
 
-        let derivation_path = DerivationPath::from(vec![
-            ChildNumber::from_hardened_idx(0).expect("0 is a valid index"),
-            ChildNumber::from_hardened_idx(unit_idx).expect("should be a valid index"),
-            ChildNumber::from_hardened_idx(index).expect("should be a valid index"),
-        ]);
+        let derivation_path = self.derivation.keyset_path(unit_idx, index);
 
         let xpriv = self
-            .0
+            .root_key
             .derive_priv(&secp_ctx, &derivation_path)
             .expect("RNG busted");
 
@@ -35,7 +77,7 @@ impl SharedRootKey {
 
     pub fn get_pubkey(&self) -> bitcoin::secp256k1::PublicKey {
         let secp256k1 = Secp256k1::new();
-        let private_key = &self.0.private_key;
+        let private_key = &self.root_key.private_key;
         private_key.public_key(&secp256k1)
     }
 }
@@ -50,3 +92,76 @@ impl SharedKeySetCache {
         write_lock.insert(keyset_id, Arc::new(key_pairs));
     }
 }
+
+/// Parameters a keyset was declared with
+///
+/// The signer is stateless, this only covers the keysets declared since it started.
+/// Whether a keyset is active is decided by the node, the signer doesn't know it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeysetMetadata {
+    pub unit: String,
+    pub index: u32,
+    pub max_order: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SharedKeysetMetadata(pub Arc<RwLock<BTreeMap<KeysetId, KeysetMetadata>>>);
+
+impl SharedKeysetMetadata {
+    pub async fn insert(&self, keyset_id: KeysetId, metadata: KeysetMetadata) {
+        self.0.write().await.insert(keyset_id, metadata);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    const UNIT: starknet_types::Unit = starknet_types::Unit::MilliStrk;
+
+    fn root_key() -> Xpriv {
+        Xpriv::new_master(bitcoin::Network::Bitcoin, &[7u8; 32]).unwrap()
+    }
+
+    #[test]
+    fn default_derivation_path_is_unchanged() {
+        let unit_idx: u32 = UNIT.into();
+        assert_eq!(
+            DerivationConfig::default().keyset_path(unit_idx, 3),
+            DerivationPath::from_str(&format!("m/0'/{unit_idx}'/3'")).unwrap()
+        );
+
+        let secp_ctx = Secp256k1::new();
+        let xpriv = root_key()
+            .derive_priv(
+                &secp_ctx,
+                &DerivationPath::from_str(&format!("m/0'/{unit_idx}'/3'")).unwrap(),
+            )
+            .unwrap();
+        let expected = MintKeySet::generate(&secp_ctx, xpriv, UNIT, 8);
+
+        let keyset =
+            SharedRootKey::new(root_key(), DerivationConfig::default()).generate_keyset(UNIT, 3, 8);
+        assert_eq!(keyset.id, expected.id);
+    }
+
+    #[test]
+    fn custom_prefix_derives_other_deterministic_keys() {
+        let unit_idx: u32 = UNIT.into();
+        let config = DerivationConfig::new(DerivationPath::from_str("m/129372'/1'").unwrap());
+        assert_eq!(
+            config.keyset_path(unit_idx, 3),
+            DerivationPath::from_str(&format!("m/129372'/1'/{unit_idx}'/3'")).unwrap()
+        );
+
+        let custom = SharedRootKey::new(root_key(), config.clone()).generate_keyset(UNIT, 3, 8);
+        let default =
+            SharedRootKey::new(root_key(), DerivationConfig::default()).generate_keyset(UNIT, 3, 8);
+        assert_ne!(custom.id, default.id);
+
+        let again = SharedRootKey::new(root_key(), config).generate_keyset(UNIT, 3, 8);
+        assert_eq!(custom.id, again.id);
+    }
+}
@@ -124,7 +124,7 @@ async fn pay(
         payload.call_data.asset_contract_address,
         &payload.call_data.amount,
         payload.call_data.payee,
-    );
+    )?;
 
     let tx_hash = account
         .execute_v3(calls.to_vec())
@@ -0,0 +1,342 @@
+use std::{future::Future, str::FromStr};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use nuts::nut04::MintQuoteState;
+use primitive_types::U256;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use starknet_core::types::Felt;
+use starknet_types::{
+    sum_payment_amounts, ChainId, DepositPayload, PaymentTotalOverflow, StarknetU256, Unit,
+};
+
+use crate::{parse_payload, PayloadError, RouteParams};
+
+/// How much of a deposit has been received so far
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DepositStatus {
+    pub state: MintQuoteState,
+    /// Sum of the payments seen on-chain, in the asset's on-chain precision
+    #[serde(serialize_with = "serialize_u256")]
+    pub paid: U256,
+    /// Amount expected by the quote, in the asset's on-chain precision
+    #[serde(serialize_with = "serialize_u256")]
+    pub required: U256,
+}
+
+fn serialize_u256<S: serde::Serializer>(value: &U256, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&value.to_string())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("unsupported network `{0}`")]
+    InvalidNetwork(String),
+    #[error("invalid payload: {0}")]
+    InvalidPayload(#[from] PayloadError),
+    #[error("no deposit for this payload")]
+    UnknownDeposit,
+    #[error("failed to read the deposit status: {0}")]
+    Db(#[from] db_node::Error),
+    #[error("failed to read the deposit status: {0}")]
+    Sqlx(#[from] sqlx::Error),
+    #[error("invalid payment amount stored for this deposit")]
+    InvalidStoredAmount,
+    #[error(transparent)]
+    PaymentTotal(#[from] PaymentTotalOverflow),
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Error::InvalidNetwork(_) | Error::InvalidPayload(_) => StatusCode::BAD_REQUEST,
+            Error::UnknownDeposit => StatusCode::NOT_FOUND,
+            Error::Db(_) | Error::Sqlx(_) | Error::InvalidStoredAmount | Error::PaymentTotal(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+
+        (status, self.to_string()).into_response()
+    }
+}
+
+/// Where the state of a deposit is read from
+pub trait DepositStatusSource: Clone + Send + Sync + 'static {
+    fn deposit_status(
+        &self,
+        invoice_id: Felt,
+    ) -> impl Future<Output = Result<Option<DepositStatus>, Error>> + Send;
+}
+
+/// The indexer database shared with the node
+impl DepositStatusSource for PgPool {
+    async fn deposit_status(&self, invoice_id: Felt) -> Result<Option<DepositStatus>, Error> {
+        let invoice_id = invoice_id.to_bytes_be();
+        let mut conn = self.acquire().await?;
+
+        let Some((quote_id, amount, unit)) =
+            db_node::mint_quote::get_quote_infos_by_invoice_id::<Unit>(&mut conn, &invoice_id)
+                .await?
+        else {
+            return Ok(None);
+        };
+        let (_, state) = db_node::mint_quote::get_amount_and_state(&mut conn, quote_id).await?;
+
+        let amounts = db_node::mint_payment_event::get_current_paid(&mut conn, &invoice_id)
+            .await?
+            .map(|(low, high)| -> Result<U256, Error> {
+                Ok(U256::from(StarknetU256 {
+                    low: Felt::from_str(&low).map_err(|_| Error::InvalidStoredAmount)?,
+                    high: Felt::from_str(&high).map_err(|_| Error::InvalidStoredAmount)?,
+                }))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let paid = sum_payment_amounts(amounts)?;
+
+        Ok(Some(DepositStatus {
+            state,
+            paid,
+            required: unit.convert_amount_into_u256(amount),
+        }))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct StatusQuery {
+    payload: String,
+}
+
+/// Report how much of the deposit described by `payload` has been paid
+///
+/// Takes the same payload as the deposit page, so that the page can poll it.
+async fn handle_deposit_status<S: DepositStatusSource>(
+    State(source): State<S>,
+    Path(params): Path<RouteParams>,
+    Query(query): Query<StatusQuery>,
+) -> Result<Json<DepositStatus>, Error> {
+    let chain_id = match ChainId::from_str(&params.network) {
+        Ok(chain_id @ (ChainId::Sepolia | ChainId::Devnet)) => chain_id,
+        Ok(ChainId::Custom(_)) | Ok(ChainId::Mainnet) | Err(_) => {
+            return Err(Error::InvalidNetwork(params.network));
+        }
+    };
+    let call_data = parse_payload(&query.payload)?;
+    let invoice_id = DepositPayload {
+        chain_id,
+        call_data,
+    }
+    .invoice_id();
+
+    source
+        .deposit_status(invoice_id)
+        .await?
+        .map(Json)
+        .ok_or(Error::UnknownDeposit)
+}
+
+pub fn routes<S: DepositStatusSource>(source: S) -> Router {
+    Router::new()
+        .route(
+            "/deposit/{method}/{network}/status",
+            get(handle_deposit_status::<S>),
+        )
+        .with_state(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use axum::{body::Body, http::Request};
+    use nuts::Amount;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use starknet_types::PayInvoiceCallData;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct InMemorySource(HashMap<Felt, DepositStatus>);
+
+    impl DepositStatusSource for InMemorySource {
+        async fn deposit_status(&self, invoice_id: Felt) -> Result<Option<DepositStatus>, Error> {
+            Ok(self.0.get(&invoice_id).cloned())
+        }
+    }
+
+    fn call_data(quote_id_hash: u64) -> PayInvoiceCallData {
+        PayInvoiceCallData {
+            quote_id_hash: Felt::from(quote_id_hash),
+            expiry: Felt::from(1_750_000_000u64),
+            asset_contract_address: Felt::from(3u64),
+            amount: StarknetU256 {
+                low: Felt::from(32u64),
+                high: Felt::ZERO,
+            },
+            payee: Felt::from(4u64),
+        }
+    }
+
+    async fn get_status(
+        source: InMemorySource,
+        call_data: &PayInvoiceCallData,
+    ) -> (StatusCode, String) {
+        let payload = serde_json::to_string(call_data).unwrap();
+        let uri = format!(
+            "/deposit/starknet/SN_DEVNET/status?payload={}",
+            url_encode(&payload)
+        );
+        let response = routes(source)
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    fn url_encode(s: &str) -> String {
+        s.bytes()
+            .map(|b| match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                    (b as char).to_string()
+                }
+                _ => format!("%{:02X}", b),
+            })
+            .collect()
+    }
+
+    fn invoice_id(call_data: &PayInvoiceCallData) -> Felt {
+        DepositPayload {
+            chain_id: ChainId::Devnet,
+            call_data: call_data.clone(),
+        }
+        .invoice_id()
+    }
+
+    #[tokio::test]
+    async fn reports_paid_and_unpaid_deposits() {
+        let paid = call_data(1);
+        let unpaid = call_data(2);
+        let source = InMemorySource(HashMap::from([
+            (
+                invoice_id(&paid),
+                DepositStatus {
+                    state: MintQuoteState::Paid,
+                    paid: U256::from(32),
+                    required: U256::from(32),
+                },
+            ),
+            (
+                invoice_id(&unpaid),
+                DepositStatus {
+                    state: MintQuoteState::Unpaid,
+                    paid: U256::from(16),
+                    required: U256::from(32),
+                },
+            ),
+        ]));
+
+        assert_eq!(
+            get_status(source.clone(), &paid).await,
+            (
+                StatusCode::OK,
+                r#"{"state":"PAID","paid":"32","required":"32"}"#.to_string()
+            )
+        );
+        assert_eq!(
+            get_status(source, &unpaid).await,
+            (
+                StatusCode::OK,
+                r#"{"state":"UNPAID","paid":"16","required":"32"}"#.to_string()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_deposit_is_not_found() {
+        let (status, _) = get_status(InMemorySource::default(), &call_data(3)).await;
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    fn random_felt() -> Felt {
+        Felt::from_bytes_be_slice(Uuid::new_v4().as_bytes())
+    }
+
+    #[tokio::test]
+    async fn db_source_sums_the_recorded_payments() {
+        let pg_url = std::env::var("PG_URL").expect("PG_URL should be set to run the db tests");
+        let pool = PgPool::connect(&pg_url).await.unwrap();
+        db_node::run_migrations(&pool).await.unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+
+        let invoice_id = random_felt();
+        let amount = Amount::from(32u64);
+        let expiry = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            + 600;
+        db_node::mint_quote::insert_new(
+            &mut conn,
+            Uuid::new_v4(),
+            invoice_id.to_bytes_be(),
+            Unit::MilliStrk,
+            amount,
+            "request",
+            expiry,
+        )
+        .await
+        .unwrap();
+        let block_id = format!("block-{}", Uuid::new_v4());
+        sqlx::query(
+            r#"INSERT INTO substreams_starknet_block (id, number, timestamp) VALUES ($1, 0, now())"#,
+        )
+        .bind(&block_id)
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+
+        let required = Unit::MilliStrk.convert_amount_into_u256(amount);
+        let quarter = required / 4;
+        for _ in 0..2 {
+            db_node::mint_payment_event::insert_new_payment_event(
+                &mut conn,
+                &db_node::PaymentEvent {
+                    block_id: block_id.clone(),
+                    tx_hash: random_felt().to_hex_string(),
+                    index: 0,
+                    asset: Felt::ONE.to_hex_string(),
+                    payee: Felt::ONE.to_hex_string(),
+                    invoice_id: invoice_id.to_bytes_be(),
+                    payer: Felt::TWO.to_hex_string(),
+                    amount_low: Felt::from(quarter.low_u128()).to_hex_string(),
+                    amount_high: Felt::ZERO.to_hex_string(),
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        assert_eq!(
+            pool.deposit_status(invoice_id).await.unwrap(),
+            Some(DepositStatus {
+                state: MintQuoteState::Unpaid,
+                paid: quarter * 2,
+                required,
+            })
+        );
+        assert_eq!(pool.deposit_status(random_felt()).await.unwrap(), None);
+    }
+}
@@ -1,25 +1,64 @@
 use abis::{IERC20_CONTRACT_ABI, INVOICE_CONTRACT_ABI};
 use askama::Template;
 use axum::{
-    extract::{Path, Query},
+    extract::{Path, Query, State},
     response::{Html, IntoResponse},
     routing::get,
     Router,
 };
+use provider_urls::ProviderUrls;
 use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
 use starknet_core::types::{contract::AbiEntry, Felt};
-use starknet_types::{constants::ON_CHAIN_CONSTANTS, ChainId, PayInvoiceCallData};
+use starknet_types::{
+    constants::ON_CHAIN_CONSTANTS, is_valid_starknet_address, ChainId, PayInvoiceCallData,
+};
 use std::str::FromStr;
-use std::{collections::HashMap, net::SocketAddr};
+use std::{collections::HashMap, net::SocketAddr, sync::Arc};
 use tower::ServiceBuilder;
 use tower_http::{cors::CorsLayer, services::ServeDir};
 use tracing_subscriber::{self, EnvFilter};
 
 mod abis;
+mod deposit_status;
+mod provider_urls;
+mod security_headers;
 mod serve;
 
 use serve::serve;
 
+/// A valid payload is a few hundred bytes, anything much bigger is not worth parsing
+const MAX_PAYLOAD_LEN: usize = 2048;
+
+#[derive(Debug, thiserror::Error)]
+enum PayloadError {
+    #[error("payload is {0} bytes long, the maximum is {MAX_PAYLOAD_LEN}")]
+    TooLong(usize),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("{0} {1:#x} is not a valid starknet address")]
+    InvalidAddress(&'static str, Felt),
+}
+
+fn parse_payload(payload_raw: &str) -> Result<PayInvoiceCallData, PayloadError> {
+    if payload_raw.len() > MAX_PAYLOAD_LEN {
+        return Err(PayloadError::TooLong(payload_raw.len()));
+    }
+
+    let call_data = serde_json::from_str::<PayInvoiceCallData>(payload_raw)?;
+    if !is_valid_starknet_address(&call_data.payee) {
+        return Err(PayloadError::InvalidAddress("payee", call_data.payee));
+    }
+    if !is_valid_starknet_address(&call_data.asset_contract_address) {
+        return Err(PayloadError::InvalidAddress(
+            "asset contract",
+            call_data.asset_contract_address,
+        ));
+    }
+
+    Ok(call_data)
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 struct RouteParams {
     method: String,
@@ -79,11 +118,28 @@ async fn main() {
         .with_env_filter(EnvFilter::from_default_env())
         .init();
 
+    let shutdown_timeout =
+        serve::shutdown_timeout_from_env().expect("`SHUTDOWN_TIMEOUT` should be valid");
+    let provider_urls = ProviderUrls::from_env().expect("`PROVIDER_URLS` should be valid");
+    let content_security_policy = security_headers::content_security_policy(&provider_urls);
+
     // Build our application with routes
-    let app = Router::new()
+    let mut app = Router::new()
         .route("/", get(index))
         .route("/deposit/{method}/{network}/", get(handle_deposit))
-        .nest_service("/static", ServeDir::new("crates/bins/web-app/static"))
+        .with_state(Arc::new(provider_urls))
+        .nest_service("/static", ServeDir::new("crates/bins/web-app/static"));
+
+    // The deposit status is read from the node database, without it the page can't poll
+    match std::env::var("PG_URL") {
+        Ok(pg_url) => {
+            let pg_pool = PgPool::connect_lazy(&pg_url).expect("`PG_URL` should be a valid url");
+            app = app.merge(deposit_status::routes(pg_pool));
+        }
+        Err(_) => tracing::warn!("`PG_URL` is not set, the deposit status endpoint is disabled"),
+    }
+
+    let app = security_headers::apply(app, content_security_policy)
         .layer(ServiceBuilder::new().layer(CorsLayer::permissive()));
 
     // Get port from environment variable or use default
@@ -92,7 +148,7 @@ async fn main() {
         .parse()
         .expect("Invalid bind address");
 
-    serve(app, bind_address).await;
+    serve(app, bind_address, shutdown_timeout).await;
 }
 
 async fn index() -> impl IntoResponse {
@@ -100,11 +156,12 @@ async fn index() -> impl IntoResponse {
 }
 
 async fn handle_deposit(
+    State(provider_urls): State<Arc<ProviderUrls>>,
     Path(params): Path<RouteParams>,
     Query(query_params): Query<HashMap<String, String>>,
 ) -> impl IntoResponse {
     // Validate method parameter
-    if params.method != "starknet" {
+    if params.method != starknet_types::STARKNET_STR {
         let template = InvalidMethodTemplate {
             method: params.method,
         };
@@ -116,7 +173,21 @@ async fn handle_deposit(
     }
 
     // Validate network parameter using ChainId
-    let chain_id = match ChainId::from_str(&params.network) {
+    let (chain_id, provider_url) = match ChainId::from_str(&params.network) {
+        Ok(chain_id @ (ChainId::Sepolia | ChainId::Devnet)) => match provider_urls.get(&chain_id) {
+            Some(provider_url) => (chain_id, provider_url.to_string()),
+            None => {
+                tracing::error!("no provider url configured for {}", chain_id);
+                let template = InvalidNetworkTemplate {
+                    network: params.network,
+                };
+                return Html(
+                    template
+                        .render()
+                        .unwrap_or_else(|_| "Template render error".to_string()),
+                );
+            }
+        },
         Ok(ChainId::Custom(_)) | Ok(ChainId::Mainnet) | Err(_) => {
             let template = InvalidNetworkTemplate {
                 network: params.network,
@@ -127,21 +198,23 @@ async fn handle_deposit(
                     .unwrap_or_else(|_| "Template render error".to_string()),
             );
         }
-        Ok(ChainId::Sepolia) => ChainId::Sepolia,
-        Ok(ChainId::Devnet) => ChainId::Devnet,
     };
 
     let payload_raw = query_params
         .get("payload")
-        .unwrap_or(&String::new())
-        .clone();
+        .map(String::as_str)
+        .unwrap_or_default();
 
-    let pay_invoice_call_data = match serde_json::from_str::<PayInvoiceCallData>(&payload_raw) {
+    let pay_invoice_call_data = match parse_payload(payload_raw) {
         Ok(payload) => payload,
         Err(err) => {
             let template = InvalidPayloadTemplate {
+                // Don't echo back an oversized payload
+                payload_raw: match err {
+                    PayloadError::TooLong(_) => String::new(),
+                    _ => payload_raw.to_string(),
+                },
                 error: err.to_string(),
-                payload_raw,
             };
             return Html(
                 template
@@ -152,18 +225,12 @@ async fn handle_deposit(
     };
 
     let formatted_payload =
-        serde_json::to_string_pretty(&pay_invoice_call_data).unwrap_or(payload_raw.clone());
+        serde_json::to_string_pretty(&pay_invoice_call_data).unwrap_or(payload_raw.to_string());
 
     let on_chain_constants = ON_CHAIN_CONSTANTS
         .get(chain_id.as_str())
         .expect("a supported chain");
 
-    let provider_url = match &chain_id {
-        ChainId::Devnet => "http://localhost:5050".to_string(),
-        ChainId::Sepolia => "https://starknet-sepolia.public.blastapi.io/rpc/v0_8".to_string(),
-        ChainId::Custom(_) | ChainId::Mainnet => panic!("unsuported at the moment"),
-    };
-
     let deposit_data = DepositData {
         provider_url,
         invoice_contract: ConctractData {
@@ -194,3 +261,106 @@ async fn handle_deposit(
             .unwrap_or_else(|_| "Template render error".to_string()),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use starknet_types::StarknetU256;
+
+    use super::*;
+
+    fn call_data() -> PayInvoiceCallData {
+        PayInvoiceCallData {
+            quote_id_hash: Felt::from(1u64),
+            expiry: Felt::from(1_750_000_000u64),
+            asset_contract_address: Felt::from(3u64),
+            amount: StarknetU256 {
+                low: Felt::from(32u64),
+                high: Felt::ZERO,
+            },
+            payee: Felt::from(4u64),
+        }
+    }
+
+    #[test]
+    fn valid_payload_is_parsed() {
+        let payload_raw = serde_json::to_string(&call_data()).unwrap();
+
+        assert!(parse_payload(&payload_raw).is_ok());
+    }
+
+    #[test]
+    fn oversized_payload_is_rejected() {
+        let payload_raw = " ".repeat(MAX_PAYLOAD_LEN + 1);
+
+        assert!(matches!(
+            parse_payload(&payload_raw),
+            Err(PayloadError::TooLong(len)) if len == MAX_PAYLOAD_LEN + 1
+        ));
+    }
+
+    #[test]
+    fn invalid_payee_is_rejected() {
+        let payload_raw = serde_json::to_string(&PayInvoiceCallData {
+            payee: Felt::ONE,
+            ..call_data()
+        })
+        .unwrap();
+
+        assert!(matches!(
+            parse_payload(&payload_raw),
+            Err(PayloadError::InvalidAddress("payee", payee)) if payee == Felt::ONE
+        ));
+    }
+
+    #[tokio::test]
+    async fn invalid_payload_renders_error_page() {
+        let response = handle_deposit(
+            State(Arc::new(ProviderUrls::default())),
+            Path(RouteParams {
+                method: starknet_types::STARKNET_STR.to_string(),
+                network: ChainId::Devnet.to_string(),
+            }),
+            Query(HashMap::from([(
+                "payload".to_string(),
+                " ".repeat(MAX_PAYLOAD_LEN + 1),
+            )])),
+        )
+        .await
+        .into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+
+        assert!(body.contains("Invalid Payload"));
+        assert!(body.contains("the maximum is 2048"));
+    }
+
+    #[tokio::test]
+    async fn configured_provider_url_is_used() {
+        let provider_urls = ProviderUrls::default()
+            .with_overrides("SN_DEVNET=http://my-devnet:5050")
+            .unwrap();
+
+        let response = handle_deposit(
+            State(Arc::new(provider_urls)),
+            Path(RouteParams {
+                method: starknet_types::STARKNET_STR.to_string(),
+                network: ChainId::Devnet.to_string(),
+            }),
+            Query(HashMap::from([(
+                "payload".to_string(),
+                serde_json::to_string(&call_data()).unwrap(),
+            )])),
+        )
+        .await
+        .into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+
+        assert!(body.contains("http://my-devnet:5050"));
+        assert!(!body.contains("http://localhost:5050"));
+    }
+}
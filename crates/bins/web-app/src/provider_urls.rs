@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+
+use starknet_types::ChainId;
+
+const DEFAULT_DEVNET_PROVIDER_URL: &str = "http://localhost:5050";
+const DEFAULT_SEPOLIA_PROVIDER_URL: &str = "https://starknet-sepolia.public.blastapi.io/rpc/v0_8";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("`{0}` should be formatted as `<chain_id>=<provider_url>`")]
+    InvalidEntry(String),
+}
+
+/// The rpc node the deposit page connects to, for each chain
+///
+/// Deployments can point at their own nodes through the `PROVIDER_URLS` env var.
+/// Chains it doesn't mention keep their default url.
+#[derive(Debug, Clone)]
+pub struct ProviderUrls(HashMap<String, String>);
+
+impl Default for ProviderUrls {
+    fn default() -> Self {
+        Self(HashMap::from([
+            (
+                ChainId::Devnet.to_string(),
+                DEFAULT_DEVNET_PROVIDER_URL.to_string(),
+            ),
+            (
+                ChainId::Sepolia.to_string(),
+                DEFAULT_SEPOLIA_PROVIDER_URL.to_string(),
+            ),
+        ]))
+    }
+}
+
+impl ProviderUrls {
+    /// Read the overrides from `PROVIDER_URLS`, e.g. `SN_SEPOLIA=https://my.node/rpc,SN_DEVNET=http://devnet:5050`
+    pub fn from_env() -> Result<Self, Error> {
+        match std::env::var("PROVIDER_URLS") {
+            Ok(overrides) => Self::default().with_overrides(&overrides),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    pub fn with_overrides(mut self, overrides: &str) -> Result<Self, Error> {
+        for entry in overrides
+            .split(',')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+        {
+            let (chain_id, url) = entry
+                .split_once('=')
+                .map(|(chain_id, url)| (chain_id.trim(), url.trim()))
+                .filter(|(chain_id, url)| !chain_id.is_empty() && !url.is_empty())
+                .ok_or_else(|| Error::InvalidEntry(entry.to_string()))?;
+            self.0.insert(chain_id.to_string(), url.to_string());
+        }
+
+        Ok(self)
+    }
+
+    pub fn get(&self, chain_id: &ChainId) -> Option<&str> {
+        self.0.get(chain_id.as_str()).map(String::as_str)
+    }
+
+    pub fn urls(&self) -> impl Iterator<Item = &str> {
+        self.0.values().map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_are_kept_for_chains_not_overridden() {
+        let provider_urls = ProviderUrls::default()
+            .with_overrides("SN_SEPOLIA=https://my.node/rpc")
+            .unwrap();
+
+        assert_eq!(
+            provider_urls.get(&ChainId::Sepolia),
+            Some("https://my.node/rpc")
+        );
+        assert_eq!(
+            provider_urls.get(&ChainId::Devnet),
+            Some(DEFAULT_DEVNET_PROVIDER_URL)
+        );
+        assert_eq!(provider_urls.get(&ChainId::Mainnet), None);
+    }
+
+    #[test]
+    fn entry_without_url_is_rejected() {
+        assert!(matches!(
+            ProviderUrls::default().with_overrides("SN_SEPOLIA="),
+            Err(Error::InvalidEntry(entry)) if entry == "SN_SEPOLIA="
+        ));
+    }
+}
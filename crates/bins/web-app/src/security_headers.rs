@@ -0,0 +1,94 @@
+use axum::{
+    http::{
+        header::{CONTENT_SECURITY_POLICY, REFERRER_POLICY, X_CONTENT_TYPE_OPTIONS},
+        HeaderValue,
+    },
+    Router,
+};
+use tower_http::set_header::SetResponseHeaderLayer;
+
+use crate::provider_urls::ProviderUrls;
+
+/// Only the bundled script and styles are loaded
+///
+/// The wallet connection modal injects its own styles and displays the wallets icons as data urls.
+/// The page reads the chain through the configured provider urls, and nothing else.
+pub fn default_content_security_policy(provider_urls: &ProviderUrls) -> String {
+    let mut connect_src = provider_urls.urls().collect::<Vec<_>>();
+    connect_src.sort_unstable();
+
+    format!(
+        "default-src 'self'; \
+        script-src 'self'; \
+        style-src 'self' 'unsafe-inline'; \
+        img-src 'self' data:; \
+        connect-src 'self' {}; \
+        object-src 'none'; \
+        base-uri 'none'; \
+        form-action 'self'; \
+        frame-ancestors 'none'",
+        connect_src.join(" ")
+    )
+}
+
+/// Read the policy from the `CONTENT_SECURITY_POLICY` env var, or use the default one
+pub fn content_security_policy(provider_urls: &ProviderUrls) -> HeaderValue {
+    let csp = std::env::var("CONTENT_SECURITY_POLICY")
+        .unwrap_or_else(|_| default_content_security_policy(provider_urls));
+
+    HeaderValue::try_from(csp).expect("the content security policy should be a valid header value")
+}
+
+/// Add the security headers to every response of `router`
+pub fn apply(router: Router, content_security_policy: HeaderValue) -> Router {
+    router
+        .layer(SetResponseHeaderLayer::if_not_present(
+            CONTENT_SECURITY_POLICY,
+            content_security_policy,
+        ))
+        .layer(SetResponseHeaderLayer::overriding(
+            X_CONTENT_TYPE_OPTIONS,
+            HeaderValue::from_static("nosniff"),
+        ))
+        .layer(SetResponseHeaderLayer::overriding(
+            REFERRER_POLICY,
+            HeaderValue::from_static("no-referrer"),
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{body::Body, http::Request, routing::get};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::handle_deposit;
+
+    #[tokio::test]
+    async fn deposit_response_has_security_headers() {
+        let provider_urls = ProviderUrls::default();
+        let csp = default_content_security_policy(&provider_urls);
+        let app = apply(
+            Router::new()
+                .route("/deposit/{method}/{network}/", get(handle_deposit))
+                .with_state(Arc::new(provider_urls)),
+            HeaderValue::try_from(csp.clone()).unwrap(),
+        );
+
+        let response = app
+            .oneshot(
+                Request::get("/deposit/starknet/SN_DEVNET/?payload=")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let headers = response.headers();
+        assert_eq!(headers[CONTENT_SECURITY_POLICY], csp.as_str());
+        assert_eq!(headers[X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert_eq!(headers[REFERRER_POLICY], "no-referrer");
+    }
+}
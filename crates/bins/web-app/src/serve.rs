@@ -1,12 +1,41 @@
-use std::net::SocketAddr;
+use std::{net::SocketAddr, time::Duration};
 
 use axum::Router;
 
 #[cfg(feature = "tls")]
 use axum_server::tls_rustls::RustlsConfig;
 
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 30;
+
+#[derive(Debug, thiserror::Error)]
+#[error("`SHUTDOWN_TIMEOUT` should be a number of seconds, got {0:?}")]
+pub struct InvalidShutdownTimeout(String);
+
+/// Time given to in-flight requests to complete once a shutdown signal is received
+pub fn shutdown_timeout_from_env() -> Result<Duration, InvalidShutdownTimeout> {
+    parse_shutdown_timeout(std::env::var("SHUTDOWN_TIMEOUT").ok().as_deref())
+}
+
+fn parse_shutdown_timeout(value: Option<&str>) -> Result<Duration, InvalidShutdownTimeout> {
+    let secs = match value {
+        Some(value) => value
+            .parse()
+            .map_err(|_| InvalidShutdownTimeout(value.to_string()))?,
+        None => DEFAULT_SHUTDOWN_TIMEOUT_SECS,
+    };
+
+    Ok(Duration::from_secs(secs))
+}
+
+async fn shutdown_signal() {
+    tokio::signal::ctrl_c()
+        .await
+        .expect("should be able to listen for shutdown signal");
+    println!("🛑 Shutdown signal received, draining in-flight requests...");
+}
+
 #[cfg(feature = "tls")]
-pub async fn serve(app: Router, bind_address: SocketAddr) {
+pub async fn serve(app: Router, bind_address: SocketAddr, shutdown_timeout: Duration) {
     // Get certificate and key paths from environment or use defaults
     let cert_path = std::env::var("TLS_CERT_PATH").unwrap_or_else(|_| "certs/cert.pem".to_string());
     let key_path = std::env::var("TLS_KEY_PATH").unwrap_or_else(|_| "certs/key.pem".to_string());
@@ -44,21 +73,147 @@ pub async fn serve(app: Router, bind_address: SocketAddr) {
     println!("🔑 Private key: {}", key_path);
     println!("🚀 Binding to: https://{}", bind_address);
 
+    let handle = axum_server::Handle::new();
+    tokio::spawn({
+        let handle = handle.clone();
+        async move {
+            shutdown_signal().await;
+            handle.graceful_shutdown(Some(shutdown_timeout));
+        }
+    });
+
     // Serve
     axum_server::bind_rustls(bind_address, tls_config)
+        .handle(handle)
         .serve(app.into_make_service())
         .await
         .expect("the server should run")
 }
 
 #[cfg(not(feature = "tls"))]
-pub async fn serve(app: Router, bind_address: SocketAddr) {
+pub async fn serve(app: Router, bind_address: SocketAddr, shutdown_timeout: Duration) {
     let listener = tokio::net::TcpListener::bind(&bind_address)
         .await
         .expect("should be able to listen");
 
     println!("🚀 Server running on http://{}", bind_address);
-    axum::serve(listener, app)
+    serve_until(listener, app, shutdown_signal(), shutdown_timeout).await;
+}
+
+/// Serve until `signal` resolves, then give in-flight requests `shutdown_timeout` to complete
+#[cfg(any(not(feature = "tls"), test))]
+async fn serve_until(
+    listener: tokio::net::TcpListener,
+    app: Router,
+    signal: impl std::future::Future<Output = ()> + Send + 'static,
+    shutdown_timeout: Duration,
+) {
+    let (drain_started_sender, drain_started_receiver) = tokio::sync::oneshot::channel::<()>();
+    let server = async {
+        axum::serve(listener, app)
+            .with_graceful_shutdown(async move {
+                signal.await;
+                let _ = drain_started_sender.send(());
+            })
+            .await
+    };
+    let mut server = std::pin::pin!(server);
+
+    tokio::select! {
+        res = &mut server => res.expect("the server should run"),
+        Ok(()) = drain_started_receiver => {
+            if tokio::time::timeout(shutdown_timeout, server).await.is_err() {
+                eprintln!("⚠️ In-flight requests did not complete in time, forcing shutdown");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::routing::get;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+        sync::{oneshot, Notify},
+    };
+
+    use super::*;
+
+    #[test]
+    fn shutdown_timeout_is_parsed() {
+        assert_eq!(
+            parse_shutdown_timeout(None).unwrap(),
+            Duration::from_secs(DEFAULT_SHUTDOWN_TIMEOUT_SECS)
+        );
+        assert_eq!(
+            parse_shutdown_timeout(Some("5")).unwrap(),
+            Duration::from_secs(5)
+        );
+    }
+
+    #[test]
+    fn invalid_shutdown_timeout_is_rejected() {
+        assert!(parse_shutdown_timeout(Some("5s")).is_err());
+        assert!(parse_shutdown_timeout(Some("-1")).is_err());
+        assert!(parse_shutdown_timeout(Some("")).is_err());
+    }
+
+    #[tokio::test]
+    async fn in_flight_request_completes_during_shutdown() {
+        let request_started = Arc::new(Notify::new());
+        let release_request = Arc::new(Notify::new());
+        let app = Router::new().route(
+            "/slow",
+            get({
+                let request_started = request_started.clone();
+                let release_request = release_request.clone();
+                move || async move {
+                    request_started.notify_one();
+                    release_request.notified().await;
+                    "done"
+                }
+            }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (shutdown_sender, shutdown_receiver) = oneshot::channel::<()>();
+        let server = tokio::spawn(serve_until(
+            listener,
+            app,
+            async move {
+                let _ = shutdown_receiver.await;
+            },
+            Duration::from_secs(5),
+        ));
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(b"GET /slow HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        request_started.notified().await;
+        shutdown_sender.send(()).unwrap();
+
+        // The listener is closed once draining starts, while the request is still being served
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while TcpStream::connect(address).await.is_ok() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
         .await
-        .expect("the server should run");
+        .expect("new connections should be refused while draining");
+        release_request.notify_one();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("done"));
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("the server should stop once the request is drained")
+            .unwrap();
+    }
 }
@@ -0,0 +1,2 @@
+DROP TABLE melt_quote_input;
+ALTER TABLE melt_quote DROP COLUMN transfer_id;
@@ -0,0 +1,9 @@
+-- Hash of the transaction paying the quote, to follow its execution
+ALTER TABLE melt_quote ADD COLUMN transfer_id BYTEA;
+
+-- The proofs spent by a melt, to restore them if the withdrawal fails
+CREATE TABLE IF NOT EXISTS melt_quote_input (
+    quote_id UUID NOT NULL REFERENCES melt_quote(id),
+    y BYTEA NOT NULL REFERENCES proof(y),
+    PRIMARY KEY (quote_id, y)
+);
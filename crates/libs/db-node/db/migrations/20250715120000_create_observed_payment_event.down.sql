@@ -0,0 +1 @@
+DROP TABLE observed_payment_event;
@@ -0,0 +1,13 @@
+-- Remittance events of the invoice contract that don't involve the cashier, kept for observability only
+CREATE TABLE IF NOT EXISTS observed_payment_event (
+    block_id TEXT NOT NULL REFERENCES substreams_starknet_block(id) ON DELETE CASCADE,
+    tx_hash TEXT NOT NULL,
+    event_index BIGINT NOT NULL,
+    payee TEXT NOT NULL,
+    asset TEXT NOT NULL,
+    invoice_id BYTEA NOT NULL,
+    payer TEXT NOT NULL,
+    amount_low TEXT NOT NULL,
+    amount_high TEXT NOT NULL,
+    PRIMARY KEY (tx_hash, event_index)
+);
@@ -0,0 +1,2 @@
+DROP TABLE keyset_fee_audit;
+ALTER TABLE keyset DROP COLUMN input_fee_ppk;
@@ -0,0 +1,14 @@
+-- NUT-02 fee, in parts per thousand of a unit, charged for each input of the keyset
+-- Stored as the bit representation of a u16
+ALTER TABLE keyset ADD COLUMN input_fee_ppk INT2 NOT NULL DEFAULT 0;
+
+-- Every change of a keyset fee made by an operator
+CREATE TABLE IF NOT EXISTS keyset_fee_audit (
+    id BIGSERIAL PRIMARY KEY,
+    keyset_id BIGINT NOT NULL REFERENCES keyset(id),
+    old_input_fee_ppk INT2 NOT NULL,
+    new_input_fee_ppk INT2 NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS keyset_fee_audit_keyset_id_index ON keyset_fee_audit(keyset_id);
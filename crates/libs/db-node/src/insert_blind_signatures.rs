@@ -1,35 +1,62 @@
 use nuts::nut00::BlindSignature;
 use nuts::nut01::PublicKey;
-use sqlx::{PgConnection, Postgres, QueryBuilder};
+use sqlx::{Connection, PgConnection, Postgres, QueryBuilder};
 
+use crate::Error;
+
+const BIND_PARAMS_PER_ROW: usize = 4;
+/// Postgres refuses statements with more bind parameters than this
+const MAX_BIND_PARAMS: usize = u16::MAX as usize;
+const MAX_ROWS_PER_STATEMENT: usize = MAX_BIND_PARAMS / BIND_PARAMS_PER_ROW;
+
+/// Insert many blind signatures at once
+///
+/// Rows are split across as many statements as required to stay under the bind parameter limit.
+/// Those are then executed in a single transaction.
 pub struct InsertBlindSignaturesQueryBuilder<'args> {
-    builder: QueryBuilder<'args, Postgres>,
-    first: bool,
+    builders: Vec<QueryBuilder<'args, Postgres>>,
+    n_rows_in_last: usize,
 }
 
-impl InsertBlindSignaturesQueryBuilder<'_> {
+impl<'args> InsertBlindSignaturesQueryBuilder<'args> {
     pub fn new() -> Self {
         Self {
-            builder: QueryBuilder::new(
-                r#"INSERT INTO blind_signature (y, amount, keyset_id, c) VALUES "#,
-            ),
-            first: true,
+            builders: vec![Self::new_statement()],
+            n_rows_in_last: 0,
         }
     }
 
-    pub fn add_row(&mut self, blind_message: PublicKey, blind_signature: &BlindSignature) {
+    fn new_statement() -> QueryBuilder<'args, Postgres> {
+        QueryBuilder::new(r#"INSERT INTO blind_signature (y, amount, keyset_id, c) VALUES "#)
+    }
+
+    pub fn add_row(
+        &mut self,
+        blind_message: PublicKey,
+        blind_signature: &BlindSignature,
+    ) -> Result<(), Error> {
         let y = blind_message.to_bytes();
-        let amount = blind_signature.amount.into_i64_repr();
+        let amount = blind_signature
+            .amount
+            .try_into_i64_repr()
+            .map_err(|_| Error::RuntimeToDbConversion)?;
         let keyset_id = blind_signature.keyset_id.as_i64();
         let c = blind_signature.c.to_bytes();
 
-        if self.first {
-            self.first = false;
-        } else {
-            self.builder.push(", ");
+        if self.n_rows_in_last == MAX_ROWS_PER_STATEMENT {
+            self.builders.push(Self::new_statement());
+            self.n_rows_in_last = 0;
+        }
+        let builder = self
+            .builders
+            .last_mut()
+            .expect("there is always at least one statement");
+        if self.n_rows_in_last != 0 {
+            builder.push(", ");
         }
+        self.n_rows_in_last += 1;
 
-        self.builder
+        builder
             .push('(')
             .push_bind(y)
             .push(", ")
@@ -39,10 +66,21 @@ impl InsertBlindSignaturesQueryBuilder<'_> {
             .push(", ")
             .push_bind(c)
             .push(')');
+
+        Ok(())
     }
 
     pub async fn execute(mut self, conn: &mut PgConnection) -> Result<(), sqlx::Error> {
-        _ = self.builder.push(r#";"#).build().execute(conn).await?;
+        if let [builder] = &mut self.builders[..] {
+            _ = builder.push(r#";"#).build().execute(conn).await?;
+            return Ok(());
+        }
+
+        let mut tx = conn.begin().await?;
+        for mut builder in self.builders {
+            _ = builder.push(r#";"#).build().execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
 
         Ok(())
     }
@@ -57,9 +95,29 @@ impl Default for InsertBlindSignaturesQueryBuilder<'_> {
 #[cfg(test)]
 mod query_builder {
     use num_traits::One;
-    use nuts::{Amount, nut00::BlindSignature, nut01::PublicKey, nut02::KeysetId};
+    use nuts::{
+        Amount,
+        nut00::BlindSignature,
+        nut01::{PublicKey, SecretKey},
+        nut02::KeysetId,
+    };
+
+    use super::MAX_ROWS_PER_STATEMENT;
+    use crate::{
+        Error, InsertBlindSignaturesQueryBuilder,
+        test_utils::{db_conn, insert_new_keyset},
+    };
 
-    use crate::InsertBlindSignaturesQueryBuilder;
+    fn blind_signature(keyset_id: KeysetId) -> BlindSignature {
+        BlindSignature {
+            amount: Amount::one(),
+            keyset_id,
+            c: PublicKey::from_hex(
+                "02194603ffa36356f4a56b7df9371fc3192472351453ec7398b8da8117e7c3e104",
+            )
+            .unwrap(),
+        }
+    }
 
     #[test]
     fn produce_expected_sql() {
@@ -78,12 +136,76 @@ mod query_builder {
         )
         .unwrap();
 
-        builder.add_row(y, &proof);
-        builder.add_row(y, &proof);
-        let query = builder.builder.sql();
+        builder.add_row(y, &proof).unwrap();
+        builder.add_row(y, &proof).unwrap();
+        let query = builder.builders[0].sql();
         assert_eq!(
             query,
             "INSERT INTO blind_signature (y, amount, keyset_id, c) VALUES ($1, $2, $3, $4), ($5, $6, $7, $8)"
         );
     }
+
+    #[test]
+    fn amount_above_i64_max_is_rejected() {
+        let mut builder = InsertBlindSignaturesQueryBuilder::new();
+        let mut blind_signature = blind_signature(KeysetId::try_from(0x1i64).unwrap());
+        blind_signature.amount = Amount::from(u64::MAX);
+
+        assert!(matches!(
+            builder.add_row(blind_signature.c, &blind_signature),
+            Err(Error::RuntimeToDbConversion)
+        ));
+        assert_eq!(builder.n_rows_in_last, 0);
+    }
+
+    #[test]
+    fn split_rows_over_the_bind_params_limit() {
+        let mut builder = InsertBlindSignaturesQueryBuilder::new();
+        let blind_signature = blind_signature(KeysetId::try_from(0x1i64).unwrap());
+        let y = blind_signature.c;
+
+        for _ in 0..MAX_ROWS_PER_STATEMENT + 1 {
+            builder.add_row(y, &blind_signature).unwrap();
+        }
+
+        assert_eq!(builder.builders.len(), 2);
+        assert_eq!(
+            builder.builders[1].sql(),
+            "INSERT INTO blind_signature (y, amount, keyset_id, c) VALUES ($1, $2, $3, $4)"
+        );
+    }
+
+    async fn count_persisted(conn: &mut sqlx::PgConnection, ys: &[PublicKey]) -> i64 {
+        let ys: Vec<_> = ys.iter().map(|y| y.to_bytes().to_vec()).collect();
+        sqlx::query_scalar(r#"SELECT COUNT(*) FROM blind_signature WHERE y = ANY($1)"#)
+            .bind(&ys)
+            .fetch_one(conn)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn multi_statement_insert_is_atomic() {
+        let mut conn = db_conn().await;
+        let blind_signature = blind_signature(insert_new_keyset(&mut conn).await);
+
+        let ys: Vec<_> = (0..MAX_ROWS_PER_STATEMENT + 1)
+            .map(|_| SecretKey::generate().public_key())
+            .collect();
+
+        // The duplicated y only conflicts in the second statement, the first one must be rolled back
+        let mut builder = InsertBlindSignaturesQueryBuilder::new();
+        for y in ys.iter().chain(ys.first()) {
+            builder.add_row(*y, &blind_signature).unwrap();
+        }
+        assert!(builder.execute(&mut conn).await.is_err());
+        assert_eq!(count_persisted(&mut conn, &ys).await, 0);
+
+        let mut builder = InsertBlindSignaturesQueryBuilder::new();
+        for y in &ys {
+            builder.add_row(*y, &blind_signature).unwrap();
+        }
+        builder.execute(&mut conn).await.unwrap();
+        assert_eq!(count_persisted(&mut conn, &ys).await, ys.len() as i64);
+    }
 }
@@ -11,6 +11,7 @@ pub struct KeysetInfo<U> {
     active: bool,
     max_order: u8,
     derivation_path_index: u32,
+    input_fee_ppk: u16,
 }
 
 impl<U> KeysetInfo<U> {
@@ -23,6 +24,9 @@ impl<U> KeysetInfo<U> {
     pub fn derivation_path_index(&self) -> u32 {
         self.derivation_path_index
     }
+    pub fn input_fee_ppk(&self) -> u16 {
+        self.input_fee_ppk
+    }
 }
 
 impl<U: Clone> KeysetInfo<U> {
@@ -33,14 +37,19 @@ impl<U: Clone> KeysetInfo<U> {
 
 pub async fn get_keysets(
     conn: &mut PgConnection,
-) -> Result<impl Iterator<Item = ([u8; 8], String, bool)>, sqlx::Error> {
-    let record = sqlx::query!("SELECT id, unit, active FROM keyset")
+) -> Result<impl Iterator<Item = ([u8; 8], String, bool, u16)>, sqlx::Error> {
+    let record = sqlx::query!("SELECT id, unit, active, input_fee_ppk FROM keyset")
         .fetch_all(conn)
         .await?;
 
-    Ok(record
-        .into_iter()
-        .map(|r| (r.id.to_be_bytes(), r.unit, r.active)))
+    Ok(record.into_iter().map(|r| {
+        (
+            r.id.to_be_bytes(),
+            r.unit,
+            r.active,
+            u16::from_be_bytes(r.input_fee_ppk.to_be_bytes()),
+        )
+    }))
 }
 
 pub async fn get_keyset<U: FromStr>(
@@ -48,7 +57,7 @@ pub async fn get_keyset<U: FromStr>(
     keyset_id: &KeysetId,
 ) -> Result<KeysetInfo<U>, Error> {
     let record = sqlx::query!(
-        r#"SELECT unit, active, max_order, derivation_path_index
+        r#"SELECT unit, active, max_order, derivation_path_index, input_fee_ppk
         FROM keyset
         WHERE id = $1"#,
         keyset_id.as_i64()
@@ -61,6 +70,7 @@ pub async fn get_keyset<U: FromStr>(
         active: record.active,
         max_order: u8::try_from(record.max_order).map_err(|_| Error::DbToRuntimeConversion)?,
         derivation_path_index: u32::from_be_bytes(record.derivation_path_index.to_be_bytes()),
+        input_fee_ppk: u16::from_be_bytes(record.input_fee_ppk.to_be_bytes()),
     };
 
     Ok(info)
@@ -86,7 +96,7 @@ pub async fn get_active_keysets<U: FromStr>(
     conn: &mut PgConnection,
 ) -> Result<Vec<(KeysetId, KeysetInfo<U>)>, Error> {
     let records = sqlx::query!(
-        r#"SELECT id, unit, active, max_order, derivation_path_index
+        r#"SELECT id, unit, active, max_order, derivation_path_index, input_fee_ppk
         FROM keyset
         WHERE active = TRUE"#,
     )
@@ -107,6 +117,7 @@ pub async fn get_active_keysets<U: FromStr>(
                     derivation_path_index: u32::from_be_bytes(
                         record.derivation_path_index.to_be_bytes(),
                     ),
+                    input_fee_ppk: u16::from_be_bytes(record.input_fee_ppk.to_be_bytes()),
                 },
             ))
         })
@@ -125,3 +136,89 @@ pub async fn deactivate_keysets(conn: &mut PgConnection, keyset_ids: &[i64]) ->
 
     Ok(())
 }
+
+pub async fn get_input_fee(conn: &mut PgConnection, keyset_id: KeysetId) -> Result<u16, Error> {
+    let fee = sqlx::query_scalar!(
+        r#"SELECT input_fee_ppk FROM keyset WHERE id = $1"#,
+        keyset_id.as_i64()
+    )
+    .fetch_one(conn)
+    .await?;
+
+    Ok(u16::from_be_bytes(fee.to_be_bytes()))
+}
+
+/// Change the fee of an existing keyset, without rotating it
+///
+/// The previous and new values are recorded in `keyset_fee_audit` so that operators can tell
+/// which fee applied to a past swap. Meant to be run inside a tx. Returns the previous fee.
+pub async fn update_input_fee(
+    conn: &mut PgConnection,
+    keyset_id: KeysetId,
+    new_fee: u16,
+) -> Result<u16, Error> {
+    let new_fee = i16::from_be_bytes(new_fee.to_be_bytes());
+
+    let old_fee = sqlx::query_scalar!(
+        r#"SELECT input_fee_ppk FROM keyset WHERE id = $1 FOR UPDATE"#,
+        keyset_id.as_i64()
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        r#"UPDATE keyset SET input_fee_ppk = $2 WHERE id = $1"#,
+        keyset_id.as_i64(),
+        new_fee
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        r#"INSERT INTO keyset_fee_audit (keyset_id, old_input_fee_ppk, new_input_fee_ppk)
+        VALUES ($1, $2, $3)"#,
+        keyset_id.as_i64(),
+        old_fee,
+        new_fee
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(u16::from_be_bytes(old_fee.to_be_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Connection;
+
+    use super::*;
+    use crate::test_utils::{db_conn, insert_new_keyset};
+
+    #[tokio::test]
+    async fn update_input_fee_is_audited() {
+        let mut conn = db_conn().await;
+        let keyset_id = insert_new_keyset(&mut conn).await;
+        assert_eq!(get_input_fee(&mut conn, keyset_id).await.unwrap(), 0);
+
+        let mut tx = conn.begin().await.unwrap();
+        assert_eq!(update_input_fee(&mut tx, keyset_id, 100).await.unwrap(), 0);
+        assert_eq!(
+            update_input_fee(&mut tx, keyset_id, u16::MAX)
+                .await
+                .unwrap(),
+            100
+        );
+        tx.commit().await.unwrap();
+
+        assert_eq!(get_input_fee(&mut conn, keyset_id).await.unwrap(), u16::MAX);
+        let audit: Vec<(i16, i16)> = sqlx::query_as(
+            r#"SELECT old_input_fee_ppk, new_input_fee_ppk FROM keyset_fee_audit
+            WHERE keyset_id = $1 ORDER BY id"#,
+        )
+        .bind(keyset_id.as_i64())
+        .fetch_all(&mut *conn)
+        .await
+        .unwrap();
+        assert_eq!(audit, vec![(0, 100), (100, -1)]);
+    }
+}
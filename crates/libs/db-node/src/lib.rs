@@ -13,8 +13,12 @@ pub mod melt_payment_event;
 pub mod melt_quote;
 pub mod mint_payment_event;
 pub mod mint_quote;
+pub mod observed_payment_event;
 pub mod proof;
+pub mod quote;
 pub use proof::InsertSpentProofsQueryBuilder;
+#[cfg(test)]
+mod test_utils;
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -30,6 +34,10 @@ pub enum Error {
     DbToRuntimeConversion,
     #[error("Failed to convert the runtime type into the db type")]
     RuntimeToDbConversion,
+    #[error("Blind message {y} has already been signed")]
+    BlindMessageAlreadyUsed { y: PublicKey },
+    #[error("Proof {y} has already been spent")]
+    ProofAlreadySpent { y: PublicKey },
 }
 
 /// Will return true if this secret has already been signed by us
@@ -51,6 +59,37 @@ pub async fn is_any_blind_message_already_used(
     Ok(record.exists)
 }
 
+/// Fail with the first of those secrets that has already been signed by us
+///
+/// Unlike `is_any_blind_message_already_used`, the caller can tell the user which output is reused.
+pub async fn ensure_blind_messages_unused(
+    conn: &mut PgConnection,
+    blind_secrets: impl Iterator<Item = PublicKey>,
+) -> Result<(), Error> {
+    let blind_secrets: Vec<_> = blind_secrets.collect();
+    let ys: Vec<_> = blind_secrets
+        .iter()
+        .map(|pk| pk.to_bytes().to_vec())
+        .collect();
+
+    let used_y: Option<Vec<u8>> =
+        sqlx::query_scalar(r#"SELECT y FROM blind_signature WHERE y = ANY($1) LIMIT 1;"#)
+            .bind(&ys)
+            .fetch_optional(conn)
+            .await?;
+
+    match used_y {
+        Some(used_y) => {
+            let y = blind_secrets
+                .into_iter()
+                .find(|pk| pk.to_bytes()[..] == used_y[..])
+                .ok_or(Error::DbToRuntimeConversion)?;
+            Err(Error::BlindMessageAlreadyUsed { y })
+        }
+        None => Ok(()),
+    }
+}
+
 /// Handle concurency at the database level
 /// If one transaction alter a field that is used in another one
 /// in a way that would result in a different statement output,
@@ -109,3 +148,41 @@ pub struct PaymentEvent {
     pub amount_low: String,
     pub amount_high: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use num_traits::One;
+    use nuts::{Amount, nut00::BlindSignature, nut01::SecretKey};
+
+    use super::*;
+    use crate::test_utils::{db_conn, insert_new_keyset};
+
+    #[tokio::test]
+    async fn ensure_blind_messages_unused_names_the_reused_y() {
+        let mut conn = db_conn().await;
+        let keyset_id = insert_new_keyset(&mut conn).await;
+
+        let used_y = SecretKey::generate().public_key();
+        let fresh_y = SecretKey::generate().public_key();
+        let mut signatures_builder = InsertBlindSignaturesQueryBuilder::new();
+        let blind_signature = BlindSignature {
+            amount: Amount::one(),
+            keyset_id,
+            c: SecretKey::generate().public_key(),
+        };
+        signatures_builder
+            .add_row(used_y, &blind_signature)
+            .unwrap();
+        signatures_builder.execute(&mut conn).await.unwrap();
+
+        assert!(
+            ensure_blind_messages_unused(&mut conn, [fresh_y].into_iter())
+                .await
+                .is_ok()
+        );
+        assert!(matches!(
+            ensure_blind_messages_unused(&mut conn, [fresh_y, used_y].into_iter()).await,
+            Err(Error::BlindMessageAlreadyUsed { y }) if y == used_y
+        ));
+    }
+}
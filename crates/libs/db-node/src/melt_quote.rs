@@ -1,6 +1,8 @@
 use nuts::{
     Amount,
+    nut01::PublicKey,
     nut05::{MeltQuoteResponse, MeltQuoteState},
+    nut07::ProofState,
     traits::Unit,
 };
 use sqlx::{PgConnection, types::time::OffsetDateTime};
@@ -25,6 +27,12 @@ pub async fn insert_new<U: Unit>(
         .map_err(|_| Error::RuntimeToDbConversion)?;
     let expiry =
         OffsetDateTime::from_unix_timestamp(expiry).map_err(|_| Error::RuntimeToDbConversion)?;
+    let amount = amount
+        .try_into_i64_repr()
+        .map_err(|_| Error::RuntimeToDbConversion)?;
+    let fee = fee
+        .try_into_i64_repr()
+        .map_err(|_| Error::RuntimeToDbConversion)?;
 
     sqlx::query!(
         r#"
@@ -35,8 +43,8 @@ pub async fn insert_new<U: Unit>(
         quote_id,
         invoice_id,
         &unit.to_string(),
-        amount.into_i64_repr(),
-        fee.into_i64_repr(),
+        amount,
+        fee,
         request,
         expiry,
     )
@@ -194,3 +202,236 @@ pub async fn get_state_and_transfer_ids(
 
     Ok((record.state, record.tx_hashes))
 }
+
+/// Remember which proofs were spent to pay `quote_id`
+pub async fn register_inputs(
+    conn: &mut PgConnection,
+    quote_id: Uuid,
+    ys: &[PublicKey],
+) -> Result<(), sqlx::Error> {
+    let ys: Vec<_> = ys.iter().map(|y| y.to_bytes().to_vec()).collect();
+
+    sqlx::query!(
+        r#"INSERT INTO melt_quote_input (quote_id, y) SELECT $1, UNNEST($2::BYTEA[])"#,
+        quote_id,
+        &ys
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Attach the transaction paying them to the quotes of `invoice_ids`
+pub async fn set_transfer_id(
+    conn: &mut PgConnection,
+    invoice_ids: &[[u8; 32]],
+    transfer_id: &[u8],
+) -> Result<(), sqlx::Error> {
+    let invoice_ids: Vec<_> = invoice_ids.iter().map(|id| id.to_vec()).collect();
+
+    sqlx::query!(
+        r#"UPDATE melt_quote SET transfer_id = $1 WHERE invoice_id = ANY($2)"#,
+        transfer_id,
+        &invoice_ids
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Pending quotes whose withdrawal has been submitted, with its transfer id
+pub async fn get_pending_withdrawals(
+    conn: &mut PgConnection,
+) -> Result<Vec<(Uuid, Vec<u8>)>, sqlx::Error> {
+    let records = sqlx::query!(
+        r#"SELECT id, transfer_id AS "transfer_id!" FROM melt_quote
+           WHERE state = 'PENDING' AND transfer_id IS NOT NULL"#
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(records.into_iter().map(|r| (r.id, r.transfer_id)).collect())
+}
+
+/// Undo a melt whose withdrawal `transfer_id` failed
+///
+/// The quote goes back to Unpaid and the proofs it spent can be used again.
+/// The quote is updated first, so that a quote that got paid or was attached to another
+/// withdrawal in the meantime keeps its proofs spent.
+/// Returns whether the melt was undone.
+/// Meant to run inside a transaction, so that the proofs are never released without the quote.
+pub async fn rollback_withdrawal(
+    conn: &mut PgConnection,
+    quote_id: Uuid,
+    transfer_id: &[u8],
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"UPDATE melt_quote SET state = 'UNPAID', transfer_id = NULL
+           WHERE id = $1 AND state = 'PENDING' AND transfer_id = $2"#,
+        quote_id,
+        transfer_id
+    )
+    .execute(&mut *conn)
+    .await?;
+    if result.rows_affected() != 1 {
+        return Ok(false);
+    }
+
+    sqlx::query!(
+        r#"UPDATE proof SET state = $2
+           WHERE y IN (SELECT y FROM melt_quote_input WHERE quote_id = $1)"#,
+        quote_id,
+        ProofState::Unspent as i16
+    )
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query!(
+        r#"DELETE FROM melt_quote_input WHERE quote_id = $1"#,
+        quote_id
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use nuts::{
+        nut00::{Proof, secret::Secret},
+        nut01::SecretKey,
+        nut02::KeysetId,
+    };
+    use sqlx::Connection;
+    use starknet_types::Unit as StarknetUnit;
+
+    use super::*;
+    use crate::{
+        InsertSpentProofsQueryBuilder,
+        test_utils::{db_conn, insert_new_keyset, new_invoice_id},
+    };
+
+    /// A Pending quote whose withdrawal `transfer_id` spent one proof, returns the proof y
+    async fn insert_pending_withdrawal(
+        conn: &mut PgConnection,
+        keyset_id: KeysetId,
+        quote_id: Uuid,
+        transfer_id: &[u8],
+    ) -> PublicKey {
+        let invoice_id = new_invoice_id();
+        // 2100-01-01
+        let expiry = 4_102_444_800;
+        insert_new(
+            conn,
+            quote_id,
+            &invoice_id,
+            StarknetUnit::MilliStrk,
+            Amount::from(8u64),
+            Amount::ZERO,
+            "",
+            expiry,
+        )
+        .await
+        .unwrap();
+        set_state(conn, quote_id, MeltQuoteState::Pending)
+            .await
+            .unwrap();
+        set_transfer_id(conn, &[invoice_id], transfer_id)
+            .await
+            .unwrap();
+
+        let proof = Proof {
+            amount: Amount::from(8u64),
+            keyset_id,
+            secret: Secret::generate(),
+            c: SecretKey::generate().public_key(),
+        };
+        let y = proof.y().unwrap();
+        let mut builder = InsertSpentProofsQueryBuilder::new();
+        builder.add_row(&y, &proof).unwrap();
+        builder.execute(conn).await.unwrap();
+        register_inputs(conn, quote_id, &[y]).await.unwrap();
+
+        y
+    }
+
+    async fn get_proof_state(conn: &mut PgConnection, y: PublicKey) -> i16 {
+        sqlx::query_scalar(r#"SELECT state FROM proof WHERE y = $1"#)
+            .bind(y.to_bytes().to_vec())
+            .fetch_one(conn)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn rollback_releases_the_proofs_of_the_pending_quote() {
+        let mut conn = db_conn().await;
+        let keyset_id = insert_new_keyset(&mut conn).await;
+        let quote_id = Uuid::new_v4();
+        let transfer_id = Uuid::new_v4().as_bytes().to_vec();
+        let y = insert_pending_withdrawal(&mut conn, keyset_id, quote_id, &transfer_id).await;
+
+        let mut tx = conn.begin().await.unwrap();
+        // Another withdrawal has been attached to the quote since it was listed
+        assert!(
+            !rollback_withdrawal(&mut tx, quote_id, Uuid::new_v4().as_bytes())
+                .await
+                .unwrap()
+        );
+        assert!(
+            rollback_withdrawal(&mut tx, quote_id, &transfer_id)
+                .await
+                .unwrap()
+        );
+        tx.commit().await.unwrap();
+
+        assert_eq!(
+            get_state(&mut conn, quote_id).await.unwrap(),
+            MeltQuoteState::Unpaid
+        );
+        assert_eq!(
+            get_proof_state(&mut conn, y).await,
+            ProofState::Unspent as i16
+        );
+        assert!(
+            get_pending_withdrawals(&mut conn)
+                .await
+                .unwrap()
+                .iter()
+                .all(|(id, _)| *id != quote_id)
+        );
+    }
+
+    #[tokio::test]
+    async fn rollback_of_an_already_paid_quote_keeps_the_proofs_spent() {
+        let mut conn = db_conn().await;
+        let keyset_id = insert_new_keyset(&mut conn).await;
+        let quote_id = Uuid::new_v4();
+        let transfer_id = Uuid::new_v4().as_bytes().to_vec();
+        let y = insert_pending_withdrawal(&mut conn, keyset_id, quote_id, &transfer_id).await;
+
+        // The payment got indexed between the listing of the pending withdrawals and the rollback
+        set_state(&mut conn, quote_id, MeltQuoteState::Paid)
+            .await
+            .unwrap();
+
+        let mut tx = conn.begin().await.unwrap();
+        assert!(
+            !rollback_withdrawal(&mut tx, quote_id, &transfer_id)
+                .await
+                .unwrap()
+        );
+        tx.commit().await.unwrap();
+
+        assert_eq!(
+            get_state(&mut conn, quote_id).await.unwrap(),
+            MeltQuoteState::Paid
+        );
+        assert_eq!(
+            get_proof_state(&mut conn, y).await,
+            ProofState::Spent as i16
+        );
+    }
+}
@@ -22,12 +22,15 @@ pub async fn insert_new<U: Unit>(
         .map_err(|_| Error::RuntimeToDbConversion)?;
     let expiry =
         OffsetDateTime::from_unix_timestamp(expiry).map_err(|_| Error::RuntimeToDbConversion)?;
+    let amount = amount
+        .try_into_i64_repr()
+        .map_err(|_| Error::RuntimeToDbConversion)?;
     sqlx::query!(
         r#"INSERT INTO mint_quote (id, invoice_id, unit, amount, request, expiry, state) VALUES ($1, $2, $3, $4, $5, $6, 'UNPAID')"#,
         quote_id,
         &invoice_id,
         &unit.to_string(),
-        amount.into_i64_repr(),
+        amount,
         request,
         expiry,
     ).execute(conn).await?;
@@ -120,3 +123,28 @@ pub async fn get_quote_infos_by_invoice_id<U: Unit>(
 
     Ok(ret)
 }
+
+/// The quotes still waiting for a payment and not yet expired
+pub async fn get_unpaid_not_expired<U: Unit>(
+    conn: &mut PgConnection,
+) -> Result<Vec<(Uuid, [u8; 32], Amount, U)>, Error> {
+    let records = sqlx::query!(
+        r#"SELECT id, invoice_id, amount, unit FROM mint_quote WHERE state = 'UNPAID' AND expiry > NOW()"#
+    )
+    .fetch_all(conn)
+    .await?;
+
+    records
+        .into_iter()
+        .map(|r| {
+            Ok((
+                r.id,
+                r.invoice_id
+                    .try_into()
+                    .map_err(|_| Error::DbToRuntimeConversion)?,
+                Amount::from_i64_repr(r.amount),
+                U::from_str(&r.unit).map_err(|_| Error::DbToRuntimeConversion)?,
+            ))
+        })
+        .collect()
+}
@@ -0,0 +1,46 @@
+use sqlx::PgConnection;
+
+use crate::PaymentEvent;
+
+/// Record an event that has no effect on any quote
+pub async fn insert_new_payment_event(
+    db_conn: &mut PgConnection,
+    payment_event: &PaymentEvent,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"INSERT INTO observed_payment_event
+                (block_id, tx_hash, event_index, payee, asset, invoice_id, payer, amount_low, amount_high)
+            VALUES
+                ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            ON CONFLICT DO NOTHING"#,
+        &payment_event.block_id,
+        &payment_event.tx_hash,
+        payment_event.index,
+        &payment_event.payee,
+        &payment_event.asset,
+        &payment_event.invoice_id,
+        &payment_event.payer,
+        &payment_event.amount_low,
+        &payment_event.amount_high
+    )
+    .execute(db_conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn exists(
+    db_conn: &mut PgConnection,
+    tx_hash: &str,
+    event_index: i64,
+) -> Result<bool, sqlx::Error> {
+    let exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM observed_payment_event WHERE tx_hash = $1 AND event_index = $2) AS "exists!""#,
+        tx_hash,
+        event_index
+    )
+    .fetch_one(db_conn)
+    .await?;
+
+    Ok(exists)
+}
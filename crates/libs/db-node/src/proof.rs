@@ -7,6 +7,8 @@ use nuts::{
 
 use sqlx::{PgConnection, Postgres, QueryBuilder, Row};
 
+use crate::Error;
+
 /// Return true if one of the provided secret
 /// is already in db with state = SPENT
 pub async fn is_any_already_spent(
@@ -142,8 +144,9 @@ pub async fn get_proofs_by_ids(
     for row in rows {
         let state: Option<i16> = row.try_get("state")?;
         let proof_state = match state {
-            Some(state_val) => ProofState::from_i32(state_val as i32)
-                .ok_or_else(|| sqlx::Error::Decode("Invalid proof state".into()))?,
+            Some(state_val) => ProofState::from_i32(state_val as i32).ok_or_else(|| {
+                sqlx::Error::Decode(format!("Invalid proof state: {state_val}").into())
+            })?,
             // Unkown proofs by definition Unspent
             None => ProofState::Unspent,
         };
@@ -155,15 +158,17 @@ pub async fn get_proofs_by_ids(
 
 /// Generate a query following this model:
 /// INSERT INTO proof (y, amount, keyset_id, secret, c, state)
-/// VALUES  ($1, $2, $3, $4, $5, 1), ($6, $7, $8, $9, $10, 1)
-///  ON CONFLICT (y) WHERE state = 0 DO UPDATE SET state = 1;
+/// VALUES  ($1, $2, $3, $4, $5, 3), ($6, $7, $8, $9, $10, 3)
+///  ON CONFLICT (y) DO UPDATE SET state = 3 WHERE proof.state = 1
+///  RETURNING y;
 ///
-/// Meaning it will fail if a state is already set to 1 (SPENT).
-/// Otherwise it will either inset new proofs AS SPENT,
-/// or or update previously existing UNSPENT proofs to SPENT.
+/// It will either insert new proofs AS SPENT,
+/// or update previously existing UNSPENT proofs to SPENT.
+/// Proofs already SPENT are left untouched and not returned,
+/// which is how a double spend racing with this one is detected.
 pub struct InsertSpentProofsQueryBuilder<'args> {
     builder: QueryBuilder<'args, Postgres>,
-    first: bool,
+    ys: Vec<PublicKey>,
 }
 
 impl<'args> InsertSpentProofsQueryBuilder<'args> {
@@ -172,21 +177,24 @@ impl<'args> InsertSpentProofsQueryBuilder<'args> {
             builder: QueryBuilder::new(
                 r#"INSERT INTO proof (y, amount, keyset_id, secret, c, state) VALUES "#,
             ),
-            first: true,
+            ys: Vec::new(),
         }
     }
 
-    pub fn add_row(&mut self, y: &PublicKey, proof: &'args Proof) {
+    pub fn add_row(&mut self, y: &PublicKey, proof: &'args Proof) -> Result<(), Error> {
+        let amount = proof
+            .amount
+            .try_into_i64_repr()
+            .map_err(|_| Error::RuntimeToDbConversion)?;
+        let first = self.ys.is_empty();
+        self.ys.push(*y);
         let y = y.to_bytes();
-        let amount = proof.amount.into_i64_repr();
         let keyset_id = proof.keyset_id.as_i64();
         let secret: &str = proof.secret.as_ref();
         let c = proof.c.to_bytes();
         let state = ProofState::Spent as i16;
 
-        if self.first {
-            self.first = false;
-        } else {
+        if !first {
             self.builder.push(", ");
         }
 
@@ -204,22 +212,35 @@ impl<'args> InsertSpentProofsQueryBuilder<'args> {
             .push(", ")
             .push(state)
             .push(')');
+
+        Ok(())
     }
 
     // this will insert the proofs as SPENT, or update existing UNSPENT proofs to SPENT
     // if they are already in the database.
-    pub async fn execute(mut self, conn: &mut PgConnection) -> Result<(), sqlx::Error> {
-        _ = self
+    //
+    // Under serializable isolation, a concurrent tx spending the same proof makes this one fail
+    // with a serialization error instead, before any row is returned.
+    pub async fn execute(mut self, conn: &mut PgConnection) -> Result<(), Error> {
+        let written_ys: Vec<Vec<u8>> = self
             .builder
             .push(format!(
-                "ON CONFLICT (y) WHERE state = {} DO UPDATE SET state = {};",
+                " ON CONFLICT (y) DO UPDATE SET state = {} WHERE proof.state = {} RETURNING y;",
+                ProofState::Spent as i16,
                 ProofState::Unspent as i16,
-                ProofState::Spent as i16
             ))
-            .build()
-            .execute(conn)
+            .build_query_scalar()
+            .fetch_all(conn)
             .await?;
 
+        if let Some(y) = self
+            .ys
+            .into_iter()
+            .find(|y| !written_ys.iter().any(|w| w[..] == y.to_bytes()[..]))
+        {
+            return Err(Error::ProofAlreadySpent { y });
+        }
+
         Ok(())
     }
 }
@@ -240,7 +261,12 @@ mod query_builder {
         nut02::KeysetId,
     };
 
-    use crate::InsertSpentProofsQueryBuilder;
+    use sqlx::Connection;
+
+    use crate::{
+        Error, InsertSpentProofsQueryBuilder,
+        test_utils::{db_pool, insert_new_keyset},
+    };
 
     #[test]
     fn produce_expected_sql() {
@@ -256,8 +282,8 @@ mod query_builder {
         };
         let y = proof.y().unwrap();
 
-        builder.add_row(&y, &proof);
-        builder.add_row(&y, &proof);
+        builder.add_row(&y, &proof).unwrap();
+        builder.add_row(&y, &proof).unwrap();
         let query = builder.builder.sql();
 
         let spent_as_i16 = nuts::nut07::ProofState::Spent as i16;
@@ -269,4 +295,49 @@ mod query_builder {
             )
         );
     }
+
+    fn new_proof(keyset_id: KeysetId) -> Proof {
+        Proof {
+            amount: Amount::one(),
+            keyset_id,
+            secret: Secret::generate(),
+            c: PublicKey::from_hex(
+                "02194603ffa36356f4a56b7df9371fc3192472351453ec7398b8da8117e7c3e104",
+            )
+            .unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrent_spend_of_the_same_proof_is_detected() {
+        let pg_pool = db_pool().await;
+        let mut first_conn = pg_pool.acquire().await.unwrap();
+        let proof = new_proof(insert_new_keyset(&mut first_conn).await);
+        let y = proof.y().unwrap();
+
+        let mut first_tx = first_conn.begin().await.unwrap();
+        let mut builder = InsertSpentProofsQueryBuilder::new();
+        builder.add_row(&y, &proof).unwrap();
+        builder.execute(&mut first_tx).await.unwrap();
+
+        // Blocks on the row inserted by the first tx until it commits
+        let second_spend = tokio::spawn({
+            let pg_pool = pg_pool.clone();
+            let proof = proof.clone();
+            async move {
+                let mut conn = pg_pool.acquire().await.unwrap();
+                let mut tx = conn.begin().await.unwrap();
+                let mut builder = InsertSpentProofsQueryBuilder::new();
+                builder.add_row(&y, &proof).unwrap();
+                builder.execute(&mut tx).await
+            }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        first_tx.commit().await.unwrap();
+
+        assert!(matches!(
+            second_spend.await.unwrap(),
+            Err(Error::ProofAlreadySpent { y: spent }) if spent == y
+        ));
+    }
 }
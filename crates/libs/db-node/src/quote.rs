@@ -0,0 +1,129 @@
+use nuts::{Amount, traits::Unit};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuoteInfos<U> {
+    pub quote_id: Uuid,
+    pub amount: Amount,
+    pub unit: U,
+}
+
+/// The quote an invoice id belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuoteKind<U> {
+    Mint(QuoteInfos<U>),
+    Melt(QuoteInfos<U>),
+    None,
+}
+
+/// Look for the invoice id among both mint and melt quotes in a single query
+///
+/// Should the same id exist in both tables, the mint quote wins,
+/// as it did when the two tables were queried one after the other.
+pub async fn get_quote_kind_by_invoice_id<U: Unit>(
+    conn: &mut PgConnection,
+    invoice_id: &[u8; 32],
+) -> Result<QuoteKind<U>, Error> {
+    let record = sqlx::query!(
+        r#"
+            SELECT TRUE AS "is_mint!", id AS "id!", amount AS "amount!", unit AS "unit!"
+            FROM mint_quote WHERE invoice_id = $1
+            UNION ALL
+            SELECT FALSE, id, amount, unit FROM melt_quote WHERE invoice_id = $1
+            ORDER BY 1 DESC
+            LIMIT 1
+        "#,
+        invoice_id
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    let Some(record) = record else {
+        return Ok(QuoteKind::None);
+    };
+
+    let infos = QuoteInfos {
+        quote_id: record.id,
+        amount: Amount::from_i64_repr(record.amount),
+        unit: U::from_str(&record.unit).map_err(|_| Error::DbToRuntimeConversion)?,
+    };
+
+    Ok(if record.is_mint {
+        QuoteKind::Mint(infos)
+    } else {
+        QuoteKind::Melt(infos)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet_types::Unit as StarknetUnit;
+
+    use super::*;
+    use crate::test_utils::{db_conn, new_invoice_id};
+
+    #[tokio::test]
+    async fn quote_kind_is_found_in_a_single_lookup() {
+        let mut conn = db_conn().await;
+        let unit = StarknetUnit::MilliStrk;
+        let amount = Amount::from(32u64);
+        // 2100-01-01
+        let expiry = 4_102_444_800;
+
+        let mint_quote_id = Uuid::new_v4();
+        let mint_invoice_id = new_invoice_id();
+        crate::mint_quote::insert_new(
+            &mut conn,
+            mint_quote_id,
+            mint_invoice_id,
+            unit,
+            amount,
+            "",
+            expiry,
+        )
+        .await
+        .unwrap();
+
+        let melt_quote_id = Uuid::new_v4();
+        let melt_invoice_id = new_invoice_id();
+        crate::melt_quote::insert_new(
+            &mut conn,
+            melt_quote_id,
+            &melt_invoice_id,
+            unit,
+            amount,
+            Amount::ZERO,
+            "",
+            expiry,
+        )
+        .await
+        .unwrap();
+
+        let expected = |quote_id| QuoteInfos {
+            quote_id,
+            amount,
+            unit,
+        };
+        assert_eq!(
+            get_quote_kind_by_invoice_id::<StarknetUnit>(&mut conn, &mint_invoice_id)
+                .await
+                .unwrap(),
+            QuoteKind::Mint(expected(mint_quote_id))
+        );
+        assert_eq!(
+            get_quote_kind_by_invoice_id::<StarknetUnit>(&mut conn, &melt_invoice_id)
+                .await
+                .unwrap(),
+            QuoteKind::Melt(expected(melt_quote_id))
+        );
+        assert_eq!(
+            get_quote_kind_by_invoice_id::<StarknetUnit>(&mut conn, &new_invoice_id())
+                .await
+                .unwrap(),
+            QuoteKind::None
+        );
+    }
+}
@@ -0,0 +1,45 @@
+//! Fixtures shared by the tests running against a real database.
+//!
+//! `PG_URL` must point at a database the migrations can be run against.
+//! Every id is generated, so the tests can be re-run against the same database.
+
+use nuts::nut02::KeysetId;
+use sqlx::{PgConnection, PgPool, Postgres, pool::PoolConnection};
+use uuid::Uuid;
+
+use crate::InsertKeysetsQueryBuilder;
+
+pub async fn db_pool() -> PgPool {
+    let pg_url = std::env::var("PG_URL").expect("PG_URL should be set to run the db tests");
+    let pg_pool = PgPool::connect(&pg_url).await.unwrap();
+    crate::run_migrations(&pg_pool).await.unwrap();
+
+    pg_pool
+}
+
+pub async fn db_conn() -> PoolConnection<Postgres> {
+    db_pool().await.acquire().await.unwrap()
+}
+
+pub fn new_keyset_id() -> KeysetId {
+    let mut id = *Uuid::new_v4().as_bytes();
+    // First byte is the keyset version
+    id[0] = 0;
+    KeysetId::from_bytes(&id[..8]).unwrap()
+}
+
+/// An invoice id that no other test run uses
+pub fn new_invoice_id() -> [u8; 32] {
+    let mut invoice_id = [0; 32];
+    invoice_id[..16].copy_from_slice(Uuid::new_v4().as_bytes());
+    invoice_id
+}
+
+pub async fn insert_new_keyset(conn: &mut PgConnection) -> KeysetId {
+    let keyset_id = new_keyset_id();
+    let mut keysets_builder = InsertKeysetsQueryBuilder::new();
+    keysets_builder.add_row(keyset_id, "strk", 32, 0);
+    keysets_builder.execute(conn).await.unwrap();
+
+    keyset_id
+}
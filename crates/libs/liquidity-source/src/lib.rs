@@ -5,7 +5,7 @@ pub use deposit::DepositInterface;
 mod withdraw;
 use nuts::traits::Unit;
 use uuid::Uuid;
-pub use withdraw::WithdrawInterface;
+pub use withdraw::{WithdrawInterface, WithdrawalStatus};
 
 pub trait LiquiditySource {
     type InvoiceId: Into<[u8; 32]> + LowerHex + UpperHex + Clone + Send + Sync + 'static;
@@ -1,8 +1,18 @@
 use nuts::{Amount, nut05::MeltQuoteState, traits::Unit};
 use uuid::Uuid;
 
+/// Where a withdrawal sent by a liquidity source stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WithdrawalStatus {
+    /// Submitted, not executed yet
+    Pending,
+    Succeeded,
+    /// Executed and failed, or refused, the funds never left
+    Reverted,
+}
+
 #[async_trait::async_trait]
-pub trait WithdrawInterface: Send {
+pub trait WithdrawInterface: Send + Sync {
     type Error: std::error::Error + Send + Sync + 'static;
     type Request: std::fmt::Debug + serde::Serialize + for<'de> serde::Deserialize<'de> + Send;
     type Unit: Unit;
@@ -26,4 +36,10 @@ pub trait WithdrawInterface: Send {
         request: Self::Request,
         expiry: u64,
     ) -> Result<MeltQuoteState, Self::Error>;
+
+    /// Status of the withdrawal identified by `transfer_id`, as registered on the melt quote
+    async fn check_withdrawal_status(
+        &self,
+        transfer_id: &[u8],
+    ) -> Result<WithdrawalStatus, Self::Error>;
 }
@@ -7,6 +7,8 @@ pub use proto::keyset_rotation::*;
 pub use proto::node::node_client::NodeClient;
 pub use proto::node::*;
 
+pub mod trace_context;
+
 mod proto {
     pub mod bdhke {
         tonic::include_proto!("bdhke");
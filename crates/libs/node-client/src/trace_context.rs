@@ -0,0 +1,54 @@
+//! Propagation of the caller trace context to the node
+//!
+//! The context is written in the request metadata by the globally registered propagator,
+//! W3C `traceparent` once `open_telemetry_tracing::init` has run.
+//! Without a registered propagator nothing is written.
+
+use opentelemetry::{Context, propagation::Injector};
+use tonic::{
+    Request, Status,
+    metadata::{MetadataKey, MetadataMap, MetadataValue},
+    service::Interceptor,
+};
+
+struct MetadataInjector<'a>(&'a mut MetadataMap);
+
+impl Injector for MetadataInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        // Propagators only emit ascii keys and values, anything else is skipped
+        if let (Ok(key), Ok(value)) = (
+            MetadataKey::from_bytes(key.as_bytes()),
+            MetadataValue::try_from(value),
+        ) {
+            self.0.insert(key, value);
+        }
+    }
+}
+
+/// Write the current context in `metadata`
+pub fn inject_current_context(metadata: &mut MetadataMap) {
+    let context = Context::current();
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut MetadataInjector(metadata))
+    });
+}
+
+/// Wrap `message` in a request carrying the current context
+pub fn with_trace_context<T>(message: T) -> Request<T> {
+    let mut request = Request::new(message);
+    inject_current_context(request.metadata_mut());
+
+    request
+}
+
+/// Same as `with_trace_context`, for clients built with `NodeClient::with_interceptor`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TraceContextInterceptor;
+
+impl Interceptor for TraceContextInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        inject_current_context(request.metadata_mut());
+
+        Ok(request)
+    }
+}
@@ -32,6 +32,9 @@ pub enum Error {
     /// Cannot convert units
     #[error("Cannot convert units")]
     CannotConvertUnits,
+    /// Amount is too big to be stored as an i64
+    #[error("Amount {0} does not fit in an i64")]
+    I64ReprOverflow(u64),
 }
 
 /// A typed wrapper around u64 for safely handling monetary values.
@@ -141,6 +144,14 @@ impl Amount {
         i64::from_be_bytes(self.0.to_be_bytes())
     }
 
+    /// Same as `into_i64_repr` but refuses amounts above `i64::MAX`
+    ///
+    /// Those would be stored as negative numbers in databases without unsigned types,
+    /// breaking every `SUM` or comparison done on the column.
+    pub fn try_into_i64_repr(&self) -> Result<i64, Error> {
+        i64::try_from(self.0).map_err(|_| Error::I64ReprOverflow(self.0))
+    }
+
     pub fn from_i64_repr(value: i64) -> Self {
         Self(u64::from_be_bytes(value.to_be_bytes()))
     }
@@ -552,4 +563,18 @@ mod tests {
 
         assert!(converted.is_err());
     }
+
+    #[test]
+    fn test_try_into_i64_repr() {
+        let max = Amount::from(i64::MAX as u64);
+        assert_eq!(max.try_into_i64_repr().unwrap(), i64::MAX);
+        assert_eq!(Amount::from_i64_repr(max.try_into_i64_repr().unwrap()), max);
+
+        let too_big = Amount::from(i64::MAX as u64 + 1);
+        assert!(matches!(
+            too_big.try_into_i64_repr(),
+            Err(Error::I64ReprOverflow(v)) if v == i64::MAX as u64 + 1
+        ));
+        assert!(Amount::from(u64::MAX).try_into_i64_repr().is_err());
+    }
 }
@@ -7,6 +7,7 @@ use bitcoin::hashes::sha256::Hash as Sha256Hash;
 use bitcoin::secp256k1::{
     Parity, PublicKey as NormalizedPublicKey, Scalar, Secp256k1, XOnlyPublicKey,
 };
+use subtle::ConstantTimeEq;
 use thiserror::Error;
 
 use crate::SECP256K1;
@@ -162,7 +163,19 @@ pub fn sign_message(k: &SecretKey, blind_message: &PublicKey) -> Result<PublicKe
     Ok(blind_message.mul_tweak(&SECP256K1, &k)?.into())
 }
 
+/// Compare two points without leaking, through timing, how many leading bytes match
+///
+/// The expected point is derived from the mint private key,
+/// a short-circuiting comparison would let a caller probe it byte by byte.
+fn points_ct_eq(lhs: &PublicKey, rhs: &PublicKey) -> bool {
+    lhs.to_bytes()[..].ct_eq(&rhs.to_bytes()[..]).into()
+}
+
 /// Verify Message
+///
+/// The comparison against the point computed from the secret key `a` is constant-time.
+/// The remaining steps only branch on public data: `hash_to_curve` on the proof secret,
+/// and the error cases of the curve operations.
 pub fn verify_message(
     a: &SecretKey,
     unblind_message: PublicKey,
@@ -177,7 +190,55 @@ pub fn verify_message(
         .into();
 
     // Compare the unblind_message with the expected value
-    Ok(unblind_message == expected_unblind_message)
+    Ok(points_ct_eq(&unblind_message, &expected_unblind_message))
+}
+
+/// Error of a batch operation, along with the position of the item that caused it
+#[derive(Debug, Error)]
+#[error("item {index}: {source}")]
+pub struct BatchError {
+    pub index: usize,
+    #[source]
+    pub source: Error,
+}
+
+/// Sign many blind messages, each with its own key
+///
+/// Stops at the first failure, reporting its position in the batch.
+pub fn sign_messages<'a, I>(items: I) -> Result<Vec<PublicKey>, BatchError>
+where
+    I: IntoIterator<Item = (&'a SecretKey, &'a PublicKey)>,
+{
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(index, (k, blind_message))| {
+            sign_message(k, blind_message).map_err(|source| BatchError { index, source })
+        })
+        .collect()
+}
+
+/// Verify many proofs, each against its own key
+///
+/// Same constant-time guarantee as `verify_message`.
+///
+/// Unlike `verify_message`, the secp context is shared by the whole batch instead of being
+/// created for each message.
+/// One result per item, in the same order, so a failure doesn't hide the outcome of the others.
+pub fn verify_messages<'a, I>(items: I) -> Vec<Result<bool, Error>>
+where
+    I: IntoIterator<Item = (&'a SecretKey, PublicKey, &'a [u8])>,
+{
+    items
+        .into_iter()
+        .map(|(a, unblind_message, msg)| {
+            let y: PublicKey = hash_to_curve(msg)?;
+            let expected_unblind_message: PublicKey =
+                y.mul_tweak(&SECP256K1, &Scalar::from(*a.deref()))?.into();
+
+            Ok(points_ct_eq(&unblind_message, &expected_unblind_message))
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -378,4 +439,80 @@ mod tests {
 
         assert!(verify_message(&bob_sec, unblind, &message).is_ok());
     }
+
+    #[test]
+    fn verify_message_rejects_foreign_signature() {
+        let message = b"test_message";
+        let mint_key = SecretKey::generate();
+        let other_key = SecretKey::generate();
+
+        let (b, r) = blind_message(message, None).unwrap();
+        let valid = unblind_message(
+            &sign_message(&mint_key, &b).unwrap(),
+            &r,
+            &mint_key.public_key(),
+        )
+        .unwrap();
+        let forged = unblind_message(
+            &sign_message(&other_key, &b).unwrap(),
+            &r,
+            &other_key.public_key(),
+        )
+        .unwrap();
+
+        assert!(verify_message(&mint_key, valid, message).unwrap());
+        assert!(!verify_message(&mint_key, forged, message).unwrap());
+    }
+
+    #[test]
+    fn batch_matches_single_calls() {
+        let keys: Vec<SecretKey> = (0..100).map(|_| SecretKey::generate()).collect();
+        let secrets: Vec<Secret> = (0..100).map(|_| Secret::generate()).collect();
+        let blinded: Vec<(PublicKey, SecretKey)> = secrets
+            .iter()
+            .map(|s| blind_message(s.as_bytes(), None).unwrap())
+            .collect();
+
+        let single_signatures: Vec<PublicKey> = keys
+            .iter()
+            .zip(&blinded)
+            .map(|(k, (b, _))| sign_message(k, b).unwrap())
+            .collect();
+        let batch_signatures =
+            sign_messages(keys.iter().zip(blinded.iter().map(|(b, _)| b))).unwrap();
+        assert_eq!(batch_signatures, single_signatures);
+
+        let mut unblinded: Vec<PublicKey> = single_signatures
+            .iter()
+            .zip(&blinded)
+            .zip(&keys)
+            .map(|((c, (_, r)), k)| unblind_message(c, r, &k.public_key()).unwrap())
+            .collect();
+        // Corrupt one of them
+        unblinded[42] = unblinded[41];
+
+        let single_results: Vec<bool> = keys
+            .iter()
+            .zip(&unblinded)
+            .zip(&secrets)
+            .map(|((k, c), s)| verify_message(k, *c, s.as_bytes()).unwrap())
+            .collect();
+        let batch_results: Vec<bool> = verify_messages(
+            keys.iter()
+                .zip(&unblinded)
+                .zip(&secrets)
+                .map(|((k, c), s)| (k, *c, s.as_bytes())),
+        )
+        .into_iter()
+        .map(Result::unwrap)
+        .collect();
+
+        assert_eq!(batch_results, single_results);
+        let invalid_indices: Vec<usize> = batch_results
+            .iter()
+            .enumerate()
+            .filter_map(|(i, valid)| (!valid).then_some(i))
+            .collect();
+        assert_eq!(invalid_indices, vec![42]);
+    }
 }
@@ -1,8 +1,10 @@
 use bitcoin::hashes::Hash;
 use bitcoin::hashes::sha256::Hash as Sha256;
 use core::fmt;
+use std::collections::BTreeMap;
 use std::str::FromStr;
 
+use crate::Amount;
 use crate::nut01::PublicKey;
 #[cfg(feature = "rusqlite")]
 use rusqlite::{
@@ -94,6 +96,13 @@ impl KeysetId {
             id: bytes[1..].try_into()?,
         })
     }
+
+    /// NUT-02 id of the keyset made of `keys`
+    ///
+    /// The map iterates in ascending amount order, which is the order the derivation hashes the keys in.
+    pub fn derive(keys: &BTreeMap<Amount, PublicKey>) -> Self {
+        Self::from_iter(keys.values().copied())
+    }
 }
 
 /// As per NUT-02:
@@ -107,6 +107,7 @@ impl<U: Unit> MintKeySet<U> {
         max_order: u8,
     ) -> Self {
         let mut map = BTreeMap::new();
+        let mut public_keys = BTreeMap::new();
         for i in 0..max_order {
             let amount = Amount::from(2_u64.pow(i as u32));
             let secret_key = xpriv
@@ -117,6 +118,7 @@ impl<U: Unit> MintKeySet<U> {
                 .expect("RNG busted")
                 .private_key;
             let public_key = secret_key.public_key(secp);
+            public_keys.insert(amount, public_key.into());
             map.insert(
                 amount,
                 KeyPair {
@@ -126,11 +128,10 @@ impl<U: Unit> MintKeySet<U> {
             );
         }
 
-        let keys = SetKeyPairs::new(map);
         Self {
-            id: (&keys).into(),
+            id: KeysetId::derive(&public_keys),
             unit,
-            keys,
+            keys: SetKeyPairs::new(map),
         }
     }
 
@@ -182,16 +183,21 @@ impl From<&SetKeyPairs> for KeysetId {
 #[cfg(test)]
 mod test {
     use super::Error;
+    use std::collections::BTreeMap;
     use std::str::FromStr;
 
+    use bitcoin::{bip32::Xpriv, key::Secp256k1};
+
     use rand::RngCore;
 
     use crate::{
+        Amount,
+        nut01::PublicKey,
         nut02::{KeysResponse, KeysetId},
         traits::test_types::TestUnit,
     };
 
-    use super::{KeySetInfo, KeysetResponse, SetPubKeys};
+    use super::{KeySetInfo, KeysetResponse, MintKeySet, SetPubKeys};
 
     const SHORT_KEYSET_ID: &str = "00456a94ab4e1c46";
     const SHORT_KEYSET: &str = r#"
@@ -290,6 +296,37 @@ mod test {
         assert_eq!(id, KeysetId::from_str(KEYSET_ID).unwrap());
     }
 
+    #[test]
+    fn test_derive_from_known_keysets() {
+        for (keyset, keyset_id) in [(SHORT_KEYSET, SHORT_KEYSET_ID), (KEYSET, KEYSET_ID)] {
+            let keys: BTreeMap<String, PublicKey> = serde_json::from_str(keyset).unwrap();
+            let keys: BTreeMap<Amount, PublicKey> = keys
+                .into_iter()
+                .map(|(amount, pk)| (Amount::from(amount.parse::<u64>().unwrap()), pk))
+                .collect();
+
+            assert_eq!(
+                KeysetId::derive(&keys),
+                KeysetId::from_str(keyset_id).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_generated_keyset_id_is_derived_from_its_public_keys() {
+        let secp = Secp256k1::new();
+        let xpriv = Xpriv::new_master(bitcoin::Network::Bitcoin, &[1u8; 32]).unwrap();
+        let keyset = MintKeySet::generate(&secp, xpriv, TestUnit::Sat, 8);
+
+        let public_keys = keyset
+            .keys
+            .iter()
+            .map(|(amount, key_pair)| (*amount, key_pair.public_key))
+            .collect();
+        assert_eq!(keyset.id, KeysetId::derive(&public_keys));
+        assert_eq!(keyset.id, KeysetId::from(&keyset.keys));
+    }
+
     #[test]
     fn test_deserialization_keyset_info() {
         let h = r#"{"id":"009a1f293253e41e","unit":"sat","active":true}"#;
@@ -14,8 +14,8 @@ use rusqlite::{
 
 #[derive(Debug, Error)]
 pub enum Error {
-    #[error("Unknown state")]
-    UnknownState,
+    #[error("Invalid quote state: {0}")]
+    InvalidQuoteState(i64),
 }
 
 #[derive(
@@ -51,6 +51,19 @@ impl core::fmt::Display for MintQuoteState {
     }
 }
 
+impl TryFrom<i64> for MintQuoteState {
+    type Error = Error;
+
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(MintQuoteState::Unpaid),
+            2 => Ok(MintQuoteState::Paid),
+            3 => Ok(MintQuoteState::Issued),
+            _ => Err(Error::InvalidQuoteState(value)),
+        }
+    }
+}
+
 #[cfg(feature = "rusqlite")]
 impl ToSql for MintQuoteState {
     fn to_sql(&self) -> Result<ToSqlOutput<'_>, rusqlite::Error> {
@@ -65,12 +78,9 @@ impl ToSql for MintQuoteState {
 #[cfg(feature = "rusqlite")]
 impl FromSql for MintQuoteState {
     fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
-        value.as_i64().and_then(|i| match i {
-            1 => Ok(MintQuoteState::Unpaid),
-            2 => Ok(MintQuoteState::Paid),
-            3 => Ok(MintQuoteState::Issued),
-            _ => Err(FromSqlError::Other(Box::new(Error::UnknownState))),
-        })
+        value
+            .as_i64()
+            .and_then(|i| MintQuoteState::try_from(i).map_err(|e| FromSqlError::Other(Box::new(e))))
     }
 }
 
@@ -140,3 +150,29 @@ where
             .cloned()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mint_quote_state_from_db_integer() {
+        assert_eq!(MintQuoteState::try_from(1).unwrap(), MintQuoteState::Unpaid);
+        assert_eq!(MintQuoteState::try_from(2).unwrap(), MintQuoteState::Paid);
+        assert_eq!(MintQuoteState::try_from(3).unwrap(), MintQuoteState::Issued);
+    }
+
+    #[test]
+    fn invalid_mint_quote_state_keeps_the_value() {
+        for value in [0, 4, -1] {
+            assert!(matches!(
+                MintQuoteState::try_from(value),
+                Err(Error::InvalidQuoteState(v)) if v == value
+            ));
+        }
+        assert_eq!(
+            MintQuoteState::try_from(42).unwrap_err().to_string(),
+            "Invalid quote state: 42"
+        );
+    }
+}
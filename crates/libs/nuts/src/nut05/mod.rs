@@ -12,9 +12,9 @@ use thiserror::Error;
 /// NUT05 Error
 #[derive(Debug, Error)]
 pub enum Error {
-    /// Unknown Quote State
-    #[error("Unknown quote state")]
-    UnknownState,
+    /// The stored integer doesn't match any quote state
+    #[error("Invalid quote state: {0}")]
+    InvalidQuoteState(i64),
     /// Amount overflow
     #[error("Amount Overflow")]
     AmountOverflow,
@@ -63,6 +63,19 @@ impl core::fmt::Display for MeltQuoteState {
     }
 }
 
+impl TryFrom<i64> for MeltQuoteState {
+    type Error = Error;
+
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(MeltQuoteState::Unpaid),
+            2 => Ok(MeltQuoteState::Pending),
+            3 => Ok(MeltQuoteState::Paid),
+            _ => Err(Error::InvalidQuoteState(value)),
+        }
+    }
+}
+
 #[cfg(feature = "rusqlite")]
 impl ToSql for MeltQuoteState {
     fn to_sql(&self) -> Result<ToSqlOutput<'_>, rusqlite::Error> {
@@ -77,12 +90,9 @@ impl ToSql for MeltQuoteState {
 #[cfg(feature = "rusqlite")]
 impl FromSql for MeltQuoteState {
     fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
-        value.as_i64().and_then(|i| match i {
-            1 => Ok(MeltQuoteState::Unpaid),
-            2 => Ok(MeltQuoteState::Pending),
-            3 => Ok(MeltQuoteState::Paid),
-            _ => Err(FromSqlError::Other(Box::new(Error::UnknownState))),
-        })
+        value
+            .as_i64()
+            .and_then(|i| MeltQuoteState::try_from(i).map_err(|e| FromSqlError::Other(Box::new(e))))
     }
 }
 
@@ -162,3 +172,30 @@ pub struct Settings<M, U> {
     /// Minting disabled
     pub disabled: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn melt_quote_state_from_db_integer() {
+        for state in [
+            MeltQuoteState::Unpaid,
+            MeltQuoteState::Pending,
+            MeltQuoteState::Paid,
+        ] {
+            let value = i32::from(state);
+            assert_eq!(MeltQuoteState::try_from(i64::from(value)).unwrap(), state);
+        }
+    }
+
+    #[test]
+    fn invalid_melt_quote_state_keeps_the_value() {
+        for value in [0, 4, -1] {
+            assert!(matches!(
+                MeltQuoteState::try_from(value),
+                Err(Error::InvalidQuoteState(v)) if v == value
+            ));
+        }
+    }
+}
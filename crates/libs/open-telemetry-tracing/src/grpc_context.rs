@@ -0,0 +1,82 @@
+//! Server side of the trace context propagation
+//!
+//! Clients write their context in the request metadata, see `node_client::trace_context`.
+//! Reading it back makes the server spans children of the client ones, in the same trace.
+
+use opentelemetry::{Context, propagation::Extractor};
+use tonic::metadata::{KeyRef, MetadataMap};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+struct MetadataExtractor<'a>(&'a MetadataMap);
+
+impl Extractor for MetadataExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0
+            .keys()
+            .map(|key| match key {
+                KeyRef::Ascii(key) => key.as_str(),
+                KeyRef::Binary(key) => key.as_str(),
+            })
+            .collect()
+    }
+}
+
+/// The context propagated by the caller, empty if the request carries none
+pub fn extract_context(metadata: &MetadataMap) -> Context {
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.extract(&MetadataExtractor(metadata))
+    })
+}
+
+/// Attach the current span to the trace of the caller, if it propagated one
+pub fn follow_remote_parent(metadata: &MetadataMap) {
+    let context = extract_context(metadata);
+    tracing::Span::current().set_parent(context);
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::trace::{TraceContextExt, Tracer, TracerProvider};
+    use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SdkTracerProvider};
+
+    use super::*;
+
+    #[test]
+    fn server_reads_the_client_trace_id() {
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+        let provider = SdkTracerProvider::builder().build();
+        let client_span = provider.tracer("wallet").start("swap");
+        let client_context = Context::current_with_span(client_span);
+
+        let request = {
+            let _guard = client_context.clone().attach();
+            node_client::trace_context::with_trace_context(())
+        };
+
+        assert!(request.metadata().get("traceparent").is_some());
+        let server_context = extract_context(request.metadata());
+        let remote_span_context = server_context.span().span_context().clone();
+        assert!(remote_span_context.is_remote());
+        assert_eq!(
+            remote_span_context.trace_id(),
+            client_context.span().span_context().trace_id()
+        );
+        assert_eq!(
+            remote_span_context.span_id(),
+            client_context.span().span_context().span_id()
+        );
+    }
+
+    #[test]
+    fn request_without_context_has_no_parent() {
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+
+        let context = extract_context(&MetadataMap::new());
+
+        assert!(!context.span().span_context().is_valid());
+    }
+}
@@ -17,7 +17,8 @@
 //! by setting the `OTEL_EXPORTER_OTLP_ENDPOINT` environment variable.
 //!
 //! Terminal logging respects the `RUST_LOG` environment variable for filtering, defaulting
-//! to `info` level if not set.
+//! to `info` level if not set. Set `LOG_FORMAT=json` to print one JSON object per line,
+//! for log collectors that don't parse the human-readable format.
 //!
 //! ## Filtering
 //!
@@ -29,12 +30,91 @@
 //! - `reqwest` - HTTP client library
 //! - `opentelemetry` - OpenTelemetry SDK itself
 
-use std::time::Duration;
+pub mod grpc_context;
+pub mod outcome_filter;
+#[cfg(feature = "prometheus")]
+pub mod prometheus_endpoint;
+pub mod quote_metrics;
+
+use std::{str::FromStr, time::Duration};
 
 use opentelemetry::trace::TracerProvider;
 use tracing::Subscriber;
 
-use tracing_subscriber::{EnvFilter, Layer, layer::SubscriberExt};
+use tracing_subscriber::{
+    EnvFilter, Layer, fmt::MakeWriter, layer::SubscriberExt, registry::LookupSpan,
+};
+
+/// Env var selecting the terminal output format
+const LOG_FORMAT_ENV_VAR: &str = "LOG_FORMAT";
+/// Env var holding the fraction of successful spans to export, see `outcome_filter::OutcomeFilter`
+const SUCCESS_SAMPLE_RATIO_ENV_VAR: &str = "TRACE_SUCCESS_SAMPLE_RATIO";
+
+/// Read `TRACE_SUCCESS_SAMPLE_RATIO`, defaulting to keeping every span
+fn success_sample_ratio_from_env() -> f64 {
+    match std::env::var(SUCCESS_SAMPLE_RATIO_ENV_VAR) {
+        Ok(value) => value.parse().unwrap_or_else(|e| {
+            eprintln!("invalid {SUCCESS_SAMPLE_RATIO_ENV_VAR} '{value}': {e}, keeping every span");
+            1.0
+        }),
+        Err(_) => 1.0,
+    }
+}
+
+/// Format of the terminal logs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("unknown log format '{0}', expected 'text' or 'json'")]
+pub struct UnknownLogFormat(String);
+
+impl FromStr for LogFormat {
+    type Err = UnknownLogFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(UnknownLogFormat(s.to_string())),
+        }
+    }
+}
+
+impl LogFormat {
+    /// Read `LOG_FORMAT`, defaulting to `Text`
+    ///
+    /// Tracing is not set up yet when this is called, an invalid value is reported on stderr.
+    pub fn from_env() -> Self {
+        match std::env::var(LOG_FORMAT_ENV_VAR) {
+            Ok(value) => value.parse().unwrap_or_else(|e| {
+                eprintln!("{e}, falling back to text");
+                LogFormat::Text
+            }),
+            Err(_) => LogFormat::Text,
+        }
+    }
+}
+
+/// The terminal output layer, writing to `make_writer` in `log_format`
+fn fmt_layer<S, W>(log_format: LogFormat, make_writer: W) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_level(true)
+        .with_writer(make_writer);
+
+    match log_format {
+        LogFormat::Text => layer.boxed(),
+        LogFormat::Json => layer.json().boxed(),
+    }
+}
 
 /// Initializes OpenTelemetry tracing, metrics, and logging with sensible defaults.
 ///
@@ -59,6 +139,9 @@ use tracing_subscriber::{EnvFilter, Layer, layer::SubscriberExt};
 ///
 /// * `OTEL_EXPORTER_OTLP_ENDPOINT` - Override the default OTLP endpoint (default: `http://localhost:4317`)
 /// * `RUST_LOG` - Control terminal logging levels (default: `info`)
+/// * `LOG_FORMAT` - `text` or `json`, format of the terminal logs (default: `text`)
+/// * `PROMETHEUS_METRICS_ADDRESS` - Address of the `/metrics` endpoint, only read by `init_with_prometheus`
+/// * `TRACE_SUCCESS_SAMPLE_RATIO` - Fraction of the successful spans exported, errors are always kept (default: `1.0`)
 ///
 /// ## Example
 ///
@@ -84,6 +167,55 @@ pub fn init(
 ) -> (
     opentelemetry_sdk::metrics::SdkMeterProvider,
     impl Subscriber + Send + Sync + 'static,
+) {
+    build(
+        pkg_name,
+        pkg_version,
+        #[cfg(feature = "prometheus")]
+        None,
+    )
+}
+
+/// Same as [`init`], also exposing the metrics to Prometheus when `PROMETHEUS_METRICS_ADDRESS` is set
+///
+/// Metrics are still pushed through OTLP. The returned endpoint must be served by the caller,
+/// see [`prometheus_endpoint::PrometheusEndpoint::serve`].
+#[cfg(feature = "prometheus")]
+pub fn init_with_prometheus(
+    pkg_name: &'static str,
+    pkg_version: &'static str,
+) -> Result<
+    (
+        opentelemetry_sdk::metrics::SdkMeterProvider,
+        impl Subscriber + Send + Sync + 'static,
+        Option<prometheus_endpoint::PrometheusEndpoint>,
+    ),
+    prometheus_endpoint::Error,
+> {
+    let (prometheus_reader, endpoint) = match prometheus_endpoint::address_from_env()? {
+        Some(address) => {
+            let (reader, registry) = prometheus_endpoint::exporter()?;
+            (
+                Some(reader),
+                Some(prometheus_endpoint::PrometheusEndpoint { address, registry }),
+            )
+        }
+        None => (None, None),
+    };
+    let (meter_provider, subscriber) = build(pkg_name, pkg_version, prometheus_reader);
+
+    Ok((meter_provider, subscriber, endpoint))
+}
+
+fn build(
+    pkg_name: &'static str,
+    pkg_version: &'static str,
+    #[cfg(feature = "prometheus")] prometheus_reader: Option<
+        opentelemetry_prometheus::PrometheusExporter,
+    >,
+) -> (
+    opentelemetry_sdk::metrics::SdkMeterProvider,
+    impl Subscriber + Send + Sync + 'static,
 ) {
     // Configure trace context propagation for distributed tracing
     // This ensures trace context is properly propagated across service boundaries
@@ -105,12 +237,13 @@ pub fn init(
         .build()
         .unwrap();
 
-    // Create the tracer provider with always-on sampling
-    // In production, you might want to use probabilistic sampling for high-volume services
+    // Create the tracer provider, exporting every error but only a fraction of the successes
     let tracer_provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
-        .with_sampler(opentelemetry_sdk::trace::Sampler::AlwaysOn)
         .with_resource(resource.clone())
-        .with_batch_exporter(span_exporter)
+        .with_span_processor(outcome_filter::OutcomeFilter::new(
+            success_sample_ratio_from_env(),
+            opentelemetry_sdk::trace::BatchSpanProcessor::builder(span_exporter).build(),
+        ))
         .build();
 
     // Create the tracing layer that bridges tracing spans to OpenTelemetry
@@ -135,10 +268,16 @@ pub fn init(
         .build();
 
     // Build the meter provider that applications use to create custom metrics
-    let meter_provider = opentelemetry_sdk::metrics::SdkMeterProvider::builder()
+    let meter_provider_builder = opentelemetry_sdk::metrics::SdkMeterProvider::builder()
         .with_resource(resource.clone())
-        .with_reader(metrics_reader)
-        .build();
+        .with_reader(metrics_reader);
+    // Both readers see the same instruments, OTLP push and Prometheus pull can run together
+    #[cfg(feature = "prometheus")]
+    let meter_provider_builder = match prometheus_reader {
+        Some(reader) => meter_provider_builder.with_reader(reader),
+        None => meter_provider_builder,
+    };
+    let meter_provider = meter_provider_builder.build();
 
     // Create the metrics layer that automatically exports tracing-derived metrics
     let metrics_layer = tracing_opentelemetry::MetricsLayer::new(meter_provider.clone());
@@ -187,10 +326,8 @@ pub fn init(
     // This allows users to control terminal log verbosity independently of telemetry export
     let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
 
-    // Create a human-readable formatter for terminal output
-    let fmt_layer = tracing_subscriber::fmt::layer()
-        .with_level(true)
-        .with_filter(env_filter);
+    // Human-readable or JSON formatter for terminal output, see `LogFormat`
+    let fmt_layer = fmt_layer(LogFormat::from_env(), std::io::stdout).with_filter(env_filter);
 
     // === COMPOSE ALL LAYERS ===
     // Combine all the layers into a single subscriber
@@ -203,3 +340,55 @@ pub fn init(
 
     (meter_provider, subsciber)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Write,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct CapturedOutput(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for CapturedOutput {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn log_format_is_parsed() {
+        assert_eq!(LogFormat::from_str("json").unwrap(), LogFormat::Json);
+        assert_eq!(LogFormat::from_str("text").unwrap(), LogFormat::Text);
+        assert!(LogFormat::from_str("JSON").is_err());
+    }
+
+    #[test]
+    fn json_format_emits_one_object_per_event() {
+        let output = CapturedOutput::default();
+        let writer = output.clone();
+        let subscriber = tracing_subscriber::registry().with(
+            fmt_layer(LogFormat::Json, move || writer.clone()).with_filter(EnvFilter::new("info")),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(quote_id = "abc", "quote paid");
+            tracing::debug!("filtered out");
+        });
+
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 1);
+        let event: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(event["level"], "INFO");
+        assert_eq!(event["fields"]["message"], "quote paid");
+        assert_eq!(event["fields"]["quote_id"], "abc");
+    }
+}
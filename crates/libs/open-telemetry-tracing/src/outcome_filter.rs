@@ -0,0 +1,179 @@
+//! Export every failure, and a fraction of the successes
+//!
+//! High-volume routes produce a span per request, most of them uneventful.
+//! Dropping a fraction of the successful ones keeps the collector load bounded,
+//! while errors, the spans someone will actually look at, are all kept.
+//!
+//! This can't be done by a `Sampler`: it decides when the span starts,
+//! before the request outcome is known. The filter runs when the span ends instead.
+
+use opentelemetry::{
+    Context, KeyValue,
+    trace::{Status, TraceId},
+};
+use opentelemetry_sdk::{
+    Resource,
+    error::OTelSdkResult,
+    trace::{Span, SpanData, SpanProcessor},
+};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Attribute the filter reads, `"error"` for failed operations
+pub const OUTCOME_ATTRIBUTE: &str = "outcome";
+const ERROR_OUTCOME: &str = "error";
+const SUCCESS_OUTCOME: &str = "success";
+
+/// Set the outcome of the operation on the current span
+pub fn record_outcome(is_error: bool) {
+    let outcome = if is_error {
+        ERROR_OUTCOME
+    } else {
+        SUCCESS_OUTCOME
+    };
+    tracing::Span::current().set_attribute(OUTCOME_ATTRIBUTE, outcome);
+}
+
+/// Forwards all failed spans to `inner`, and `success_ratio` of the other ones
+///
+/// A span failed if its status is an error, or its `outcome` attribute is `"error"`.
+/// The other spans are kept or dropped based on their trace id, so that a successful trace
+/// is exported entirely or not at all. Out of a dropped trace, only the failed spans are kept.
+#[derive(Debug)]
+pub struct OutcomeFilter<P> {
+    /// Compared to the lower 63 bits of the trace id
+    threshold: u64,
+    inner: P,
+}
+
+impl<P> OutcomeFilter<P> {
+    /// `success_ratio` is clamped to `[0, 1]`
+    pub fn new(success_ratio: f64, inner: P) -> Self {
+        let success_ratio = success_ratio.clamp(0.0, 1.0);
+
+        Self {
+            threshold: (success_ratio * (1u64 << 63) as f64) as u64,
+            inner,
+        }
+    }
+
+    /// Same trace id, same decision, as done by `TraceIdRatioBased`
+    fn keeps_success(&self, trace_id: TraceId) -> bool {
+        let bytes = trace_id.to_bytes();
+        let mut lower = [0u8; 8];
+        lower.copy_from_slice(&bytes[8..]);
+
+        (u64::from_be_bytes(lower) >> 1) < self.threshold
+    }
+
+    fn keeps(&self, trace_id: TraceId, status: &Status, attributes: &[KeyValue]) -> bool {
+        let failed = matches!(status, Status::Error { .. })
+            || attributes.iter().any(|kv| {
+                kv.key.as_str() == OUTCOME_ATTRIBUTE && kv.value.as_str() == ERROR_OUTCOME
+            });
+
+        failed || self.keeps_success(trace_id)
+    }
+}
+
+impl<P: SpanProcessor> SpanProcessor for OutcomeFilter<P> {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        self.inner.on_start(span, cx);
+    }
+
+    fn on_end(&self, span: SpanData) {
+        if self.keeps(span.span_context.trace_id(), &span.status, &span.attributes) {
+            self.inner.on_end(span);
+        }
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn shutdown(&self) -> OTelSdkResult {
+        self.inner.shutdown()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_sdk::trace::{BatchSpanProcessor, InMemorySpanExporter, SdkTracerProvider};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+
+    /// Run `f` with a tracer exporting through an `OutcomeFilter`, return the exported spans
+    fn exported_spans(success_ratio: f64, f: impl FnOnce()) -> Vec<SpanData> {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_span_processor(OutcomeFilter::new(
+                success_ratio,
+                BatchSpanProcessor::builder(exporter.clone()).build(),
+            ))
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+
+        tracing::subscriber::with_default(subscriber, f);
+        provider.force_flush().unwrap();
+
+        exporter.get_finished_spans().unwrap()
+    }
+
+    fn request(is_error: bool) {
+        let span = tracing::info_span!("swap");
+        let _guard = span.enter();
+        tracing::info_span!("db_query").in_scope(|| {});
+        record_outcome(is_error);
+    }
+
+    #[test]
+    fn errors_are_always_exported() {
+        let spans = exported_spans(0.0, || {
+            for i in 0..100 {
+                request(i % 10 == 0);
+            }
+        });
+
+        // The children of a dropped trace are dropped too, only the failed spans remain
+        assert_eq!(spans.len(), 10);
+        assert!(spans.iter().all(|span| span.name == "swap"));
+    }
+
+    #[test]
+    fn successes_are_downsampled_by_trace() {
+        let spans = exported_spans(0.1, || {
+            for _ in 0..1000 {
+                request(false);
+            }
+        });
+
+        let kept_requests = spans.iter().filter(|span| span.name == "swap").count();
+        assert!(
+            (50..=150).contains(&kept_requests),
+            "kept {kept_requests} out of 1000"
+        );
+        // Whole traces are kept
+        assert_eq!(spans.len(), 2 * kept_requests);
+
+        assert_eq!(exported_spans(1.0, || request(false)).len(), 2);
+    }
+
+    #[test]
+    fn error_status_is_a_failure() {
+        let filter = OutcomeFilter::new(0.0, ());
+
+        assert!(filter.keeps(TraceId::from(1u128), &Status::error("failed"), &[]));
+        assert!(!filter.keeps(TraceId::from(1u128), &Status::Ok, &[]));
+        assert!(!filter.keeps(
+            TraceId::from(1u128),
+            &Status::Unset,
+            &[KeyValue::new(OUTCOME_ATTRIBUTE, SUCCESS_OUTCOME)]
+        ));
+    }
+}
@@ -0,0 +1,118 @@
+//! Prometheus scrape endpoint, alongside the OTLP push
+//!
+//! The exporter is one more reader of the meter provider, the instruments are the same.
+
+use std::net::SocketAddr;
+
+use axum::{Router, extract::State, http::header, response::IntoResponse, routing::get};
+use opentelemetry_prometheus::PrometheusExporter;
+use prometheus::{Encoder, Registry, TextEncoder};
+
+/// Env var holding the address the `/metrics` endpoint listens on
+const PROMETHEUS_ADDRESS_ENV_VAR: &str = "PROMETHEUS_METRICS_ADDRESS";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("invalid {PROMETHEUS_ADDRESS_ENV_VAR} '{0}': {1}")]
+    InvalidAddress(String, std::net::AddrParseError),
+    #[error("failed to build the prometheus exporter: {0}")]
+    Exporter(#[from] opentelemetry_sdk::metrics::MetricError),
+    #[error("failed to serve the metrics endpoint: {0}")]
+    Serve(#[from] std::io::Error),
+}
+
+/// Where and what to expose, returned by `init_with_prometheus`
+#[derive(Debug, Clone)]
+pub struct PrometheusEndpoint {
+    pub address: SocketAddr,
+    pub registry: Registry,
+}
+
+impl PrometheusEndpoint {
+    /// Listen on `address` until the task is dropped
+    pub async fn serve(self) -> Result<(), Error> {
+        let listener = tokio::net::TcpListener::bind(self.address).await?;
+        axum::serve(listener, router(self.registry)).await?;
+
+        Ok(())
+    }
+}
+
+/// Read `PROMETHEUS_METRICS_ADDRESS`, the endpoint is disabled when it is not set
+pub(crate) fn address_from_env() -> Result<Option<SocketAddr>, Error> {
+    match std::env::var(PROMETHEUS_ADDRESS_ENV_VAR) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|e| Error::InvalidAddress(value, e)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// The reader to register on the meter provider, and the registry it fills
+pub(crate) fn exporter() -> Result<(PrometheusExporter, Registry), Error> {
+    let registry = Registry::new();
+    let exporter = opentelemetry_prometheus::exporter()
+        .with_registry(registry.clone())
+        .build()?;
+
+    Ok((exporter, registry))
+}
+
+fn router(registry: Registry) -> Router {
+    Router::new()
+        .route("/metrics", get(metrics))
+        .with_state(registry)
+}
+
+async fn metrics(State(registry): State<Registry>) -> impl IntoResponse {
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    if let Err(e) = encoder.encode(&registry.gather(), &mut buffer) {
+        tracing::error!("failed to encode metrics: {e}");
+    }
+
+    (
+        [(header::CONTENT_TYPE, encoder.format_type().to_string())],
+        buffer,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use opentelemetry::metrics::MeterProvider;
+    use opentelemetry_sdk::metrics::SdkMeterProvider;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn counter_is_scraped() {
+        let (exporter, registry) = exporter().unwrap();
+        let provider = SdkMeterProvider::builder().with_reader(exporter).build();
+        provider
+            .meter("business")
+            .u64_counter("mint_quotes_created_total")
+            .build()
+            .add(3, &[opentelemetry::KeyValue::new("unit", "millistrk")]);
+
+        let response = router(registry)
+            .oneshot(Request::get("/metrics").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert!(response.status().is_success());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("# TYPE mint_quotes_created_total counter"));
+        assert!(
+            body.lines()
+                .any(|line| line.starts_with("mint_quotes_created_total{")
+                    && line.contains(r#"unit="millistrk""#)
+                    && line.ends_with(" 3"))
+        );
+    }
+}
@@ -0,0 +1,122 @@
+//! Counters of the quotes lifecycle transitions
+//!
+//! Transitions happen in different crates (the node routes, the chain indexer),
+//! this module keeps the counter names in one place so that dashboards can compute
+//! the conversion rate between each step.
+
+use opentelemetry::{KeyValue, metrics::Meter};
+
+/// Name of the meter the business metrics are attached to
+const BUSINESS_METER: &str = "business";
+
+/// A quote reaching a new step of its lifecycle
+///
+/// Mint quotes expiring unpaid are never observed by the node, there is no point at which
+/// it could count them. Their number is `created - paid`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteTransition {
+    MintCreated,
+    MintPaid,
+    MintIssued,
+    MeltCreated,
+    MeltPaid,
+    /// An expired quote was used to melt
+    MeltExpired,
+}
+
+impl QuoteTransition {
+    pub fn counter_name(self) -> &'static str {
+        match self {
+            QuoteTransition::MintCreated => "mint_quotes_created_total",
+            QuoteTransition::MintPaid => "mint_quotes_paid_total",
+            QuoteTransition::MintIssued => "mint_quotes_issued_total",
+            QuoteTransition::MeltCreated => "melt_quotes_created_total",
+            QuoteTransition::MeltPaid => "melt_quotes_paid_total",
+            QuoteTransition::MeltExpired => "melt_quotes_expired_total",
+        }
+    }
+}
+
+/// Count `transition` for a quote of `unit`, on the global business meter
+pub fn record_quote_transition(transition: QuoteTransition, unit: &str) {
+    record_quote_transition_on(
+        &opentelemetry::global::meter(BUSINESS_METER),
+        transition,
+        unit,
+    );
+}
+
+fn record_quote_transition_on(meter: &Meter, transition: QuoteTransition, unit: &str) {
+    meter
+        .u64_counter(transition.counter_name())
+        .build()
+        .add(1, &[KeyValue::new("unit", unit.to_string())]);
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::metrics::MeterProvider;
+    use opentelemetry_sdk::metrics::{
+        InMemoryMetricExporter, PeriodicReader, SdkMeterProvider,
+        data::{Sum, SumDataPoint},
+    };
+
+    use super::*;
+
+    fn counter_value(exporter: &InMemoryMetricExporter, name: &str, unit: &str) -> u64 {
+        exporter
+            .get_finished_metrics()
+            .unwrap()
+            .iter()
+            .flat_map(|rm| rm.scope_metrics.iter())
+            .flat_map(|sm| sm.metrics.iter())
+            .filter(|m| m.name == name)
+            .filter_map(|m| m.data.as_any().downcast_ref::<Sum<u64>>())
+            .flat_map(|sum| sum.data_points.iter())
+            .filter(|dp: &&SumDataPoint<u64>| {
+                dp.attributes
+                    .iter()
+                    .any(|kv| kv.key.as_str() == "unit" && kv.value.as_str() == unit)
+            })
+            .map(|dp| dp.value)
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn mint_quote_lifecycle_increments_each_counter_once() {
+        let exporter = InMemoryMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(exporter.clone()).build())
+            .build();
+        let meter = provider.meter(BUSINESS_METER);
+
+        for transition in [
+            QuoteTransition::MintCreated,
+            QuoteTransition::MintPaid,
+            QuoteTransition::MintIssued,
+        ] {
+            record_quote_transition_on(&meter, transition, "millistrk");
+        }
+        provider.force_flush().unwrap();
+
+        for transition in [
+            QuoteTransition::MintCreated,
+            QuoteTransition::MintPaid,
+            QuoteTransition::MintIssued,
+        ] {
+            assert_eq!(
+                counter_value(&exporter, transition.counter_name(), "millistrk"),
+                1
+            );
+        }
+        assert_eq!(
+            counter_value(
+                &exporter,
+                QuoteTransition::MeltCreated.counter_name(),
+                "millistrk"
+            ),
+            0
+        );
+    }
+}
@@ -1,8 +1,8 @@
 use liquidity_source::DepositInterface;
-use starknet_types_core::{felt::Felt, hash::Poseidon};
+use starknet_types::InvoiceIdVersion;
 use uuid::Uuid;
 
-use crate::StarknetInvoiceId;
+use crate::{StarknetInvoiceId, derive_invoice_id};
 
 #[derive(Debug, thiserror::Error)]
 #[error("mock liquidity source error")]
@@ -21,11 +21,9 @@ impl DepositInterface for Depositer {
         _amount: nuts::Amount,
         expiry: u64,
     ) -> Result<(Self::InvoiceId, String), Self::Error> {
-        let quote_id_hash =
-            Felt::from_bytes_be(bitcoin_hashes::Sha256::hash(quote_id.as_bytes()).as_byte_array());
-        let mut values = [quote_id_hash, expiry.into(), 2.into()];
-        Poseidon::hades_permutation(&mut values);
-
-        Ok((StarknetInvoiceId(values[0]), "".to_string()))
+        Ok((
+            derive_invoice_id(InvoiceIdVersion::default(), quote_id, expiry),
+            "".to_string(),
+        ))
     }
 }
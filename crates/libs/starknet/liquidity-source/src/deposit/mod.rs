@@ -8,13 +8,9 @@ pub use not_mock::*;
 
 #[cfg(not(feature = "mock"))]
 mod not_mock {
-    use bitcoin_hashes::Sha256;
     use liquidity_source::DepositInterface;
     use nuts::Amount;
-    use starknet_types::{
-        Asset, ChainId, DepositPayload, PayInvoiceCallData, Unit, compute_invoice_id,
-        constants::ON_CHAIN_CONSTANTS,
-    };
+    use starknet_types::{BuildDepositPayloadError, ChainId, Unit, build_deposit_payload};
     use starknet_types_core::felt::Felt;
     use uuid::Uuid;
 
@@ -37,8 +33,8 @@ mod not_mock {
 
     #[derive(Debug, thiserror::Error)]
     pub enum Error {
-        #[error("asset {0} not found in on-chain constants")]
-        AssetNotFound(Asset),
+        #[error(transparent)]
+        Payload(#[from] BuildDepositPayloadError),
         #[error("failed to serialize Calls: {0}")]
         SerdeJson(#[from] serde_json::Error),
     }
@@ -54,31 +50,18 @@ mod not_mock {
             amount: Amount,
             expiry: u64,
         ) -> Result<(Self::InvoiceId, String), Self::Error> {
-            let asset = unit.asset();
-            let amount = unit.convert_amount_into_u256(amount);
-            let on_chain_constants = ON_CHAIN_CONSTANTS.get(self.chain_id.as_str()).unwrap();
-            let token_contract_address = on_chain_constants
-                .assets_contract_address
-                .get_contract_address_for_asset(asset)
-                .ok_or(Error::AssetNotFound(asset))?;
-
-            let quote_id_hash =
-                Felt::from_bytes_be(Sha256::hash(quote_id.as_bytes()).as_byte_array());
-
-            let payload = DepositPayload {
-                chain_id: self.chain_id.clone(),
-                call_data: PayInvoiceCallData {
-                    quote_id_hash,
-                    expiry: Felt::from(expiry),
-                    asset_contract_address: token_contract_address,
-                    amount: amount.into(),
-                    payee: self.our_account_address,
-                },
-            };
+            let payload = build_deposit_payload(
+                self.chain_id.clone(),
+                quote_id,
+                expiry,
+                unit.convert_amount_into_u256(amount),
+                unit.asset(),
+                self.our_account_address,
+            )?;
 
             let payload_json_string = serde_json::to_string(&payload)?;
 
-            let invoice_id = compute_invoice_id(quote_id_hash, expiry);
+            let invoice_id = payload.invoice_id();
 
             Ok((StarknetInvoiceId(invoice_id), payload_json_string))
         }
@@ -1,4 +1,8 @@
-use std::{num::ParseIntError, str::FromStr};
+use std::{
+    env::VarError,
+    num::ParseIntError,
+    str::{FromStr, ParseBoolError},
+};
 
 use http::{Uri, uri};
 use starknet_types::CairoShortStringToFeltError;
@@ -13,6 +17,10 @@ pub enum ReadStarknetConfigError {
     ChainId(#[from] CairoShortStringToFeltError),
     #[error("Invalid value for env var `{STARKNET_CASHIER_ACCOUNT_ADDRESS_ENV_VAR}`: {0}")]
     CashierAccountAddress(FromStrError),
+    #[error(
+        "Invalid value for env var `{STARKNET_ADDITIONAL_CASHIER_ACCOUNT_ADDRESSES_ENV_VAR}`: {0}"
+    )]
+    AdditionalCashierAccountAddresses(FromStrError),
     #[error("Invalid value for env var `{STARKNET_CASHIER_PRIVATE_KEY_ENV_VAR}`: {0}")]
     CashierPrivateKey(FromStrError),
     #[error("Invalid value for env var `{STARKNET_RPC_NODE_URL_ENV_VAR}`: {0}")]
@@ -21,43 +29,138 @@ pub enum ReadStarknetConfigError {
     Uri(#[from] uri::InvalidUri),
     #[error("Invalid value for env var `{STARKNET_INDEXER_START_BLOCK_ENV_VAR}`: {0}")]
     StartBlock(#[from] ParseIntError),
+    #[error("Invalid value for env var `{STARKNET_INDEX_ALL_EVENTS_ENV_VAR}`: {0}")]
+    IndexAllEvents(#[from] ParseBoolError),
 }
 
 const STARKNET_CASHIER_PRIVATE_KEY_ENV_VAR: &str = "STARKNET_CASHIER_PRIVATE_KEY";
 const STARKNET_CHAIN_ID_ENV_VAR: &str = "STARKNET_CHAIN_ID";
 const STARKNET_INDEXER_START_BLOCK_ENV_VAR: &str = "STARKNET_INDEXER_START_BLOCK";
 const STARKNET_CASHIER_ACCOUNT_ADDRESS_ENV_VAR: &str = "STARKNET_CASHIER_ACCOUNT_ADDRESS";
+const STARKNET_ADDITIONAL_CASHIER_ACCOUNT_ADDRESSES_ENV_VAR: &str =
+    "STARKNET_ADDITIONAL_CASHIER_ACCOUNT_ADDRESSES";
 const STARKNET_SUBSTREAMS_URL_ENV_VAR: &str = "STARKNET_SUBSTREAMS_URL";
 const STARKNET_RPC_NODE_URL_ENV_VAR: &str = "STARKNET_RPC_NODE_URL";
+const STARKNET_INDEX_ALL_EVENTS_ENV_VAR: &str = "STARKNET_INDEX_ALL_EVENTS";
+const STARKNET_SUBSTREAMS_OUTPUT_MODULE_ENV_VAR: &str = "STARKNET_SUBSTREAMS_OUTPUT_MODULE";
+const STARKNET_SUBSTREAMS_FILTERED_TRANSACTIONS_MODULE_ENV_VAR: &str =
+    "STARKNET_SUBSTREAMS_FILTERED_TRANSACTIONS_MODULE";
 
-pub(crate) fn read_env_variables() -> Result<StarknetCliConfig, ReadStarknetConfigError> {
-    let chain_id = std::env::var(STARKNET_CHAIN_ID_ENV_VAR)
-        .map_err(|e| ReadStarknetConfigError::Env(STARKNET_CHAIN_ID_ENV_VAR, e))?;
-    let indexer_start_block = std::env::var(STARKNET_INDEXER_START_BLOCK_ENV_VAR)
-        .map_err(|e| ReadStarknetConfigError::Env(STARKNET_INDEXER_START_BLOCK_ENV_VAR, e))?;
-    let cashier_account_address = std::env::var(STARKNET_CASHIER_ACCOUNT_ADDRESS_ENV_VAR)
-        .map_err(|e| ReadStarknetConfigError::Env(STARKNET_CASHIER_ACCOUNT_ADDRESS_ENV_VAR, e))?;
-    let cashier_private_key = std::env::var(STARKNET_CASHIER_PRIVATE_KEY_ENV_VAR)
-        .map_err(|e| ReadStarknetConfigError::Env(STARKNET_CASHIER_PRIVATE_KEY_ENV_VAR, e))?;
-    let rpc_node_url = std::env::var(STARKNET_RPC_NODE_URL_ENV_VAR)
-        .map_err(|e| ReadStarknetConfigError::Env(STARKNET_RPC_NODE_URL_ENV_VAR, e))?;
-    let substreams_url = std::env::var(STARKNET_SUBSTREAMS_URL_ENV_VAR)
-        .map_err(|e| ReadStarknetConfigError::Env(STARKNET_SUBSTREAMS_URL_ENV_VAR, e))?;
+/// Resolve the config from the env, falling back on `from_file` for the vars that are not set
+pub fn read_config(
+    from_file: Option<StarknetCliConfig>,
+) -> Result<StarknetCliConfig, ReadStarknetConfigError> {
+    let (
+        file_chain_id,
+        file_indexer_start_block,
+        file_cashier_account_address,
+        file_cashier_private_key,
+        file_rpc_node_url,
+        file_substreams_url,
+        file_substreams_output_module,
+        file_substreams_filtered_transactions_module,
+        file_index_all_events,
+        file_additional_cashier_account_addresses,
+    ) = match from_file {
+        Some(c) => (
+            Some(c.chain_id),
+            Some(c.indexer_start_block),
+            Some(c.cashier_account_address),
+            Some(c.cashier_private_key),
+            Some(c.rpc_node_url),
+            Some(c.substreams_url),
+            c.substreams_output_module,
+            c.substreams_filtered_transactions_module,
+            Some(c.index_all_events),
+            Some(c.additional_cashier_account_addresses),
+        ),
+        None => (None, None, None, None, None, None, None, None, None, None),
+    };
 
     let config = StarknetCliConfig {
-        chain_id: starknet_types::ChainId::from_str(&chain_id)?,
-        indexer_start_block: indexer_start_block.parse()?,
-        cashier_account_address: Felt::from_str(&cashier_account_address)
-            .map_err(ReadStarknetConfigError::CashierAccountAddress)?,
-        cashier_private_key: Felt::from_str(&cashier_private_key)
-            .map_err(ReadStarknetConfigError::CashierPrivateKey)?,
-        rpc_node_url: Url::from_str(&rpc_node_url)?,
-        substreams_url: Uri::from_str(&substreams_url)?,
+        chain_id: read_var(STARKNET_CHAIN_ID_ENV_VAR, file_chain_id, |v| {
+            Ok(starknet_types::ChainId::from_str(v)?)
+        })?,
+        indexer_start_block: read_var(
+            STARKNET_INDEXER_START_BLOCK_ENV_VAR,
+            file_indexer_start_block,
+            |v| Ok(v.parse()?),
+        )?,
+        cashier_account_address: read_var(
+            STARKNET_CASHIER_ACCOUNT_ADDRESS_ENV_VAR,
+            file_cashier_account_address,
+            |v| Felt::from_str(v).map_err(ReadStarknetConfigError::CashierAccountAddress),
+        )?,
+        additional_cashier_account_addresses: read_var(
+            STARKNET_ADDITIONAL_CASHIER_ACCOUNT_ADDRESSES_ENV_VAR,
+            Some(file_additional_cashier_account_addresses.unwrap_or_default()),
+            |v| {
+                v.split(',')
+                    .map(str::trim)
+                    .filter(|a| !a.is_empty())
+                    .map(Felt::from_str)
+                    .collect::<Result<_, _>>()
+                    .map_err(ReadStarknetConfigError::AdditionalCashierAccountAddresses)
+            },
+        )?,
+        cashier_private_key: read_var(
+            STARKNET_CASHIER_PRIVATE_KEY_ENV_VAR,
+            file_cashier_private_key,
+            |v| Felt::from_str(v).map_err(ReadStarknetConfigError::CashierPrivateKey),
+        )?,
+        rpc_node_url: read_var(STARKNET_RPC_NODE_URL_ENV_VAR, file_rpc_node_url, |v| {
+            Ok(Url::from_str(v)?)
+        })?,
+        substreams_url: read_var(STARKNET_SUBSTREAMS_URL_ENV_VAR, file_substreams_url, |v| {
+            Ok(Uri::from_str(v)?)
+        })?,
+        substreams_output_module: read_optional_var(
+            STARKNET_SUBSTREAMS_OUTPUT_MODULE_ENV_VAR,
+            file_substreams_output_module,
+        )?,
+        substreams_filtered_transactions_module: read_optional_var(
+            STARKNET_SUBSTREAMS_FILTERED_TRANSACTIONS_MODULE_ENV_VAR,
+            file_substreams_filtered_transactions_module,
+        )?,
+        index_all_events: read_optional_var(
+            STARKNET_INDEX_ALL_EVENTS_ENV_VAR,
+            file_index_all_events.map(|b| b.to_string()),
+        )?
+        .map(|v| v.parse())
+        .transpose()?
+        .unwrap_or_default(),
     };
 
     Ok(config)
 }
 
+/// Parse the env var `name`, or use `default` when it's not set
+fn read_var<T>(
+    name: &'static str,
+    default: Option<T>,
+    parse: impl FnOnce(&str) -> Result<T, ReadStarknetConfigError>,
+) -> Result<T, ReadStarknetConfigError> {
+    match std::env::var(name) {
+        Ok(v) => parse(&v),
+        Err(VarError::NotPresent) => {
+            default.ok_or(ReadStarknetConfigError::Env(name, VarError::NotPresent))
+        }
+        Err(e) => Err(ReadStarknetConfigError::Env(name, e)),
+    }
+}
+
+/// Read the env var `name`, or use `default` when it's not set, the value being optional
+fn read_optional_var(
+    name: &'static str,
+    default: Option<String>,
+) -> Result<Option<String>, ReadStarknetConfigError> {
+    match std::env::var(name) {
+        Ok(v) => Ok(Some(v)),
+        Err(VarError::NotPresent) => Ok(default),
+        Err(e) => Err(ReadStarknetConfigError::Env(name, e)),
+    }
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct StarknetCliConfig {
     /// The chain we are using as backend
@@ -65,11 +168,40 @@ pub struct StarknetCliConfig {
     pub indexer_start_block: i64,
     /// The address of the on-chain account managing deposited assets
     pub cashier_account_address: starknet_types_core::felt::Felt,
+    /// Previous cashier accounts whose payments must still be recognized, while rotating the cashier
+    #[serde(default)]
+    pub additional_cashier_account_addresses: Vec<starknet_types_core::felt::Felt>,
     pub cashier_private_key: starknet_types_core::felt::Felt,
     /// The url of the starknet rpc node we want to use
     pub rpc_node_url: Url,
     #[serde(with = "uri_serde")]
     pub substreams_url: Uri,
+    /// Override the name of the substreams module whose output is indexed
+    #[serde(default)]
+    pub substreams_output_module: Option<String>,
+    /// Override the name of the substreams module receiving the transaction filter
+    #[serde(default)]
+    pub substreams_filtered_transactions_module: Option<String>,
+    /// Also record the invoice contract events that don't involve the cashier
+    #[serde(default)]
+    pub index_all_events: bool,
+}
+
+impl StarknetCliConfig {
+    pub fn substreams_module_names(&self) -> substreams_sink::ModuleNames {
+        let default = substreams_sink::ModuleNames::default();
+
+        substreams_sink::ModuleNames {
+            output: self
+                .substreams_output_module
+                .clone()
+                .unwrap_or(default.output),
+            filtered_transactions: self
+                .substreams_filtered_transactions_module
+                .clone()
+                .unwrap_or(default.filtered_transactions),
+        }
+    }
 }
 
 mod uri_serde {
@@ -1,4 +1,5 @@
-use futures::{FutureExt, select};
+use std::collections::HashSet;
+
 use http::Uri;
 use sqlx::PgPool;
 use starknet_types::ChainId;
@@ -10,32 +11,30 @@ pub async fn init_indexer_task(
     substreams_endpoint: Uri,
     chain_id: ChainId,
     start_block: i64,
-    cashier_account_address: Felt,
+    cashier_account_addresses: HashSet<Felt>,
+    index_all_events: bool,
+    module_names: substreams_sink::ModuleNames,
 ) {
     tokio::spawn(async move {
-        select! {
-          indexer_res =
-          substreams_sink::launch(
-              pg_pool,
-              substreams_endpoint,
-              chain_id,
-              start_block,
-              cashier_account_address,
-          ).fuse() => match indexer_res {
-                Ok(()) => {
-                    error!(name: "indexer-task-error", name = "indexer-task-error", error = "returned");
-                },
-                Err(err) => {
-                    error!(name: "indexer-task-error", name = "indexer-task-error", error = ?err);
-                },
-
-          },
-          sig = tokio::signal::ctrl_c().fuse() => match sig {
-                Ok(()) => {},
-                Err(err) => {
-                    error!(name: "ctrl-c-error", name = "ctrl-c-error", error = ?err);
-                },
+        let shutdown_signal = async {
+            if let Err(err) = tokio::signal::ctrl_c().await {
+                error!(name: "ctrl-c-error", name = "ctrl-c-error", error = ?err);
             }
         };
+
+        if let Err(err) = substreams_sink::launch(
+            pg_pool,
+            substreams_endpoint,
+            chain_id,
+            start_block,
+            cashier_account_addresses,
+            index_all_events,
+            module_names,
+            shutdown_signal,
+        )
+        .await
+        {
+            error!(name: "indexer-task-error", name = "indexer-task-error", error = ?err);
+        }
     });
 }
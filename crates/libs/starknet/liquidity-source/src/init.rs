@@ -1,21 +1,20 @@
 #[cfg(feature = "mock")]
 mod mock_impl {
+    use sqlx::PgPool;
+    use starknet_types::InvoiceIdVersion;
+
     use crate::{Depositer, StarknetLiquiditySource, Withdrawer};
 
     impl StarknetLiquiditySource {
-        pub fn new() -> Self {
+        pub fn new(pg_pool: PgPool) -> Self {
             StarknetLiquiditySource {
                 depositer: Depositer,
                 withdrawer: Withdrawer,
+                invoice_id_version: InvoiceIdVersion::default(),
+                pg_pool,
             }
         }
     }
-
-    impl Default for StarknetLiquiditySource {
-        fn default() -> Self {
-            Self::new()
-        }
-    }
 }
 
 #[cfg(not(feature = "mock"))]
@@ -31,16 +30,20 @@ mod not_mock_impl {
     use starknet_types::constants::ON_CHAIN_CONSTANTS;
 
     use crate::{
-        Depositer, Error, StarknetLiquiditySource, Withdrawer, env_config::read_env_variables,
-        indexer,
+        Depositer, Error, StarknetCliConfig, StarknetLiquiditySource, Withdrawer, indexer,
+        read_config,
     };
 
     impl StarknetLiquiditySource {
-        pub async fn init(pg_pool: PgPool) -> Result<Self, Error> {
-            let config = read_env_variables()?;
+        /// `from_file` holds the settings of the node config file, the env vars take precedence
+        pub async fn init(
+            pg_pool: PgPool,
+            from_file: Option<StarknetCliConfig>,
+        ) -> Result<Self, Error> {
+            let config = read_config(from_file)?;
 
             // Create provider
-            let provider = JsonRpcClient::new(HttpTransport::new(config.rpc_node_url));
+            let provider = JsonRpcClient::new(HttpTransport::new(config.rpc_node_url.clone()));
 
             // Create signer
             let signer =
@@ -55,15 +58,20 @@ mod not_mock_impl {
             ));
 
             let cloned_chain_id = config.chain_id.clone();
-            let cloned_cashier_account_address = config.cashier_account_address;
+            let cashier_account_addresses = std::iter::once(config.cashier_account_address)
+                .chain(config.additional_cashier_account_addresses.iter().copied())
+                .collect();
             let cloned_pg_pool = pg_pool.clone();
+            let module_names = config.substreams_module_names();
             let _handle = tokio::spawn(async move {
                 indexer::init_indexer_task(
                     cloned_pg_pool,
                     config.substreams_url,
                     cloned_chain_id,
                     config.indexer_start_block,
-                    cloned_cashier_account_address,
+                    cashier_account_addresses,
+                    config.index_all_events,
+                    module_names,
                 )
                 .await
             });
@@ -76,7 +84,10 @@ mod not_mock_impl {
                     config.chain_id,
                     account,
                     on_chain_constants.invoice_payment_contract_address,
+                    pg_pool,
+                    on_chain_constants.invoice_id_version,
                 ),
+                invoice_id_version: on_chain_constants.invoice_id_version,
             })
         }
     }
@@ -1,23 +1,29 @@
 mod deposit;
-#[cfg(not(feature = "mock"))]
 mod env_config;
 #[cfg(not(feature = "mock"))]
 mod indexer;
 mod init;
+#[cfg(feature = "mock")]
+mod mock;
 mod withdraw;
 
 use std::fmt::{LowerHex, UpperHex};
 
 pub use deposit::{Depositer, Error as DepositError};
-use starknet_types::{CairoShortStringToFeltError, Unit};
-use starknet_types_core::{felt::Felt, hash::Poseidon};
+pub use env_config::{ReadStarknetConfigError, StarknetCliConfig, read_config};
+#[cfg(feature = "mock")]
+pub use mock::MarkInvoicePaidError;
+use starknet_types::{
+    CairoShortStringToFeltError, InvoiceIdVersion, Unit, compute_invoice_id, hash_quote_id,
+};
+use starknet_types_core::felt::Felt;
 pub use withdraw::{Error as WithdrawalError, MeltPaymentRequest, Withdrawer};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[cfg(not(feature = "mock"))]
     #[error("failed to init config from env variables: {0}")]
-    Config(#[from] env_config::ReadStarknetConfigError),
+    Config(#[from] ReadStarknetConfigError),
     #[error("invalid chain id value: {0}")]
     ChainId(CairoShortStringToFeltError),
 }
@@ -42,10 +48,28 @@ impl UpperHex for StarknetInvoiceId {
     }
 }
 
+/// Same derivation as the one done onchain by the invoice payment contract of `version`
+pub(crate) fn derive_invoice_id(
+    version: InvoiceIdVersion,
+    quote_id: uuid::Uuid,
+    expiry: u64,
+) -> StarknetInvoiceId {
+    StarknetInvoiceId(compute_invoice_id(
+        version,
+        hash_quote_id(&quote_id),
+        expiry,
+    ))
+}
+
 #[derive(Debug, Clone)]
 pub struct StarknetLiquiditySource {
     pub depositer: Depositer,
     pub withdrawer: Withdrawer,
+    /// Version of the invoice payment contract deployed on the chain
+    invoice_id_version: InvoiceIdVersion,
+    /// Where the mock writes the payments a real indexer would have seen
+    #[cfg(feature = "mock")]
+    pg_pool: sqlx::PgPool,
 }
 
 impl liquidity_source::LiquiditySource for StarknetLiquiditySource {
@@ -63,11 +87,6 @@ impl liquidity_source::LiquiditySource for StarknetLiquiditySource {
     }
 
     fn compute_invoice_id(&self, quote_id: uuid::Uuid, expiry: u64) -> Self::InvoiceId {
-        let quote_id_hash =
-            Felt::from_bytes_be(bitcoin_hashes::Sha256::hash(quote_id.as_bytes()).as_byte_array());
-        let mut values = [quote_id_hash, expiry.into(), 2.into()];
-        Poseidon::hades_permutation(&mut values);
-
-        StarknetInvoiceId(values[0])
+        derive_invoice_id(self.invoice_id_version, quote_id, expiry)
     }
 }
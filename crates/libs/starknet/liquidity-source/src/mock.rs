@@ -0,0 +1,66 @@
+//! Payments for the mock liquidity source
+//!
+//! There is no chain nor indexer behind the mock. Tests play the indexer's part,
+//! declaring that an invoice received some amount, and the quote moves through
+//! the same state transitions as it would when the real payment is seen onchain.
+
+use nuts::Amount;
+use starknet_types::{StarknetU256, Unit};
+use starknet_types_core::felt::Felt;
+use uuid::Uuid;
+
+use crate::{StarknetInvoiceId, StarknetLiquiditySource};
+
+#[derive(Debug, thiserror::Error)]
+pub enum MarkInvoicePaidError {
+    #[error("failed to acquire db connection: {0}")]
+    Acquire(#[source] sqlx::Error),
+    #[error(transparent)]
+    Db(#[from] db_node::Error),
+    #[error("no mint quote for invoice id {0:#x}")]
+    UnknownInvoice(StarknetInvoiceId),
+    #[error("failed to register the payment: {0}")]
+    Payment(#[source] anyhow::Error),
+}
+
+impl StarknetLiquiditySource {
+    /// Register a payment of `amount` to the mint quote of `invoice_id`
+    ///
+    /// Payments add up, the quote is Paid once they cover its amount.
+    pub async fn mark_invoice_paid(
+        &self,
+        invoice_id: &StarknetInvoiceId,
+        amount: Amount,
+    ) -> Result<(), MarkInvoicePaidError> {
+        let mut conn = self
+            .pg_pool
+            .acquire()
+            .await
+            .map_err(MarkInvoicePaidError::Acquire)?;
+        let invoice_id_bytes = invoice_id.0.to_bytes_be();
+
+        let (quote_id, quote_amount, unit) = db_node::mint_quote::get_quote_infos_by_invoice_id::<
+            Unit,
+        >(&mut conn, &invoice_id_bytes)
+        .await?
+        .ok_or_else(|| MarkInvoicePaidError::UnknownInvoice(invoice_id.clone()))?;
+
+        let paid = StarknetU256::from(unit.convert_amount_into_u256(amount));
+        let payment_event = db_node::PaymentEvent {
+            block_id: "mock".to_string(),
+            // Unique, so that successive payments to the same invoice all count
+            tx_hash: Felt::from(Uuid::new_v4().as_u128()).to_hex_string(),
+            index: 0,
+            asset: unit.asset().to_string(),
+            payee: Felt::ZERO.to_hex_string(),
+            invoice_id: invoice_id_bytes,
+            payer: Felt::ZERO.to_hex_string(),
+            amount_low: paid.low.to_hex_string(),
+            amount_high: paid.high.to_hex_string(),
+        };
+
+        substreams_sink::handle_mint_payment(&mut conn, quote_id, payment_event, unit, quote_amount)
+            .await
+            .map_err(MarkInvoicePaidError::Payment)
+    }
+}
@@ -1,4 +1,4 @@
-use liquidity_source::WithdrawInterface;
+use liquidity_source::{WithdrawInterface, WithdrawalStatus};
 use num_traits::CheckedAdd;
 use nuts::traits::Unit as UnitT;
 use nuts::{Amount, nut05::MeltQuoteState};
@@ -78,4 +78,11 @@ impl WithdrawInterface for Withdrawer {
     ) -> Result<MeltQuoteState, Error> {
         Ok(MeltQuoteState::Paid)
     }
+
+    async fn check_withdrawal_status(
+        &self,
+        _transfer_id: &[u8],
+    ) -> Result<WithdrawalStatus, Error> {
+        Ok(WithdrawalStatus::Succeeded)
+    }
 }
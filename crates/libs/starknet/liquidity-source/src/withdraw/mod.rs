@@ -27,8 +27,9 @@ mod not_mock {
         constants::ON_CHAIN_CONSTANTS,
     };
 
-    use liquidity_source::WithdrawInterface;
-    use starknet_types::is_valid_starknet_address;
+    use liquidity_source::{WithdrawInterface, WithdrawalStatus};
+    use sqlx::PgPool;
+    use starknet_types::{InvoiceIdVersion, compute_invoice_id, is_valid_starknet_address};
     use uuid::Uuid;
 
     use std::{sync::Arc, time::Duration};
@@ -57,6 +58,8 @@ mod not_mock {
         InvalidPaymentRequest(#[from] serde_json::Error),
         #[error("invalid starknet address: {0}")]
         InvalidStarknetAddress(Felt),
+        #[error("invalid transfer id, expected a transaction hash")]
+        InvalidTransferId,
         #[error("failed to send transaction: {0}")]
         Transaction(#[from] starknet_types::transactions::Error<OurAccount>),
         #[error("withdraw order channel has been closed")]
@@ -86,6 +89,7 @@ mod not_mock {
     #[derive(Debug, Clone)]
     pub struct Withdrawer {
         chain_id: ChainId,
+        account: Arc<OurAccount>,
         withdraw_order_sender: mpsc::UnboundedSender<PayInvoiceCallData>,
     }
 
@@ -94,12 +98,21 @@ mod not_mock {
             chain_id: ChainId,
             account: Arc<OurAccount>,
             invoice_payment_contract_address: Felt,
+            pg_pool: PgPool,
+            invoice_id_version: InvoiceIdVersion,
         ) -> Self {
             let (tx, rx) = mpsc::unbounded_channel();
 
+            let worker_account = account.clone();
             let _join_handle = tokio::spawn(async move {
-                let res =
-                    process_withdraw_requests(account, rx, invoice_payment_contract_address).await;
+                let res = process_withdraw_requests(
+                    worker_account,
+                    rx,
+                    invoice_payment_contract_address,
+                    pg_pool,
+                    invoice_id_version,
+                )
+                .await;
 
                 match res {
                     Ok(_) => error!(name: "cashier-worker", error = "returned"),
@@ -109,11 +122,25 @@ mod not_mock {
 
             Self {
                 chain_id,
+                account,
                 withdraw_order_sender: tx,
             }
         }
     }
 
+    pub(super) fn withdrawal_status(status: TransactionStatus) -> WithdrawalStatus {
+        match status {
+            TransactionStatus::Received => WithdrawalStatus::Pending,
+            TransactionStatus::AcceptedOnL2(ExecutionResult::Succeeded)
+            | TransactionStatus::AcceptedOnL1(ExecutionResult::Succeeded) => {
+                WithdrawalStatus::Succeeded
+            }
+            TransactionStatus::AcceptedOnL2(ExecutionResult::Reverted { .. })
+            | TransactionStatus::AcceptedOnL1(ExecutionResult::Reverted { .. })
+            | TransactionStatus::Rejected => WithdrawalStatus::Reverted,
+        }
+    }
+
     #[async_trait::async_trait]
     impl WithdrawInterface for Withdrawer {
         type Error = Error;
@@ -186,6 +213,26 @@ mod not_mock {
 
             Ok(MeltQuoteState::Pending)
         }
+
+        /// `transfer_id` is the hash of the transaction that paid the quote
+        async fn check_withdrawal_status(
+            &self,
+            transfer_id: &[u8],
+        ) -> Result<WithdrawalStatus, Error> {
+            if transfer_id.len() != 32 {
+                return Err(Error::InvalidTransferId);
+            }
+            let tx_hash = Felt::from_bytes_be_slice(transfer_id);
+
+            let status = self
+                .account
+                .provider()
+                .get_transaction_status(tx_hash)
+                .await
+                .map_err(Error::GetTransactionStatus)?;
+
+            Ok(withdrawal_status(status))
+        }
     }
 
     async fn wait_for_tx_completion<A: Account + ConnectedAccount + Sync>(
@@ -239,10 +286,33 @@ mod not_mock {
         Ok(())
     }
 
+    /// Record `tx_hash` on the quotes it pays, for their status to be followed
+    async fn register_transfer_id(
+        pg_pool: &PgPool,
+        invoice_id_version: InvoiceIdVersion,
+        orders: &[PayInvoiceCallData],
+        tx_hash: Felt,
+    ) -> Result<(), Error> {
+        let invoice_ids: Vec<[u8; 32]> = orders
+            .iter()
+            .map(|order| {
+                compute_invoice_id(invoice_id_version, order.quote_id_hash, order.expiry)
+                    .to_bytes_be()
+            })
+            .collect();
+        let mut conn = pg_pool.acquire().await.map_err(Error::PgPool)?;
+
+        db_node::melt_quote::set_transfer_id(&mut conn, &invoice_ids, &tx_hash.to_bytes_be())
+            .await
+            .map_err(Error::RegisterTxHash)
+    }
+
     pub async fn process_withdraw_requests(
         account: Arc<SingleOwnerAccount<JsonRpcClient<HttpTransport>, LocalWallet>>,
         mut withdraw_queue: mpsc::UnboundedReceiver<PayInvoiceCallData>,
         invoice_payment_contract_address: Felt,
+        pg_pool: PgPool,
+        invoice_id_version: InvoiceIdVersion,
     ) -> Result<(), Error> {
         let mut orders = Vec::new();
         let mut tx_handle: Option<tokio::task::JoinHandle<Result<(), Error>>> = None;
@@ -281,6 +351,12 @@ mod not_mock {
                         .await?
                     };
 
+                    // The payment is already sent, failing to record it only delays its reconciliation
+                    if let Err(err) =
+                        register_transfer_id(&pg_pool, invoice_id_version, &orders, tx_hash).await
+                    {
+                        error!(name: "register-transfer-id", tx_hash = tx_hash.to_hex_string(), error = %err);
+                    }
                     orders.clear();
 
                     tx_handle = Some(tokio::spawn(wait_for_tx_completion(
@@ -292,3 +368,33 @@ mod not_mock {
         }
     }
 }
+
+#[cfg(all(test, not(feature = "mock")))]
+mod tests {
+    use liquidity_source::WithdrawalStatus;
+    use starknet::core::types::{ExecutionResult, TransactionStatus};
+
+    use super::not_mock::withdrawal_status;
+
+    #[test]
+    fn failed_transactions_are_reverted_withdrawals() {
+        assert_eq!(
+            withdrawal_status(TransactionStatus::AcceptedOnL2(ExecutionResult::Reverted {
+                reason: "u256_sub Overflow".to_string()
+            })),
+            WithdrawalStatus::Reverted
+        );
+        assert_eq!(
+            withdrawal_status(TransactionStatus::Rejected),
+            WithdrawalStatus::Reverted
+        );
+        assert_eq!(
+            withdrawal_status(TransactionStatus::Received),
+            WithdrawalStatus::Pending
+        );
+        assert_eq!(
+            withdrawal_status(TransactionStatus::AcceptedOnL2(ExecutionResult::Succeeded)),
+            WithdrawalStatus::Succeeded
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use starknet::core::types::Felt;
+
+#[derive(Debug, thiserror::Error)]
+#[error("a transaction filter needs at least one contract address")]
+pub struct EmptyFilterError;
+
+/// Build the expression passed as param to the `filtered_transactions` module
+///
+/// Transactions are kept if they emitted an event from any of the contract addresses,
+/// and, when event keys are set, if that event's first key is one of them.
+#[derive(Debug, Clone, Default)]
+pub struct SubstreamsFilterBuilder {
+    from_addresses: Vec<Felt>,
+    event_keys: Vec<Felt>,
+}
+
+impl SubstreamsFilterBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_address(mut self, address: Felt) -> Self {
+        self.from_addresses.push(address);
+        self
+    }
+
+    pub fn event_key(mut self, key: Felt) -> Self {
+        self.event_keys.push(key);
+        self
+    }
+
+    pub fn build(&self) -> Result<String, EmptyFilterError> {
+        if self.from_addresses.is_empty() {
+            return Err(EmptyFilterError);
+        }
+
+        let addresses = disjunction("ev:from_address", &self.from_addresses);
+        if self.event_keys.is_empty() {
+            return Ok(addresses);
+        }
+
+        Ok(format!(
+            "{} && {}",
+            parenthesize(addresses, self.from_addresses.len()),
+            parenthesize(
+                disjunction("ev:k0", &self.event_keys),
+                self.event_keys.len()
+            )
+        ))
+    }
+}
+
+fn disjunction(prefix: &str, values: &[Felt]) -> String {
+    values
+        .iter()
+        .map(|v| format!("{}:{}", prefix, v.to_fixed_hex_string()))
+        .collect::<Vec<_>>()
+        .join(" || ")
+}
+
+fn parenthesize(expression: String, n_terms: usize) -> String {
+    if n_terms > 1 {
+        format!("({})", expression)
+    } else {
+        expression
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS_A: &str = "0x0000000000000000000000000000000000000000000000000000000000000a0a";
+    const ADDRESS_B: &str = "0x0000000000000000000000000000000000000000000000000000000000000b0b";
+    const KEY_A: &str = "0x00000000000000000000000000000000000000000000000000000000000000ca";
+    const KEY_B: &str = "0x00000000000000000000000000000000000000000000000000000000000000cb";
+
+    fn felt(hex: &str) -> Felt {
+        Felt::from_hex(hex).unwrap()
+    }
+
+    #[test]
+    fn single_address() {
+        let expression = SubstreamsFilterBuilder::new()
+            .from_address(felt(ADDRESS_A))
+            .build()
+            .unwrap();
+
+        assert_eq!(expression, format!("ev:from_address:{ADDRESS_A}"));
+    }
+
+    #[test]
+    fn multiple_addresses() {
+        let expression = SubstreamsFilterBuilder::new()
+            .from_address(felt(ADDRESS_A))
+            .from_address(felt(ADDRESS_B))
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            expression,
+            format!("ev:from_address:{ADDRESS_A} || ev:from_address:{ADDRESS_B}")
+        );
+    }
+
+    #[test]
+    fn addresses_and_event_keys() {
+        let expression = SubstreamsFilterBuilder::new()
+            .from_address(felt(ADDRESS_A))
+            .event_key(felt(KEY_A))
+            .build()
+            .unwrap();
+        assert_eq!(
+            expression,
+            format!("ev:from_address:{ADDRESS_A} && ev:k0:{KEY_A}")
+        );
+
+        let expression = SubstreamsFilterBuilder::new()
+            .from_address(felt(ADDRESS_A))
+            .from_address(felt(ADDRESS_B))
+            .event_key(felt(KEY_A))
+            .event_key(felt(KEY_B))
+            .build()
+            .unwrap();
+        assert_eq!(
+            expression,
+            format!(
+                "(ev:from_address:{ADDRESS_A} || ev:from_address:{ADDRESS_B}) && (ev:k0:{KEY_A} || ev:k0:{KEY_B})"
+            )
+        );
+    }
+
+    #[test]
+    fn no_address_is_an_error() {
+        assert!(
+            SubstreamsFilterBuilder::new()
+                .event_key(felt(KEY_A))
+                .build()
+                .is_err()
+        );
+    }
+}
@@ -1,51 +1,79 @@
 use std::{
+    collections::HashSet,
     env::{self, VarError},
+    pin::pin,
     str::FromStr,
     sync::Arc,
 };
 
 use crate::pb::{invoice_contract::v1::RemittanceEvents, sf::substreams::rpc::v2::BlockScopedData};
 use anyhow::{Error, Result, anyhow};
-use db_node::PaymentEvent;
-use futures::StreamExt;
+use db_node::{
+    PaymentEvent,
+    quote::{QuoteInfos, QuoteKind, get_quote_kind_by_invoice_id},
+};
+use futures::{FutureExt, Stream, StreamExt, select};
 use http::Uri;
 use nuts::traits::Unit as UnitT;
 use nuts::{Amount, nut04::MintQuoteState, nut05::MeltQuoteState};
+use open_telemetry_tracing::quote_metrics::{QuoteTransition, record_quote_transition};
 use pb::{
     invoice_contract::v1::RemittanceEvent,
     sf::substreams::v1::module::input::{Input, Params},
 };
 use prost::Message;
+use remittance::Remittance;
 use sqlx::{
-    PgConnection, PgPool,
+    Connection, PgConnection, PgPool,
     types::{
         Uuid,
         chrono::{DateTime, Utc},
     },
 };
 use starknet::core::types::Felt;
-use starknet_types::{ChainId, StarknetU256, Unit, constants::ON_CHAIN_CONSTANTS};
+use starknet_types::{
+    ChainId, StarknetU256, Unit, constants::ON_CHAIN_CONSTANTS, sum_payment_amounts,
+};
 use substreams::SubstreamsEndpoint;
 use substreams_stream::{BlockResponse, SubstreamsStream};
 use tracing::{Level, debug, error, event};
 
+mod filter;
+pub use filter::{EmptyFilterError, SubstreamsFilterBuilder};
 mod parse_inputs;
+pub use parse_inputs::{
+    DEFAULT_FILTERED_TRANSACTIONS_MODULE_NAME, DEFAULT_OUTPUT_MODULE_NAME, ModuleNames,
+    ModuleNotFoundError,
+};
 #[allow(clippy::enum_variant_names)]
 mod pb;
+mod remittance;
 mod substreams;
 mod substreams_stream;
 
+/// Index the invoice contract events until `shutdown_signal` resolves
+///
+/// Payments are only relevant to the quotes if they go to or come from one of `cashier_account_addresses`,
+/// more than one being needed while the cashier account is being rotated.
+///
+/// The shutdown signal is only observed between two blocks,
+/// so the cursor of the last processed block is always persisted before returning.
+///
+/// With `index_all_events`, the remittances that don't involve the cashier are also recorded,
+/// without any effect on the quotes.
+#[allow(clippy::too_many_arguments)]
 pub async fn launch(
     pg_pool: PgPool,
     endpoint_url: Uri,
     chain_id: ChainId,
     initial_block: i64,
-    cashier_account_address: Felt,
+    cashier_account_addresses: HashSet<Felt>,
+    index_all_events: bool,
+    module_names: ModuleNames,
+    shutdown_signal: impl Future<Output = ()>,
 ) -> Result<()> {
-    const OUTPUT_MODULE_NAME: &str = "map_invoice_contract_events";
-    const STARKNET_FILTERED_TRANSACTIONS_MODULE_NAME: &str = "starknet:filtered_transactions";
-
     let mut package = parse_inputs::read_package(vec![])?;
+    parse_inputs::check_modules_exist(&package, &module_names)?;
 
     let token = match env::var("SUBSTREAMS_API_TOKEN") {
         Err(VarError::NotPresent) => None,
@@ -58,12 +86,9 @@ pub async fn launch(
         .get(chain_id.as_str())
         .ok_or(anyhow!("unsuported chain id"))?;
 
-    let starknet_filtered_transactions_expression = format!(
-        "ev:from_address:{}",
-        on_chain_constants
-            .invoice_payment_contract_address
-            .to_fixed_hex_string()
-    );
+    let starknet_filtered_transactions_expression = SubstreamsFilterBuilder::new()
+        .from_address(on_chain_constants.invoice_payment_contract_address)
+        .build()?;
     // Update tx filter
     package
         .modules
@@ -71,10 +96,10 @@ pub async fn launch(
         .unwrap()
         .modules
         .iter_mut()
-        .find(|m| m.name == STARKNET_FILTERED_TRANSACTIONS_MODULE_NAME)
+        .find(|m| m.name == module_names.filtered_transactions)
         .ok_or(anyhow!(
             "module `{}` not found",
-            STARKNET_FILTERED_TRANSACTIONS_MODULE_NAME
+            module_names.filtered_transactions
         ))?
         .inputs[0]
         .input = Some(Input::Params(Params {
@@ -85,31 +110,74 @@ pub async fn launch(
 
     let mut db_conn = pg_pool.acquire().await?;
 
+    let n_reconciled = reconcile_unpaid_quotes(&mut db_conn).await?;
+    if n_reconciled != 0 {
+        event!(
+            name: "unpaid-quotes-reconciled",
+            Level::INFO,
+            name = "unpaid-quotes-reconciled",
+            n_reconciled,
+        );
+    }
+
     let cursor: Option<String> = load_persisted_cursor(&mut db_conn).await?;
 
-    let mut stream = SubstreamsStream::new(
+    let stream = SubstreamsStream::new(
         endpoint,
         cursor,
         package.modules,
-        OUTPUT_MODULE_NAME.to_string(),
+        module_names.output,
         initial_block,
         0,
     );
 
+    let res = consume_stream(
+        &mut db_conn,
+        stream,
+        &chain_id,
+        &cashier_account_addresses,
+        index_all_events,
+        shutdown_signal,
+    )
+    .await;
+
+    // Return the connection eagerly, rather than from a task spawned on drop
+    // that may never run once the runtime shuts down
+    db_conn.return_to_pool().await;
+
+    res
+}
+
+async fn consume_stream(
+    db_conn: &mut PgConnection,
+    stream: impl Stream<Item = Result<BlockResponse>> + Unpin,
+    chain_id: &ChainId,
+    cashier_account_addresses: &HashSet<Felt>,
+    index_all_events: bool,
+    shutdown_signal: impl Future<Output = ()>,
+) -> Result<()> {
+    let mut stream = stream.fuse();
+    let mut shutdown_signal = pin!(shutdown_signal.fuse());
+
     loop {
-        match stream.next().await {
+        let block_response = select! {
+            block_response = stream.next() => block_response,
+            () = shutdown_signal => break,
+        };
+
+        match block_response {
             None => {
-                break;
-            }
-            Some(Ok(BlockResponse::New(data))) => {
-                process_block_scoped_data(&mut db_conn, &data, &chain_id, cashier_account_address)
-                    .await?;
-                persist_cursor(&mut db_conn, data.cursor).await?;
+                return Err(anyhow!("substreams block stream ended"));
             }
-            Some(Ok(BlockResponse::Undo(undo_signal))) => {
-                delete_invalid_blocks(&mut db_conn, undo_signal.last_valid_block.unwrap().number)
-                    .await?;
-                persist_cursor(&mut db_conn, undo_signal.last_valid_cursor).await?;
+            Some(Ok(block_response)) => {
+                handle_block_response(
+                    db_conn,
+                    block_response,
+                    chain_id,
+                    cashier_account_addresses,
+                    index_all_events,
+                )
+                .await?;
             }
             Some(Err(err)) => {
                 return Err(err);
@@ -120,18 +188,68 @@ pub async fn launch(
     Ok(())
 }
 
+/// Apply a block response and move the cursor past it, atomically
+///
+/// If this future is dropped half way, neither the block content nor the cursor is committed,
+/// and the block will be streamed again on the next launch.
+async fn handle_block_response(
+    conn: &mut PgConnection,
+    block_response: BlockResponse,
+    chain_id: &ChainId,
+    cashier_account_addresses: &HashSet<Felt>,
+    index_all_events: bool,
+) -> Result<()> {
+    let mut tx = conn.begin().await?;
+
+    match block_response {
+        BlockResponse::New(data) => {
+            process_block_scoped_data(
+                &mut tx,
+                &data,
+                chain_id,
+                cashier_account_addresses,
+                index_all_events,
+            )
+            .await?;
+            persist_cursor(&mut tx, data.cursor).await?;
+        }
+        BlockResponse::Undo(undo_signal) => {
+            let last_valid_block = undo_signal
+                .last_valid_block
+                .ok_or(anyhow!("undo signal without a last valid block"))?;
+            delete_invalid_blocks(&mut tx, last_valid_block.number).await?;
+            persist_cursor(&mut tx, undo_signal.last_valid_cursor).await?;
+        }
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
 async fn process_block_scoped_data(
     conn: &mut PgConnection,
     data: &BlockScopedData,
     chain_id: &ChainId,
-    cashier_account_address: Felt,
+    cashier_account_addresses: &HashSet<Felt>,
+    index_all_events: bool,
 ) -> Result<(), Error> {
-    let output = data.output.as_ref().unwrap().map_output.as_ref().unwrap();
-
-    let clock = data.clock.as_ref().unwrap();
-    let timestamp = clock.timestamp.as_ref().unwrap();
+    let output = data
+        .output
+        .as_ref()
+        .and_then(|o| o.map_output.as_ref())
+        .ok_or(anyhow!("block scoped data without map output"))?;
+
+    let clock = data
+        .clock
+        .as_ref()
+        .ok_or(anyhow!("block scoped data without clock"))?;
+    let timestamp = clock
+        .timestamp
+        .as_ref()
+        .ok_or(anyhow!("clock of block {} without timestamp", clock.number))?;
     let date = DateTime::from_timestamp(timestamp.seconds, timestamp.nanos as u32)
-        .expect("received timestamp should always be valid");
+        .ok_or(anyhow!("invalid timestamp for block {}", clock.number))?;
 
     let events = RemittanceEvents::decode(output.value.as_slice())?;
 
@@ -148,7 +266,7 @@ async fn process_block_scoped_data(
             INSERT INTO substreams_starknet_block (id, number, timestamp) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING;
         "#)
         .bind(&clock.id)
-            .bind(i64::try_from(clock.number).unwrap())
+            .bind(i64::try_from(clock.number)?)
                 .bind(date)
         .execute(&mut *conn).await?;
 
@@ -156,7 +274,8 @@ async fn process_block_scoped_data(
             events.events,
             conn,
             chain_id,
-            cashier_account_address,
+            cashier_account_addresses,
+            index_all_events,
             clock.id.clone(),
         )
         .await?;
@@ -173,7 +292,7 @@ async fn delete_invalid_blocks(
         r#"
             DELETE FROM substreams_starknet_block WHERE number > $1;
         "#,
-        i64::try_from(last_valid_block_number).unwrap()
+        i64::try_from(last_valid_block_number)?
     )
     .execute(conn)
     .await?;
@@ -213,47 +332,58 @@ async fn process_payment_event(
     remittance_events: Vec<RemittanceEvent>,
     conn: &mut PgConnection,
     chain_id: &ChainId,
-    cashier_account_address: Felt,
+    cashier_account_addresses: &HashSet<Felt>,
+    index_all_events: bool,
     block_id: String,
 ) -> Result<(), Error> {
-    for payment_event in remittance_events {
-        let invoice_id = Felt::from_bytes_be_slice(&payment_event.invoice_id);
-        let (is_mint, quote_id, quote_amount, unit) = if let Some((quote_id, amount, unit)) =
-            db_node::mint_quote::get_quote_infos_by_invoice_id::<Unit>(
-                conn,
-                &invoice_id.to_bytes_be(),
-            )
-            .await?
-        {
-            (true, quote_id, amount, unit)
-        } else if let Some((quote_id, amount, unit)) =
-            db_node::melt_quote::get_quote_infos_by_invoice_id::<Unit>(
-                conn,
-                &invoice_id.to_bytes_be(),
-            )
-            .await?
+    for remittance_event in remittance_events {
+        let remittance = match Remittance::try_from(&remittance_event) {
+            Ok(r) => r,
+            Err(err) => {
+                error!("skipping event in block {}: {}", block_id, err);
+                continue;
+            }
+        };
+        // Neither a deposit to nor a withdrawal from the cashier, it cannot pay any of our quotes
+        if !cashier_account_addresses.contains(&remittance.payee)
+            && !cashier_account_addresses.contains(&remittance.payer)
         {
-            (false, quote_id, amount, unit)
-        } else {
-            error!("no quote for invoice_id {:#x}", invoice_id);
+            if index_all_events {
+                let db_event = remittance.into_payment_event(block_id.clone());
+                db_node::observed_payment_event::insert_new_payment_event(conn, &db_event).await?;
+            }
             continue;
-        };
+        }
+        let invoice_id = remittance.invoice_id;
+        let (is_mint, quote_infos) =
+            match get_quote_kind_by_invoice_id::<Unit>(conn, &invoice_id.to_bytes_be()).await? {
+                QuoteKind::Mint(quote_infos) => (true, quote_infos),
+                QuoteKind::Melt(quote_infos) => (false, quote_infos),
+                QuoteKind::None => {
+                    error!("no quote for invoice_id {:#x}", invoice_id);
+                    continue;
+                }
+            };
+        let QuoteInfos {
+            quote_id,
+            amount: quote_amount,
+            unit,
+        } = quote_infos;
 
         let on_chain_constants = ON_CHAIN_CONSTANTS
             .get(chain_id.as_str())
             .ok_or(anyhow!("unkonwn chain id {}", chain_id))?;
 
-        let asset = Felt::from_bytes_be_slice(&payment_event.asset);
         let asset = match on_chain_constants
             .assets_contract_address
-            .get_asset_for_contract_address(asset)
+            .get_asset_for_contract_address(remittance.asset)
         {
             Some(asset) => asset,
             None => {
                 error!(
                     r#"Got an event for token with address {} which doesn't match any known asset.
                     This is not supposed to happen as we configure both at compile time."#,
-                    asset
+                    remittance.asset
                 );
                 continue;
             }
@@ -263,47 +393,21 @@ async fn process_payment_event(
             // Could just be someone reusing an already existing invoice id he saw onchain.
             // But it could also be an error in the wallet.
             debug!(
-                "Got payment for quote {}, that expect asset {}, using asset {}, which is not the expected one.",
-                quote_id, asset, asset
+                "Got payment of (low: {:#x}, high: {:#x}) for quote {}, that expect unit {}, using asset {}, which is not the expected one.",
+                remittance.amount_low, remittance.amount_high, quote_id, unit, asset
             );
             continue;
         }
 
         #[allow(clippy::collapsible_else_if)]
         if is_mint {
-            let payee = Felt::from_bytes_be_slice(&payment_event.payee);
-            if payee == cashier_account_address {
-                let db_event = PaymentEvent {
-                    block_id: block_id.clone(),
-                    tx_hash: Felt::from_bytes_be_slice(&payment_event.tx_hash).to_hex_string(),
-                    index: i64::try_from(payment_event.event_index).unwrap(),
-                    asset: Felt::from_bytes_be_slice(&payment_event.asset).to_hex_string(),
-                    payee: Felt::from_bytes_be_slice(&payment_event.payee).to_hex_string(),
-                    invoice_id: Felt::from_bytes_be_slice(&payment_event.invoice_id).to_bytes_be(),
-                    payer: Felt::from_bytes_be_slice(&payment_event.payer).to_hex_string(),
-                    amount_low: Felt::from_bytes_be_slice(&payment_event.amount_low)
-                        .to_hex_string(),
-                    amount_high: Felt::from_bytes_be_slice(&payment_event.amount_high)
-                        .to_hex_string(),
-                };
+            if cashier_account_addresses.contains(&remittance.payee) {
+                let db_event = remittance.into_payment_event(block_id.clone());
                 handle_mint_payment(conn, quote_id, db_event, unit, quote_amount).await?;
             }
         } else {
-            let payer = Felt::from_bytes_be_slice(&payment_event.payer);
-            if payer == cashier_account_address {
-                let db_event = PaymentEvent {
-                    block_id: block_id.clone(),
-                    tx_hash: Felt::from_bytes_be_slice(&payment_event.tx_hash).to_hex_string(),
-                    index: i64::try_from(payment_event.event_index).unwrap(),
-                    asset: Felt::from_bytes_be_slice(&payment_event.asset).to_hex_string(),
-                    payee: Felt::from_bytes_be_slice(&payment_event.payee).to_hex_string(),
-                    invoice_id: Felt::from_bytes_be_slice(&payment_event.invoice_id).to_bytes_be(),
-                    payer: Felt::from_bytes_be_slice(&payment_event.payer).to_hex_string(),
-                    amount_low: Felt::from_bytes_be_slice(&payment_event.amount_low)
-                        .to_hex_string(),
-                    amount_high: Felt::from_bytes_be_slice(&payment_event.amount_high)
-                        .to_hex_string(),
-                };
+            if cashier_account_addresses.contains(&remittance.payer) {
+                let db_event = remittance.into_payment_event(block_id.clone());
                 handle_melt_payment(conn, quote_id, db_event, unit, quote_amount).await?;
             }
         }
@@ -312,9 +416,22 @@ async fn process_payment_event(
     Ok(())
 }
 
+/// Parse the amount of a payment event, stored as the hex strings of its two u256 limbs
+fn parse_payment_amount(low: &str, high: &str) -> Result<primitive_types::U256, Error> {
+    let amount_as_strk_256 = StarknetU256 {
+        low: Felt::from_str(low)?,
+        high: Felt::from_str(high)?,
+    };
+
+    Ok(primitive_types::U256::from(amount_as_strk_256))
+}
+
 // Yeah I know it's basically the same code copied and pasted.
 // For now it's fine, better this than adding trait and struct and so on.
-async fn handle_mint_payment(
+/// Record a payment toward a mint quote, setting it Paid once the sum covers its amount
+///
+/// Public so that the mock liquidity source drives quotes through the same transition.
+pub async fn handle_mint_payment(
     db_conn: &mut PgConnection,
     quote_id: Uuid,
     payment_event: PaymentEvent,
@@ -323,40 +440,67 @@ async fn handle_mint_payment(
 ) -> Result<(), Error> {
     db_node::mint_payment_event::insert_new_payment_event(db_conn, &payment_event).await?;
 
-    let current_paid =
-        db_node::mint_payment_event::get_current_paid(db_conn, &payment_event.invoice_id)
-            .await?
-            .map(|(low, high)| -> Result<primitive_types::U256, Error> {
-                let amount_as_strk_256 = StarknetU256 {
-                    low: Felt::from_str(&low)?,
-                    high: Felt::from_str(&high)?,
-                };
-
-                Ok(primitive_types::U256::from(amount_as_strk_256))
-            })
-            .try_fold(primitive_types::U256::zero(), |acc, a| {
-                match a {
-        Ok(v) => v.checked_add(acc).ok_or(anyhow!(
-            "u256 value overflowed during the computation of the total amount paid for invoice"
-        )),
-        Err(e) => Err(e),
+    if is_mint_quote_fully_paid(db_conn, &payment_event.invoice_id, unit, quote_amount).await? {
+        mark_mint_quote_paid(db_conn, quote_id, unit).await?;
     }
-            })?;
+
+    Ok(())
+}
+
+/// Whether the payment events recorded for `invoice_id` cover the quote amount
+async fn is_mint_quote_fully_paid(
+    db_conn: &mut PgConnection,
+    invoice_id: &[u8; 32],
+    unit: Unit,
+    quote_amount: Amount,
+) -> Result<bool, Error> {
+    let amounts = db_node::mint_payment_event::get_current_paid(db_conn, invoice_id)
+        .await?
+        .map(|(low, high)| parse_payment_amount(&low, &high))
+        .collect::<Result<Vec<_>, _>>()?;
+    let current_paid = sum_payment_amounts(amounts)?;
 
     let to_pay = unit.convert_amount_into_u256(quote_amount);
-    if current_paid >= to_pay {
-        db_node::mint_quote::set_state(db_conn, quote_id, MintQuoteState::Paid).await?;
-        event!(
-            name: "mint-quote-paid",
-            Level::INFO,
-            name = "mint-quote-paid",
-            %quote_id,
-        );
-    }
+
+    Ok(current_paid >= to_pay)
+}
+
+async fn mark_mint_quote_paid(
+    db_conn: &mut PgConnection,
+    quote_id: Uuid,
+    unit: Unit,
+) -> Result<(), Error> {
+    db_node::mint_quote::set_state(db_conn, quote_id, MintQuoteState::Paid).await?;
+    record_quote_transition(QuoteTransition::MintPaid, unit.as_ref());
+    event!(
+        name: "mint-quote-paid",
+        Level::INFO,
+        name = "mint-quote-paid",
+        %quote_id,
+    );
 
     Ok(())
 }
 
+/// Mark as Paid the unpaid mint quotes whose recorded payment events already cover their amount
+///
+/// The payment events and the quote state were not always written atomically,
+/// so a quote could be left Unpaid despite its payment having been indexed.
+/// Returns the number of quotes that were updated.
+pub async fn reconcile_unpaid_quotes(db_conn: &mut PgConnection) -> Result<usize, Error> {
+    let unpaid_quotes = db_node::mint_quote::get_unpaid_not_expired::<Unit>(db_conn).await?;
+
+    let mut n_reconciled = 0;
+    for (quote_id, invoice_id, quote_amount, unit) in unpaid_quotes {
+        if is_mint_quote_fully_paid(db_conn, &invoice_id, unit, quote_amount).await? {
+            mark_mint_quote_paid(db_conn, quote_id, unit).await?;
+            n_reconciled += 1;
+        }
+    }
+
+    Ok(n_reconciled)
+}
+
 async fn handle_melt_payment(
     db_conn: &mut PgConnection,
     quote_id: Uuid,
@@ -365,29 +509,16 @@ async fn handle_melt_payment(
     quote_amount: Amount,
 ) -> Result<(), Error> {
     db_node::melt_payment_event::insert_new_payment_event(db_conn, &payment_event).await?;
-    let current_paid =
-        db_node::melt_payment_event::get_current_paid(db_conn, &payment_event.invoice_id)
-            .await?
-            .map(|(low, high)| -> Result<primitive_types::U256, Error> {
-                let amount_as_strk_256 = StarknetU256 {
-                    low: Felt::from_str(&low)?,
-                    high: Felt::from_str(&high)?,
-                };
-
-                Ok(primitive_types::U256::from(amount_as_strk_256))
-            })
-            .try_fold(primitive_types::U256::zero(), |acc, a| {
-                match a {
-        Ok(v) => v.checked_add(acc).ok_or(anyhow!(
-            "u256 value overflowed during the computation of the total amount paid for invoice"
-        )),
-                Err(e) => Err(e),
-            }
-            })?;
+    let amounts = db_node::melt_payment_event::get_current_paid(db_conn, &payment_event.invoice_id)
+        .await?
+        .map(|(low, high)| parse_payment_amount(&low, &high))
+        .collect::<Result<Vec<_>, _>>()?;
+    let current_paid = sum_payment_amounts(amounts)?;
 
     let to_pay = unit.convert_amount_into_u256(quote_amount);
     if current_paid >= to_pay {
         db_node::melt_quote::set_state(db_conn, quote_id, MeltQuoteState::Paid).await?;
+        record_quote_transition(QuoteTransition::MeltPaid, unit.as_ref());
         event!(
             name: "melt-quote-paid",
             Level::INFO,
@@ -398,3 +529,304 @@ async fn handle_melt_payment(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use futures::{channel::oneshot, stream};
+    use prost_types::{Any, Timestamp};
+
+    use super::*;
+    use crate::pb::sf::substreams::{rpc::v2::MapModuleOutput, v1::Clock};
+
+    fn empty_block(number: u64, cursor: &str) -> BlockScopedData {
+        BlockScopedData {
+            output: Some(MapModuleOutput {
+                map_output: Some(Any {
+                    type_url: String::new(),
+                    value: RemittanceEvents::default().encode_to_vec(),
+                }),
+                ..Default::default()
+            }),
+            clock: Some(Clock {
+                id: format!("block-{number}"),
+                number,
+                timestamp: Some(Timestamp::default()),
+            }),
+            cursor: cursor.to_string(),
+            ..Default::default()
+        }
+    }
+
+    async fn db_conn() -> sqlx::pool::PoolConnection<sqlx::Postgres> {
+        let pg_url = env::var("PG_URL").expect("PG_URL should be set to run the db tests");
+        let pg_pool = PgPool::connect(&pg_url).await.unwrap();
+        db_node::run_migrations(&pg_pool).await.unwrap();
+        pg_pool.acquire().await.unwrap()
+    }
+
+    async fn insert_block(db_conn: &mut PgConnection) -> String {
+        let block_id = format!("block-{}", Uuid::new_v4());
+        sqlx::query(
+            r#"INSERT INTO substreams_starknet_block (id, number, timestamp) VALUES ($1, 0, now())"#,
+        )
+        .bind(&block_id)
+        .execute(db_conn)
+        .await
+        .unwrap();
+
+        block_id
+    }
+
+    async fn insert_unpaid_mint_quote(
+        db_conn: &mut PgConnection,
+        amount: Amount,
+    ) -> (Uuid, [u8; 32]) {
+        let quote_id = Uuid::new_v4();
+        let invoice_id = Felt::from_bytes_be_slice(Uuid::new_v4().as_bytes()).to_bytes_be();
+        let expiry = Utc::now().timestamp() as u64 + 600;
+        db_node::mint_quote::insert_new(
+            db_conn,
+            quote_id,
+            invoice_id,
+            Unit::MilliStrk,
+            amount,
+            "request",
+            expiry,
+        )
+        .await
+        .unwrap();
+
+        (quote_id, invoice_id)
+    }
+
+    #[tokio::test]
+    async fn cursor_is_persisted_when_shutdown_follows_a_block() {
+        let mut db_conn = db_conn().await;
+        let cursor = Uuid::new_v4().to_string();
+
+        // Shutdown is requested as soon as the block has been handed over,
+        // after which the stream never yields again
+        let (shutdown_sender, shutdown_receiver) = oneshot::channel::<()>();
+        let blocks = stream::iter([Ok(BlockResponse::New(empty_block(1, &cursor)))])
+            .chain(
+                stream::once(async move {
+                    let _ = shutdown_sender.send(());
+                })
+                .filter_map(|()| async { None }),
+            )
+            .chain(stream::pending());
+
+        consume_stream(
+            &mut db_conn,
+            Box::pin(blocks),
+            &ChainId::Devnet,
+            &HashSet::from([Felt::ZERO]),
+            false,
+            shutdown_receiver.map(|_| ()),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            load_persisted_cursor(&mut db_conn).await.unwrap(),
+            Some(cursor)
+        );
+    }
+
+    #[tokio::test]
+    async fn malformed_block_is_an_error() {
+        let mut db_conn = db_conn().await;
+        let encoded_event = RemittanceEvents {
+            events: vec![RemittanceEvent {
+                tx_hash: Felt::ONE.to_bytes_be().to_vec(),
+                invoice_id: Felt::TWO.to_bytes_be().to_vec(),
+                ..Default::default()
+            }],
+        }
+        .encode_to_vec();
+        let with_output = |value: Vec<u8>| {
+            let mut block = empty_block(1, &Uuid::new_v4().to_string());
+            block
+                .output
+                .as_mut()
+                .unwrap()
+                .map_output
+                .as_mut()
+                .unwrap()
+                .value = value;
+            block
+        };
+
+        let malformed_blocks = [
+            (
+                "truncated output",
+                with_output(encoded_event[..encoded_event.len() - 1].to_vec()),
+            ),
+            ("garbage output", with_output(vec![0xff; 8])),
+            (
+                "missing output",
+                BlockScopedData {
+                    output: None,
+                    ..empty_block(1, &Uuid::new_v4().to_string())
+                },
+            ),
+            (
+                "missing clock",
+                BlockScopedData {
+                    clock: None,
+                    ..empty_block(1, &Uuid::new_v4().to_string())
+                },
+            ),
+            ("invalid timestamp", {
+                let mut block = empty_block(1, &Uuid::new_v4().to_string());
+                block.clock.as_mut().unwrap().timestamp = Some(Timestamp {
+                    seconds: i64::MAX,
+                    nanos: 0,
+                });
+                block
+            }),
+        ];
+
+        for (case, block) in malformed_blocks {
+            let cursor = block.cursor.clone();
+            let blocks = stream::iter([Ok(BlockResponse::New(block))]).chain(stream::pending());
+
+            let result = consume_stream(
+                &mut db_conn,
+                Box::pin(blocks),
+                &ChainId::Devnet,
+                &HashSet::from([Felt::ZERO]),
+                false,
+                std::future::pending(),
+            )
+            .await;
+
+            assert!(result.is_err(), "{case} should be an error");
+            assert_ne!(
+                load_persisted_cursor(&mut db_conn).await.unwrap(),
+                Some(cursor),
+                "the cursor should not move past a block with {case}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn non_cashier_events_are_only_recorded_when_enabled() {
+        let mut db_conn = db_conn().await;
+        let block_id = insert_block(&mut db_conn).await;
+        let cashier_account_addresses = HashSet::from([Felt::from(1u64)]);
+
+        for index_all_events in [true, false] {
+            let tx_hash = Felt::from_bytes_be_slice(Uuid::new_v4().as_bytes());
+            let event = RemittanceEvent {
+                tx_hash: tx_hash.to_bytes_be().to_vec(),
+                event_index: 0,
+                asset: Felt::from(2u64).to_bytes_be().to_vec(),
+                payer: Felt::from(3u64).to_bytes_be().to_vec(),
+                payee: Felt::from(4u64).to_bytes_be().to_vec(),
+                invoice_id: Felt::from(5u64).to_bytes_be().to_vec(),
+                amount_low: Felt::from(6u64).to_bytes_be().to_vec(),
+                amount_high: Felt::ZERO.to_bytes_be().to_vec(),
+            };
+
+            process_payment_event(
+                vec![event],
+                &mut db_conn,
+                &ChainId::Devnet,
+                &cashier_account_addresses,
+                index_all_events,
+                block_id.clone(),
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(
+                db_node::observed_payment_event::exists(&mut db_conn, &tx_hash.to_hex_string(), 0)
+                    .await
+                    .unwrap(),
+                index_all_events
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn unpaid_quote_with_recorded_payment_is_reconciled() {
+        let mut db_conn = db_conn().await;
+        let amount = Amount::from(32u64);
+        let (quote_id, invoice_id) = insert_unpaid_mint_quote(&mut db_conn, amount).await;
+        let block_id = insert_block(&mut db_conn).await;
+        let to_pay = Unit::MilliStrk.convert_amount_into_u256(amount);
+        db_node::mint_payment_event::insert_new_payment_event(
+            &mut db_conn,
+            &PaymentEvent {
+                block_id,
+                tx_hash: Felt::from_bytes_be_slice(Uuid::new_v4().as_bytes()).to_hex_string(),
+                index: 0,
+                asset: Felt::ONE.to_hex_string(),
+                payee: Felt::ONE.to_hex_string(),
+                invoice_id,
+                payer: Felt::TWO.to_hex_string(),
+                amount_low: Felt::from(to_pay.low_u128()).to_hex_string(),
+                amount_high: Felt::ZERO.to_hex_string(),
+            },
+        )
+        .await
+        .unwrap();
+
+        reconcile_unpaid_quotes(&mut db_conn).await.unwrap();
+
+        let (_, state) = db_node::mint_quote::get_amount_and_state(&mut db_conn, quote_id)
+            .await
+            .unwrap();
+        assert_eq!(state, MintQuoteState::Paid);
+    }
+
+    #[tokio::test]
+    async fn payments_to_any_cashier_address_are_handled() {
+        let mut db_conn = db_conn().await;
+        let block_id = insert_block(&mut db_conn).await;
+        let cashier_account_addresses = HashSet::from([Felt::from(11u64), Felt::from(12u64)]);
+        let strk_address = ON_CHAIN_CONSTANTS
+            .get(ChainId::Devnet.as_str())
+            .unwrap()
+            .assets_contract_address
+            .get_contract_address_for_asset(starknet_types::Asset::Strk)
+            .unwrap();
+        let amount = Amount::from(32u64);
+        let to_pay = Unit::MilliStrk.convert_amount_into_u256(amount);
+
+        for (payee, expected_state) in [
+            (Felt::from(11u64), MintQuoteState::Paid),
+            (Felt::from(12u64), MintQuoteState::Paid),
+            (Felt::from(13u64), MintQuoteState::Unpaid),
+        ] {
+            let (quote_id, invoice_id) = insert_unpaid_mint_quote(&mut db_conn, amount).await;
+            let event = RemittanceEvent {
+                tx_hash: Uuid::new_v4().as_bytes().to_vec(),
+                event_index: 0,
+                asset: strk_address.to_bytes_be().to_vec(),
+                payer: Felt::from(99u64).to_bytes_be().to_vec(),
+                payee: payee.to_bytes_be().to_vec(),
+                invoice_id: invoice_id.to_vec(),
+                amount_low: Felt::from(to_pay.low_u128()).to_bytes_be().to_vec(),
+                amount_high: Felt::ZERO.to_bytes_be().to_vec(),
+            };
+
+            process_payment_event(
+                vec![event],
+                &mut db_conn,
+                &ChainId::Devnet,
+                &cashier_account_addresses,
+                false,
+                block_id.clone(),
+            )
+            .await
+            .unwrap();
+
+            let (_, state) = db_node::mint_quote::get_amount_and_state(&mut db_conn, quote_id)
+                .await
+                .unwrap();
+            assert_eq!(state, expected_state, "payment to {payee:#x}");
+        }
+    }
+}
@@ -6,6 +6,64 @@ use crate::pb::sf::substreams::v1::{
     module::input::{Input, Params},
 };
 
+pub const DEFAULT_OUTPUT_MODULE_NAME: &str = "map_invoice_contract_events";
+pub const DEFAULT_FILTERED_TRANSACTIONS_MODULE_NAME: &str = "starknet:filtered_transactions";
+
+/// Names of the package modules the sink relies on
+///
+/// They depend on how the substreams package was built,
+/// so pointing the sink at another package may require changing them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleNames {
+    /// The module whose output is streamed and decoded as `RemittanceEvents`
+    pub output: String,
+    /// The module receiving the transaction filter expression as param
+    pub filtered_transactions: String,
+}
+
+impl Default for ModuleNames {
+    fn default() -> Self {
+        Self {
+            output: DEFAULT_OUTPUT_MODULE_NAME.to_string(),
+            filtered_transactions: DEFAULT_FILTERED_TRANSACTIONS_MODULE_NAME.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("module `{name}` not found in the substreams package, available modules: [{available}]")]
+pub struct ModuleNotFoundError {
+    pub name: String,
+    pub available: String,
+}
+
+/// Make sure every module in `module_names` is part of `package`
+pub fn check_modules_exist(
+    package: &Package,
+    module_names: &ModuleNames,
+) -> Result<(), ModuleNotFoundError> {
+    let modules = package
+        .modules
+        .as_ref()
+        .map(|m| m.modules.as_slice())
+        .unwrap_or_default();
+
+    for name in [&module_names.output, &module_names.filtered_transactions] {
+        if !modules.iter().any(|m| &m.name == name) {
+            return Err(ModuleNotFoundError {
+                name: name.clone(),
+                available: modules
+                    .iter()
+                    .map(|m| m.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            });
+        }
+    }
+
+    Ok(())
+}
+
 pub struct Param {
     pub module_name: String,
     pub expression: String,
@@ -36,3 +94,47 @@ pub fn read_package(params: Vec<Param>) -> Result<Package, Error> {
         Ok(package)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::pb::sf::substreams::v1::{Module, Modules};
+
+    use super::*;
+
+    fn package_with_modules(names: &[&str]) -> Package {
+        Package {
+            modules: Some(Modules {
+                modules: names
+                    .iter()
+                    .map(|name| Module {
+                        name: name.to_string(),
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn embedded_package_has_default_modules() {
+        let package = read_package(vec![]).unwrap();
+
+        check_modules_exist(&package, &ModuleNames::default()).unwrap();
+    }
+
+    #[test]
+    fn missing_module_is_named_in_error() {
+        let package =
+            package_with_modules(&[DEFAULT_FILTERED_TRANSACTIONS_MODULE_NAME, "map_events"]);
+
+        let err = check_modules_exist(&package, &ModuleNames::default()).unwrap_err();
+
+        assert_eq!(err.name, DEFAULT_OUTPUT_MODULE_NAME);
+        assert_eq!(
+            err.to_string(),
+            "module `map_invoice_contract_events` not found in the substreams package, available modules: [starknet:filtered_transactions, map_events]"
+        );
+    }
+}
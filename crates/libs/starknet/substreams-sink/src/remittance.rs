@@ -0,0 +1,165 @@
+use db_node::PaymentEvent;
+use starknet::core::types::Felt;
+use thiserror::Error;
+
+use crate::pb::invoice_contract::v1::RemittanceEvent;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum Error {
+    #[error("malformed remittance event: {reason}")]
+    MalformedRemittanceEvent { reason: String },
+}
+
+/// A `RemittanceEvent` whose raw fields have been checked to be valid felts
+///
+/// The substreams module forwards the event keys and data as opaque bytes,
+/// so an ABI change on the contract side would otherwise only surface as a panic here.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remittance {
+    pub tx_hash: Felt,
+    pub event_index: i64,
+    pub asset: Felt,
+    pub payer: Felt,
+    pub payee: Felt,
+    pub invoice_id: Felt,
+    pub amount_low: Felt,
+    pub amount_high: Felt,
+}
+
+fn parse_felt(field: &str, bytes: &[u8]) -> Result<Felt, Error> {
+    if bytes.is_empty() {
+        return Err(Error::MalformedRemittanceEvent {
+            reason: format!("missing {field}"),
+        });
+    }
+    if bytes.len() > 32 {
+        return Err(Error::MalformedRemittanceEvent {
+            reason: format!("{field} is {} bytes long, expected at most 32", bytes.len()),
+        });
+    }
+
+    Ok(Felt::from_bytes_be_slice(bytes))
+}
+
+impl TryFrom<&RemittanceEvent> for Remittance {
+    type Error = Error;
+
+    fn try_from(event: &RemittanceEvent) -> Result<Self, Self::Error> {
+        Ok(Self {
+            tx_hash: parse_felt("tx_hash", &event.tx_hash)?,
+            event_index: i64::try_from(event.event_index).map_err(|_| {
+                Error::MalformedRemittanceEvent {
+                    reason: format!("event_index {} does not fit in an i64", event.event_index),
+                }
+            })?,
+            asset: parse_felt("asset", &event.asset)?,
+            payer: parse_felt("payer", &event.payer)?,
+            payee: parse_felt("payee", &event.payee)?,
+            invoice_id: parse_felt("invoice_id", &event.invoice_id)?,
+            amount_low: parse_felt("amount_low", &event.amount_low)?,
+            amount_high: parse_felt("amount_high", &event.amount_high)?,
+        })
+    }
+}
+
+impl Remittance {
+    pub fn into_payment_event(self, block_id: String) -> PaymentEvent {
+        PaymentEvent {
+            block_id,
+            tx_hash: self.tx_hash.to_hex_string(),
+            index: self.event_index,
+            asset: self.asset.to_hex_string(),
+            payee: self.payee.to_hex_string(),
+            invoice_id: self.invoice_id.to_bytes_be(),
+            payer: self.payer.to_hex_string(),
+            amount_low: self.amount_low.to_hex_string(),
+            amount_high: self.amount_high.to_hex_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn felt_bytes(value: u64) -> Vec<u8> {
+        Felt::from(value).to_bytes_be().to_vec()
+    }
+
+    fn well_formed_event() -> RemittanceEvent {
+        RemittanceEvent {
+            tx_hash: felt_bytes(1),
+            event_index: 2,
+            asset: felt_bytes(3),
+            payer: felt_bytes(4),
+            payee: felt_bytes(5),
+            invoice_id: felt_bytes(6),
+            amount_low: felt_bytes(7),
+            amount_high: felt_bytes(0),
+        }
+    }
+
+    #[test]
+    fn parses_well_formed_event() {
+        let remittance = Remittance::try_from(&well_formed_event()).unwrap();
+
+        assert_eq!(remittance.event_index, 2);
+        assert_eq!(remittance.invoice_id, Felt::from(6u64));
+
+        let payment_event = remittance.into_payment_event("block".to_string());
+        assert_eq!(payment_event.block_id, "block");
+        assert_eq!(payment_event.payer, "0x4");
+        assert_eq!(payment_event.amount_high, "0x0");
+        assert_eq!(payment_event.invoice_id, Felt::from(6u64).to_bytes_be());
+    }
+
+    #[test]
+    fn rejects_missing_key() {
+        let event = RemittanceEvent {
+            payee: vec![],
+            ..well_formed_event()
+        };
+
+        assert_eq!(
+            Remittance::try_from(&event),
+            Err(Error::MalformedRemittanceEvent {
+                reason: "missing payee".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_missing_data() {
+        let event = RemittanceEvent {
+            amount_low: vec![],
+            ..well_formed_event()
+        };
+
+        assert_eq!(
+            Remittance::try_from(&event),
+            Err(Error::MalformedRemittanceEvent {
+                reason: "missing amount_low".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_oversized_field() {
+        let event = RemittanceEvent {
+            invoice_id: vec![1; 33],
+            ..well_formed_event()
+        };
+
+        assert!(Remittance::try_from(&event).is_err());
+    }
+
+    #[test]
+    fn rejects_out_of_range_event_index() {
+        let event = RemittanceEvent {
+            event_index: u64::MAX,
+            ..well_formed_event()
+        };
+
+        assert!(Remittance::try_from(&event).is_err());
+    }
+}
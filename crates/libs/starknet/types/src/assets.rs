@@ -1,4 +1,4 @@
-use std::str::FromStr;
+use std::{collections::HashMap, str::FromStr};
 
 use nuts::Amount;
 use primitive_types::U256;
@@ -45,6 +45,10 @@ impl Asset {
         }
     }
 
+    /// Number of on-chain base units in one whole asset, `10^precision`
+    ///
+    /// The protocol unit picked by `find_best_unit` keeps `asset_extra_precision` of those digits,
+    /// leaving `10^(precision - asset_extra_precision)` protocol amounts per whole asset.
     pub fn scale_factor(&self) -> U256 {
         match self {
             Asset::Strk | Asset::Eth => U256::from(1_000_000_000_000_000_000u64),
@@ -53,6 +57,14 @@ impl Asset {
         }
     }
 
+    /// Convert an amount of whole assets into its on-chain representation
+    ///
+    /// Returns `None` if the result doesn't fit in a U256.
+    pub fn checked_scale(&self, amount: U256) -> Option<U256> {
+        amount.checked_mul(self.scale_factor())
+    }
+
+    /// Default unit of the asset, used unless a [`UnitPreferences`] says otherwise
     pub fn find_best_unit(&self) -> Unit {
         match self {
             Asset::Strk => Unit::MilliStrk,
@@ -83,7 +95,7 @@ impl Asset {
         ))
     }
 
-    /// Convert an onchain amount of asset to a protocol amount of the most appropriate unit
+    /// Convert an onchain amount of asset to a protocol amount of the preferred unit
     ///
     /// # WARNING
     /// The input amount HAS to be specified including on-chain precision.
@@ -91,14 +103,39 @@ impl Asset {
     pub fn convert_to_amount_and_unit(
         &self,
         asset_amount: U256,
+        preferences: &UnitPreferences,
     ) -> Result<(Amount, Unit, U256), AssetToUnitConversionError> {
-        let unit = self.find_best_unit();
+        let unit = preferences.unit_for(*self);
         let (amount, rem) = self.convert_to_amount_of_unit(asset_amount, unit)?;
 
         Ok((amount, unit, rem))
     }
 }
 
+/// Unit to use for each asset
+///
+/// Deployments may not agree on the right precision for an asset.
+/// Assets without a preference use [`Asset::find_best_unit`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UnitPreferences {
+    pub(crate) preferred: HashMap<Asset, Unit>,
+}
+
+impl UnitPreferences {
+    /// Use `unit` for the asset backing it
+    pub fn prefer(mut self, unit: Unit) -> Self {
+        self.preferred.insert(unit.asset(), unit);
+        self
+    }
+
+    pub fn unit_for(&self, asset: Asset) -> Unit {
+        self.preferred
+            .get(&asset)
+            .copied()
+            .unwrap_or_else(|| asset.find_best_unit())
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 #[error("invalid asset")]
 pub struct AssetFromStrError;
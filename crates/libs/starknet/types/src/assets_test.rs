@@ -2,8 +2,10 @@
 mod tests {
     use crate::Asset;
     use crate::Unit;
+    use crate::UnitPreferences;
     use nuts::Amount;
     use nuts::traits::Asset as AssetT;
+    use nuts::traits::Unit as UnitT;
     use primitive_types::U256;
     use std::str::FromStr;
 
@@ -41,6 +43,51 @@ mod tests {
         assert_eq!(Asset::UsdT.scale_factor(), U256::from(1_000_000u64));
     }
 
+    #[test]
+    fn test_asset_scale_factor_matches_precision() {
+        for asset in [
+            Asset::Strk,
+            Asset::Eth,
+            Asset::WBtc,
+            Asset::UsdC,
+            Asset::UsdT,
+        ] {
+            let unit = asset.find_best_unit();
+            assert_eq!(asset.scale_factor(), U256::exp10(asset.precision().into()));
+            assert_eq!(
+                unit.scale_factor(),
+                10u64.pow(unit.asset_extra_precision().into())
+            );
+            assert_eq!(
+                asset.scale_factor(),
+                U256::from(unit.scale_factor())
+                    * U256::exp10((asset.precision() - unit.asset_extra_precision()).into())
+            );
+        }
+    }
+
+    #[test]
+    fn test_asset_checked_scale() {
+        assert_eq!(
+            Asset::Strk.checked_scale(U256::from(2)),
+            Some(U256::from(2_000_000_000_000_000_000u64))
+        );
+        assert_eq!(
+            Asset::Eth.checked_scale(U256::from(3)),
+            Some(U256::from(3_000_000_000_000_000_000u64))
+        );
+        assert_eq!(Asset::Strk.checked_scale(U256::zero()), Some(U256::zero()));
+
+        for asset in [Asset::Strk, Asset::Eth, Asset::WBtc, Asset::UsdC] {
+            let max_representable = U256::MAX / asset.scale_factor();
+            assert_eq!(
+                asset.checked_scale(max_representable),
+                Some(max_representable * asset.scale_factor())
+            );
+            assert_eq!(asset.checked_scale(max_representable + 1), None);
+        }
+    }
+
     #[test]
     fn test_asset_find_best_unit() {
         assert_eq!(Asset::Strk.find_best_unit(), Unit::MilliStrk);
@@ -54,37 +101,79 @@ mod tests {
     fn test_asset_conversions() {
         // Test STRK conversion
         let strk_amount = U256::from(1_000_000_000_000_000_000u64); // 1 STRK
-        let (amount, unit, rem) = Asset::Strk.convert_to_amount_and_unit(strk_amount).unwrap();
+        let (amount, unit, rem) = Asset::Strk
+            .convert_to_amount_and_unit(strk_amount, &UnitPreferences::default())
+            .unwrap();
         assert_eq!(amount, Amount::from(1000u64)); // Should be 1000 milliSTRK
         assert_eq!(unit, Unit::MilliStrk);
         assert_eq!(rem, U256::zero());
 
         // Test ETH conversion
         let eth_amount = U256::from(1_000_000_000_000_000_000u64); // 1 ETH
-        let (amount, unit, rem) = Asset::Eth.convert_to_amount_and_unit(eth_amount).unwrap();
+        let (amount, unit, rem) = Asset::Eth
+            .convert_to_amount_and_unit(eth_amount, &UnitPreferences::default())
+            .unwrap();
         assert_eq!(amount, Amount::from(1_000_000_000u64)); // Should be 1,000,000,000 Gwei
         assert_eq!(unit, Unit::Gwei);
         assert_eq!(rem, U256::zero());
 
         // Test WBTC conversion
         let wbtc_amount = U256::from(100_000_000u64); // 1 WBTC
-        let (amount, unit, rem) = Asset::WBtc.convert_to_amount_and_unit(wbtc_amount).unwrap();
+        let (amount, unit, rem) = Asset::WBtc
+            .convert_to_amount_and_unit(wbtc_amount, &UnitPreferences::default())
+            .unwrap();
         assert_eq!(amount, Amount::from(100_000_000u64)); // Should be 100,000,000 satoshis
         assert_eq!(unit, Unit::Satoshi);
         assert_eq!(rem, U256::zero());
 
         // Test USDC conversion
         let usdc_amount = U256::from(1_000_000u64); // 1 USDC
-        let (amount, unit, rem) = Asset::UsdC.convert_to_amount_and_unit(usdc_amount).unwrap();
+        let (amount, unit, rem) = Asset::UsdC
+            .convert_to_amount_and_unit(usdc_amount, &UnitPreferences::default())
+            .unwrap();
         assert_eq!(amount, Amount::from(1_000_000u64)); // Should be 100 cents
         assert_eq!(unit, Unit::MicroUsdC);
         assert_eq!(rem, U256::zero());
 
         // Test USDT conversion
         let usdt_amount = U256::from(1_000_000u64); // 1 USDC
-        let (amount, unit, rem) = Asset::UsdT.convert_to_amount_and_unit(usdt_amount).unwrap();
+        let (amount, unit, rem) = Asset::UsdT
+            .convert_to_amount_and_unit(usdt_amount, &UnitPreferences::default())
+            .unwrap();
         assert_eq!(amount, Amount::from(1_000_000u64)); // Should be 100 cents
         assert_eq!(unit, Unit::MicroUsdT);
         assert_eq!(rem, U256::zero());
     }
+
+    #[test]
+    fn unit_preferences_fall_back_to_the_default_unit() {
+        let preferences = UnitPreferences::default().prefer(Unit::MilliStrk);
+
+        assert_eq!(preferences.unit_for(Asset::Strk), Unit::MilliStrk);
+        assert_eq!(
+            preferences.unit_for(Asset::Eth),
+            Asset::Eth.find_best_unit()
+        );
+        assert_eq!(preferences.unit_for(Asset::WBtc), Unit::Satoshi);
+    }
+
+    #[test]
+    fn conversion_honors_unit_preferences() {
+        // Strk has a single unit for now, stand for a finer one with a unit of another scale
+        let mut preferences = UnitPreferences::default();
+        preferences.preferred.insert(Asset::Strk, Unit::Gwei);
+        let strk_amount = U256::from(1_000_000_000_000_000_000u64); // 1 STRK
+
+        let (amount, unit, rem) = Asset::Strk
+            .convert_to_amount_and_unit(strk_amount, &preferences)
+            .unwrap();
+        assert_eq!(unit, Unit::Gwei);
+        assert_eq!(amount, Amount::from(1_000_000_000u64));
+        assert_eq!(rem, U256::zero());
+
+        let (_, unit, _) = Asset::Eth
+            .convert_to_amount_and_unit(strk_amount, &preferences)
+            .unwrap();
+        assert_eq!(unit, Unit::Gwei);
+    }
 }
@@ -1,5 +1,8 @@
 use std::str::FromStr;
 
+use starknet_types_core::felt::Felt;
+use url::Url;
+
 use crate::{CairoShortStringToFeltError, felt_from_short_string};
 
 // Constants representing predefined Starknet networks
@@ -62,6 +65,24 @@ impl std::fmt::Display for ChainId {
     }
 }
 
+/// Link to the page of a transaction on the chain's block explorer
+///
+/// Returns `None` for chains without a public explorer (devnet, custom) and for
+/// strings that are not a valid tx hash, so callers can fall back to printing the raw hash.
+pub fn explorer_tx_url(chain_id: &ChainId, tx_hash: &str) -> Option<Url> {
+    let base = match chain_id {
+        ChainId::Mainnet => "https://voyager.online/tx/",
+        ChainId::Sepolia => "https://sepolia.voyager.online/tx/",
+        ChainId::Devnet | ChainId::Custom(_) => return None,
+    };
+    let tx_hash = Felt::from_hex(tx_hash).ok()?;
+
+    Url::parse(base)
+        .ok()?
+        .join(&tx_hash.to_fixed_hex_string())
+        .ok()
+}
+
 impl FromStr for ChainId {
     type Err = CairoShortStringToFeltError;
 
@@ -106,3 +127,41 @@ impl<'de> serde::Deserialize<'de> for ChainId {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TX_HASH: &str = "0x3a1f0ce9b5dcd8e1a3b0b5ac8d2c1f6ee2e0a2b1c3d4e5f60718293a4b5c6d7";
+
+    #[test]
+    fn mainnet_explorer_url() {
+        assert_eq!(
+            explorer_tx_url(&ChainId::Mainnet, TX_HASH)
+                .unwrap()
+                .as_str(),
+            "https://voyager.online/tx/0x03a1f0ce9b5dcd8e1a3b0b5ac8d2c1f6ee2e0a2b1c3d4e5f60718293a4b5c6d7"
+        );
+    }
+
+    #[test]
+    fn sepolia_explorer_url() {
+        assert_eq!(
+            explorer_tx_url(&ChainId::Sepolia, "0x1").unwrap().as_str(),
+            "https://sepolia.voyager.online/tx/0x0000000000000000000000000000000000000000000000000000000000000001"
+        );
+    }
+
+    #[test]
+    fn no_explorer_url() {
+        assert!(explorer_tx_url(&ChainId::Devnet, TX_HASH).is_none());
+        assert!(
+            explorer_tx_url(
+                &ChainId::new_custom("MY_CHAIN".to_string()).unwrap(),
+                TX_HASH
+            )
+            .is_none()
+        );
+        assert!(explorer_tx_url(&ChainId::Mainnet, "not a tx hash").is_none());
+    }
+}
@@ -10,7 +10,7 @@
 
 use starknet_types_core::felt::Felt;
 
-use crate::Asset;
+use crate::{Asset, InvoiceIdVersion};
 
 #[derive(Debug, Clone, Copy)]
 pub enum AssetsAddress {
@@ -98,6 +98,8 @@ const DEVNET_ASSETS_ADDRESSES: AssetsAddress = AssetsAddress::Devnet([
 #[derive(Debug, Clone)]
 pub struct OnChainConstants {
     pub invoice_payment_contract_address: Felt,
+    /// Must be bumped together with `invoice_payment_contract_address` on a contract upgrade
+    pub invoice_id_version: InvoiceIdVersion,
     pub assets_contract_address: AssetsAddress,
 }
 
@@ -110,6 +112,7 @@ pub static ON_CHAIN_CONSTANTS: phf::Map<&'static str, OnChainConstants> = phf::p
         // Starting block is the one which contains the invoice_payment_contract deployment
         // Tx: 0x3ff1f5d34e471b30f12bd28f69c4edfc25c40856b8ca269d92bc1fe1bd3da11
         invoice_payment_contract_address: Felt::from_hex_unchecked("0x03b7d6935858cc0e84cba7267cc9daa76dfaf060303761608f12cf84191e3571"),
+        invoice_id_version: InvoiceIdVersion::V1,
         assets_contract_address: SEPOLIA_ASSETS_ADDRESSES,
     },
     "SN_DEVNET" =>  OnChainConstants {
@@ -117,6 +120,7 @@ pub static ON_CHAIN_CONSTANTS: phf::Map<&'static str, OnChainConstants> = phf::p
         // you are using our `starknet-on-chain-setup` rust deployment executable.
         // It is automaticaly used when setting up the network using this repo's `docker-compose.yml`
         invoice_payment_contract_address: Felt::from_hex_unchecked("0x054eb8613832317fc641555b852b0a3b4cef5cc444fccab5e3de94430fb8fcda"),
+        invoice_id_version: InvoiceIdVersion::V1,
         // The default starknet-devnet config reuses Sepolia asset addresses
         // TODO: will only work for `eth` and `strk` assets. So we will change it later on.
         assets_contract_address: DEVNET_ASSETS_ADDRESSES,
@@ -0,0 +1,136 @@
+use bitcoin_hashes::Sha256;
+use primitive_types::U256;
+use starknet_types_core::felt::Felt;
+use uuid::Uuid;
+
+use crate::{
+    Asset, ChainId, DepositPayload, PayInvoiceCallData, compute_invoice_id,
+    constants::ON_CHAIN_CONSTANTS,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum BuildDepositPayloadError {
+    #[error("no on-chain constants for chain {0}")]
+    UnknownChain(ChainId),
+    #[error("asset {0} not deployed on chain {1}")]
+    AssetNotFound(Asset, ChainId),
+}
+
+/// The value identifying a quote on-chain
+///
+/// The quote id itself never leaves the node, only its hash is part of the payment.
+pub fn hash_quote_id(quote_id: &Uuid) -> Felt {
+    Felt::from_bytes_be(Sha256::hash(quote_id.as_bytes()).as_byte_array())
+}
+
+/// Build the deposit payload a node would return for this quote
+///
+/// Pure computation, no RPC involved, so clients can assemble the on-chain call themselves.
+/// `amount` is expressed with the asset's on-chain precision.
+pub fn build_deposit_payload(
+    chain_id: ChainId,
+    quote_id: Uuid,
+    expiry: u64,
+    amount: U256,
+    asset: Asset,
+    payee: Felt,
+) -> Result<DepositPayload, BuildDepositPayloadError> {
+    let on_chain_constants = ON_CHAIN_CONSTANTS
+        .get(chain_id.as_str())
+        .ok_or_else(|| BuildDepositPayloadError::UnknownChain(chain_id.clone()))?;
+    let asset_contract_address = on_chain_constants
+        .assets_contract_address
+        .get_contract_address_for_asset(asset)
+        .ok_or_else(|| BuildDepositPayloadError::AssetNotFound(asset, chain_id.clone()))?;
+
+    Ok(DepositPayload {
+        chain_id,
+        call_data: PayInvoiceCallData {
+            quote_id_hash: hash_quote_id(&quote_id),
+            expiry: Felt::from(expiry),
+            asset_contract_address,
+            amount: amount.into(),
+            payee,
+        },
+    })
+}
+
+impl DepositPayload {
+    /// The id the invoice contract will emit when this payload is paid
+    ///
+    /// Chains without on-chain constants are assumed to run the first contract version.
+    pub fn invoice_id(&self) -> Felt {
+        let version = ON_CHAIN_CONSTANTS
+            .get(self.chain_id.as_str())
+            .map(|constants| constants.invoice_id_version)
+            .unwrap_or_default();
+
+        compute_invoice_id(version, self.call_data.quote_id_hash, self.call_data.expiry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::InvoiceIdVersion;
+
+    const QUOTE_ID: &str = "8a4d3c1e-2b7f-4e6a-9c0d-1f2e3a4b5c6d";
+    const EXPIRY: u64 = 1_750_000_000;
+
+    fn payee() -> Felt {
+        Felt::from_hex_unchecked(
+            "0x064b48806902a367c8598f4f95c305e8c1a1acba5f082d294a43793113115691",
+        )
+    }
+
+    #[test]
+    fn payload_matches_invoice_id() {
+        let quote_id = Uuid::from_str(QUOTE_ID).unwrap();
+        let payload = build_deposit_payload(
+            ChainId::Sepolia,
+            quote_id,
+            EXPIRY,
+            U256::from(1_000_000_000_000_000u64),
+            Asset::Strk,
+            payee(),
+        )
+        .unwrap();
+
+        let expected_quote_id_hash =
+            Felt::from_bytes_be(Sha256::hash(quote_id.as_bytes()).as_byte_array());
+        assert_eq!(payload.call_data.quote_id_hash, expected_quote_id_hash);
+        assert_eq!(payload.call_data.expiry, Felt::from(EXPIRY));
+        assert_eq!(payload.call_data.payee, payee());
+        assert_eq!(
+            payload.invoice_id(),
+            compute_invoice_id(InvoiceIdVersion::V1, expected_quote_id_hash, EXPIRY)
+        );
+        assert_eq!(
+            Some(payload.call_data.asset_contract_address),
+            ON_CHAIN_CONSTANTS
+                .get(ChainId::Sepolia.as_str())
+                .unwrap()
+                .assets_contract_address
+                .get_contract_address_for_asset(Asset::Strk)
+        );
+    }
+
+    #[test]
+    fn unknown_chain() {
+        let res = build_deposit_payload(
+            ChainId::Mainnet,
+            Uuid::from_str(QUOTE_ID).unwrap(),
+            EXPIRY,
+            U256::one(),
+            Asset::Strk,
+            payee(),
+        );
+
+        assert!(matches!(
+            res,
+            Err(BuildDepositPayloadError::UnknownChain(ChainId::Mainnet))
+        ));
+    }
+}
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 use starknet::core::types::Call;
-use starknet_crypto::poseidon_hash;
+use starknet_crypto::{poseidon_hash, poseidon_hash_many};
 use starknet_types_core::felt::Felt;
 
 mod assets;
@@ -8,10 +8,14 @@ pub use assets::*;
 mod u256;
 pub use u256::*;
 mod unit;
-pub use unit::{Unit, UnitFromStrError};
+pub use unit::{Unit, UnitFromStrError, unit_str_to_asset};
 mod chain_id;
+mod deposit;
+mod payment_total;
+pub use deposit::{BuildDepositPayloadError, build_deposit_payload, hash_quote_id};
+pub use payment_total::{PaymentTotalOverflow, sum_payment_amounts};
 pub mod constants;
-pub use chain_id::ChainId;
+pub use chain_id::{ChainId, explorer_tx_url};
 mod assets_test;
 pub mod transactions;
 
@@ -43,7 +47,10 @@ impl PayInvoiceCallData {
         }
     }
 
-    pub fn to_starknet_calls(self, invoice_payment_contract_address: Felt) -> [Call; 2] {
+    pub fn to_starknet_calls(
+        self,
+        invoice_payment_contract_address: Felt,
+    ) -> Result<[Call; 2], transactions::CallDataError> {
         transactions::generate_single_payment_transaction_calls(
             invoice_payment_contract_address,
             self.quote_id_hash,
@@ -107,13 +114,45 @@ pub fn is_valid_starknet_address(felt: &Felt) -> bool {
             )
 }
 
-/// Calculate the invoice_id using poseidon hash of quote_id_hash and expiry
-pub fn compute_invoice_id<E: Into<Felt>>(quote_id_hash: Felt, expiry: E) -> Felt {
-    // Convert expiry to Felt
+/// How an invoice payment contract derives the invoice id it emits
+///
+/// The node must compute the exact same id to match the payment events with its quotes.
+/// Each chain records the version of the contract it runs in [`constants::OnChainConstants`],
+/// a contract upgrade changing the derivation is then a new variant here and a constant update.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InvoiceIdVersion {
+    /// `poseidon(quote_id_hash, expiry)`, the deployed contract
+    #[default]
+    V1,
+    /// `poseidon_many([quote_id_hash, expiry, 2])`, not deployed on any chain yet
+    ///
+    /// Committing to the version makes the ids of two contract versions unable to collide.
+    V2,
+}
+
+impl InvoiceIdVersion {
+    pub fn as_u8(self) -> u8 {
+        match self {
+            InvoiceIdVersion::V1 => 1,
+            InvoiceIdVersion::V2 => 2,
+        }
+    }
+}
+
+/// Calculate the invoice_id of a quote, the way the contract of `version` does
+pub fn compute_invoice_id<E: Into<Felt>>(
+    version: InvoiceIdVersion,
+    quote_id_hash: Felt,
+    expiry: E,
+) -> Felt {
     let expiry_felt = expiry.into();
 
-    // Calculate poseidon hash
-    poseidon_hash(quote_id_hash, expiry_felt)
+    match version {
+        InvoiceIdVersion::V1 => poseidon_hash(quote_id_hash, expiry_felt),
+        InvoiceIdVersion::V2 => {
+            poseidon_hash_many(&[quote_id_hash, expiry_felt, version.as_u8().into()])
+        }
+    }
 }
 
 #[cfg(test)]
@@ -147,4 +186,40 @@ mod tests {
         assert!(!is_valid_starknet_address(&invalid_address4));
         assert!(!is_valid_starknet_address(&invalid_address5));
     }
+
+    #[test]
+    fn invoice_id_v1_is_the_historical_derivation() {
+        let quote_id_hash = Felt::from_hex_unchecked(
+            "0x2f1a8e3b6d5c4f7e9a0b1c2d3e4f5a6b7c8d9e0f1a2b3c4d5e6f7a8b9c0d1e2f",
+        );
+        let expiry = 1_750_000_000u64;
+
+        // What the liquidity source computed before the derivation was versioned
+        let mut values = [quote_id_hash, expiry.into(), 2.into()];
+        starknet_types_core::hash::Poseidon::hades_permutation(&mut values);
+
+        assert_eq!(
+            compute_invoice_id(InvoiceIdVersion::V1, quote_id_hash, expiry),
+            values[0]
+        );
+    }
+
+    #[test]
+    fn invoice_id_v2_commits_to_the_version() {
+        let quote_id_hash = Felt::from_hex_unchecked(
+            "0x2f1a8e3b6d5c4f7e9a0b1c2d3e4f5a6b7c8d9e0f1a2b3c4d5e6f7a8b9c0d1e2f",
+        );
+        let expiry = 1_750_000_000u64;
+
+        let v2 = compute_invoice_id(InvoiceIdVersion::V2, quote_id_hash, expiry);
+
+        assert_ne!(
+            v2,
+            compute_invoice_id(InvoiceIdVersion::V1, quote_id_hash, expiry)
+        );
+        assert_eq!(
+            v2,
+            poseidon_hash_many(&[quote_id_hash, Felt::from(expiry), Felt::from(2)])
+        );
+    }
 }
@@ -0,0 +1,36 @@
+use primitive_types::U256;
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+#[error("u256 value overflowed during the computation of the total amount paid for invoice")]
+pub struct PaymentTotalOverflow;
+
+/// Sum the amounts of all the payment events of an invoice
+pub fn sum_payment_amounts(
+    amounts: impl IntoIterator<Item = U256>,
+) -> Result<U256, PaymentTotalOverflow> {
+    amounts.into_iter().try_fold(U256::zero(), |acc, amount| {
+        acc.checked_add(amount).ok_or(PaymentTotalOverflow)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sums_amounts() {
+        assert_eq!(sum_payment_amounts([]), Ok(U256::zero()));
+        assert_eq!(
+            sum_payment_amounts([U256::from(1), U256::from(2), U256::from(u128::MAX)]),
+            Ok(U256::from(u128::MAX) + 3)
+        );
+    }
+
+    #[test]
+    fn overflow_is_an_error() {
+        assert_eq!(
+            sum_payment_amounts([U256::from(1), U256::MAX, U256::from(1)]),
+            Err(PaymentTotalOverflow)
+        );
+    }
+}
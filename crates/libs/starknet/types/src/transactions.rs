@@ -1,5 +1,6 @@
 use std::sync::Arc;
 
+use num_traits::ToPrimitive;
 use primitive_types::U256;
 use starknet::{
     accounts::{Account, AccountError, ConnectedAccount},
@@ -10,7 +11,9 @@ use starknet_types_core::felt::Felt;
 use tracing::{Instrument, info_span};
 use tracing::{error, info};
 
-use crate::{PayInvoiceCallData, StarknetU256};
+use crate::{
+    PayInvoiceCallData, StarknetU256, StarknetU256LimbOverflowError, is_valid_starknet_address,
+};
 
 const PAY_INVOICE_SELECTOR: Felt =
     Felt::from_hex_unchecked("0x000d5c0f26335ab142eb700850eded4619418b0f6e98c5b92a6347b68d2f2a0c");
@@ -63,6 +66,56 @@ pub fn generate_payment_transaction_calls<'a>(
     calls
 }
 
+// Number of felts each entrypoint of the deployed contracts expects
+// approve(spender: ContractAddress, amount: u256)
+const APPROVE_CALLDATA_LEN: usize = 3;
+// pay_invoice(quote_id_hash: felt252, expiry: u64, asset: ContractAddress, amount: u256, payee: ContractAddress)
+const PAY_INVOICE_CALLDATA_LEN: usize = 6;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CallDataError {
+    #[error("invalid amount: {0}")]
+    Amount(#[from] StarknetU256LimbOverflowError),
+    #[error("expiry {0} does not fit in a u64")]
+    Expiry(Felt),
+    #[error("invalid {0} address: {1}")]
+    Address(&'static str, Felt),
+    #[error("{entrypoint} calldata has {actual} elements, expected {expected}")]
+    Arity {
+        entrypoint: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+fn check_address(name: &'static str, address: Felt) -> Result<(), CallDataError> {
+    if !is_valid_starknet_address(&address) {
+        return Err(CallDataError::Address(name, address));
+    }
+
+    Ok(())
+}
+
+fn check_arity(
+    entrypoint: &'static str,
+    call: &Call,
+    expected: usize,
+) -> Result<(), CallDataError> {
+    if call.calldata.len() != expected {
+        return Err(CallDataError::Arity {
+            entrypoint,
+            expected,
+            actual: call.calldata.len(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Build the approve + pay_invoice calls paying a single invoice
+///
+/// The arguments are checked against what the contracts accept,
+/// so that a malformed payload fails here rather than in a reverted transaction we paid gas for.
 pub fn generate_single_payment_transaction_calls(
     invoice_payment_contract_address: Felt,
     quote_id_hash: Felt,
@@ -70,7 +123,15 @@ pub fn generate_single_payment_transaction_calls(
     token_contract_address: Felt,
     amount: &StarknetU256,
     payee: Felt,
-) -> [Call; 2] {
+) -> Result<[Call; 2], CallDataError> {
+    amount.try_to_bytes_be()?;
+    if expiry.to_u64().is_none() {
+        return Err(CallDataError::Expiry(expiry));
+    }
+    check_address("invoice payment contract", invoice_payment_contract_address)?;
+    check_address("token contract", token_contract_address)?;
+    check_address("payee", payee)?;
+
     // First approve our invoice contract to spend the account funds
     let approve_call = Call {
         to: token_contract_address,
@@ -90,8 +151,10 @@ pub fn generate_single_payment_transaction_calls(
             payee,
         ],
     };
+    check_arity("approve", &approve_call, APPROVE_CALLDATA_LEN)?;
+    check_arity("pay_invoice", &transfer_call, PAY_INVOICE_CALLDATA_LEN)?;
 
-    [approve_call, transfer_call]
+    Ok([approve_call, transfer_call])
 }
 
 pub async fn sign_and_send_payment_transactions<
@@ -121,7 +184,7 @@ pub async fn sign_and_send_single_payment_transactions<
         withdrawal_order.asset_contract_address,
         &withdrawal_order.amount,
         withdrawal_order.payee,
-    );
+    )?;
 
     send_transation(account, calls.to_vec()).await
 }
@@ -158,4 +221,97 @@ pub enum Error<A: Account> {
     Account(#[from] AccountError<A::SignError>),
     #[error(transparent)]
     Provider(#[from] ProviderError),
+    #[error(transparent)]
+    CallData(#[from] CallDataError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn felt(hex: &str) -> Felt {
+        Felt::from_hex_unchecked(hex)
+    }
+
+    fn invoice_contract() -> Felt {
+        felt("0x03b7d6935858cc0e84cba7267cc9daa76dfaf060303761608f12cf84191e3571")
+    }
+
+    fn token() -> Felt {
+        felt("0x04718f5a0fc34cc1af16a1cdee98ffb20c31f5cd61d6ab07201858f4287c938d")
+    }
+
+    fn payee() -> Felt {
+        felt("0x064b48806902a367c8598f4f95c305e8c1a1acba5f082d294a43793113115691")
+    }
+
+    #[test]
+    fn single_payment_calls() {
+        let amount = StarknetU256::from_parts(1_000u128, 2u128);
+        let [approve, pay_invoice] = generate_single_payment_transaction_calls(
+            invoice_contract(),
+            Felt::from(42u64),
+            Felt::from(1_750_000_000u64),
+            token(),
+            &amount,
+            payee(),
+        )
+        .unwrap();
+
+        assert_eq!(approve.to, token());
+        assert_eq!(approve.selector, APPROVE_SELECTOR);
+        assert_eq!(approve.calldata.len(), APPROVE_CALLDATA_LEN);
+        assert_eq!(
+            approve.calldata,
+            vec![invoice_contract(), Felt::from(1_000u64), Felt::from(2u64)]
+        );
+
+        assert_eq!(pay_invoice.to, invoice_contract());
+        assert_eq!(pay_invoice.selector, PAY_INVOICE_SELECTOR);
+        assert_eq!(pay_invoice.calldata.len(), PAY_INVOICE_CALLDATA_LEN);
+        assert_eq!(pay_invoice.calldata[5], payee());
+    }
+
+    #[test]
+    fn reject_malformed_inputs() {
+        let overflowing_amount = StarknetU256 {
+            low: Felt::from(u128::MAX) + Felt::ONE,
+            high: Felt::ZERO,
+        };
+        assert!(matches!(
+            generate_single_payment_transaction_calls(
+                invoice_contract(),
+                Felt::from(42u64),
+                Felt::from(1u64),
+                token(),
+                &overflowing_amount,
+                payee(),
+            ),
+            Err(CallDataError::Amount(_))
+        ));
+
+        let amount = StarknetU256::from_parts(1u128, 0u128);
+        assert!(matches!(
+            generate_single_payment_transaction_calls(
+                invoice_contract(),
+                Felt::from(42u64),
+                Felt::from(u64::MAX) + Felt::ONE,
+                token(),
+                &amount,
+                payee(),
+            ),
+            Err(CallDataError::Expiry(_))
+        ));
+        assert!(matches!(
+            generate_single_payment_transaction_calls(
+                invoice_contract(),
+                Felt::from(42u64),
+                Felt::from(1u64),
+                token(),
+                &amount,
+                Felt::ZERO,
+            ),
+            Err(CallDataError::Address("payee", _))
+        ));
+    }
 }
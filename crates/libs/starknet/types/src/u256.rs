@@ -20,6 +20,15 @@ impl StarknetU256 {
 #[error("Slice too long, max is 32, received {0}")]
 pub struct StarknetU256FromBytesSliceError(usize);
 
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+#[error("the {0} limb does not fit in 128 bits")]
+pub struct StarknetU256LimbOverflowError(&'static str);
+
+// A `Felt` can hold up to 252 bits, a u256 limb only 128
+fn fits_in_u128(limb: &Felt) -> bool {
+    limb.to_bytes_be()[..16].iter().all(|b| *b == 0)
+}
+
 impl StarknetU256 {
     pub fn from_parts<L: Into<u128>, H: Into<u128>>(low: L, high: H) -> Self {
         let low: u128 = low.into();
@@ -30,7 +39,13 @@ impl StarknetU256 {
         }
     }
 
+    /// Any bits of a limb above 128 are dropped,
+    /// use `try_to_bytes_be` if the value didn't come from a trusted conversion.
     pub fn to_bytes_be(&self) -> [u8; 32] {
+        debug_assert!(
+            fits_in_u128(&self.low) && fits_in_u128(&self.high),
+            "StarknetU256 limbs must fit in 128 bits"
+        );
         let mut ret = self.low.to_bytes_be();
 
         ret[0..16].copy_from_slice(&self.high.to_bytes_be()[16..32]);
@@ -38,6 +53,34 @@ impl StarknetU256 {
         ret
     }
 
+    pub fn try_to_bytes_be(&self) -> Result<[u8; 32], StarknetU256LimbOverflowError> {
+        if !fits_in_u128(&self.low) {
+            return Err(StarknetU256LimbOverflowError("low"));
+        }
+        if !fits_in_u128(&self.high) {
+            return Err(StarknetU256LimbOverflowError("high"));
+        }
+
+        let mut ret = self.low.to_bytes_be();
+        ret[0..16].copy_from_slice(&self.high.to_bytes_be()[16..32]);
+
+        Ok(ret)
+    }
+
+    /// `0x`-prefixed, zero-padded 32 bytes big-endian representation
+    ///
+    /// Matches the way felts and hashes are displayed everywhere else,
+    /// unlike `Display` which prints both limbs separately.
+    pub fn to_hex_string(&self) -> String {
+        let mut hex = String::with_capacity(2 + 64);
+        hex.push_str("0x");
+        for byte in self.to_bytes_be() {
+            hex.push_str(&format!("{byte:02x}"));
+        }
+
+        hex
+    }
+
     pub fn from_bytes(bytes: &[u8; 32]) -> Self {
         Self {
             low: Felt::from(u128::from_be_bytes(bytes[16..].try_into().unwrap())),
@@ -122,6 +165,7 @@ impl TryFrom<BigUint> for StarknetU256 {
 
 impl From<primitive_types::U256> for StarknetU256 {
     fn from(value: primitive_types::U256) -> Self {
+        // Little-endian, so the 16 first bytes are the least significant ones, hence `low`
         let bytes = value.to_little_endian();
         let low = u128::from_le_bytes(bytes[..16].try_into().unwrap());
         let high = u128::from_le_bytes(bytes[16..].try_into().unwrap());
@@ -140,6 +184,8 @@ impl From<StarknetU256> for primitive_types::U256 {
 
 impl From<&StarknetU256> for primitive_types::U256 {
     fn from(value: &StarknetU256) -> Self {
+        // A limb is at most 128 bits, so only the 16 first little-endian bytes of each felt are set.
+        // `low` fills the least significant half and `high` overwrites the (zeroed) upper one.
         let mut bytes = value.low.to_bytes_le();
         bytes[16..].copy_from_slice(&value.high.to_bytes_le()[..16]);
 
@@ -152,9 +198,10 @@ mod tests {
     use bitcoin_hashes::sha256::Hash as Sha256;
     use num_bigint::BigUint;
     use primitive_types::U256;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
     use starknet_types_core::felt::Felt;
 
-    use super::{StarknetU256, StarknetU256FromBytesSliceError};
+    use super::{StarknetU256, StarknetU256FromBytesSliceError, StarknetU256LimbOverflowError};
 
     #[test]
     fn test_zero() {
@@ -190,6 +237,41 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_try_to_bytes_be() {
+        let value = StarknetU256::from_parts(u128::MAX, u128::MAX);
+        assert_eq!(value.try_to_bytes_be().unwrap(), [0xFF; 32]);
+
+        let value = StarknetU256::from_parts(1u64, 2u64);
+        assert_eq!(value.try_to_bytes_be().unwrap(), value.to_bytes_be());
+
+        let value = StarknetU256::ZERO;
+        assert_eq!(value.try_to_bytes_be().unwrap(), [0; 32]);
+    }
+
+    #[test]
+    fn test_try_to_bytes_be_limb_overflow() {
+        let too_big = Felt::from(u128::MAX) + Felt::ONE;
+
+        let value = StarknetU256 {
+            low: Felt::ONE,
+            high: too_big,
+        };
+        assert_eq!(
+            value.try_to_bytes_be(),
+            Err(StarknetU256LimbOverflowError("high"))
+        );
+
+        let value = StarknetU256 {
+            low: too_big,
+            high: Felt::ZERO,
+        };
+        assert_eq!(
+            value.try_to_bytes_be(),
+            Err(StarknetU256LimbOverflowError("low"))
+        );
+    }
+
     #[test]
     fn test_from_bytes() {
         let mut bytes = [0u8; 32];
@@ -253,6 +335,21 @@ mod tests {
         assert_eq!(&bytes, hash.as_byte_array());
     }
 
+    #[test]
+    fn test_to_hex_string() {
+        let value = StarknetU256::from(Sha256::hash(b""));
+        assert_eq!(
+            value.to_hex_string(),
+            "0xe3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+
+        let value = StarknetU256::from_parts(1u64, 0u64);
+        assert_eq!(
+            value.to_hex_string(),
+            "0x0000000000000000000000000000000000000000000000000000000000000001"
+        );
+    }
+
     #[test]
     fn test_try_from_biguint() {
         // Test small number
@@ -310,6 +407,30 @@ mod tests {
         assert_eq!(StarknetU256::from(pt), s);
     }
 
+    #[test]
+    fn test_primitive_types_u256_roundtrip() {
+        let mut rng = StdRng::seed_from_u64(0xca5e);
+
+        for _ in 0..10_000 {
+            let pt = U256::from_big_endian(&rng.random::<[u8; 32]>());
+            assert_eq!(U256::from(StarknetU256::from(pt)), pt);
+
+            let s = StarknetU256::from_parts(rng.random::<u128>(), rng.random::<u128>());
+            assert_eq!(StarknetU256::from(U256::from(&s)), s);
+        }
+
+        // Around the limb boundary
+        let limb_max = U256::from(u128::MAX);
+        for pt in [
+            limb_max,
+            limb_max + U256::one(),
+            limb_max * (limb_max + U256::one()),
+            limb_max * (limb_max + U256::one()) - U256::one(),
+        ] {
+            assert_eq!(U256::from(StarknetU256::from(pt)), pt);
+        }
+    }
+
     #[test]
     fn test_display() {
         let value = StarknetU256 {
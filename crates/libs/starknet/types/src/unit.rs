@@ -102,6 +102,27 @@ impl FromStr for Unit {
     }
 }
 
+/// Maps any unit representation to the asset it is backed by
+///
+/// Units reach the apps in their storage form (`m-strk`), in their serde form (`millistrk`)
+/// and sometimes already as an asset symbol (`strk`).
+/// Centralizing the normalization keeps callers from special-casing each of those.
+pub fn unit_str_to_asset(unit: &str) -> Result<Asset, UnitFromStrError> {
+    if let Ok(unit) = Unit::from_str(unit) {
+        return Ok(nuts::traits::Unit::matching_asset(&unit));
+    }
+
+    let unit = match unit.to_lowercase().as_str() {
+        "millistrk" => Unit::MilliStrk,
+        "satoshi" => Unit::Satoshi,
+        "microusdt" => Unit::MicroUsdT,
+        "microusdc" => Unit::MicroUsdC,
+        other => return Asset::from_str(other).map_err(|_| UnitFromStrError),
+    };
+
+    Ok(nuts::traits::Unit::matching_asset(&unit))
+}
+
 impl std::fmt::Display for Unit {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         std::fmt::Display::fmt(self.as_str(), f)
@@ -172,3 +193,19 @@ impl Unit {
         U256::from(u64::from(amount)) * U256::from(self.scale_factor())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_str_to_asset_normalizes_every_representation() {
+        assert_eq!(unit_str_to_asset("millistrk").unwrap(), Asset::Strk);
+        assert_eq!(unit_str_to_asset("m-strk").unwrap(), Asset::Strk);
+        assert_eq!(unit_str_to_asset("strk").unwrap(), Asset::Strk);
+        assert_eq!(unit_str_to_asset("gwei").unwrap(), Asset::Eth);
+        assert_eq!(unit_str_to_asset("eth").unwrap(), Asset::Eth);
+        assert_eq!(unit_str_to_asset("MilliStrk").unwrap(), Asset::Strk);
+        assert!(unit_str_to_asset("doge").is_err());
+    }
+}
@@ -0,0 +1,143 @@
+//! Detection of funds left locked by an interrupted operation
+//!
+//! Proofs are set Pending or Reserved for the duration of an operation and released once it settles.
+//! A crash in between leaves them in that state, excluded from the balance and from input selection,
+//! until something reconciles them with the node.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use nuts::{Amount, nut01::PublicKey};
+use rusqlite::{Connection, Result, params};
+
+use crate::{db::wad::WadStatus, types::ProofState};
+
+/// What the user can do about a [`StuckProof`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Remediation {
+    /// The operation may have reached the node, its answer tells whether the proof is spent
+    Reconcile,
+    /// The proof was taken out of the wallet but is not part of any pending wad
+    Release,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StuckProof {
+    pub y: PublicKey,
+    pub node_id: u32,
+    pub amount: Amount,
+    pub state: ProofState,
+    /// Time since the proof entered its current state
+    pub age: Duration,
+}
+
+impl StuckProof {
+    pub fn remediation(&self) -> Remediation {
+        match self.state {
+            ProofState::Reserved => Remediation::Release,
+            _ => Remediation::Reconcile,
+        }
+    }
+}
+
+/// Proofs that have been Pending or Reserved for longer than `older_than`
+///
+/// Reserved proofs of a wad that is still pending are expected to wait for their receiver,
+/// they are not reported.
+pub fn find_stuck_proofs(conn: &Connection, older_than: Duration) -> Result<Vec<StuckProof>> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time is after unix epoch")
+        .as_secs();
+    let cutoff = now.saturating_sub(older_than.as_secs());
+
+    let mut stmt = conn.prepare(
+        r#"SELECT y, node_id, amount, state, state_updated_at FROM proof
+           WHERE state IN (?1, ?2) AND state_updated_at <= ?3
+           AND y NOT IN (
+               SELECT wp.proof_y FROM wad_proof wp JOIN wad w ON wp.wad_id = w.id
+               WHERE w.status = ?4
+           )
+           ORDER BY state_updated_at ASC;"#,
+    )?;
+    let stuck_proofs = stmt
+        .query_map(
+            params![
+                ProofState::Pending,
+                ProofState::Reserved,
+                cutoff,
+                WadStatus::Pending
+            ],
+            |r| {
+                Ok(StuckProof {
+                    y: r.get(0)?,
+                    node_id: r.get(1)?,
+                    amount: r.get(2)?,
+                    state: r.get(3)?,
+                    age: Duration::from_secs(now.saturating_sub(r.get(4)?)),
+                })
+            },
+        )?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(stuck_proofs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{self, test_utils};
+
+    const HOUR: u64 = 60 * 60;
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    fn insert_proof(
+        conn: &Connection,
+        node_id: u32,
+        state: ProofState,
+        updated_at: u64,
+    ) -> PublicKey {
+        let y = test_utils::insert_proof(conn, node_id, 8, state);
+        test_utils::set_state_updated_at(conn, y, updated_at);
+
+        y
+    }
+
+    #[test]
+    fn stale_reservation_is_reported() {
+        let (conn, node_id) = test_utils::setup_node("sat");
+        let now = now();
+        let stale = insert_proof(&conn, node_id, ProofState::Reserved, now - 2 * HOUR);
+        insert_proof(&conn, node_id, ProofState::Reserved, now);
+        insert_proof(&conn, node_id, ProofState::Unspent, now - 2 * HOUR);
+
+        let stuck = find_stuck_proofs(&conn, Duration::from_secs(HOUR)).unwrap();
+
+        assert_eq!(stuck.len(), 1);
+        assert_eq!(stuck[0].y, stale);
+        assert_eq!(stuck[0].state, ProofState::Reserved);
+        assert_eq!(stuck[0].remediation(), Remediation::Release);
+        assert!(stuck[0].age >= Duration::from_secs(2 * HOUR));
+    }
+
+    #[test]
+    fn reservation_of_a_pending_wad_is_not_stuck() {
+        let (conn, node_id) = test_utils::setup_node("sat");
+        let now = now();
+        let in_wad = insert_proof(&conn, node_id, ProofState::Reserved, now - 2 * HOUR);
+        let pending = insert_proof(&conn, node_id, ProofState::Pending, now - 2 * HOUR);
+        let node_url = db::node::get_url_by_id(&conn, node_id).unwrap().unwrap();
+        db::wad::register_wad(&conn, db::wad::WadType::OUT, &node_url, &None, &[in_wad]).unwrap();
+
+        let stuck = find_stuck_proofs(&conn, Duration::from_secs(HOUR)).unwrap();
+
+        assert_eq!(stuck.len(), 1);
+        assert_eq!(stuck[0].y, pending);
+        assert_eq!(stuck[0].remediation(), Remediation::Reconcile);
+    }
+}
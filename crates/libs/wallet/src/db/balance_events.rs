@@ -0,0 +1,281 @@
+//! Opt-in notifications of proof state changes
+//!
+//! Lets a UI refresh the balances it displays when they change, instead of polling the db.
+//! Nothing is computed until a callback has been registered.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{
+        Arc, LazyLock, Mutex, RwLock,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use nuts::{nut01::PublicKey, nut02::KeysetId};
+use rusqlite::{Connection, Result};
+
+/// `(node_id, unit)` of a balance
+pub type BalanceKey = (u32, String);
+
+type Callback = Arc<dyn Fn(u32, &str) + Send + Sync>;
+
+/// Source of the ids of both the callbacks and the connection hooks registrations
+static NEXT_REGISTRATION_ID: AtomicU64 = AtomicU64::new(0);
+
+static CALLBACKS: RwLock<BTreeMap<u64, Callback>> = RwLock::new(BTreeMap::new());
+
+static PENDING: LazyLock<Mutex<Pending>> = LazyLock::new(Default::default);
+
+#[derive(Default)]
+struct Pending {
+    /// Registration of the hooks installed on each live connection, by connection handle
+    registrations: HashMap<usize, u64>,
+    /// Changes made by transactions that are still open, by registration
+    changes: HashMap<u64, HashSet<BalanceKey>>,
+}
+
+fn next_registration_id() -> u64 {
+    NEXT_REGISTRATION_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Keeps a callback registered, it is unregistered when this is dropped
+#[must_use = "the callback is unregistered as soon as this is dropped"]
+#[derive(Debug)]
+pub struct BalanceChangeListener {
+    id: u64,
+}
+
+impl Drop for BalanceChangeListener {
+    fn drop(&mut self) {
+        CALLBACKS.write().unwrap().remove(&self.id);
+    }
+}
+
+/// Register `callback`, called with the `(node_id, unit)` of every balance modified by a committed change
+///
+/// Changes made inside a transaction are reported once it commits, and dropped if it rolls back.
+/// In that case the callback runs from sqlite's commit hook,
+/// so it must not use the connection that is committing.
+pub fn on_balance_change(
+    callback: impl Fn(u32, &str) + Send + Sync + 'static,
+) -> BalanceChangeListener {
+    let id = next_registration_id();
+    CALLBACKS.write().unwrap().insert(id, Arc::new(callback));
+
+    BalanceChangeListener { id }
+}
+
+pub(crate) fn has_listeners() -> bool {
+    !CALLBACKS.read().unwrap().is_empty()
+}
+
+fn notify(changes: HashSet<BalanceKey>) {
+    let callbacks: Vec<_> = CALLBACKS.read().unwrap().values().cloned().collect();
+    for (node_id, unit) in changes {
+        for callback in &callbacks {
+            callback(node_id, &unit);
+        }
+    }
+}
+
+/// Owned by the hooks installed on a connection
+///
+/// They are dropped along with the connection, even if it is closed in the middle of a transaction,
+/// so that a new connection reusing the same handle doesn't inherit its registration.
+struct HooksRegistration {
+    id: u64,
+    handle: usize,
+}
+
+impl Drop for HooksRegistration {
+    fn drop(&mut self) {
+        let mut pending = PENDING.lock().unwrap();
+        if pending.registrations.get(&self.handle) == Some(&self.id) {
+            pending.registrations.remove(&self.handle);
+        }
+        pending.changes.remove(&self.id);
+    }
+}
+
+/// Registration of the hooks of `conn`, installing them first if needed
+fn hooks_registration(conn: &Connection) -> u64 {
+    // SAFETY: the handle is only used to find the registration of a live connection,
+    // it is never dereferenced
+    let handle = unsafe { conn.handle() } as usize;
+    if let Some(id) = PENDING.lock().unwrap().registrations.get(&handle) {
+        return *id;
+    }
+
+    let id = next_registration_id();
+    PENDING.lock().unwrap().registrations.insert(handle, id);
+    // Installing the hooks drops the previous ones, whose registration locks `PENDING`
+    let on_commit = Arc::new(HooksRegistration { id, handle });
+    let on_rollback = on_commit.clone();
+    conn.commit_hook(Some(move || {
+        let changes = PENDING.lock().unwrap().changes.remove(&on_commit.id);
+        if let Some(changes) = changes {
+            notify(changes);
+        }
+        // Don't turn the commit into a rollback
+        false
+    }));
+    conn.rollback_hook(Some(move || {
+        PENDING.lock().unwrap().changes.remove(&on_rollback.id);
+    }));
+
+    id
+}
+
+/// Report `changes`, made by the last statements run on `conn`
+///
+/// Outside of a transaction the change is already committed and reported right away.
+pub(crate) fn record(conn: &Connection, changes: HashSet<BalanceKey>) {
+    if changes.is_empty() {
+        return;
+    }
+    if conn.is_autocommit() {
+        notify(changes);
+        return;
+    }
+
+    let id = hooks_registration(conn);
+    PENDING
+        .lock()
+        .unwrap()
+        .changes
+        .entry(id)
+        .or_default()
+        .extend(changes);
+}
+
+/// Balances holding the proofs identified by `ys`
+pub(crate) fn balances_of_proofs(
+    conn: &Connection,
+    ys: &[PublicKey],
+) -> Result<HashSet<BalanceKey>> {
+    let mut stmt = conn.prepare(
+        "SELECT p.node_id, k.unit FROM proof p JOIN keyset k ON p.keyset_id = k.id WHERE p.y = ?1",
+    )?;
+    let mut balances = HashSet::new();
+    for y in ys {
+        let mut rows = stmt.query([y])?;
+        if let Some(row) = rows.next()? {
+            balances.insert((row.get(0)?, row.get(1)?));
+        }
+    }
+
+    Ok(balances)
+}
+
+/// Balance holding the proofs of `keyset_id` for `node_id`
+pub(crate) fn balance_of_keyset(
+    conn: &Connection,
+    node_id: u32,
+    keyset_id: KeysetId,
+) -> Result<HashSet<BalanceKey>> {
+    let mut stmt = conn.prepare("SELECT unit FROM keyset WHERE id = ?1")?;
+    let mut rows = stmt.query([keyset_id])?;
+    let mut balances = HashSet::new();
+    if let Some(row) = rows.next()? {
+        balances.insert((node_id, row.get(0)?));
+    }
+
+    Ok(balances)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{proof, test_utils},
+        types::ProofState,
+    };
+
+    use rusqlite::DropBehavior;
+
+    type Seen = Arc<Mutex<Vec<BalanceKey>>>;
+
+    // Each test uses its own unit, so changes made by concurrent tests are filtered out
+    fn setup(unit: &'static str) -> (Connection, u32, PublicKey, BalanceChangeListener, Seen) {
+        let (conn, node_id) = test_utils::setup_node(unit);
+        let y = test_utils::insert_proof(&conn, node_id, 1, ProofState::Unspent);
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen_clone = seen.clone();
+        let listener = on_balance_change(move |node_id, changed_unit| {
+            if changed_unit == unit {
+                seen_clone
+                    .lock()
+                    .unwrap()
+                    .push((node_id, changed_unit.to_string()));
+            }
+        });
+
+        (conn, node_id, y, listener, seen)
+    }
+
+    #[test]
+    fn callback_fires_on_state_change() {
+        let (conn, node_id, y, _listener, seen) = setup("balance-events-state-change");
+
+        proof::set_proof_to_state(&conn, y, ProofState::Pending).unwrap();
+
+        assert!(
+            seen.lock()
+                .unwrap()
+                .contains(&(node_id, "balance-events-state-change".to_string()))
+        );
+    }
+
+    #[test]
+    fn callback_waits_for_commit() {
+        let (mut conn, _, y, _listener, seen) = setup("balance-events-commit");
+        let before = seen.lock().unwrap().len();
+
+        let tx = conn.transaction().unwrap();
+        proof::set_proofs_to_state(&tx, &[y], ProofState::Spent).unwrap();
+        let during = seen.lock().unwrap().len();
+        tx.rollback().unwrap();
+        let after_rollback = seen.lock().unwrap().len();
+
+        let tx = conn.transaction().unwrap();
+        proof::set_proofs_to_state(&tx, &[y], ProofState::Spent).unwrap();
+        tx.commit().unwrap();
+        let after_commit = seen.lock().unwrap().len();
+
+        assert_eq!(during, before);
+        assert_eq!(after_rollback, before);
+        assert!(after_commit > before);
+    }
+
+    #[test]
+    fn dropped_listener_is_not_called() {
+        let (conn, _, y, listener, seen) = setup("balance-events-dropped");
+
+        drop(listener);
+        proof::set_proof_to_state(&conn, y, ProofState::Pending).unwrap();
+
+        assert!(seen.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn connection_closed_mid_transaction_is_unregistered() {
+        let (mut conn, _, y, _listener, _) = setup("balance-events-closed");
+        // SAFETY: only compared with the registered handles
+        let handle = unsafe { conn.handle() } as usize;
+
+        let mut tx = conn.transaction().unwrap();
+        tx.set_drop_behavior(DropBehavior::Ignore);
+        proof::set_proofs_to_state(&tx, &[y], ProofState::Spent).unwrap();
+        drop(tx);
+        let id = PENDING.lock().unwrap().registrations[&handle];
+        assert!(PENDING.lock().unwrap().changes.contains_key(&id));
+
+        // Sqlite rolls the transaction back without calling the rollback hook
+        drop(conn);
+
+        let pending = PENDING.lock().unwrap();
+        assert_ne!(pending.registrations.get(&handle), Some(&id));
+        assert!(!pending.changes.contains_key(&id));
+    }
+}
@@ -28,11 +28,11 @@ pub fn upsert_many_for_node(
     )?;
 
     const UPSERT_NODE_KEYSET: &str = r#"
-            INSERT INTO keyset (id, node_id, unit, active)
-            VALUES (?1, ?2, ?3, ?4)
+            INSERT INTO keyset (id, node_id, unit, active, input_fee_ppk)
+            VALUES (?1, ?2, ?3, ?4, ?5)
             ON CONFLICT(id) DO UPDATE
-                SET active=excluded.active
-                WHERE active != excluded.active;
+                SET active=excluded.active, input_fee_ppk=excluded.input_fee_ppk
+                WHERE active != excluded.active OR input_fee_ppk != excluded.input_fee_ppk;
     "#;
 
     for keyset in keysets {
@@ -45,7 +45,13 @@ pub fn upsert_many_for_node(
         })?;
         conn.execute(
             UPSERT_NODE_KEYSET,
-            params![id, node_id, keyset.unit, keyset.active],
+            params![
+                id,
+                node_id,
+                keyset.unit,
+                keyset.active,
+                keyset.input_fee_ppk
+            ],
         )?;
     }
 
@@ -92,6 +98,16 @@ pub fn get_unit_by_id(conn: &Connection, keyset_id: KeysetId) -> Result<Option<S
     Ok(opt_unit)
 }
 
+/// NUT-02 fee of the keyset, in parts per thousand of a unit, charged for each of its inputs
+pub fn get_input_fee_ppk(conn: &Connection, keyset_id: KeysetId) -> Result<Option<u64>> {
+    let mut stmt = conn.prepare("SELECT input_fee_ppk FROM keyset WHERE id = ?1 LIMIT 1")?;
+    let opt_input_fee_ppk = stmt
+        .query_row(params![keyset_id], |r| r.get::<_, u64>(0))
+        .optional()?;
+
+    Ok(opt_input_fee_ppk)
+}
+
 pub fn get_counter(conn: &Connection, keyset_id: KeysetId) -> Result<u32> {
     let mut stmt = conn.prepare("SELECT counter FROM keyset WHERE id = ?1 LIMIT 1")?;
 
@@ -0,0 +1,224 @@
+//! Housekeeping to keep the wallet db from growing forever
+//!
+//! Spent proofs are useless to the wallet once no history entry points to them anymore,
+//! they only have to be kept as long as the user may want to look at recent wads.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rusqlite::{Connection, Result, TransactionBehavior, params};
+
+use crate::types::ProofState;
+
+use super::wad::WadStatus;
+
+#[derive(Debug, Clone, Copy)]
+pub struct PruneOptions {
+    /// Spent proofs whose state didn't change for this long are deleted
+    pub spent_proofs_older_than: Duration,
+    /// Keep every wad record, and the proofs they reference, regardless of their age
+    pub keep_wad_history: bool,
+    /// Reclaim the freed pages afterward. Rewrites the whole file, so it can be slow on big dbs.
+    pub vacuum: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    pub deleted_proofs: usize,
+    pub deleted_wads: usize,
+}
+
+/// Delete old spent proofs and, unless asked to keep them, old settled wads
+///
+/// Pending wads and proofs referenced by a remaining wad are never touched,
+/// otherwise the history would show wads with missing amounts.
+/// The deletions happen in a single immediate transaction so concurrent readers
+/// see either the db before or after the prune.
+pub fn prune(conn: &mut Connection, options: PruneOptions) -> Result<PruneReport> {
+    let cutoff = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time is after unix epoch")
+        .saturating_sub(options.spent_proofs_older_than)
+        .as_secs();
+
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    let deleted_wads = if options.keep_wad_history {
+        0
+    } else {
+        tx.execute(
+            r#"DELETE FROM wad_proof WHERE wad_id IN (
+                   SELECT id FROM wad WHERE status IN (?1, ?2) AND modified_at < ?3
+               );"#,
+            params![WadStatus::Finished, WadStatus::Failed, cutoff],
+        )?;
+        tx.execute(
+            "DELETE FROM wad WHERE status IN (?1, ?2) AND modified_at < ?3;",
+            params![WadStatus::Finished, WadStatus::Failed, cutoff],
+        )?
+    };
+
+    let deleted_proofs = tx.execute(
+        r#"DELETE FROM proof
+           WHERE state = ?1 AND state_updated_at < ?2
+           AND y NOT IN (SELECT proof_y FROM wad_proof);"#,
+        params![ProofState::Spent, cutoff],
+    )?;
+
+    tx.commit()?;
+
+    // Cannot run inside a transaction
+    if options.vacuum {
+        conn.execute("VACUUM;", ())?;
+    }
+
+    Ok(PruneReport {
+        deleted_proofs,
+        deleted_wads,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use nuts::nut01::PublicKey;
+
+    use super::*;
+    use crate::db::test_utils;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    fn insert_proof(
+        conn: &Connection,
+        node_id: u32,
+        state: ProofState,
+        updated_at: u64,
+    ) -> PublicKey {
+        let y = test_utils::insert_proof(conn, node_id, 8, state);
+        test_utils::set_state_updated_at(conn, y, updated_at);
+
+        y
+    }
+
+    fn insert_wad(
+        conn: &Connection,
+        id: u8,
+        status: WadStatus,
+        modified_at: u64,
+        proof_y: PublicKey,
+    ) {
+        conn.execute(
+            r#"INSERT INTO wad (id, type, status, node_url, created_at, modified_at)
+               VALUES (?1, 'OUT', ?2, 'https://node.example/', ?3, ?3);"#,
+            params![vec![id], status, modified_at],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO wad_proof (wad_id, proof_y) VALUES (?1, ?2);",
+            params![vec![id], proof_y],
+        )
+        .unwrap();
+    }
+
+    fn proof_exists(conn: &Connection, y: PublicKey) -> bool {
+        conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM proof WHERE y = ?1);",
+            [y],
+            |r| r.get(0),
+        )
+        .unwrap()
+    }
+
+    fn options(keep_wad_history: bool) -> PruneOptions {
+        PruneOptions {
+            spent_proofs_older_than: Duration::from_secs(30 * DAY),
+            keep_wad_history,
+            vacuum: false,
+        }
+    }
+
+    #[test]
+    fn prune_old_spent_proofs() {
+        let (mut conn, node_id) = test_utils::setup_node("sat");
+        let now = now();
+        let old_spent = insert_proof(&conn, node_id, ProofState::Spent, now - 60 * DAY);
+        let recent_spent = insert_proof(&conn, node_id, ProofState::Spent, now - DAY);
+        let old_unspent = insert_proof(&conn, node_id, ProofState::Unspent, now - 60 * DAY);
+        let old_spent_in_wad = insert_proof(&conn, node_id, ProofState::Spent, now - 60 * DAY);
+        insert_wad(&conn, 1, WadStatus::Finished, now - DAY, old_spent_in_wad);
+
+        let report = prune(&mut conn, options(false)).unwrap();
+
+        assert_eq!(
+            report,
+            PruneReport {
+                deleted_proofs: 1,
+                deleted_wads: 0
+            }
+        );
+        assert!(!proof_exists(&conn, old_spent));
+        assert!(proof_exists(&conn, recent_spent));
+        assert!(proof_exists(&conn, old_unspent));
+        // Still referenced by a recent wad
+        assert!(proof_exists(&conn, old_spent_in_wad));
+    }
+
+    #[test]
+    fn prune_old_wads_unless_history_is_kept() {
+        let (mut conn, node_id) = test_utils::setup_node("sat");
+        let now = now();
+        let finished_wad_proof = insert_proof(&conn, node_id, ProofState::Spent, now - 60 * DAY);
+        insert_wad(
+            &conn,
+            1,
+            WadStatus::Finished,
+            now - 60 * DAY,
+            finished_wad_proof,
+        );
+        let pending_wad_proof = insert_proof(&conn, node_id, ProofState::Spent, now - 60 * DAY);
+        insert_wad(
+            &conn,
+            2,
+            WadStatus::Pending,
+            now - 60 * DAY,
+            pending_wad_proof,
+        );
+
+        let report = prune(&mut conn, options(true)).unwrap();
+        assert_eq!(report, PruneReport::default());
+        assert!(proof_exists(&conn, finished_wad_proof));
+
+        let report = prune(&mut conn, options(false)).unwrap();
+        assert_eq!(
+            report,
+            PruneReport {
+                deleted_proofs: 1,
+                deleted_wads: 1
+            }
+        );
+        assert!(!proof_exists(&conn, finished_wad_proof));
+        assert!(proof_exists(&conn, pending_wad_proof));
+    }
+
+    #[test]
+    fn prune_then_vacuum() {
+        let (mut conn, node_id) = test_utils::setup_node("sat");
+        insert_proof(&conn, node_id, ProofState::Spent, now() - 60 * DAY);
+
+        let report = prune(
+            &mut conn,
+            PruneOptions {
+                vacuum: true,
+                ..options(false)
+            },
+        )
+        .unwrap();
+
+        assert_eq!(report.deleted_proofs, 1);
+    }
+}
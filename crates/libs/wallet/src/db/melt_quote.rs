@@ -1,5 +1,5 @@
-use nuts::nut05::MeltQuoteState;
-use rusqlite::{Connection, Result};
+use nuts::{nut01::PublicKey, nut05::MeltQuoteState};
+use rusqlite::{Connection, OptionalExtension, Result, params};
 
 #[derive(Debug)]
 pub struct MeltQuote {
@@ -53,6 +53,29 @@ pub fn register_transfer_ids(conn: &Connection, quote_id: &str, transfer_ids: &s
     Ok(())
 }
 
+/// Remember which chain the quote settles on, so its transfers can later be linked to an explorer
+pub fn set_chain_id(conn: &Connection, quote_id: &str, chain_id: &str) -> Result<()> {
+    conn.execute(
+        "UPDATE melt_quote SET chain_id = ?2 WHERE id = ?1;",
+        [quote_id, chain_id],
+    )?;
+
+    Ok(())
+}
+
+pub fn get_chain_id(conn: &Connection, quote_id: &str) -> Result<Option<String>> {
+    let opt_chain_id = conn
+        .query_row(
+            "SELECT chain_id FROM melt_quote WHERE id = ?1;",
+            [quote_id],
+            |r| r.get::<_, Option<String>>(0),
+        )
+        .optional()?
+        .flatten();
+
+    Ok(opt_chain_id)
+}
+
 #[derive(Debug, Clone)]
 pub struct PendingMeltQuote {
     pub id: String,
@@ -108,7 +131,40 @@ pub fn delete(conn: &Connection, quote_id: &str) -> Result<()> {
         WHERE id = ?1;
     "#;
 
+    delete_inputs(conn, quote_id)?;
     conn.execute(DELETE_MELT_QUOTE, [quote_id])?;
 
     Ok(())
 }
+
+/// Remember which proofs were sent to the node to pay `quote_id`
+///
+/// If the node's answer is lost, the next attempt uses them to settle those proofs.
+pub fn register_inputs(conn: &Connection, quote_id: &str, ys: &[PublicKey]) -> Result<()> {
+    let mut stmt = conn.prepare(
+        "INSERT INTO melt_quote_input (quote_id, proof_y) VALUES (?1, ?2) ON CONFLICT DO NOTHING;",
+    )?;
+    for y in ys {
+        stmt.execute(params![quote_id, y])?;
+    }
+
+    Ok(())
+}
+
+pub fn get_inputs(conn: &Connection, quote_id: &str) -> Result<Vec<PublicKey>> {
+    let mut stmt = conn.prepare("SELECT proof_y FROM melt_quote_input WHERE quote_id = ?1;")?;
+    let ys = stmt
+        .query_map([quote_id], |r| r.get::<_, PublicKey>(0))?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(ys)
+}
+
+pub fn delete_inputs(conn: &Connection, quote_id: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM melt_quote_input WHERE quote_id = ?1;",
+        [quote_id],
+    )?;
+
+    Ok(())
+}
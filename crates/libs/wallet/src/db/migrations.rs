@@ -0,0 +1,195 @@
+//! Schema changes applied on top of the tables created by `create_tables`
+//!
+//! `create_tables` only creates missing tables, it cannot alter existing ones.
+//! Every change to an already released schema must be appended here instead.
+//! Migrations are plain sql so that the mobile app can feed them to its own migration system.
+
+use rusqlite::{Connection, OptionalExtension, Result, TransactionBehavior};
+
+pub const CREATE_TABLE_SCHEMA_VERSION: &str = r#"
+        CREATE TABLE IF NOT EXISTS schema_version (
+            id INTEGER PRIMARY KEY CHECK (id = 0),
+            version INTEGER NOT NULL
+        );
+    "#;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+/// Append only. Applying `MIGRATIONS[i]` brings the schema to version `i + 1`.
+pub const MIGRATIONS: &[Migration] = &[
+    // Lets the spent proofs be pruned once they are old enough
+    Migration {
+        description: "add_proof_state_updated_at",
+        sql: r#"
+        ALTER TABLE proof ADD COLUMN state_updated_at INTEGER NOT NULL DEFAULT 0;
+        UPDATE proof SET state_updated_at = CAST(strftime('%s', 'now') AS INTEGER);
+
+        CREATE TRIGGER IF NOT EXISTS proof_insert_state_updated_at AFTER INSERT ON proof
+        BEGIN
+            UPDATE proof SET state_updated_at = CAST(strftime('%s', 'now') AS INTEGER) WHERE y = NEW.y;
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS proof_update_state_updated_at AFTER UPDATE OF state ON proof
+        BEGIN
+            UPDATE proof SET state_updated_at = CAST(strftime('%s', 'now') AS INTEGER) WHERE y = NEW.y;
+        END;
+    "#,
+    },
+    Migration {
+        description: "add_melt_quote_chain_id",
+        sql: "ALTER TABLE melt_quote ADD COLUMN chain_id TEXT;",
+    },
+    Migration {
+        description: "add_wad_proof_role",
+        sql: r#"
+        ALTER TABLE wad_proof ADD COLUMN role TEXT NOT NULL DEFAULT 'INPUT' CHECK (role IN ('INPUT', 'OUTPUT'));
+    "#,
+    },
+    Migration {
+        description: "create_table_melt_quote_input",
+        sql: r#"
+        CREATE TABLE IF NOT EXISTS melt_quote_input (
+            quote_id TEXT NOT NULL REFERENCES melt_quote(id) ON DELETE CASCADE,
+            proof_y BLOB(33) NOT NULL REFERENCES proof(y) ON DELETE CASCADE,
+            PRIMARY KEY (quote_id, proof_y)
+        );
+    "#,
+    },
+    // Swaps must leave the NUT-02 fee of their inputs to the node
+    Migration {
+        description: "add_keyset_input_fee_ppk",
+        sql: "ALTER TABLE keyset ADD COLUMN input_fee_ppk INTEGER NOT NULL DEFAULT 0;",
+    },
+];
+
+pub fn get_schema_version(conn: &Connection) -> Result<u32> {
+    let version = conn
+        .query_row(
+            "SELECT version FROM schema_version WHERE id = 0;",
+            [],
+            |r| r.get::<_, u32>(0),
+        )
+        .optional()?
+        .unwrap_or(0);
+
+    Ok(version)
+}
+
+/// Apply all the migrations not applied yet, returns the resulting schema version
+///
+/// Runs in a single immediate transaction, so two processes opening the same db
+/// cannot apply the same migration twice, and a failing migration leaves the db untouched.
+pub fn run(conn: &mut Connection) -> Result<u32> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    tx.execute(CREATE_TABLE_SCHEMA_VERSION, ())?;
+
+    let current_version = get_schema_version(&tx)?;
+    let target_version = MIGRATIONS.len() as u32;
+    if current_version >= target_version {
+        if current_version > target_version {
+            log::warn!(
+                "wallet db schema version {} is newer than the {} known by this build",
+                current_version,
+                target_version
+            );
+        }
+        return Ok(current_version);
+    }
+
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(current_version as usize) {
+        log::info!(
+            "applying wallet db migration {}: {}",
+            i + 1,
+            migration.description
+        );
+        tx.execute_batch(migration.sql)?;
+    }
+
+    tx.execute(
+        r#"INSERT INTO schema_version (id, version) VALUES (0, ?1)
+           ON CONFLICT(id) DO UPDATE SET version = excluded.version;"#,
+        [target_version],
+    )?;
+    tx.commit()?;
+
+    Ok(target_version)
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::params;
+
+    use super::*;
+    use crate::{
+        db::test_utils::{insert_node, insert_proof, setup_node},
+        types::ProofState,
+    };
+
+    fn column_names(conn: &Connection, table: &str) -> Vec<String> {
+        let mut stmt = conn
+            .prepare(&format!("SELECT name FROM pragma_table_info('{table}');"))
+            .unwrap();
+        stmt.query_map([], |r| r.get(0))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn migrate_old_schema() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        // Schema as released before any migration existed
+        let tx = conn.transaction().unwrap();
+        super::super::create_baseline_tables(&tx).unwrap();
+        tx.commit().unwrap();
+        let node_id = insert_node(&conn, "sat");
+        let y = insert_proof(&conn, node_id, 8, ProofState::Reserved);
+        assert!(!column_names(&conn, "proof").contains(&"state_updated_at".to_string()));
+
+        assert_eq!(run(&mut conn).unwrap(), MIGRATIONS.len() as u32);
+
+        assert!(column_names(&conn, "proof").contains(&"state_updated_at".to_string()));
+        let (amount, state, state_updated_at): (u64, ProofState, i64) = conn
+            .query_row(
+                "SELECT amount, state, state_updated_at FROM proof WHERE y = ?1;",
+                params![y],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(amount, 8);
+        assert_eq!(state, ProofState::Reserved);
+        assert!(state_updated_at > 0);
+
+        // Running again is a no-op
+        assert_eq!(run(&mut conn).unwrap(), MIGRATIONS.len() as u32);
+        assert_eq!(get_schema_version(&conn).unwrap(), MIGRATIONS.len() as u32);
+    }
+
+    #[test]
+    fn state_change_updates_timestamp() {
+        let (conn, node_id) = setup_node("sat");
+        let y = insert_proof(&conn, node_id, 8, ProofState::Unspent);
+        let get_timestamp = |conn: &Connection| -> i64 {
+            conn.query_row(
+                "SELECT state_updated_at FROM proof WHERE y = ?1;",
+                params![y],
+                |r| r.get(0),
+            )
+            .unwrap()
+        };
+        assert!(get_timestamp(&conn) > 0);
+
+        conn.execute("UPDATE proof SET state_updated_at = 0;", [])
+            .unwrap();
+        conn.execute(
+            "UPDATE proof SET state = ?2 WHERE y = ?1;",
+            params![y, ProofState::Spent],
+        )
+        .unwrap();
+        assert!(get_timestamp(&conn) > 0);
+    }
+}
@@ -1,15 +1,22 @@
 use nuts::nut02::KeysetId;
-use rusqlite::{Connection, Result, params};
+use rusqlite::{Connection, Result, Transaction, params};
 
 pub mod balance;
+pub mod balance_events;
 pub mod keyset;
+pub mod maintenance;
 pub mod melt_quote;
+pub mod migrations;
 pub mod mint_quote;
 pub mod node;
 pub mod proof;
+#[cfg(test)]
+pub mod test_utils;
 pub mod wad;
 pub mod wallet;
 
+pub use balance_events::{BalanceChangeListener, on_balance_change};
+
 pub const CREATE_TABLE_KEY: &str = r#"
         CREATE TABLE IF NOT EXISTS key (
             keyset_id BLOB(8) NOT NULL REFERENCES keyset(id) ON DELETE CASCADE,
@@ -42,9 +49,18 @@ pub const CREATE_TABLE_MELT_QUOTE: &str = r#"
             transfer_ids TEXT
         );"#;
 
+/// Create the missing tables, then bring the schema up to date
 pub fn create_tables(conn: &mut Connection) -> Result<()> {
     let tx = conn.transaction()?;
+    create_baseline_tables(&tx)?;
+    tx.commit()?;
+
+    migrations::run(conn)?;
 
+    Ok(())
+}
+
+fn create_baseline_tables(tx: &Transaction) -> Result<()> {
     tx.execute(wallet::CREATE_TABLE_WALLET, ())?;
     tx.execute(node::CREATE_TABLE_NODE, ())?;
     tx.execute(keyset::CREATE_TABLE_KEYSET, ())?;
@@ -55,24 +71,60 @@ pub fn create_tables(conn: &mut Connection) -> Result<()> {
     tx.execute(wad::CREATE_TABLE_WAD, ())?;
     tx.execute(wad::CREATE_TABLE_WAD_PROOF, ())?;
 
-    tx.commit()?;
-
     Ok(())
 }
 
+/// Store the keys of a keyset, returns how many of them were not already known
+///
+/// Keys already stored are left untouched, so two concurrent imports of the same keyset both succeed.
 pub fn insert_keyset_keys<'a>(
     conn: &Connection,
     keyset_id: KeysetId,
     keys: impl Iterator<Item = (u64, &'a str)>,
-) -> Result<()> {
+) -> Result<usize> {
     const INSET_NEW_KEY: &str = r#"
         INSERT INTO key (keyset_id, amount, pubkey) VALUES (?1, ?2, ?3) ON CONFLICT DO NOTHING;
     "#;
 
     let mut stmt = conn.prepare(INSET_NEW_KEY)?;
+    let mut n_inserted = 0;
     for (amount, pk) in keys {
-        stmt.execute(params![keyset_id, amount, pk])?;
+        n_inserted += stmt.execute(params![keyset_id, amount, pk])?;
     }
 
-    Ok(())
+    Ok(n_inserted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inserting_the_same_keyset_keys_twice_is_a_no_op() {
+        let (conn, node_id) = test_utils::setup_node("sat");
+        let keyset_id = test_utils::keyset_id(node_id);
+        let keys = [(1, "pubkey_1"), (2, "pubkey_2"), (4, "pubkey_4")];
+        let stored_keys = |conn: &Connection| -> Vec<(u64, String)> {
+            let mut stmt = conn
+                .prepare("SELECT amount, pubkey FROM key WHERE keyset_id = ?1 ORDER BY amount;")
+                .unwrap();
+            stmt.query_map([keyset_id], |r| Ok((r.get(0)?, r.get(1)?)))
+                .unwrap()
+                .collect::<Result<_>>()
+                .unwrap()
+        };
+
+        assert_eq!(
+            insert_keyset_keys(&conn, keyset_id, keys.into_iter()).unwrap(),
+            3
+        );
+        let first_import = stored_keys(&conn);
+        assert_eq!(first_import.len(), 3);
+
+        assert_eq!(
+            insert_keyset_keys(&conn, keyset_id, keys.into_iter()).unwrap(),
+            0
+        );
+        assert_eq!(stored_keys(&conn), first_import);
+    }
 }
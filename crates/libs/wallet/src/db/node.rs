@@ -1,6 +1,6 @@
 use rusqlite::{Connection, OptionalExtension, Result};
 
-use crate::types::NodeUrl;
+use crate::types::{NodeUrl, ProofState};
 use rusqlite::params;
 
 pub const CREATE_TABLE_NODE: &str = r#"
@@ -12,11 +12,18 @@ pub const CREATE_TABLE_NODE: &str = r#"
         CREATE INDEX node_url ON node(url); 
     "#;
 
-pub fn insert(conn: &Connection, node_url: &NodeUrl) -> Result<usize> {
+/// Register a node, returns its id whether it was just inserted or already known
+///
+/// `NodeUrl` is normalized, so different spellings of the same url map to the same node.
+pub fn insert(conn: &Connection, node_url: &NodeUrl) -> Result<u32> {
     conn.execute(
         "INSERT INTO node (url) VALUES (?1) ON CONFLICT DO NOTHING;",
         [node_url],
-    )
+    )?;
+
+    conn.query_row("SELECT id FROM node WHERE url = ?1;", [node_url], |r| {
+        r.get::<_, u32>(0)
+    })
 }
 
 pub fn get_id_by_url(conn: &Connection, node_url: &NodeUrl) -> Result<Option<u32>> {
@@ -44,3 +51,111 @@ pub fn fetch_all(conn: &Connection) -> Result<Vec<(u32, NodeUrl)>> {
 
     rows.collect::<Result<Vec<_>>>()
 }
+
+#[derive(Debug, thiserror::Error)]
+pub enum DeleteNodeError {
+    #[error("node {0} still holds proofs that are not spent")]
+    NodeHasFunds(u32),
+    #[error("database error: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+/// Forget about a node and everything we stored about it
+///
+/// Refuses to delete a node we still hold funds at, as it would make them unspendable.
+/// Reserved proofs count as funds, they belong to an operation that may still fail.
+/// Rows are removed explicitly because sqlite doesn't enforce `ON DELETE CASCADE`
+/// unless the `foreign_keys` pragma is set on each connection.
+pub fn delete(conn: &mut Connection, node_id: u32) -> std::result::Result<(), DeleteNodeError> {
+    let tx = conn.transaction()?;
+
+    let n_proofs_with_funds: u64 = tx.query_row(
+        "SELECT COUNT(*) FROM proof WHERE node_id = ?1 AND state != ?2;",
+        params![node_id, ProofState::Spent],
+        |r| r.get(0),
+    )?;
+    if n_proofs_with_funds != 0 {
+        return Err(DeleteNodeError::NodeHasFunds(node_id));
+    }
+
+    tx.execute(
+        "DELETE FROM wad_proof WHERE proof_y IN (SELECT y FROM proof WHERE node_id = ?1);",
+        [node_id],
+    )?;
+    tx.execute("DELETE FROM proof WHERE node_id = ?1;", [node_id])?;
+    tx.execute(
+        "DELETE FROM key WHERE keyset_id IN (SELECT id FROM keyset WHERE node_id = ?1);",
+        [node_id],
+    )?;
+    tx.execute("DELETE FROM keyset WHERE node_id = ?1;", [node_id])?;
+    tx.execute("DELETE FROM mint_quote WHERE node_id = ?1;", [node_id])?;
+    tx.execute("DELETE FROM melt_quote WHERE node_id = ?1;", [node_id])?;
+    tx.execute("DELETE FROM node WHERE id = ?1;", [node_id])?;
+
+    tx.commit()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::db::test_utils::{insert_proof, setup_node};
+
+    #[test]
+    fn delete_node_without_funds() {
+        let (mut conn, node_id) = setup_node("m-strk");
+        insert_proof(&conn, node_id, 8, ProofState::Spent);
+
+        delete(&mut conn, node_id).unwrap();
+
+        assert!(get_url_by_id(&conn, node_id).unwrap().is_none());
+        let n_keysets: u64 = conn
+            .query_row("SELECT COUNT(*) FROM keyset;", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(n_keysets, 0);
+        let n_proofs: u64 = conn
+            .query_row("SELECT COUNT(*) FROM proof;", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(n_proofs, 0);
+    }
+
+    #[test]
+    fn refuse_to_delete_node_with_reserved_proofs() {
+        let (mut conn, node_id) = setup_node("m-strk");
+        insert_proof(&conn, node_id, 8, ProofState::Reserved);
+
+        let res = delete(&mut conn, node_id);
+
+        assert!(matches!(res, Err(DeleteNodeError::NodeHasFunds(id)) if id == node_id));
+        assert!(get_url_by_id(&conn, node_id).unwrap().is_some());
+    }
+
+    #[test]
+    fn refuse_to_delete_node_with_unspent_proofs() {
+        let (mut conn, node_id) = setup_node("m-strk");
+        insert_proof(&conn, node_id, 8, ProofState::Unspent);
+
+        let res = delete(&mut conn, node_id);
+
+        assert!(matches!(res, Err(DeleteNodeError::NodeHasFunds(id)) if id == node_id));
+        assert!(get_url_by_id(&conn, node_id).unwrap().is_some());
+    }
+
+    #[test]
+    fn insert_same_node_twice() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::db::create_tables(&mut conn).unwrap();
+
+        let node_id = insert(&conn, &NodeUrl::from_str("https://node.example").unwrap()).unwrap();
+        for surface_form in ["https://node.example/", "https://Node.Example:443"] {
+            let node_url = NodeUrl::from_str(surface_form).unwrap();
+            assert_eq!(insert(&conn, &node_url).unwrap(), node_id);
+            assert_eq!(get_id_by_url(&conn, &node_url).unwrap(), Some(node_id));
+        }
+
+        assert_eq!(fetch_all(&conn).unwrap().len(), 1);
+    }
+}
@@ -1,6 +1,6 @@
 use rusqlite::{Connection, OptionalExtension, Result, params};
 
-use crate::types::ProofState;
+use crate::{db::balance_events, types::ProofState};
 use nuts::{Amount, nut00::secret::Secret, nut01::PublicKey, nut02::KeysetId};
 
 pub const CREATE_TABLE_PROOF: &str = r#"
@@ -46,16 +46,28 @@ pub fn get_proof_and_set_state_pending(
         })
         .optional()?
     };
+    if n_rows != 0 && balance_events::has_listeners() {
+        balance_events::record(conn, balance_events::balances_of_proofs(conn, &[y])?);
+    }
 
     Ok(values)
 }
 
 pub fn set_proof_to_state(conn: &Connection, y: PublicKey, state: ProofState) -> Result<()> {
     conn.execute("UPDATE proof SET state = ?2 WHERE y = ?1", (y, state))?;
+    if balance_events::has_listeners() {
+        balance_events::record(conn, balance_events::balances_of_proofs(conn, &[y])?);
+    }
 
     Ok(())
 }
 
+/// Max number of ys bound in a single statement
+///
+/// Stays under the default SQLITE_MAX_VARIABLE_NUMBER of SQLite < 3.32 (999),
+/// leaving room for the other parameters of the query.
+const MAX_YS_PER_STATEMENT: usize = 900;
+
 fn build_ys_placeholder_string_for_in_statement(len: usize) -> String {
     // Build placeholder string like "?,?,?" based on number of items
     let mut placeholders = "?,".repeat(len - 1);
@@ -63,25 +75,79 @@ fn build_ys_placeholder_string_for_in_statement(len: usize) -> String {
     placeholders
 }
 
+/// Run `f` inside a savepoint
+///
+/// Statements split in chunks must still apply all or nothing.
+/// Unlike a transaction, a savepoint can be opened whether or not the caller already is in one.
+fn all_or_nothing<T>(conn: &Connection, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    conn.execute_batch("SAVEPOINT proof_chunks")?;
+    match f() {
+        Ok(v) => {
+            conn.execute_batch("RELEASE proof_chunks")?;
+            Ok(v)
+        }
+        Err(e) => {
+            conn.execute_batch("ROLLBACK TO proof_chunks; RELEASE proof_chunks")?;
+            Err(e)
+        }
+    }
+}
+
 pub fn set_proofs_to_state(
     conn: &Connection,
     ys: &[PublicKey],
     state: ProofState,
 ) -> Result<usize> {
-    let placeholders = build_ys_placeholder_string_for_in_statement(ys.len());
+    update_proofs_state(conn, ys, None, state)
+}
+
+/// Same as `set_proofs_to_state`, but leaves untouched the proofs not in `current_state`
+pub fn set_proofs_in_state_to_state(
+    conn: &Connection,
+    ys: &[PublicKey],
+    current_state: ProofState,
+    state: ProofState,
+) -> Result<usize> {
+    update_proofs_state(conn, ys, Some(current_state), state)
+}
 
-    // Prepare the statement with dynamic placeholders
-    let sql = format!("UPDATE proof SET state = ?1 WHERE y IN ({})", placeholders);
-    let mut stmt = conn.prepare(&sql)?;
+fn update_proofs_state(
+    conn: &Connection,
+    ys: &[PublicKey],
+    current_state: Option<ProofState>,
+    state: ProofState,
+) -> Result<usize> {
+    let rows_affected = all_or_nothing(conn, || {
+        let mut rows_affected = 0;
+        for ys in ys.chunks(MAX_YS_PER_STATEMENT) {
+            let placeholders = build_ys_placeholder_string_for_in_statement(ys.len());
+
+            // Prepare the statement with dynamic placeholders
+            let mut sql = format!("UPDATE proof SET state = ?1 WHERE y IN ({})", placeholders);
+            if current_state.is_some() {
+                sql.push_str(" AND state = ?");
+            }
+            let mut stmt = conn.prepare(&sql)?;
 
-    // Bind state as first parameter
-    stmt.raw_bind_parameter(1, state)?;
-    // Bind each public key string to its respective placeholder
-    for (i, y) in ys.iter().enumerate() {
-        stmt.raw_bind_parameter(i + 2, y)?;
+            // Bind state as first parameter
+            stmt.raw_bind_parameter(1, state)?;
+            // Bind each public key string to its respective placeholder
+            for (i, y) in ys.iter().enumerate() {
+                stmt.raw_bind_parameter(i + 2, y)?;
+            }
+            if let Some(current_state) = current_state {
+                stmt.raw_bind_parameter(ys.len() + 2, current_state)?;
+            }
+
+            rows_affected += stmt.raw_execute()?;
+        }
+
+        Ok(rows_affected)
+    })?;
+    if rows_affected != 0 && balance_events::has_listeners() {
+        balance_events::record(conn, balance_events::balances_of_proofs(conn, ys)?);
     }
 
-    let rows_affected = stmt.raw_execute()?;
     Ok(rows_affected)
 }
 
@@ -94,36 +160,32 @@ pub fn get_proofs_by_ids(
     conn: &Connection,
     ys: &[PublicKey],
 ) -> Result<Vec<(Amount, KeysetId, PublicKey, Secret)>> {
-    if ys.is_empty() {
-        return Ok(Vec::new());
-    }
+    let mut proofs = Vec::with_capacity(ys.len());
 
-    let placeholders = build_ys_placeholder_string_for_in_statement(ys.len());
-    let sql = format!(
-        "SELECT amount, keyset_id, unblind_signature, secret FROM proof WHERE y IN ({})",
-        placeholders
-    );
-
-    let mut stmt = conn.prepare(&sql)?;
+    for ys in ys.chunks(MAX_YS_PER_STATEMENT) {
+        let placeholders = build_ys_placeholder_string_for_in_statement(ys.len());
+        let sql = format!(
+            "SELECT amount, keyset_id, unblind_signature, secret FROM proof WHERE y IN ({})",
+            placeholders
+        );
 
-    for (i, y) in ys.iter().enumerate() {
-        stmt.raw_bind_parameter(i + 1, y)?;
+        let mut stmt = conn.prepare(&sql)?;
+
+        for (i, y) in ys.iter().enumerate() {
+            stmt.raw_bind_parameter(i + 1, y)?;
+        }
+
+        let mut rows = stmt.raw_query();
+        while let Some(r) = rows.next()? {
+            proofs.push((
+                r.get::<_, Amount>(0)?,
+                r.get::<_, KeysetId>(1)?,
+                r.get::<_, PublicKey>(2)?,
+                r.get::<_, Secret>(3)?,
+            ));
+        }
     }
 
-    let proofs = stmt
-        .raw_query()
-        .mapped(|r| -> Result<(Amount, KeysetId, PublicKey, Secret)> {
-            {
-                Ok((
-                    r.get::<_, Amount>(0)?,
-                    r.get::<_, KeysetId>(1)?,
-                    r.get::<_, PublicKey>(2)?,
-                    r.get::<_, Secret>(3)?,
-                ))
-            }
-        })
-        .collect::<Result<Vec<_>>>()?;
-
     Ok(proofs)
 }
 
@@ -132,47 +194,104 @@ pub fn get_proofs_by_ids(
 /// Will error if any of those ids doesn't exist
 /// The order of the returned proofs is not guaranteed to match the input `proof_ids`.
 pub fn get_proofs_state_by_ids(conn: &Connection, ys: &[PublicKey]) -> Result<Vec<ProofState>> {
-    if ys.is_empty() {
-        return Ok(Vec::new());
-    }
+    let mut states = Vec::with_capacity(ys.len());
 
-    let placeholders = build_ys_placeholder_string_for_in_statement(ys.len());
-    let sql = format!("SELECT state FROM proof WHERE y IN ({})", placeholders);
+    for ys in ys.chunks(MAX_YS_PER_STATEMENT) {
+        let placeholders = build_ys_placeholder_string_for_in_statement(ys.len());
+        let sql = format!("SELECT state FROM proof WHERE y IN ({})", placeholders);
 
-    let mut stmt = conn.prepare(&sql)?;
+        let mut stmt = conn.prepare(&sql)?;
 
-    for (i, y) in ys.iter().enumerate() {
-        stmt.raw_bind_parameter(i + 1, y)?;
+        for (i, y) in ys.iter().enumerate() {
+            stmt.raw_bind_parameter(i + 1, y)?;
+        }
+
+        let mut rows = stmt.raw_query();
+        while let Some(r) = rows.next()? {
+            states.push(r.get::<_, ProofState>(0)?);
+        }
     }
 
+    Ok(states)
+}
+
+/// Return the ys of the `node_id` proofs that are either Pending or Reserved, along with their state
+///
+/// Those are the proofs whose fate depends on an operation the node may or may not have processed.
+pub fn get_unsettled_proofs(
+    conn: &Connection,
+    node_id: u32,
+) -> Result<Vec<(PublicKey, ProofState)>> {
+    let mut stmt =
+        conn.prepare("SELECT y, state FROM proof WHERE node_id = ?1 AND state IN (?2, ?3)")?;
     let proofs = stmt
-        .raw_query()
-        .mapped(|r| -> Result<ProofState> { r.get::<_, ProofState>(0) })
+        .query_map(
+            params![node_id, ProofState::Pending, ProofState::Reserved],
+            |r| Ok((r.get::<_, PublicKey>(0)?, r.get::<_, ProofState>(1)?)),
+        )?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(proofs)
+}
+
+/// Return the `node_id` unspent proofs of `keyset_id`, as `(y, amount, secret, unblind_signature)`
+#[allow(clippy::type_complexity)]
+pub fn get_unspent_by_keyset(
+    conn: &Connection,
+    node_id: u32,
+    keyset_id: KeysetId,
+) -> Result<Vec<(PublicKey, Amount, Secret, PublicKey)>> {
+    let mut stmt = conn.prepare(
+        "SELECT y, amount, secret, unblind_signature FROM proof WHERE node_id = ?1 AND keyset_id = ?2 AND state = ?3",
+    )?;
+    let proofs = stmt
+        .query_map(params![node_id, keyset_id, ProofState::Unspent], |r| {
+            Ok((
+                r.get::<_, PublicKey>(0)?,
+                r.get::<_, Amount>(1)?,
+                r.get::<_, Secret>(2)?,
+                r.get::<_, PublicKey>(3)?,
+            ))
+        })?
         .collect::<Result<Vec<_>>>()?;
 
     Ok(proofs)
 }
 
-/// Returns the maximum allowed amount (max_order) for a given keyset_id from the key table.
-pub fn get_max_order_for_keyset(
+/// Returns the largest denomination of a keyset, which is the biggest amount a single proof can hold
+pub fn get_max_denomination_for_keyset(
     conn: &rusqlite::Connection,
     keyset_id: nuts::nut02::KeysetId,
 ) -> rusqlite::Result<Option<u64>> {
     let mut stmt = conn.prepare("SELECT MAX(amount) FROM key WHERE keyset_id = ?1")?;
-    let max_order = stmt.query_row([keyset_id], |row| row.get::<_, Option<u64>>(0))?;
+    let max_denomination = stmt.query_row([keyset_id], |row| row.get::<_, Option<u64>>(0))?;
 
-    Ok(max_order)
+    Ok(max_denomination)
 }
 
 pub fn delete_proofs(conn: &Connection, ys: &[PublicKey]) -> Result<()> {
-    let placeholders = build_ys_placeholder_string_for_in_statement(ys.len());
-    let sql = format!("DELETE FROM proof WHERE y IN ({})", placeholders);
-    let mut stmt = conn.prepare(&sql)?;
-    for (i, y) in ys.iter().enumerate() {
-        stmt.raw_bind_parameter(i + 1, y)?;
-    }
+    // Must be read before the rows are gone
+    let changes = if balance_events::has_listeners() {
+        balance_events::balances_of_proofs(conn, ys)?
+    } else {
+        Default::default()
+    };
+
+    all_or_nothing(conn, || {
+        for ys in ys.chunks(MAX_YS_PER_STATEMENT) {
+            let placeholders = build_ys_placeholder_string_for_in_statement(ys.len());
+            let sql = format!("DELETE FROM proof WHERE y IN ({})", placeholders);
+            let mut stmt = conn.prepare(&sql)?;
+            for (i, y) in ys.iter().enumerate() {
+                stmt.raw_bind_parameter(i + 1, y)?;
+            }
+
+            stmt.raw_execute()?;
+        }
 
-    stmt.raw_execute()?;
+        Ok(())
+    })?;
+    balance_events::record(conn, changes);
 
     Ok(())
 }
@@ -245,3 +364,47 @@ pub fn get_nodes_ids_and_available_funds_ordered_desc(
 
     Ok(res)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_utils;
+
+    fn insert_proofs(conn: &Connection, node_id: u32, n: usize) -> Vec<PublicKey> {
+        (0..n)
+            .map(|_| test_utils::insert_proof(conn, node_id, 1, ProofState::Unspent))
+            .collect()
+    }
+
+    #[test]
+    fn more_ids_than_sqlite_variables() {
+        let (conn, node_id) = test_utils::setup_node("sat");
+        let ys = insert_proofs(&conn, node_id, 2 * MAX_YS_PER_STATEMENT + 200);
+
+        assert_eq!(get_proofs_by_ids(&conn, &ys).unwrap().len(), ys.len());
+
+        let updated = set_proofs_to_state(&conn, &ys, ProofState::Spent).unwrap();
+        assert_eq!(updated, ys.len());
+        let states = get_proofs_state_by_ids(&conn, &ys).unwrap();
+        assert_eq!(states.len(), ys.len());
+        assert!(states.iter().all(|s| *s == ProofState::Spent));
+
+        delete_proofs(&conn, &ys).unwrap();
+        assert!(get_proofs_by_ids(&conn, &ys).unwrap().is_empty());
+    }
+
+    #[test]
+    fn chunked_update_is_atomic() {
+        let (conn, node_id) = test_utils::setup_node("sat");
+        let ys = insert_proofs(&conn, node_id, MAX_YS_PER_STATEMENT + 10);
+        // Make the statement of the last chunk fail
+        conn.execute_batch(
+            "CREATE TRIGGER fail_last BEFORE UPDATE ON proof WHEN old.y = (SELECT y FROM proof ORDER BY rowid DESC LIMIT 1) BEGIN SELECT RAISE(ABORT, 'boom'); END;",
+        )
+        .unwrap();
+
+        assert!(set_proofs_to_state(&conn, &ys, ProofState::Spent).is_err());
+        let states = get_proofs_state_by_ids(&conn, &ys).unwrap();
+        assert!(states.iter().all(|s| *s == ProofState::Unspent));
+    }
+}
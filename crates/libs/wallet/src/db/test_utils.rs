@@ -0,0 +1,102 @@
+//! Fixtures shared by the tests touching the wallet database
+
+use std::str::FromStr;
+
+use nuts::{
+    dhke::hash_to_curve,
+    nut00::secret::Secret,
+    nut01::{PublicKey, SecretKey},
+    nut02::KeysetId,
+};
+use rusqlite::{Connection, params};
+
+use crate::types::{NodeUrl, ProofState};
+
+/// A fresh in-memory database
+pub fn setup_db() -> Connection {
+    let mut conn = Connection::open_in_memory().unwrap();
+    super::create_tables(&mut conn).unwrap();
+
+    conn
+}
+
+/// A fresh database with a single node, see [`insert_node`]
+pub fn setup_node(unit: &str) -> (Connection, u32) {
+    let conn = setup_db();
+    let node_id = insert_node(&conn, unit);
+
+    (conn, node_id)
+}
+
+/// The id of the active keyset created along with the node
+pub fn keyset_id(node_id: u32) -> KeysetId {
+    KeysetId::from_bytes(&[0x00, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff, node_id as u8]).unwrap()
+}
+
+/// Register a new node, with an active keyset of `unit`
+pub fn insert_node(conn: &Connection, unit: &str) -> u32 {
+    let n_nodes: u16 = conn
+        .query_row("SELECT COUNT(*) FROM node;", [], |r| r.get(0))
+        .unwrap();
+    let node_url = NodeUrl::from_str(&format!("https://localhost:{}", 10003 + n_nodes)).unwrap();
+    let node_id = super::node::insert(conn, &node_url).unwrap();
+    insert_keyset(conn, node_id, keyset_id(node_id), unit, true);
+
+    node_id
+}
+
+pub fn insert_keyset(
+    conn: &Connection,
+    node_id: u32,
+    keyset_id: KeysetId,
+    unit: &str,
+    active: bool,
+) {
+    conn.execute(
+        "INSERT INTO keyset (id, node_id, unit, active) VALUES (?1, ?2, ?3, ?4);",
+        params![keyset_id, node_id, unit, active],
+    )
+    .unwrap();
+}
+
+/// Store a proof of the node's active keyset, returns its y
+pub fn insert_proof(conn: &Connection, node_id: u32, amount: u64, state: ProofState) -> PublicKey {
+    insert_keyset_proof(conn, node_id, keyset_id(node_id), amount, state)
+}
+
+/// Store a proof of `keyset_id`, returns its y
+pub fn insert_keyset_proof(
+    conn: &Connection,
+    node_id: u32,
+    keyset_id: KeysetId,
+    amount: u64,
+    state: ProofState,
+) -> PublicKey {
+    let secret = Secret::generate();
+    let y = hash_to_curve(secret.as_bytes()).unwrap();
+    conn.execute(
+        r#"INSERT INTO proof (y, node_id, keyset_id, amount, secret, unblind_signature, state)
+           VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7);"#,
+        params![
+            y,
+            node_id,
+            keyset_id,
+            amount,
+            secret,
+            SecretKey::generate().public_key(),
+            state
+        ],
+    )
+    .unwrap();
+
+    y
+}
+
+/// Override the value set by the insert trigger
+pub fn set_state_updated_at(conn: &Connection, y: PublicKey, updated_at: u64) {
+    conn.execute(
+        "UPDATE proof SET state_updated_at = ?2 WHERE y = ?1;",
+        params![y, updated_at],
+    )
+    .unwrap();
+}
@@ -35,6 +35,36 @@ pub const CREATE_TABLE_WAD_PROOF: &str = r#"
         );
     "#;
 
+/// What a proof linked to a wad was used for
+///
+/// Stored in `wad_proof.role`, added by the `add_wad_proof_role` migration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WadProofRole {
+    /// Carried by the wad: sent away for an `OUT` wad, swapped by the node for an `IN` wad
+    Input,
+    /// Created by the swap that received an `IN` wad
+    Output,
+}
+
+impl ToSql for WadProofRole {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        match self {
+            WadProofRole::Input => Ok(ToSqlOutput::from("INPUT")),
+            WadProofRole::Output => Ok(ToSqlOutput::from("OUTPUT")),
+        }
+    }
+}
+
+impl FromSql for WadProofRole {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "INPUT" => Ok(WadProofRole::Input),
+            "OUTPUT" => Ok(WadProofRole::Output),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WadType {
     IN,
@@ -171,18 +201,31 @@ pub fn register_wad(
         now,
     ])?;
 
-    // Insert WAD-proof relationships
+    link_proofs(conn, wad_id, WadProofRole::Input, proof_ys)?;
+
+    Ok(wad_id)
+}
+
+/// Link `proof_ys` to the wad `wad_id`
+///
+/// The proofs must already exist in the `proof` table.
+pub fn link_proofs(
+    conn: &Connection,
+    wad_id: Uuid,
+    role: WadProofRole,
+    proof_ys: &[PublicKey],
+) -> Result<()> {
     const INSERT_WAD_PROOF: &str = r#"
-        INSERT INTO wad_proof (wad_id, proof_y)
-        VALUES (?1, ?2)
+        INSERT INTO wad_proof (wad_id, proof_y, role)
+        VALUES (?1, ?2, ?3)
         ON CONFLICT DO NOTHING;
     "#;
     let mut stmt = conn.prepare(INSERT_WAD_PROOF)?;
     for proof_y in proof_ys {
-        stmt.execute(params![wad_id, proof_y])?;
+        stmt.execute(params![wad_id, proof_y, role])?;
     }
 
-    Ok(wad_id)
+    Ok(())
 }
 
 fn parse_wad_record(row: &rusqlite::Row) -> rusqlite::Result<WadRecord> {
@@ -265,12 +308,15 @@ pub(crate) fn get_pending_wads(conn: &Connection) -> Result<Vec<SyncData>> {
     rows.collect::<Result<Vec<_>, _>>()
 }
 
+/// The ys of the proofs carried by the wad
+///
+/// Proofs created when receiving it are not returned, see [`get_proofs_for_wad`].
 pub fn get_proofs_ys_by_id(conn: &Connection, wad_id: Uuid) -> Result<Vec<PublicKey>> {
     const GET_WAD_PROOFS: &str = r#"
-        SELECT proof_y FROM wad_proof WHERE wad_id = ?1
+        SELECT proof_y FROM wad_proof WHERE wad_id = ?1 AND role = ?2
     "#;
     let mut stmt = conn.prepare(GET_WAD_PROOFS)?;
-    let rows = stmt.query_map([wad_id], |row| {
+    let rows = stmt.query_map(params![wad_id, WadProofRole::Input], |row| {
         let y_bytes: Vec<u8> = row.get(0)?;
         PublicKey::from_slice(&y_bytes).map_err(|e| {
             rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Blob, Box::new(e))
@@ -280,6 +326,20 @@ pub fn get_proofs_ys_by_id(conn: &Connection, wad_id: Uuid) -> Result<Vec<Public
     rows.collect::<Result<Vec<_>, _>>()
 }
 
+/// Every proof linked to the wad, along with its role
+pub fn get_proofs_for_wad(
+    conn: &Connection,
+    wad_id: Uuid,
+) -> Result<Vec<(PublicKey, WadProofRole)>> {
+    const GET_WAD_PROOFS_WITH_ROLE: &str = r#"
+        SELECT proof_y, role FROM wad_proof WHERE wad_id = ?1
+    "#;
+    let mut stmt = conn.prepare(GET_WAD_PROOFS_WITH_ROLE)?;
+    let rows = stmt.query_map([wad_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+}
+
 pub fn get_amounts_by_id<U: FromStr>(
     conn: &Connection,
     wad_id: Uuid,
@@ -289,11 +349,11 @@ pub fn get_amounts_by_id<U: FromStr>(
         FROM wad_proof wp
         JOIN proof p ON wp.proof_y = p.y
         JOIN keyset k ON p.keyset_id = k.id
-        WHERE wp.wad_id = ?1
+        WHERE wp.wad_id = ?1 AND wp.role = ?2
         GROUP BY k.unit
     "#;
     let mut stmt = conn.prepare(GET_WAD_UNIT_AMOUNTS)?;
-    let rows = stmt.query_map([wad_id], |row| {
+    let rows = stmt.query_map(params![wad_id, WadProofRole::Input], |row| {
         let unit: String = row.get(0)?;
         let amount: Amount = row.get(1)?;
         Ok((unit, amount))
@@ -301,3 +361,51 @@ pub fn get_amounts_by_id<U: FromStr>(
 
     rows.collect::<Result<Vec<_>, _>>()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{self, test_utils},
+        types::ProofState,
+    };
+
+    fn insert_proof(conn: &Connection, node_id: u32, state: ProofState) -> PublicKey {
+        test_utils::insert_proof(conn, node_id, 8, state)
+    }
+
+    #[test]
+    fn send_wad_links_spent_proofs_as_inputs() {
+        let (conn, node_id) = test_utils::setup_node("sat");
+        let spent = [
+            insert_proof(&conn, node_id, ProofState::Reserved),
+            insert_proof(&conn, node_id, ProofState::Reserved),
+        ];
+        insert_proof(&conn, node_id, ProofState::Unspent);
+        let node_url = db::node::get_url_by_id(&conn, node_id).unwrap().unwrap();
+
+        let wad_id = register_wad(&conn, WadType::OUT, &node_url, &None, &spent).unwrap();
+
+        let mut linked = get_proofs_for_wad(&conn, wad_id).unwrap();
+        linked.sort_by_key(|(y, _)| *y);
+        let mut expected: Vec<_> = spent.iter().map(|y| (*y, WadProofRole::Input)).collect();
+        expected.sort_by_key(|(y, _)| *y);
+        assert_eq!(linked, expected);
+    }
+
+    #[test]
+    fn receive_outputs_are_not_carried_by_the_wad() {
+        let (conn, node_id) = test_utils::setup_node("sat");
+        let received = insert_proof(&conn, node_id, ProofState::Spent);
+        let swapped = insert_proof(&conn, node_id, ProofState::Unspent);
+        let node_url = db::node::get_url_by_id(&conn, node_id).unwrap().unwrap();
+
+        let wad_id = register_wad(&conn, WadType::IN, &node_url, &None, &[received]).unwrap();
+        link_proofs(&conn, wad_id, WadProofRole::Output, &[swapped]).unwrap();
+
+        let linked = get_proofs_for_wad(&conn, wad_id).unwrap();
+        assert_eq!(linked.len(), 2);
+        assert!(linked.contains(&(swapped, WadProofRole::Output)));
+        assert_eq!(get_proofs_ys_by_id(&conn, wad_id).unwrap(), vec![received]);
+    }
+}
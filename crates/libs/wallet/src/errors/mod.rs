@@ -1,5 +1,5 @@
 use node_client::{NodeClient, UnspecifiedEnum};
-use nuts::nut01::PublicKey;
+use nuts::{Amount, nut01::PublicKey, nut02::KeysetId};
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
 use rusqlite::Connection;
@@ -37,8 +37,8 @@ pub enum Error {
     Grpc(#[from] Status),
     #[error("protocol error: {0}")]
     Protocol(String),
-    #[error("not enough funds")]
-    NotEnoughFunds,
+    #[error("not enough funds, required: {required}, available: {available}")]
+    InsufficientFunds { required: Amount, available: Amount },
     #[error("nut01 error: {0}")]
     Nut01(#[from] nuts::nut01::Error),
     #[error("nut02 error: {0}")]
@@ -73,6 +73,18 @@ pub enum Error {
     ParseError(#[from] std::num::ParseIntError),
     #[error("fail to refresh node keyset: {0}")]
     RefreshNodeKeyset(#[from] RefreshNodeKeysetError),
+    #[error("unknown node id: {0}")]
+    UnknownNode(u32),
+    #[error("invalid split target: {0}")]
+    InvalidSplitTarget(String),
+    #[error("keyset {0} is the active one for its unit, there is nowhere to migrate its proofs")]
+    KeysetStillActive(KeysetId),
+    #[error("cannot send a zero amount")]
+    ZeroAmountSend,
+    #[error("cannot create a wad without any proof")]
+    EmptyWad,
+    #[error("swapping {amount} cannot pay for the {fee} fee and the expected outputs")]
+    InputFeeNotCovered { amount: Amount, fee: Amount },
 }
 
 impl From<StoreNewProofsError> for Error {
@@ -1,8 +1,10 @@
+pub mod audit;
 pub mod db;
 pub mod errors;
 pub mod melt;
 pub mod mint;
 pub mod node;
+mod operation_log;
 mod outputs;
 pub mod seed_phrase;
 pub mod send;
@@ -12,8 +14,10 @@ pub mod wad;
 pub mod wallet;
 
 use errors::{Error, handle_out_of_sync_keyset_errors, handle_proof_verification_errors};
+use futures::{StreamExt, TryStreamExt};
+use node_client::trace_context::with_trace_context;
 use node_client::{AcknowledgeRequest, NodeClient, hash_swap_request};
-use num_traits::{CheckedAdd, Zero};
+use num_traits::{CheckedAdd, CheckedSub, Zero};
 use nuts::dhke::{self, hash_to_curve, unblind_message};
 use nuts::nut00::secret::Secret;
 use nuts::nut00::{self, BlindedMessage, Proof};
@@ -24,6 +28,7 @@ use nuts::{Amount, SplitTarget};
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
 use rusqlite::{Connection, Transaction, params};
+use std::collections::HashMap;
 use std::str::FromStr;
 use tonic::Request;
 use tonic::transport::Channel;
@@ -54,46 +59,117 @@ pub fn convert_outputs(outputs: &[BlindedMessage]) -> Vec<node_client::BlindedMe
         .collect()
 }
 
-pub async fn read_or_import_node_keyset(
+/// How many `keys` requests are in flight at once when importing the keysets of a wad
+const MAX_CONCURRENT_KEYSET_FETCHES: usize = 4;
+
+/// Return the unit, largest denomination and input fee of each keyset, importing the unknown ones from the node
+///
+/// Duplicated ids are only looked up once and keysets already in db never hit the network.
+/// The missing ones are fetched concurrently, so a wad spanning many keysets
+/// doesn't pay for one round trip per keyset.
+pub async fn read_or_import_node_keysets(
     pool: Pool<SqliteConnectionManager>,
     node_client: &mut NodeClient<Channel>,
     node_id: u32,
-    keyset_id: KeysetId,
-) -> Result<(String, u64), Error> {
+    keyset_ids: impl IntoIterator<Item = KeysetId>,
+) -> Result<HashMap<KeysetId, (String, u64, u64)>, Error> {
+    load_or_fetch_keysets(pool, node_id, keyset_ids, |keyset_id| {
+        let mut node_client = node_client.clone();
+        async move {
+            let resp = node_client
+                .keys(node_client::GetKeysRequest {
+                    keyset_id: Some(keyset_id.to_bytes().to_vec()),
+                })
+                .await?
+                .into_inner();
+
+            resp.keysets.into_iter().next().ok_or_else(|| {
+                Error::Protocol(format!("node returned no keys for keyset {}", keyset_id))
+            })
+        }
+    })
+    .await
+}
+
+async fn load_or_fetch_keysets<F, Fut>(
+    pool: Pool<SqliteConnectionManager>,
+    node_id: u32,
+    keyset_ids: impl IntoIterator<Item = KeysetId>,
+    mut fetch_keyset: F,
+) -> Result<HashMap<KeysetId, (String, u64, u64)>, Error>
+where
+    F: FnMut(KeysetId) -> Fut,
+    Fut: Future<Output = Result<node_client::KeysetKeys, Error>>,
+{
+    let mut keysets = HashMap::new();
+    let mut missing_keyset_ids = Vec::new();
+
     // Happy path, it is in DB
     {
         let db_conn = pool.get()?;
-        if let Some(unit) = db::keyset::get_unit_by_id(&db_conn, keyset_id)? {
-            // Should be safe to unwrap unless someone manually tamper with the database to remove keys
-            let max_order = db::proof::get_max_order_for_keyset(&db_conn, keyset_id)?.unwrap();
-            return Ok((unit, max_order));
+        for keyset_id in keyset_ids {
+            if keysets.contains_key(&keyset_id) || missing_keyset_ids.contains(&keyset_id) {
+                continue;
+            }
+            match db::keyset::get_unit_by_id(&db_conn, keyset_id)? {
+                Some(unit) => {
+                    // Should be safe to unwrap unless someone manually tamper with the database to remove keys
+                    let max_denomination =
+                        db::proof::get_max_denomination_for_keyset(&db_conn, keyset_id)?.unwrap();
+                    let input_fee_ppk =
+                        db::keyset::get_input_fee_ppk(&db_conn, keyset_id)?.unwrap_or_default();
+                    keysets.insert(keyset_id, (unit, max_denomination, input_fee_ppk));
+                }
+                None => missing_keyset_ids.push(keyset_id),
+            }
         }
     }
 
-    let keyset_id_as_bytes = keyset_id.to_bytes();
+    if missing_keyset_ids.is_empty() {
+        return Ok(keysets);
+    }
 
-    let resp = node_client
-        .keys(node_client::GetKeysRequest {
-            keyset_id: Some(keyset_id_as_bytes.to_vec()),
-        })
-        .await?
-        .into_inner();
-    let keyset = resp.keysets.first().unwrap();
-    let max_order = keyset.keys.iter().map(|k| k.amount).max().unwrap();
-
-    let db_conn = pool.get()?;
-    db_conn.execute(
-        "INSERT INTO keyset (id, node_id, unit, active) VALUES (?1, ?2, ?3, ?4)",
-        params![keyset_id_as_bytes, node_id, &keyset.unit, keyset.active],
-    )?;
+    let fetched_keysets: Vec<(KeysetId, node_client::KeysetKeys)> =
+        futures::stream::iter(missing_keyset_ids)
+            .map(|keyset_id| {
+                let fetch = fetch_keyset(keyset_id);
+                async move { fetch.await.map(|keyset| (keyset_id, keyset)) }
+            })
+            .buffer_unordered(MAX_CONCURRENT_KEYSET_FETCHES)
+            .try_collect()
+            .await?;
+
+    let mut db_conn = pool.get()?;
+    let tx = db_conn.transaction()?;
+    for (keyset_id, keyset) in fetched_keysets {
+        let max_denomination = keyset.keys.iter().map(|k| k.amount).max().ok_or_else(|| {
+            Error::Protocol(format!("node returned no keys for keyset {}", keyset_id))
+        })?;
 
-    db::insert_keyset_keys(
-        &db_conn,
-        keyset_id,
-        keyset.keys.iter().map(|k| (k.amount, k.pubkey.as_str())),
-    )?;
+        tx.execute(
+            "INSERT INTO keyset (id, node_id, unit, active, input_fee_ppk) VALUES (?1, ?2, ?3, ?4, ?5) ON CONFLICT DO NOTHING",
+            params![
+                keyset_id.to_bytes(),
+                node_id,
+                &keyset.unit,
+                keyset.active,
+                keyset.input_fee_ppk
+            ],
+        )?;
+        db::insert_keyset_keys(
+            &tx,
+            keyset_id,
+            keyset.keys.iter().map(|k| (k.amount, k.pubkey.as_str())),
+        )?;
+
+        keysets.insert(
+            keyset_id,
+            (keyset.unit, max_denomination, keyset.input_fee_ppk),
+        );
+    }
+    tx.commit()?;
 
-    Ok((keyset.unit.clone(), max_order))
+    Ok(keysets)
 }
 
 pub fn get_active_keyset_for_unit(
@@ -170,65 +246,168 @@ pub fn store_new_proofs_from_blind_signatures(
 
         new_tokens.push((y, amount));
     }
+    if !new_tokens.is_empty() && db::balance_events::has_listeners() {
+        db::balance_events::record(
+            tx,
+            db::balance_events::balance_of_keyset(tx, node_id, keyset_id)?,
+        );
+    }
 
     Ok(new_tokens)
 }
 
-pub async fn fetch_inputs_ids_from_db_or_node(
-    seed_phrase_manager: impl SeedPhraseManager,
-    pool: Pool<SqliteConnectionManager>,
-    node_client: &mut NodeClient<Channel>,
+/// Proofs picked from the database to reach a target amount
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputsSelection {
+    /// Proofs whose amounts sum up to `target_amount - remaining_amount`
+    pub proofs_ids: Vec<PublicKey>,
+    /// When no combination of stored proofs reaches the exact amount,
+    /// this one must be swapped to produce the missing `remaining_amount`
+    pub proof_to_swap: Option<(PublicKey, Amount)>,
+    pub remaining_amount: Amount,
+    /// Deactivated keysets some of `proofs_ids`, or `proof_to_swap`, belong to
+    ///
+    /// The node may refuse to spend them, they should be migrated to the active keyset first.
+    pub inactive_keysets: Vec<KeysetId>,
+}
+
+/// Pick the proofs to use to send `target_amount`, without modifying their state
+///
+/// Proofs of the active keysets are used first.
+/// Returns `None` if the node doesn't hold enough funds.
+pub fn select_inputs(
+    db_conn: &Connection,
     node_id: u32,
     target_amount: Amount,
     unit: &str,
-) -> Result<Option<Vec<PublicKey>>, Error> {
+) -> Result<Option<InputsSelection>, Error> {
     let mut proofs_ids = Vec::new();
     let mut proofs_not_used = Vec::new();
     let mut remaining_amount = target_amount;
 
-    {
-        let db_conn = pool.get()?;
-        let total_amount_available =
-            db::proof::get_node_total_available_amount_of_unit(&db_conn, node_id, unit)?;
+    let total_amount_available =
+        db::proof::get_node_total_available_amount_of_unit(db_conn, node_id, unit)?;
 
-        if total_amount_available < target_amount {
-            return Ok(None);
-        }
-
-        let mut stmt = db_conn.prepare(
-            "SELECT y, amount FROM proof WHERE node_id = ?1 AND state = ?2 ORDER BY amount DESC;",
-        )?;
-        let proofs_res_iterator = stmt.query_map(params![node_id, ProofState::Unspent], |r| {
-            Ok((r.get::<_, PublicKey>(0)?, r.get::<_, Amount>(1)?))
-        })?;
+    if total_amount_available < target_amount {
+        return Ok(None);
+    }
 
-        for proof_res in proofs_res_iterator {
-            let (y, proof_amount) = proof_res?;
-            match remaining_amount.cmp(&proof_amount) {
-                std::cmp::Ordering::Less => proofs_not_used.push((y, proof_amount)),
-                std::cmp::Ordering::Equal => {
-                    proofs_ids.push(y);
-                    remaining_amount -= proof_amount;
-                    break;
-                }
-                std::cmp::Ordering::Greater => {
-                    proofs_ids.push(y);
-                    remaining_amount -= proof_amount;
-                }
+    let mut stmt = db_conn.prepare(
+        r#"SELECT p.y, p.amount, p.keyset_id, k.active FROM proof p
+           JOIN keyset k ON p.keyset_id = k.id
+           WHERE p.node_id = ?1 AND p.state = ?2 AND k.unit = ?3
+           ORDER BY k.active DESC, p.amount DESC;"#,
+    )?;
+    let proofs_res_iterator = stmt.query_map(params![node_id, ProofState::Unspent, unit], |r| {
+        Ok((
+            r.get::<_, PublicKey>(0)?,
+            r.get::<_, Amount>(1)?,
+            r.get::<_, KeysetId>(2)?,
+            r.get::<_, bool>(3)?,
+        ))
+    })?;
+
+    let mut inactive_keysets = Vec::new();
+    for proof_res in proofs_res_iterator {
+        let (y, proof_amount, keyset_id, active) = proof_res?;
+        if remaining_amount >= proof_amount && !active && !inactive_keysets.contains(&keyset_id) {
+            inactive_keysets.push(keyset_id);
+        }
+        match remaining_amount.cmp(&proof_amount) {
+            std::cmp::Ordering::Less => proofs_not_used.push((y, proof_amount, keyset_id, active)),
+            std::cmp::Ordering::Equal => {
+                proofs_ids.push(y);
+                remaining_amount -= proof_amount;
+                break;
+            }
+            std::cmp::Ordering::Greater => {
+                proofs_ids.push(y);
+                remaining_amount -= proof_amount;
             }
         }
     }
 
-    if !remaining_amount.is_zero() {
-        let proof_to_swap = proofs_not_used
-            .iter()
-            .rev()
-            .find(|(_, a)| a > &remaining_amount)
+    let proof_to_swap = if remaining_amount.is_zero() {
+        None
+    } else {
+        // The smallest proof big enough, preferably of an active keyset
+        let (y, amount, keyset_id, active) = proofs_not_used
+            .into_iter()
+            .filter(|(_, a, _, _)| a > &remaining_amount)
+            .min_by_key(|(_, a, _, active)| (!active, *a))
             // We know that total_amount_available was >= target_amount
             // We know it cannot be equal to remaining amount otherwise we would have subtracted it
             // So there must be one greater stored in proofs_not_used
             .unwrap();
+        if !active && !inactive_keysets.contains(&keyset_id) {
+            inactive_keysets.push(keyset_id);
+        }
+        Some((y, amount))
+    };
+
+    Ok(Some(InputsSelection {
+        proofs_ids,
+        proof_to_swap,
+        remaining_amount,
+        inactive_keysets,
+    }))
+}
+
+/// Same as `select_inputs`, but reports how short the node is when it doesn't hold enough funds
+pub fn select_inputs_or_insufficient_funds(
+    db_conn: &Connection,
+    node_id: u32,
+    target_amount: Amount,
+    unit: &str,
+) -> Result<InputsSelection, Error> {
+    if target_amount == Amount::ZERO {
+        return Err(Error::ZeroAmountSend);
+    }
 
+    match select_inputs(db_conn, node_id, target_amount, unit)? {
+        Some(selection) => Ok(selection),
+        None => Err(Error::InsufficientFunds {
+            required: target_amount,
+            available: db::proof::get_node_total_available_amount_of_unit(db_conn, node_id, unit)?,
+        }),
+    }
+}
+
+pub async fn fetch_inputs_ids_from_db_or_node(
+    seed_phrase_manager: impl SeedPhraseManager,
+    pool: Pool<SqliteConnectionManager>,
+    node_client: &mut NodeClient<Channel>,
+    node_id: u32,
+    target_amount: Amount,
+    unit: &str,
+) -> Result<Vec<PublicKey>, Error> {
+    let mut selection = {
+        let db_conn = pool.get()?;
+        select_inputs_or_insufficient_funds(&db_conn, node_id, target_amount, unit)?
+    };
+    if !selection.inactive_keysets.is_empty() {
+        for keyset_id in selection.inactive_keysets {
+            migrate_keyset(
+                &seed_phrase_manager,
+                pool.clone(),
+                node_client,
+                node_id,
+                keyset_id,
+            )
+            .await?;
+        }
+        // The migrated proofs have been replaced by new ones, of the same total value
+        let db_conn = pool.get()?;
+        selection = select_inputs_or_insufficient_funds(&db_conn, node_id, target_amount, unit)?;
+    }
+    let InputsSelection {
+        mut proofs_ids,
+        proof_to_swap,
+        mut remaining_amount,
+        ..
+    } = selection;
+
+    if let Some(proof_to_swap) = proof_to_swap {
         let new_tokens = swap_to_have_target_amount(
             seed_phrase_manager,
             pool.clone(),
@@ -236,7 +415,7 @@ pub async fn fetch_inputs_ids_from_db_or_node(
             node_id,
             unit,
             remaining_amount,
-            proof_to_swap,
+            &proof_to_swap,
         )
         .await?;
 
@@ -256,7 +435,7 @@ pub async fn fetch_inputs_ids_from_db_or_node(
         }
     }
 
-    Ok(Some(proofs_ids))
+    Ok(proofs_ids)
 }
 
 pub fn load_tokens_from_db(
@@ -295,7 +474,7 @@ pub async fn swap_to_have_target_amount(
     target_amount: Amount,
     proof_to_swap: &(PublicKey, Amount),
 ) -> Result<Vec<(PublicKey, Amount)>, Error> {
-    let (blinding_data, input_unblind_signature) = {
+    let (blinding_data, input_unblind_signature, output_amount) = {
         let db_conn = pool.get()?;
 
         let blinding_data =
@@ -304,12 +483,25 @@ pub async fn swap_to_have_target_amount(
         let input_unblind_signature =
             db::proof::get_proof_and_set_state_pending(&db_conn, proof_to_swap.0)?
                 .ok_or(Error::ProofNotAvailable)?;
+        let input_fee_ppk = db::keyset::get_input_fee_ppk(&db_conn, input_unblind_signature.0)?
+            .ok_or(Error::NoMatchingKeyset)?;
+        let output_amount = match amount_after_input_fee(
+            proof_to_swap.1,
+            send::compute_input_fee(1, input_fee_ppk),
+            target_amount,
+        ) {
+            Ok(output_amount) => output_amount,
+            Err(e) => {
+                db::proof::set_proof_to_state(&db_conn, proof_to_swap.0, ProofState::Unspent)?;
+                return Err(e);
+            }
+        };
 
-        (blinding_data, input_unblind_signature)
+        (blinding_data, input_unblind_signature, output_amount)
     };
 
     let pre_mints = PreMints::generate_for_amount(
-        proof_to_swap.1,
+        output_amount,
         &SplitTarget::Value(target_amount),
         blinding_data,
     )?;
@@ -325,7 +517,7 @@ pub async fn swap_to_have_target_amount(
 
     let swap_request = node_client::SwapRequest { inputs, outputs };
     let swap_request_hash = hash_swap_request(&swap_request);
-    let swap_result = node_client.swap(swap_request).await;
+    let swap_result = node_client.swap(with_trace_context(swap_request)).await;
 
     let new_tokens = {
         let mut db_conn = pool.get()?;
@@ -351,82 +543,327 @@ pub async fn swap_to_have_target_amount(
 
     acknowledge(node_client, nuts::nut19::Route::Swap, swap_request_hash).await?;
 
+    operation_log::completed(
+        operation_log::Operation::Swap,
+        node_id,
+        unit,
+        proof_to_swap.1,
+    );
+
     Ok(new_tokens)
 }
 
-#[allow(clippy::too_many_arguments)]
-pub async fn receive_wad(
+/// Max number of proofs moved by a single swap of [`migrate_keyset`]
+///
+/// Keeps each request, and the work asked to the node, bounded whatever the size of the keyset.
+const MAX_INPUTS_PER_MIGRATION_SWAP: usize = 64;
+
+/// Swap every unspent proof of `from_keyset` into the active keyset of the same unit
+///
+/// Nodes stop honoring the proofs of a deprecated keyset at some point,
+/// moving them while it still does keeps those funds spendable.
+/// The proofs are moved in batches of at most [`MAX_INPUTS_PER_MIGRATION_SWAP`].
+/// Returns the migrated amount, fees deducted, zero if `from_keyset` held no unspent proof.
+pub async fn migrate_keyset(
     seed_phrase_manager: impl SeedPhraseManager,
     pool: Pool<SqliteConnectionManager>,
     node_client: &mut NodeClient<Channel>,
     node_id: u32,
-    node_url: &NodeUrl,
-    unit: &str,
-    compact_keyset_proofs: Vec<CompactKeysetProofs>,
-    memo: &Option<String>,
+    from_keyset: KeysetId,
 ) -> Result<Amount, Error> {
-    const INSERT_PROOF: &str = r#"
-        INSERT INTO proof
-            (y, node_id, keyset_id, amount, secret, unblind_signature, state)
-        VALUES
-            (?1, ?2, ?3, ?4, ?5, ?6, ?7)
-        ON CONFLICT DO UPDATE
-            SET state = excluded.state
-    "#;
-    let mut ys = Vec::with_capacity(compact_keyset_proofs.len());
-    let mut total_amount = Amount::ZERO;
-    let mut inputs = Vec::with_capacity(compact_keyset_proofs.len());
-    let mut stmt_params = Vec::with_capacity(compact_keyset_proofs.len());
-
-    for compact_keyset_proof in compact_keyset_proofs.into_iter() {
-        let (keyset_unit, max_order) = read_or_import_node_keyset(
+    let mut migrated_amount = Amount::ZERO;
+    loop {
+        let client = &mut *node_client;
+        let (amount, swap_request_hash) = swap_keyset_proofs(
+            &seed_phrase_manager,
             pool.clone(),
-            node_client,
             node_id,
-            compact_keyset_proof.keyset_id,
+            from_keyset,
+            move |swap_request| async move { client.swap(with_trace_context(swap_request)).await },
         )
         .await?;
-        if keyset_unit != unit {
-            return Err(Error::UnitMissmatch(keyset_unit, unit.to_string()));
+
+        let Some(swap_request_hash) = swap_request_hash else {
+            break;
+        };
+        acknowledge(node_client, nuts::nut19::Route::Swap, swap_request_hash).await?;
+        migrated_amount = migrated_amount
+            .checked_add(&amount)
+            .ok_or(Error::AmountOverflow)?;
+    }
+
+    Ok(migrated_amount)
+}
+
+/// Body of [`migrate_keyset`], with the swap rpc injected
+///
+/// Swaps a single batch of proofs.
+/// Returns the amount received in the active keyset, once the input fee is paid,
+/// and the hash of the swap request to acknowledge, `None` if no swap happened.
+/// If the swap fails, the proofs the node didn't report as invalid or spent are spendable again.
+async fn swap_keyset_proofs<F, Fut>(
+    seed_phrase_manager: impl SeedPhraseManager,
+    pool: Pool<SqliteConnectionManager>,
+    node_id: u32,
+    from_keyset: KeysetId,
+    swap: F,
+) -> Result<(Amount, Option<u64>), Error>
+where
+    F: FnOnce(node_client::SwapRequest) -> Fut,
+    Fut: Future<Output = Result<tonic::Response<node_client::SwapResponse>, tonic::Status>>,
+{
+    let (proofs, blinding_data, input_fee_ppk) = {
+        let mut db_conn = pool.get()?;
+        let tx = db_conn.transaction()?;
+
+        let unit = db::keyset::get_unit_by_id(&tx, from_keyset)?.ok_or(Error::NoMatchingKeyset)?;
+        let input_fee_ppk =
+            db::keyset::get_input_fee_ppk(&tx, from_keyset)?.ok_or(Error::NoMatchingKeyset)?;
+        let blinding_data = BlindingData::load_from_db(seed_phrase_manager, &tx, node_id, &unit)?;
+        if blinding_data.keyset_id() == from_keyset {
+            return Err(Error::KeysetStillActive(from_keyset));
+        }
+
+        let mut proofs = db::proof::get_unspent_by_keyset(&tx, node_id, from_keyset)?;
+        if proofs.is_empty() {
+            return Ok((Amount::ZERO, None));
+        }
+        proofs.truncate(MAX_INPUTS_PER_MIGRATION_SWAP);
+        let ys: Vec<PublicKey> = proofs.iter().map(|(y, _, _, _)| *y).collect();
+        db::proof::set_proofs_to_state(&tx, &ys, ProofState::Pending)?;
+        tx.commit()?;
+
+        (proofs, blinding_data, input_fee_ppk)
+    };
+
+    let mut ys = Vec::with_capacity(proofs.len());
+    let mut inputs = Vec::with_capacity(proofs.len());
+    let mut total_amount = Amount::ZERO;
+    for (y, amount, secret, unblind_signature) in proofs {
+        total_amount = total_amount
+            .checked_add(&amount)
+            .ok_or(Error::AmountOverflow)?;
+        ys.push(y);
+        inputs.push(node_client::Proof {
+            amount: amount.into(),
+            keyset_id: from_keyset.to_bytes().to_vec(),
+            secret: secret.to_string(),
+            unblind_signature: unblind_signature.to_bytes().to_vec(),
+        });
+    }
+
+    let input_fee = send::compute_input_fee(inputs.len(), input_fee_ppk);
+    let output_amount = match amount_after_input_fee(total_amount, input_fee, Amount::ONE) {
+        Ok(output_amount) => output_amount,
+        Err(e) => {
+            let db_conn = pool.get()?;
+            db::proof::set_proofs_in_state_to_state(
+                &db_conn,
+                &ys,
+                ProofState::Pending,
+                ProofState::Unspent,
+            )?;
+            return Err(e);
+        }
+    };
+    let pre_mints =
+        PreMints::generate_for_amount(output_amount, &SplitTarget::None, blinding_data)?;
+    let outputs = pre_mints.build_node_client_outputs();
+
+    let swap_request = node_client::SwapRequest { inputs, outputs };
+    let swap_request_hash = hash_swap_request(&swap_request);
+    let swap_result = swap(swap_request).await;
+
+    let mut db_conn = pool.get()?;
+    let swap_response = match swap_result {
+        Ok(r) => r.into_inner(),
+        Err(e) => {
+            handle_proof_verification_errors(&e, &ys, &db_conn)?;
+            db::proof::set_proofs_in_state_to_state(
+                &db_conn,
+                &ys,
+                ProofState::Pending,
+                ProofState::Unspent,
+            )?;
+            return Err(e.into());
+        }
+    };
+
+    let tx = db_conn.transaction()?;
+    db::proof::set_proofs_to_state(&tx, &ys, ProofState::Spent)?;
+    pre_mints.store_new_tokens(&tx, node_id, swap_response.signatures)?;
+    tx.commit()?;
+
+    Ok((output_amount, Some(swap_request_hash)))
+}
+
+/// What is left of `inputs_amount` for the swap outputs once `input_fee` is paid
+///
+/// Errors if that is less than `min_output_amount`.
+fn amount_after_input_fee(
+    inputs_amount: Amount,
+    input_fee: Amount,
+    min_output_amount: Amount,
+) -> Result<Amount, Error> {
+    inputs_amount
+        .checked_sub(&input_fee)
+        .filter(|output_amount| *output_amount >= min_output_amount)
+        .ok_or(Error::InputFeeNotCovered {
+            amount: inputs_amount,
+            fee: input_fee,
+        })
+}
+
+/// Row inserted in the `proof` table for a proof received from a wad
+type ReceivedProofRow = (
+    PublicKey,
+    u32,
+    KeysetId,
+    Amount,
+    Secret,
+    PublicKey,
+    ProofState,
+);
+
+/// Proofs of a wad, validated and ready to be stored and swapped
+///
+/// `y` is computed once per proof here, then shared by the db rows and the swap inputs.
+/// It's the most expensive part of reading a wad, large wads should not pay for it twice.
+struct ReceivedProofs {
+    ys: Vec<PublicKey>,
+    total_amount: Amount,
+    /// Sum of the NUT-02 fee of each proof, in parts per thousand
+    total_input_fee_ppk: u64,
+    inputs: Vec<node_client::Proof>,
+    rows: Vec<ReceivedProofRow>,
+}
+
+impl ReceivedProofs {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            ys: Vec::with_capacity(capacity),
+            total_amount: Amount::ZERO,
+            total_input_fee_ppk: 0,
+            inputs: Vec::with_capacity(capacity),
+            rows: Vec::with_capacity(capacity),
         }
+    }
 
-        for compact_proof in compact_keyset_proof.proofs.into_iter() {
+    fn push_keyset_proofs(
+        &mut self,
+        node_id: u32,
+        compact_keyset_proofs: CompactKeysetProofs,
+        max_denomination: u64,
+        input_fee_ppk: u64,
+        mut hash_to_curve: impl FnMut(&[u8]) -> Result<PublicKey, dhke::Error>,
+    ) -> Result<(), Error> {
+        let keyset_id = compact_keyset_proofs.keyset_id;
+
+        for compact_proof in compact_keyset_proofs.proofs.into_iter() {
             let amount = u64::from(compact_proof.amount);
             if !amount.is_power_of_two() || amount == 0 {
-                return Err(Error::Protocol(
-                    "All proof amounts must be powers of two".to_string(),
-                ));
+                return Err(Error::Protocol(format!(
+                    "All proof amounts must be powers of two, got {} for a proof of keyset {}",
+                    amount, keyset_id
+                )));
             }
-            if amount >= max_order {
+            if amount > max_denomination {
                 return Err(Error::Protocol(format!(
-                    "Proof amount {} is not less than max_order {} for keyset {}",
-                    amount, max_order, compact_keyset_proof.keyset_id
+                    "Proof amount {} is greater than the max denomination {} of keyset {}",
+                    amount, max_denomination, keyset_id
                 )));
             }
             let y = hash_to_curve(compact_proof.secret.as_ref())?;
-            ys.push(y);
+            self.ys.push(y);
 
-            total_amount = total_amount
+            self.total_amount = self
+                .total_amount
                 .checked_add(&compact_proof.amount)
                 .ok_or(Error::AmountOverflow)?;
+            self.total_input_fee_ppk = self
+                .total_input_fee_ppk
+                .checked_add(input_fee_ppk)
+                .ok_or(Error::AmountOverflow)?;
 
-            inputs.push(node_client::Proof {
+            self.inputs.push(node_client::Proof {
                 amount,
-                keyset_id: compact_keyset_proof.keyset_id.to_bytes().to_vec(),
+                keyset_id: keyset_id.to_bytes().to_vec(),
                 secret: compact_proof.secret.to_string(),
                 unblind_signature: compact_proof.c.to_bytes().to_vec(),
             });
-            stmt_params.push((
+            self.rows.push((
                 y,
                 node_id,
-                compact_keyset_proof.keyset_id,
+                keyset_id,
                 compact_proof.amount,
                 compact_proof.secret,
                 compact_proof.c,
                 ProofState::Pending,
             ));
         }
+
+        Ok(())
     }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn receive_wad(
+    seed_phrase_manager: impl SeedPhraseManager,
+    pool: Pool<SqliteConnectionManager>,
+    node_client: &mut NodeClient<Channel>,
+    node_id: u32,
+    node_url: &NodeUrl,
+    unit: &str,
+    compact_keyset_proofs: Vec<CompactKeysetProofs>,
+    memo: &Option<String>,
+) -> Result<Amount, Error> {
+    const INSERT_PROOF: &str = r#"
+        INSERT INTO proof
+            (y, node_id, keyset_id, amount, secret, unblind_signature, state)
+        VALUES
+            (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+        ON CONFLICT DO UPDATE
+            SET state = excluded.state
+    "#;
+    let mut received = ReceivedProofs::with_capacity(compact_keyset_proofs.len());
+
+    let keysets = read_or_import_node_keysets(
+        pool.clone(),
+        node_client,
+        node_id,
+        compact_keyset_proofs.iter().map(|p| p.keyset_id),
+    )
+    .await?;
+
+    for compact_keyset_proof in compact_keyset_proofs.into_iter() {
+        // Every requested keyset is either read or imported, or we returned early
+        let (keyset_unit, max_denomination, input_fee_ppk) =
+            &keysets[&compact_keyset_proof.keyset_id];
+        if keyset_unit != unit {
+            return Err(Error::UnitMissmatch(keyset_unit.clone(), unit.to_string()));
+        }
+
+        received.push_keyset_proofs(
+            node_id,
+            compact_keyset_proof,
+            *max_denomination,
+            *input_fee_ppk,
+            hash_to_curve,
+        )?;
+    }
+    let ReceivedProofs {
+        ys,
+        total_amount,
+        total_input_fee_ppk,
+        inputs,
+        rows: stmt_params,
+    } = received;
+    // Checked before anything is stored, a wad not worth its fee is left untouched
+    let received_amount = amount_after_input_fee(
+        total_amount,
+        send::fee_from_total_ppk(total_input_fee_ppk),
+        Amount::ONE,
+    )?;
 
     let (wad_id, blinding_data) = {
         let mut db_conn = pool.get()?;
@@ -440,6 +877,9 @@ pub async fn receive_wad(
                 insert_proof_stmt.execute(params)?;
             }
         }
+        if db::balance_events::has_listeners() {
+            db::balance_events::record(&tx, [(node_id, unit.to_string())].into());
+        }
         let binding_data = BlindingData::load_from_db(seed_phrase_manager, &tx, node_id, unit)?;
 
         tx.commit()?;
@@ -447,12 +887,13 @@ pub async fn receive_wad(
         (wad_id, binding_data)
     };
 
-    let pre_mints = PreMints::generate_for_amount(total_amount, &SplitTarget::None, blinding_data)?;
+    let pre_mints =
+        PreMints::generate_for_amount(received_amount, &SplitTarget::None, blinding_data)?;
     let outputs = pre_mints.build_node_client_outputs();
 
     let swap_request = node_client::SwapRequest { inputs, outputs };
     let swap_request_hash = hash_swap_request(&swap_request);
-    let swap_result = node_client.swap(swap_request).await;
+    let swap_result = node_client.swap(with_trace_context(swap_request)).await;
 
     {
         let mut db_conn = pool.get()?;
@@ -466,14 +907,23 @@ pub async fn receive_wad(
 
         let tx = db_conn.transaction()?;
         db::proof::set_proofs_to_state(&tx, &ys, ProofState::Spent)?;
-        pre_mints.store_new_tokens(&tx, node_id, swap_response.signatures)?;
+        let new_tokens = pre_mints.store_new_tokens(&tx, node_id, swap_response.signatures)?;
+        let new_ys: Vec<PublicKey> = new_tokens.into_iter().map(|(y, _)| y).collect();
+        db::wad::link_proofs(&tx, wad_id, db::wad::WadProofRole::Output, &new_ys)?;
         db::wad::update_wad_status(&tx, wad_id, db::wad::WadStatus::Finished)?;
         tx.commit()?;
     }
 
     acknowledge(node_client, nuts::nut19::Route::Swap, swap_request_hash).await?;
 
-    Ok(total_amount)
+    operation_log::completed(
+        operation_log::Operation::Receive,
+        node_id,
+        unit,
+        received_amount,
+    );
+
+    Ok(received_amount)
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -489,6 +939,18 @@ pub enum ConnectToNodeError {
 pub async fn connect_to_node(
     node_url: &NodeUrl,
     root_ca_certificate: Option<tonic::transport::Certificate>,
+) -> Result<NodeClient<Channel>, ConnectToNodeError> {
+    connect_to_node_with_client_identity(node_url, root_ca_certificate, None).await
+}
+
+/// Same as [`connect_to_node`], but presents `client_identity` during the tls handshake
+///
+/// Required by nodes that only accept clients holding a certificate signed by their CA.
+/// The identity is ignored when the node url doesn't use tls.
+pub async fn connect_to_node_with_client_identity(
+    node_url: &NodeUrl,
+    root_ca_certificate: Option<tonic::transport::Certificate>,
+    client_identity: Option<tonic::transport::Identity>,
 ) -> Result<NodeClient<Channel>, ConnectToNodeError> {
     let uses_tls = node_url.0.scheme() == "https";
     let url_str = node_url.0.to_string();
@@ -502,6 +964,9 @@ pub async fn connect_to_node(
         if let Some(ca_cert) = root_ca_certificate {
             tls_config = tls_config.ca_certificate(ca_cert);
         }
+        if let Some(identity) = client_identity {
+            tls_config = tls_config.identity(identity);
+        }
 
         endpoint = endpoint
             .tls_config(tls_config)
@@ -530,3 +995,523 @@ pub async fn acknowledge(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+
+    use types::compact_wad::CompactProof;
+
+    use super::*;
+    use crate::db::test_utils;
+
+    fn fake_keyset(keyset_id: KeysetId) -> node_client::KeysetKeys {
+        node_client::KeysetKeys {
+            id: keyset_id.to_bytes().to_vec(),
+            unit: "sat".to_string(),
+            active: true,
+            input_fee_ppk: 100,
+            keys: [1u64, 2, 4, 8]
+                .into_iter()
+                .map(|amount| node_client::Key {
+                    amount,
+                    pubkey: SecretKey::generate().public_key().to_hex(),
+                })
+                .collect(),
+        }
+    }
+
+    fn keyset_proofs(amounts: &[u64]) -> CompactKeysetProofs {
+        let c = SecretKey::generate().public_key();
+
+        CompactKeysetProofs {
+            keyset_id: KeysetId::from_bytes(&[0, 1, 2, 3, 4, 5, 6, 7]).unwrap(),
+            proofs: amounts
+                .iter()
+                .map(|amount| CompactProof {
+                    amount: Amount::from(*amount),
+                    secret: Secret::generate(),
+                    c,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn proof_at_max_denomination_is_accepted() {
+        let mut received = ReceivedProofs::with_capacity(1);
+        received
+            .push_keyset_proofs(1, keyset_proofs(&[1, 8, 8]), 8, 0, hash_to_curve)
+            .unwrap();
+
+        assert_eq!(received.total_amount, Amount::from(17u64));
+    }
+
+    #[test]
+    fn proof_above_max_denomination_is_rejected() {
+        let mut received = ReceivedProofs::with_capacity(1);
+        let res = received.push_keyset_proofs(1, keyset_proofs(&[1, 16]), 8, 0, hash_to_curve);
+
+        assert!(matches!(res, Err(Error::Protocol(_))));
+    }
+
+    #[test]
+    fn rejected_proof_is_identified_without_its_secret() {
+        let proofs = keyset_proofs(&[3]);
+        let keyset_id = proofs.keyset_id.to_string();
+        let secret = proofs.proofs[0].secret.to_string();
+        let signature = proofs.proofs[0].c.to_string();
+
+        let mut received = ReceivedProofs::with_capacity(1);
+        let message = received
+            .push_keyset_proofs(1, proofs, 8, 0, hash_to_curve)
+            .unwrap_err()
+            .to_string();
+
+        assert!(message.contains(&keyset_id));
+        assert!(!message.contains(&secret[..8]));
+        assert!(!message.contains(&signature[..8]));
+    }
+
+    #[test]
+    fn only_missing_keysets_are_fetched() {
+        let pool = Pool::builder()
+            .max_size(1)
+            .build(SqliteConnectionManager::memory())
+            .unwrap();
+        let node_id = {
+            let mut db_conn = pool.get().unwrap();
+            db::create_tables(&mut db_conn).unwrap();
+            db::node::insert(
+                &db_conn,
+                &NodeUrl::from_str("https://localhost:10003").unwrap(),
+            )
+            .unwrap()
+        };
+        let keyset_ids: Vec<KeysetId> = (0..5u8)
+            .map(|i| KeysetId::from_bytes(&[0, 1, 2, 3, 4, 5, 6, i]).unwrap())
+            .collect();
+
+        // The wallet already knows the first two keysets
+        futures::executor::block_on(load_or_fetch_keysets(
+            pool.clone(),
+            node_id,
+            keyset_ids[..2].iter().copied(),
+            |keyset_id| async move { Ok(fake_keyset(keyset_id)) },
+        ))
+        .unwrap();
+
+        let fetched = RefCell::new(Vec::new());
+        let wad_keyset_ids = keyset_ids.iter().chain(keyset_ids.iter()).copied();
+        let keysets = futures::executor::block_on(load_or_fetch_keysets(
+            pool.clone(),
+            node_id,
+            wad_keyset_ids,
+            |keyset_id| {
+                fetched.borrow_mut().push(keyset_id);
+                async move { Ok(fake_keyset(keyset_id)) }
+            },
+        ))
+        .unwrap();
+
+        let mut fetched = fetched.into_inner();
+        fetched.sort();
+        assert_eq!(fetched, keyset_ids[2..]);
+        assert_eq!(keysets.len(), keyset_ids.len());
+        assert!(
+            keysets
+                .values()
+                .all(|(unit, max_denomination, input_fee_ppk)| unit == "sat"
+                    && *max_denomination == 8
+                    && *input_fee_ppk == 100)
+        );
+
+        // Everything is in db now
+        let keysets = futures::executor::block_on(load_or_fetch_keysets(
+            pool,
+            node_id,
+            keyset_ids.iter().copied(),
+            |_| async { Err(Error::Protocol("no rpc expected".to_string())) },
+        ))
+        .unwrap();
+        assert!(
+            keysets
+                .values()
+                .all(|(_, _, input_fee_ppk)| *input_fee_ppk == 100)
+        );
+    }
+
+    #[test]
+    fn received_proofs_add_up_the_fee_of_their_keyset() {
+        let mut received = ReceivedProofs::with_capacity(2);
+        received
+            .push_keyset_proofs(1, keyset_proofs(&[1, 2]), 8, 300, hash_to_curve)
+            .unwrap();
+        received
+            .push_keyset_proofs(1, keyset_proofs(&[4]), 8, 500, hash_to_curve)
+            .unwrap();
+
+        assert_eq!(received.total_input_fee_ppk, 1100);
+        // Rounded up once for the whole swap
+        assert_eq!(
+            send::fee_from_total_ppk(received.total_input_fee_ppk),
+            Amount::from(2u64)
+        );
+    }
+
+    #[test]
+    fn receive_computes_y_once_per_proof() {
+        const N: u64 = 20;
+        let keyset_id = KeysetId::from_bytes(&[0, 1, 2, 3, 4, 5, 6, 7]).unwrap();
+        let c = SecretKey::generate().public_key();
+        let compact_keyset_proofs = CompactKeysetProofs {
+            keyset_id,
+            proofs: (0..N)
+                .map(|_| CompactProof {
+                    amount: Amount::from(4u64),
+                    secret: Secret::generate(),
+                    c,
+                })
+                .collect(),
+        };
+
+        let calls = Cell::new(0);
+        let counting_hash_to_curve = |message: &[u8]| {
+            calls.set(calls.get() + 1);
+            hash_to_curve(message)
+        };
+
+        let mut received = ReceivedProofs::with_capacity(1);
+        received
+            .push_keyset_proofs(1, compact_keyset_proofs, 64, 0, counting_hash_to_curve)
+            .unwrap();
+
+        assert_eq!(calls.get(), N);
+        assert_eq!(received.ys.len() as u64, N);
+        assert_eq!(received.inputs.len() as u64, N);
+        assert_eq!(received.total_amount, Amount::from(4 * N));
+        assert!(
+            received
+                .rows
+                .iter()
+                .zip(&received.ys)
+                .all(|(r, y)| r.0 == *y)
+        );
+    }
+
+    const OLD_KEYSET: [u8; 8] = [0, 1, 2, 3, 4, 5, 6, 7];
+    const NEW_KEYSET: [u8; 8] = [0, 1, 2, 3, 4, 5, 6, 8];
+
+    /// A node that deactivated `OLD_KEYSET` in favor of `NEW_KEYSET`, both signed with `node_keys`
+    fn setup_rotated_keysets(
+        node_keys: &HashMap<u64, SecretKey>,
+        old_amounts: &[u64],
+        new_amounts: &[u64],
+    ) -> (Pool<SqliteConnectionManager>, u32) {
+        let pool = Pool::builder()
+            .max_size(1)
+            .build(SqliteConnectionManager::memory())
+            .unwrap();
+        let mut db_conn = pool.get().unwrap();
+        db::create_tables(&mut db_conn).unwrap();
+        let node_id = db::node::insert(
+            &db_conn,
+            &NodeUrl::from_str("https://localhost:10003").unwrap(),
+        )
+        .unwrap();
+        let pubkeys: Vec<(u64, String)> = node_keys
+            .iter()
+            .map(|(amount, k)| (*amount, k.public_key().to_hex()))
+            .collect();
+
+        for (keyset_id, active, amounts) in [
+            (OLD_KEYSET, false, old_amounts),
+            (NEW_KEYSET, true, new_amounts),
+        ] {
+            let keyset_id = KeysetId::from_bytes(&keyset_id).unwrap();
+            test_utils::insert_keyset(&db_conn, node_id, keyset_id, "sat", active);
+            db::insert_keyset_keys(
+                &db_conn,
+                keyset_id,
+                pubkeys.iter().map(|(a, pk)| (*a, pk.as_str())),
+            )
+            .unwrap();
+            for amount in amounts {
+                test_utils::insert_keyset_proof(
+                    &db_conn,
+                    node_id,
+                    keyset_id,
+                    *amount,
+                    ProofState::Unspent,
+                );
+            }
+        }
+
+        (pool, node_id)
+    }
+
+    fn node_keys() -> HashMap<u64, SecretKey> {
+        (0..8).map(|i| (1u64 << i, SecretKey::generate())).collect()
+    }
+
+    fn unlocked_wallet() -> wallet::lockable::Wallet {
+        let wallet = wallet::lockable::Wallet::new();
+        wallet.unlock(
+            &seed_phrase::create_from_str(&format!("{} vote", ["zoo"; 23].join(" "))).unwrap(),
+        );
+
+        wallet
+    }
+
+    /// Signs the outputs like a node would
+    async fn fake_swap(
+        node_keys: &HashMap<u64, SecretKey>,
+        swap_request: node_client::SwapRequest,
+    ) -> Result<tonic::Response<node_client::SwapResponse>, tonic::Status> {
+        let signatures = swap_request
+            .outputs
+            .iter()
+            .map(|output| {
+                assert_eq!(output.keyset_id, NEW_KEYSET.to_vec());
+                let blinded_secret = PublicKey::from_slice(&output.blinded_secret).unwrap();
+                let c = dhke::sign_message(&node_keys[&output.amount], &blinded_secret).unwrap();
+                node_client::BlindSignature {
+                    amount: output.amount,
+                    keyset_id: output.keyset_id.clone(),
+                    blind_signature: c.to_bytes().to_vec(),
+                }
+            })
+            .collect();
+
+        Ok(tonic::Response::new(node_client::SwapResponse {
+            signatures,
+        }))
+    }
+
+    fn sum_amounts(proofs: &[(PublicKey, Amount, Secret, PublicKey)]) -> u64 {
+        proofs
+            .iter()
+            .map(|(_, amount, _, _)| u64::from(*amount))
+            .sum()
+    }
+
+    #[test]
+    fn proofs_of_inactive_keyset_move_to_active_one() {
+        let old_keyset = KeysetId::from_bytes(&OLD_KEYSET).unwrap();
+        let new_keyset = KeysetId::from_bytes(&NEW_KEYSET).unwrap();
+        let node_keys = node_keys();
+        let (pool, node_id) = setup_rotated_keysets(&node_keys, &[1, 2, 4], &[]);
+
+        let (amount, swap_request_hash) = futures::executor::block_on(swap_keyset_proofs(
+            unlocked_wallet(),
+            pool.clone(),
+            node_id,
+            old_keyset,
+            |swap_request| fake_swap(&node_keys, swap_request),
+        ))
+        .unwrap();
+
+        assert_eq!(amount, Amount::from(7u64));
+        assert!(swap_request_hash.is_some());
+        let db_conn = pool.get().unwrap();
+        assert!(
+            db::proof::get_unspent_by_keyset(&db_conn, node_id, old_keyset)
+                .unwrap()
+                .is_empty()
+        );
+        let migrated = db::proof::get_unspent_by_keyset(&db_conn, node_id, new_keyset).unwrap();
+        assert_eq!(sum_amounts(&migrated), 7);
+        assert_eq!(
+            db::proof::get_node_total_available_amount_of_unit(&db_conn, node_id, "sat").unwrap(),
+            Amount::from(7u64)
+        );
+    }
+
+    fn set_input_fee_ppk(pool: &Pool<SqliteConnectionManager>, keyset_id: [u8; 8], fee: u64) {
+        pool.get()
+            .unwrap()
+            .execute(
+                "UPDATE keyset SET input_fee_ppk = ?2 WHERE id = ?1",
+                params![KeysetId::from_bytes(&keyset_id).unwrap(), fee],
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn keyset_migration_pays_the_input_fee() {
+        let new_keyset = KeysetId::from_bytes(&NEW_KEYSET).unwrap();
+        let node_keys = node_keys();
+        let (pool, node_id) = setup_rotated_keysets(&node_keys, &[1, 2, 4], &[]);
+        set_input_fee_ppk(&pool, OLD_KEYSET, 1000);
+
+        let (amount, _) = futures::executor::block_on(swap_keyset_proofs(
+            unlocked_wallet(),
+            pool.clone(),
+            node_id,
+            KeysetId::from_bytes(&OLD_KEYSET).unwrap(),
+            |swap_request| {
+                let outputs_amount: u64 = swap_request.outputs.iter().map(|o| o.amount).sum();
+                // What the node checks: inputs == outputs + fee
+                assert_eq!(outputs_amount + 3, 7);
+                fake_swap(&node_keys, swap_request)
+            },
+        ))
+        .unwrap();
+
+        assert_eq!(amount, Amount::from(4u64));
+        let db_conn = pool.get().unwrap();
+        assert_eq!(
+            sum_amounts(&db::proof::get_unspent_by_keyset(&db_conn, node_id, new_keyset).unwrap()),
+            4
+        );
+    }
+
+    #[test]
+    fn keyset_migration_not_worth_its_fee_releases_the_proofs() {
+        let old_keyset = KeysetId::from_bytes(&OLD_KEYSET).unwrap();
+        let (pool, node_id) = setup_rotated_keysets(&node_keys(), &[1], &[]);
+        set_input_fee_ppk(&pool, OLD_KEYSET, 1000);
+
+        let res = futures::executor::block_on(swap_keyset_proofs(
+            unlocked_wallet(),
+            pool.clone(),
+            node_id,
+            old_keyset,
+            |_| async { Err(tonic::Status::internal("no swap expected")) },
+        ));
+
+        assert!(matches!(res, Err(Error::InputFeeNotCovered { .. })));
+        let db_conn = pool.get().unwrap();
+        assert_eq!(
+            sum_amounts(&db::proof::get_unspent_by_keyset(&db_conn, node_id, old_keyset).unwrap()),
+            1
+        );
+    }
+
+    #[test]
+    fn keyset_migration_is_split_in_bounded_swaps() {
+        let old_keyset = KeysetId::from_bytes(&OLD_KEYSET).unwrap();
+        let new_keyset = KeysetId::from_bytes(&NEW_KEYSET).unwrap();
+        let node_keys = node_keys();
+        let (pool, node_id) = setup_rotated_keysets(&node_keys, &[1; 70], &[]);
+
+        // What `migrate_keyset` does
+        let mut swapped_amounts = Vec::new();
+        loop {
+            let (amount, swap_request_hash) = futures::executor::block_on(swap_keyset_proofs(
+                unlocked_wallet(),
+                pool.clone(),
+                node_id,
+                old_keyset,
+                |swap_request| {
+                    assert!(swap_request.inputs.len() <= MAX_INPUTS_PER_MIGRATION_SWAP);
+                    fake_swap(&node_keys, swap_request)
+                },
+            ))
+            .unwrap();
+            if swap_request_hash.is_none() {
+                break;
+            }
+            swapped_amounts.push(u64::from(amount));
+        }
+
+        assert_eq!(swapped_amounts, vec![64, 6]);
+        let db_conn = pool.get().unwrap();
+        assert_eq!(
+            sum_amounts(&db::proof::get_unspent_by_keyset(&db_conn, node_id, new_keyset).unwrap()),
+            70
+        );
+    }
+
+    #[test]
+    fn failed_keyset_migration_releases_the_proofs() {
+        let old_keyset = KeysetId::from_bytes(&OLD_KEYSET).unwrap();
+        let (pool, node_id) = setup_rotated_keysets(&node_keys(), &[1, 2, 4], &[]);
+
+        let res = futures::executor::block_on(swap_keyset_proofs(
+            unlocked_wallet(),
+            pool.clone(),
+            node_id,
+            old_keyset,
+            |_| async { Err(tonic::Status::internal("database unavailable")) },
+        ));
+
+        assert!(matches!(res, Err(Error::Grpc(_))));
+        let db_conn = pool.get().unwrap();
+        assert_eq!(
+            sum_amounts(&db::proof::get_unspent_by_keyset(&db_conn, node_id, old_keyset).unwrap()),
+            7
+        );
+    }
+
+    #[test]
+    fn active_keyset_is_selected_first() {
+        let old_keyset = KeysetId::from_bytes(&OLD_KEYSET).unwrap();
+        let new_keyset = KeysetId::from_bytes(&NEW_KEYSET).unwrap();
+        let node_keys = node_keys();
+        let (pool, node_id) = setup_rotated_keysets(&node_keys, &[8, 2], &[4, 1]);
+        let db_conn = pool.get().unwrap();
+        let active_ys: Vec<PublicKey> =
+            db::proof::get_unspent_by_keyset(&db_conn, node_id, new_keyset)
+                .unwrap()
+                .into_iter()
+                .map(|(y, _, _, _)| y)
+                .collect();
+
+        // Even though the bigger proof is on the inactive keyset
+        let selection = select_inputs(&db_conn, node_id, Amount::from(5u64), "sat")
+            .unwrap()
+            .unwrap();
+        assert_eq!(selection.proofs_ids.len(), 2);
+        assert!(selection.proofs_ids.iter().all(|y| active_ys.contains(y)));
+        assert!(selection.inactive_keysets.is_empty());
+
+        // Active funds are not enough
+        let selection = select_inputs(&db_conn, node_id, Amount::from(15u64), "sat")
+            .unwrap()
+            .unwrap();
+        assert_eq!(selection.inactive_keysets, vec![old_keyset]);
+        drop(db_conn);
+
+        futures::executor::block_on(swap_keyset_proofs(
+            unlocked_wallet(),
+            pool.clone(),
+            node_id,
+            old_keyset,
+            |swap_request| fake_swap(&node_keys, swap_request),
+        ))
+        .unwrap();
+
+        let db_conn = pool.get().unwrap();
+        let selection = select_inputs(&db_conn, node_id, Amount::from(15u64), "sat")
+            .unwrap()
+            .unwrap();
+        assert!(selection.inactive_keysets.is_empty());
+        assert_eq!(selection.remaining_amount, Amount::ZERO);
+        assert_eq!(
+            sum_amounts(&db::proof::get_unspent_by_keyset(&db_conn, node_id, new_keyset).unwrap()),
+            15
+        );
+    }
+
+    #[test]
+    fn proof_to_swap_is_preferably_of_an_active_keyset() {
+        let old_keyset = KeysetId::from_bytes(&OLD_KEYSET).unwrap();
+        let (pool, node_id) = setup_rotated_keysets(&node_keys(), &[4], &[8]);
+        let db_conn = pool.get().unwrap();
+
+        // Both can be swapped, the inactive one is smaller
+        let selection = select_inputs(&db_conn, node_id, Amount::from(3u64), "sat")
+            .unwrap()
+            .unwrap();
+        assert_eq!(selection.proof_to_swap.unwrap().1, Amount::from(8u64));
+        assert!(selection.inactive_keysets.is_empty());
+
+        // Only the inactive one can
+        let selection = select_inputs(&db_conn, node_id, Amount::from(11u64), "sat")
+            .unwrap()
+            .unwrap();
+        assert_eq!(selection.proof_to_swap.unwrap().1, Amount::from(4u64));
+        assert_eq!(selection.inactive_keysets, vec![old_keyset]);
+    }
+}
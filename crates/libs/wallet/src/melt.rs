@@ -1,6 +1,6 @@
 use node_client::{
-    MeltQuoteRequest, MeltQuoteResponse, MeltQuoteState, MeltResponse, NodeClient,
-    hash_melt_request,
+    MeltQuoteRequest, MeltQuoteResponse, MeltQuoteState, MeltQuoteStateRequest, MeltResponse,
+    NodeClient, hash_melt_request, trace_context::with_trace_context,
 };
 use nuts::{Amount, traits::Unit};
 use r2d2::Pool;
@@ -49,6 +49,13 @@ pub async fn pay_quote(
     method: String,
     unit: &str,
 ) -> Result<MeltResponse, Error> {
+    // A previous attempt may have reached the node even though we never got its answer.
+    // Submitting again would be refused, so look at the quote first.
+    let quote_state = fetch_quote_state(node_client, method.clone(), quote_id.clone()).await?;
+    if let Some(melt_response) = resume_prior_attempt(&mut *pool.get()?, &quote_id, quote_state)? {
+        return Ok(melt_response);
+    }
+
     // Gather the proofs
     let proofs_ids = fetch_inputs_ids_from_db_or_node(
         seed_phrase_manager,
@@ -58,9 +65,13 @@ pub async fn pay_quote(
         amount,
         unit,
     )
-    .await?
-    .ok_or(Error::NotEnoughFunds)?;
-    let inputs = load_tokens_from_db(&*pool.get()?, &proofs_ids)?;
+    .await?;
+    let inputs = {
+        let db_conn = pool.get()?;
+        let inputs = load_tokens_from_db(&db_conn, &proofs_ids)?;
+        db::melt_quote::register_inputs(&db_conn, &quote_id, &proofs_ids)?;
+        inputs
+    };
 
     // Create melt request
     let melt_request = node_client::MeltRequest {
@@ -71,13 +82,19 @@ pub async fn pay_quote(
 
     let melt_request_hash = hash_melt_request(&melt_request);
 
-    let melt_res = node_client.melt(melt_request).await;
+    let melt_res = node_client.melt(with_trace_context(melt_request)).await;
     // If this fail we won't be able to actualize the proof state. Which may lead to some bugs.
     let mut db_conn = pool.get()?;
 
     // Call the node and handle failure
     let melt_response = match melt_res {
         Ok(r) => r.into_inner(),
+        Err(e) if is_response_lost(&e) => {
+            match recheck_submission(node_client, method, quote_id.clone()).await {
+                Ok(Some(melt_response)) => melt_response,
+                _ => return Err(e.into()),
+            }
+        }
         Err(e) => {
             handle_proof_verification_errors(&e, &proofs_ids, &db_conn)?;
             return Err(e.into());
@@ -90,17 +107,109 @@ pub async fn pay_quote(
     // Relieve the node cache once we receive the answer
     acknowledge(node_client, nuts::nut19::Route::Melt, melt_request_hash).await?;
 
+    register_melt_response(&mut db_conn, &quote_id, &melt_response)?;
+
+    Ok(melt_response)
+}
+
+/// Ask the node whether a melt was already submitted for this quote
+///
+/// Returns the outcome of that submission if the quote is no longer unpaid.
+/// Using it before (re)submitting makes `pay_quote` idempotent from the wallet side.
+pub async fn recheck_submission(
+    node_client: &mut NodeClient<Channel>,
+    method: String,
+    quote_id: String,
+) -> Result<Option<MeltResponse>, Error> {
+    let quote_state = fetch_quote_state(node_client, method, quote_id).await?;
+
+    Ok(prior_submission(quote_state))
+}
+
+async fn fetch_quote_state(
+    node_client: &mut NodeClient<Channel>,
+    method: String,
+    quote_id: String,
+) -> Result<MeltQuoteResponse, Error> {
+    let quote_state = node_client
+        .melt_quote_state(MeltQuoteStateRequest {
+            method,
+            quote: quote_id,
+        })
+        .await?
+        .into_inner();
+
+    Ok(quote_state)
+}
+
+/// Settle the proofs a previous attempt to pay the quote left reserved
+///
+/// If that attempt reached the node, its outcome is returned and nothing must be submitted again.
+/// The inputs of a Paid quote are spent. Those of a Pending one stay Pending,
+/// so that the proofs sync moves them once the node settles the payment.
+/// Otherwise the attempt never reached the node and its inputs can be used again.
+fn resume_prior_attempt(
+    db_conn: &mut rusqlite::Connection,
+    quote_id: &str,
+    quote_state: MeltQuoteResponse,
+) -> Result<Option<MeltResponse>, Error> {
+    let prior_inputs = db::melt_quote::get_inputs(db_conn, quote_id)?;
+    let Some(melt_response) = prior_submission(quote_state) else {
+        let tx = db_conn.transaction()?;
+        db::proof::set_proofs_to_state(&tx, &prior_inputs, ProofState::Unspent)?;
+        db::melt_quote::delete_inputs(&tx, quote_id)?;
+        tx.commit()?;
+        return Ok(None);
+    };
+
+    let inputs_state = if melt_response.state == MeltQuoteState::MlqsPaid as i32 {
+        ProofState::Spent
+    } else {
+        ProofState::Pending
+    };
+    db::proof::set_proofs_to_state(db_conn, &prior_inputs, inputs_state)?;
+    register_melt_response(db_conn, quote_id, &melt_response)?;
+
+    Ok(Some(melt_response))
+}
+
+fn prior_submission(quote_state: MeltQuoteResponse) -> Option<MeltResponse> {
+    match MeltQuoteState::try_from(quote_state.state) {
+        Ok(MeltQuoteState::MlqsPending) | Ok(MeltQuoteState::MlqsPaid) => Some(MeltResponse {
+            state: quote_state.state,
+            transfer_ids: quote_state.transfer_ids,
+        }),
+        _ => None,
+    }
+}
+
+/// Errors after which the node may have processed the request without us receiving its answer
+fn is_response_lost(status: &tonic::Status) -> bool {
+    matches!(
+        status.code(),
+        tonic::Code::Unavailable
+            | tonic::Code::Unknown
+            | tonic::Code::Cancelled
+            | tonic::Code::DeadlineExceeded
+    )
+}
+
+fn register_melt_response(
+    db_conn: &mut rusqlite::Connection,
+    quote_id: &str,
+    melt_response: &MeltResponse,
+) -> Result<(), Error> {
     if melt_response.state == MeltQuoteState::MlqsPaid as i32 {
         let tx = db_conn.transaction()?;
-        db::melt_quote::update_state(&tx, &quote_id, melt_response.state)?;
+        db::melt_quote::update_state(&tx, quote_id, melt_response.state)?;
         if !melt_response.transfer_ids.is_empty() {
             let transfer_ids_to_store = serde_json::to_string(&melt_response.transfer_ids)?;
-            db::melt_quote::register_transfer_ids(&tx, &quote_id, &transfer_ids_to_store)?;
+            db::melt_quote::register_transfer_ids(&tx, quote_id, &transfer_ids_to_store)?;
         }
         tx.commit()?;
     }
 
-    Ok(melt_response)
+    Ok(())
 }
 
 pub async fn wait_for_payment(
@@ -120,3 +229,158 @@ pub async fn wait_for_payment(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use nuts::nut01::PublicKey;
+
+    use super::*;
+
+    const QUOTE_ID: &str = "d3b7c2c4-5f0e-4a54-9b3e-6ad1b5e0c7a1";
+
+    fn quote_state(state: MeltQuoteState, transfer_ids: Vec<String>) -> MeltQuoteResponse {
+        MeltQuoteResponse {
+            quote: QUOTE_ID.to_string(),
+            amount: 32,
+            unit: "sat".to_string(),
+            state: state as i32,
+            expiry: i64::MAX as u64,
+            transfer_ids,
+        }
+    }
+
+    fn setup_db_with_unpaid_quote() -> (rusqlite::Connection, u32) {
+        let (conn, node_id) = db::test_utils::setup_node("sat");
+        db::melt_quote::store(
+            &conn,
+            node_id,
+            "starknet".to_string(),
+            "{}".to_string(),
+            &quote_state(MeltQuoteState::MlqsUnpaid, vec![]),
+        )
+        .unwrap();
+
+        (conn, node_id)
+    }
+
+    /// What an attempt whose answer never reached us leaves behind
+    fn setup_lost_attempt() -> (rusqlite::Connection, Vec<PublicKey>) {
+        let (conn, node_id) = setup_db_with_unpaid_quote();
+        let inputs: Vec<_> = [16, 16]
+            .into_iter()
+            .map(|amount| {
+                db::test_utils::insert_proof(&conn, node_id, amount, ProofState::Reserved)
+            })
+            .collect();
+        db::melt_quote::register_inputs(&conn, QUOTE_ID, &inputs).unwrap();
+
+        (conn, inputs)
+    }
+
+    fn states(conn: &rusqlite::Connection, ys: &[PublicKey]) -> Vec<ProofState> {
+        db::proof::get_proofs_state_by_ids(conn, ys).unwrap()
+    }
+
+    #[test]
+    fn unpaid_quote_was_not_submitted() {
+        assert!(prior_submission(quote_state(MeltQuoteState::MlqsUnpaid, vec![])).is_none());
+    }
+
+    #[test]
+    fn recheck_after_lost_response() {
+        let (mut conn, _) = setup_db_with_unpaid_quote();
+        let transfer_ids = vec!["0x1".to_string(), "0x2".to_string()];
+
+        // The node processed the melt but its answer never reached us
+        assert!(is_response_lost(&tonic::Status::unavailable(
+            "connection reset"
+        )));
+        let melt_response =
+            prior_submission(quote_state(MeltQuoteState::MlqsPaid, transfer_ids.clone())).unwrap();
+        assert_eq!(melt_response.transfer_ids, transfer_ids);
+
+        register_melt_response(&mut conn, QUOTE_ID, &melt_response).unwrap();
+
+        let (state, stored_transfer_ids): (i32, String) = conn
+            .query_row(
+                "SELECT state, transfer_ids FROM melt_quote WHERE id = ?1;",
+                [QUOTE_ID],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(state, MeltQuoteState::MlqsPaid as i32);
+        assert_eq!(
+            serde_json::from_str::<Vec<String>>(&stored_transfer_ids).unwrap(),
+            transfer_ids
+        );
+    }
+
+    #[test]
+    fn pending_quote_was_submitted() {
+        let melt_response =
+            prior_submission(quote_state(MeltQuoteState::MlqsPending, vec![])).unwrap();
+        assert_eq!(melt_response.state, MeltQuoteState::MlqsPending as i32);
+        assert!(!is_response_lost(&tonic::Status::invalid_argument(
+            "already spent"
+        )));
+    }
+
+    #[test]
+    fn paid_prior_attempt_spends_its_inputs() {
+        let (mut conn, inputs) = setup_lost_attempt();
+        let transfer_ids = vec!["0x1".to_string()];
+
+        let melt_response = resume_prior_attempt(
+            &mut conn,
+            QUOTE_ID,
+            quote_state(MeltQuoteState::MlqsPaid, transfer_ids.clone()),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(melt_response.transfer_ids, transfer_ids);
+        assert_eq!(states(&conn, &inputs), vec![ProofState::Spent; 2]);
+    }
+
+    #[test]
+    fn pending_prior_attempt_keeps_tracking_its_inputs() {
+        let (mut conn, inputs) = setup_lost_attempt();
+
+        let melt_response = resume_prior_attempt(
+            &mut conn,
+            QUOTE_ID,
+            quote_state(MeltQuoteState::MlqsPending, vec![]),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(melt_response.state, MeltQuoteState::MlqsPending as i32);
+        assert_eq!(states(&conn, &inputs), vec![ProofState::Pending; 2]);
+        assert_eq!(
+            db::melt_quote::get_inputs(&conn, QUOTE_ID).unwrap().len(),
+            2
+        );
+    }
+
+    #[test]
+    fn unsubmitted_prior_attempt_releases_its_inputs() {
+        let (mut conn, inputs) = setup_lost_attempt();
+
+        assert!(
+            resume_prior_attempt(
+                &mut conn,
+                QUOTE_ID,
+                quote_state(MeltQuoteState::MlqsUnpaid, vec![])
+            )
+            .unwrap()
+            .is_none()
+        );
+
+        assert_eq!(states(&conn, &inputs), vec![ProofState::Unspent; 2]);
+        assert!(
+            db::melt_quote::get_inputs(&conn, QUOTE_ID)
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -1,5 +1,8 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use node_client::{
     MintQuoteRequest, MintQuoteResponse, MintRequest, NodeClient, hash_mint_request,
+    trace_context::with_trace_context,
 };
 use nuts::{Amount, SplitTarget, nut04::MintQuoteState, nut19::Route, traits::Unit};
 use r2d2::Pool;
@@ -44,32 +47,150 @@ pub enum QuotePaymentIssue {
     Expired,
 }
 
-pub async fn wait_for_quote_payment(
+fn generate_pre_mints(
+    total_amount: Amount,
+    split_target: Option<SplitTarget>,
+    blinding_data: BlindingData,
+) -> Result<PreMints, Error> {
+    PreMints::generate_for_amount(
+        total_amount,
+        &split_target.unwrap_or_default(),
+        blinding_data,
+    )
+}
+
+/// Delays between two polls of a quote state
+///
+/// On-chain deposits can take minutes to be indexed,
+/// polling at a fixed short interval would mostly load the node for nothing.
+#[derive(Debug, Clone, Copy)]
+pub struct PollBackoff {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for PollBackoff {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Delay doubling each time the polled state is found unchanged
+struct Backoff<S> {
+    config: PollBackoff,
+    delay: Duration,
+    last_state: Option<S>,
+}
+
+impl<S: PartialEq> Backoff<S> {
+    fn new(config: PollBackoff) -> Self {
+        Self {
+            config,
+            delay: config.initial_delay,
+            last_state: None,
+        }
+    }
+
+    /// How long to wait before polling again, after having observed `state`
+    ///
+    /// Never goes past `expiry`, so that an expired quote is reported on time.
+    /// `None` once `expiry` is reached, the state observed then is final.
+    fn next_delay(&mut self, state: S, now: u64, expiry: u64) -> Option<Duration> {
+        if now >= expiry {
+            return None;
+        }
+
+        if self.last_state.as_ref() == Some(&state) {
+            self.delay = self.delay.saturating_mul(2).min(self.config.max_delay);
+        } else {
+            self.delay = self.config.initial_delay;
+            self.last_state = Some(state);
+        }
+
+        Some(self.delay.min(Duration::from_secs(expiry - now)))
+    }
+}
+
+trait MintQuoteStateSource {
+    /// Latest state of the quote, `None` once it has expired
+    async fn poll(&mut self) -> Result<Option<MintQuoteState>, Error>;
+}
+
+struct NodeMintQuoteState<'a> {
     pool: Pool<SqliteConnectionManager>,
-    node_client: &mut NodeClient<Channel>,
+    node_client: &'a mut NodeClient<Channel>,
     method: String,
     quote_id: String,
+}
+
+impl MintQuoteStateSource for NodeMintQuoteState<'_> {
+    async fn poll(&mut self) -> Result<Option<MintQuoteState>, Error> {
+        sync::mint_quote(
+            self.pool.clone(),
+            self.node_client,
+            self.method.clone(),
+            self.quote_id.clone(),
+        )
+        .await
+    }
+}
+
+async fn poll_until_paid(
+    source: &mut impl MintQuoteStateSource,
+    expiry: u64,
+    backoff: PollBackoff,
 ) -> Result<QuotePaymentIssue, Error> {
+    let mut backoff = Backoff::new(backoff);
+
     loop {
-        let state =
-            match sync::mint_quote(pool.clone(), node_client, method.clone(), quote_id.clone())
-                .await?
-            {
-                Some(new_state) => new_state,
-                None => {
-                    return Ok(QuotePaymentIssue::Expired);
-                }
-            };
+        let state = match source.poll().await? {
+            Some(new_state) => new_state,
+            None => {
+                return Ok(QuotePaymentIssue::Expired);
+            }
+        };
 
         if state == MintQuoteState::Paid {
             return Ok(QuotePaymentIssue::Paid);
         }
 
-        // Wait a bit
-        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        match backoff.next_delay(state, now, expiry) {
+            Some(delay) => tokio::time::sleep(delay).await,
+            None => return Ok(QuotePaymentIssue::Expired),
+        }
     }
 }
 
+/// Poll the node until the quote is paid or expires at `expiry`
+pub async fn wait_for_quote_payment(
+    pool: Pool<SqliteConnectionManager>,
+    node_client: &mut NodeClient<Channel>,
+    method: String,
+    quote_id: String,
+    expiry: u64,
+    backoff: PollBackoff,
+) -> Result<QuotePaymentIssue, Error> {
+    let mut source = NodeMintQuoteState {
+        pool,
+        node_client,
+        method,
+        quote_id,
+    };
+
+    poll_until_paid(&mut source, expiry, backoff).await
+}
+
+/// Mint the tokens paid for by `quote_id`
+///
+/// `split_target` controls the denominations of the new proofs. `None` produces the fewest proofs,
+/// which is fine for storage but may force a swap on the next spend.
 #[allow(clippy::too_many_arguments)]
 pub async fn redeem_quote(
     seed_phrase_manager: impl SeedPhraseManager,
@@ -80,6 +201,7 @@ pub async fn redeem_quote(
     node_id: u32,
     unit: &str,
     total_amount: Amount,
+    split_target: Option<SplitTarget>,
 ) -> Result<(), Error> {
     refresh_keysets(pool.clone(), node_client, node_id).await?;
 
@@ -88,7 +210,7 @@ pub async fn redeem_quote(
         BlindingData::load_from_db(seed_phrase_manager, &db_conn, node_id, unit)?
     };
 
-    let pre_mints = PreMints::generate_for_amount(total_amount, &SplitTarget::None, blinding_data)?;
+    let pre_mints = generate_pre_mints(total_amount, split_target, blinding_data)?;
 
     let outputs = pre_mints.build_node_client_outputs();
 
@@ -100,7 +222,7 @@ pub async fn redeem_quote(
 
     let mint_request_hash = hash_mint_request(&mint_request);
 
-    let mint_result = node_client.mint(mint_request).await;
+    let mint_result = node_client.mint(with_trace_context(mint_request)).await;
     let mint_response = match mint_result {
         Ok(r) => r.into_inner(),
         Err(e) => {
@@ -120,5 +242,129 @@ pub async fn redeem_quote(
 
     acknowledge(node_client, Route::Mint, mint_request_hash).await?;
 
+    crate::operation_log::completed(
+        crate::operation_log::Operation::Mint,
+        node_id,
+        unit,
+        total_amount,
+    );
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::*;
+    use crate::{db::test_utils, seed_phrase, wallet::lockable};
+
+    const UNIT: &str = "sat";
+
+    fn setup() -> (Connection, u32, lockable::Wallet) {
+        let (conn, node_id) = test_utils::setup_node(UNIT);
+
+        let wallet = lockable::Wallet::new();
+        wallet.unlock(
+            &seed_phrase::create_from_str(&format!("{} vote", ["zoo"; 23].join(" "))).unwrap(),
+        );
+
+        (conn, node_id, wallet)
+    }
+
+    fn outputs_amounts(split_target: Option<SplitTarget>) -> Vec<u64> {
+        let (conn, node_id, wallet) = setup();
+        let blinding_data = BlindingData::load_from_db(wallet, &conn, node_id, UNIT).unwrap();
+
+        let mut amounts: Vec<u64> =
+            generate_pre_mints(Amount::from(13u64), split_target, blinding_data)
+                .unwrap()
+                .build_node_client_outputs()
+                .into_iter()
+                .map(|o| o.amount)
+                .collect();
+        amounts.sort();
+
+        amounts
+    }
+
+    #[test]
+    fn split_target_changes_denominations() {
+        let default_amounts = outputs_amounts(None);
+        let value_amounts = outputs_amounts(Some(SplitTarget::Value(Amount::from(2u64))));
+
+        assert_eq!(default_amounts, vec![1, 4, 8]);
+        assert_eq!(outputs_amounts(Some(SplitTarget::None)), default_amounts);
+        assert_ne!(value_amounts, default_amounts);
+        assert_eq!(value_amounts.iter().sum::<u64>(), 13);
+        assert!(value_amounts.len() > default_amounts.len());
+    }
+
+    /// Pays after `unpaid_polls` polls, recording when each poll happened
+    struct MockNode {
+        unpaid_polls: usize,
+        polled_at: Vec<tokio::time::Instant>,
+    }
+
+    impl MintQuoteStateSource for MockNode {
+        async fn poll(&mut self) -> Result<Option<MintQuoteState>, Error> {
+            self.polled_at.push(tokio::time::Instant::now());
+            if self.polled_at.len() > self.unpaid_polls {
+                Ok(Some(MintQuoteState::Paid))
+            } else {
+                Ok(Some(MintQuoteState::Unpaid))
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn polling_backs_off_until_paid() {
+        let mut node = MockNode {
+            unpaid_polls: 7,
+            polled_at: Vec::new(),
+        };
+        let expiry = u64::MAX;
+
+        let issue = poll_until_paid(&mut node, expiry, PollBackoff::default())
+            .await
+            .unwrap();
+
+        assert!(matches!(issue, QuotePaymentIssue::Paid));
+        let delays: Vec<u64> = node
+            .polled_at
+            .windows(2)
+            .map(|w| (w[1] - w[0]).as_secs())
+            .collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 30, 30]);
+    }
+
+    #[test]
+    fn backoff_resets_on_progress_and_stops_at_expiry() {
+        let mut backoff = Backoff::new(PollBackoff::default());
+
+        assert_eq!(backoff.next_delay(1, 0, 100), Some(Duration::from_secs(1)));
+        assert_eq!(backoff.next_delay(1, 0, 100), Some(Duration::from_secs(2)));
+        assert_eq!(backoff.next_delay(1, 0, 100), Some(Duration::from_secs(4)));
+        assert_eq!(backoff.next_delay(2, 0, 100), Some(Duration::from_secs(1)));
+        assert_eq!(backoff.next_delay(2, 0, 100), Some(Duration::from_secs(2)));
+        assert_eq!(backoff.next_delay(2, 99, 100), Some(Duration::from_secs(1)));
+        assert_eq!(backoff.next_delay(2, 100, 100), None);
+        assert_eq!(backoff.next_delay(2, 150, 100), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn polling_stops_once_expired() {
+        let mut node = MockNode {
+            unpaid_polls: usize::MAX,
+            polled_at: Vec::new(),
+        };
+        let already_expired = 0;
+
+        let issue = poll_until_paid(&mut node, already_expired, PollBackoff::default())
+            .await
+            .unwrap();
+
+        assert!(matches!(issue, QuotePaymentIssue::Expired));
+        assert_eq!(node.polled_at.len(), 1);
+    }
+}
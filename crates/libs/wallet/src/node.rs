@@ -9,6 +9,8 @@ use nuts::{
 };
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::Connection;
+use tokio::sync::mpsc;
 use tonic::transport::Channel;
 
 use crate::{
@@ -40,9 +42,7 @@ pub async fn register(
 ) -> Result<u32, RegisterNodeError> {
     let node_id = {
         let db_conn = pool.get()?;
-        db::node::insert(&db_conn, node_url)?;
-        db::node::get_id_by_url(&db_conn, node_url)?
-            .ok_or(RegisterNodeError::NotFound(node_url.clone()))?
+        db::node::insert(&db_conn, node_url)?
     };
 
     refresh_keysets(pool, node_client, node_id)
@@ -52,6 +52,38 @@ pub async fn register(
     Ok(node_id)
 }
 
+/// Id of a node already registered with its keysets, if any
+///
+/// A node without keysets was inserted but its keyset refresh failed, it must be registered again.
+fn known_node_id(conn: &Connection, node_url: &NodeUrl) -> Result<Option<u32>, rusqlite::Error> {
+    let opt_node_id = match db::node::get_id_by_url(conn, node_url)? {
+        Some(node_id) if !keyset::get_all_ids_for_node(conn, node_id)?.is_empty() => Some(node_id),
+        _ => None,
+    };
+
+    Ok(opt_node_id)
+}
+
+/// Same as `register` but doesn't refresh the keysets of a node we already know
+///
+/// Meant to be called on each incoming wad.
+/// Keysets we don't know about yet are imported when the wad proofs are read.
+pub async fn get_or_register(
+    pool: Pool<SqliteConnectionManager>,
+    node_client: &mut NodeClient<Channel>,
+    node_url: &NodeUrl,
+) -> Result<u32, RegisterNodeError> {
+    let opt_node_id = {
+        let db_conn = pool.get()?;
+        known_node_id(&db_conn, node_url)?
+    };
+
+    match opt_node_id {
+        Some(node_id) => Ok(node_id),
+        None => register(pool, node_client, node_url).await,
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum RestoreNodeError {
     #[error(transparent)]
@@ -74,11 +106,36 @@ pub enum RestoreNodeError {
     Wallet(#[from] crate::wallet::Error),
 }
 
+/// Reported after each batch of derivation indexes has been checked against the node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestoreProgress {
+    pub keyset_id: KeysetId,
+    /// Starts at 0 and is incremented by one for each batch of the same keyset
+    pub batch_index: u32,
+    /// Number of proofs recovered for this keyset so far
+    pub recovered_count: usize,
+}
+
 pub async fn restore(
     seed_phrase_manager: impl SeedPhraseManager,
     pool: Pool<SqliteConnectionManager>,
     node_id: u32,
     node_client: NodeClient<Channel>,
+) -> Result<(), RestoreNodeError> {
+    restore_with_progress(seed_phrase_manager, pool, node_id, node_client, None).await
+}
+
+/// Same as `restore` but reports its progress through `progress_sender`
+///
+/// Restoring a node with a long history can take a while,
+/// this lets an UI display something more useful than a spinner.
+/// Keysets are restored concurrently, so progress of different keysets are interleaved.
+pub async fn restore_with_progress(
+    seed_phrase_manager: impl SeedPhraseManager,
+    pool: Pool<SqliteConnectionManager>,
+    node_id: u32,
+    node_client: NodeClient<Channel>,
+    progress_sender: Option<mpsc::UnboundedSender<RestoreProgress>>,
 ) -> Result<(), RestoreNodeError> {
     let keyset_ids = {
         let db_conn = pool.get()?;
@@ -94,6 +151,7 @@ pub async fn restore(
             node_client.clone(),
             xpriv,
             keyset_id,
+            progress_sender.clone(),
         ));
     }
     let results = join_all(handles).await;
@@ -110,9 +168,11 @@ async fn restore_keyset(
     mut node_client: NodeClient<Channel>,
     xpriv: Xpriv,
     keyset_id: KeysetId,
+    progress_sender: Option<mpsc::UnboundedSender<RestoreProgress>>,
 ) -> Result<(), RestoreNodeError> {
     let mut empty_response_counter = 0;
     let mut n_batch_done = 0;
+    let mut recovered_count = 0;
 
     while empty_response_counter < 3 {
         let start_count = n_batch_done * 100;
@@ -203,9 +263,20 @@ async fn restore_keyset(
 
             let mut db_conn = pool.get()?;
             let tx = db_conn.transaction()?;
-            store_new_proofs_from_blind_signatures(&tx, node_id, keyset_id, iterator)?;
+            let new_proofs =
+                store_new_proofs_from_blind_signatures(&tx, node_id, keyset_id, iterator)?;
             db::keyset::set_counter(&tx, keyset_id, counter_last_known_blinded_secret + 1)?;
             tx.commit()?;
+            recovered_count += new_proofs.len();
+        }
+
+        if let Some(sender) = &progress_sender {
+            // The receiver going away should not abort the restoration
+            let _ = sender.send(RestoreProgress {
+                keyset_id,
+                batch_index: n_batch_done,
+                recovered_count,
+            });
         }
         n_batch_done += 1;
     }
@@ -281,3 +352,30 @@ pub async fn refresh_keysets(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::db::test_utils;
+
+    #[test]
+    fn node_is_known_once_its_keysets_are_stored() {
+        let conn = test_utils::setup_db();
+        let node_url = NodeUrl::from_str("https://node.example").unwrap();
+
+        // First wad: the node must be registered
+        assert_eq!(known_node_id(&conn, &node_url).unwrap(), None);
+
+        // What `register` does
+        let node_id = db::node::insert(&conn, &node_url).unwrap();
+        assert_eq!(known_node_id(&conn, &node_url).unwrap(), None);
+        test_utils::insert_keyset(&conn, node_id, test_utils::keyset_id(node_id), "sat", true);
+
+        // Second wad, from another spelling of the same url: no registration nor refresh
+        let same_node_url = NodeUrl::from_str("https://Node.Example/").unwrap();
+        assert_eq!(known_node_id(&conn, &same_node_url).unwrap(), Some(node_id));
+        assert_eq!(db::node::fetch_all(&conn).unwrap().len(), 1);
+    }
+}
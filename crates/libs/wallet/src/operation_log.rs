@@ -0,0 +1,123 @@
+//! Structured events emitted when a wallet operation completes
+//!
+//! They are meant to be surfaced as notifications by the apps and to help debugging.
+//! Only public, aggregated values go in there. Never add proofs, secrets, blinded messages
+//! or quote ids: subscribers forward these events to log files and third party collectors.
+
+use nuts::Amount;
+
+pub(crate) const TARGET: &str = "wallet::operation";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Operation {
+    Receive,
+    Swap,
+    Mint,
+}
+
+impl Operation {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Operation::Receive => "receive",
+            Operation::Swap => "swap",
+            Operation::Mint => "mint",
+        }
+    }
+}
+
+pub(crate) fn completed(operation: Operation, node_id: u32, unit: &str, amount: Amount) {
+    tracing::info!(
+        target: TARGET,
+        operation = operation.as_str(),
+        node_id,
+        unit,
+        amount = u64::from(amount),
+        "wallet operation completed"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        fmt::Debug,
+        sync::{Arc, Mutex},
+    };
+
+    use tracing::{
+        Event, Metadata, Subscriber,
+        field::{Field, Visit},
+        span,
+    };
+
+    use super::*;
+
+    type Fields = HashMap<&'static str, String>;
+
+    #[derive(Default)]
+    struct FieldsVisitor(Fields);
+
+    impl Visit for FieldsVisitor {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0.insert(field.name(), format!("{value:?}"));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name(), value.to_string());
+        }
+
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            self.0.insert(field.name(), value.to_string());
+        }
+    }
+
+    /// Keeps the fields of every event, spans are ignored
+    struct CaptureEvents(Arc<Mutex<Vec<(String, Fields)>>>);
+
+    impl Subscriber for CaptureEvents {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _: &span::Attributes<'_>) -> span::Id {
+            span::Id::from_u64(1)
+        }
+
+        fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut visitor = FieldsVisitor::default();
+            event.record(&mut visitor);
+            self.0
+                .lock()
+                .unwrap()
+                .push((event.metadata().target().to_string(), visitor.0));
+        }
+
+        fn enter(&self, _: &span::Id) {}
+
+        fn exit(&self, _: &span::Id) {}
+    }
+
+    #[test]
+    fn completed_event_has_the_operation_fields() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+
+        tracing::subscriber::with_default(CaptureEvents(events.clone()), || {
+            completed(Operation::Receive, 3, "sat", Amount::from(21u64));
+        });
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        let (target, fields) = &events[0];
+        assert_eq!(target, TARGET);
+        assert_eq!(fields["operation"], "receive");
+        assert_eq!(fields["node_id"], "3");
+        assert_eq!(fields["unit"], "sat");
+        assert_eq!(fields["amount"], "21");
+        assert_eq!(fields["message"], "wallet operation completed");
+        assert_eq!(fields.len(), 5);
+    }
+}
@@ -22,18 +22,72 @@ pub enum Error {
     ConvertPrivateKeyToXpriv(String),
     #[error("Failed to generate blinded messages: {0}")]
     GenerateBlindedMessages(String),
+    #[error("Invalid seed phrase word count: {0}, expected one of 12, 15, 18, 21 or 24")]
+    InvalidWordCount(usize),
+    #[error(
+        "Invalid seed phrase checksum, one of the words is probably misspelled or out of order"
+    )]
+    InvalidChecksum,
+    #[error("Invalid seed phrase: word {0} is not part of the BIP39 english wordlist")]
+    UnknownWord(usize),
+    #[error("Invalid seed phrase: {0}")]
+    InvalidSeedPhrase(String),
+}
+
+/// Number of words of a BIP39 mnemonic
+///
+/// Each extra 3 words adds 32 bits of entropy, from 128 bits for 12 words to 256 bits for 24.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WordCount {
+    #[default]
+    Twelve = 12,
+    Fifteen = 15,
+    Eighteen = 18,
+    TwentyOne = 21,
+    TwentyFour = 24,
+}
+
+impl TryFrom<usize> for WordCount {
+    type Error = Error;
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        let word_count = match value {
+            12 => WordCount::Twelve,
+            15 => WordCount::Fifteen,
+            18 => WordCount::Eighteen,
+            21 => WordCount::TwentyOne,
+            24 => WordCount::TwentyFour,
+            n => return Err(Error::InvalidWordCount(n)),
+        };
+
+        Ok(word_count)
+    }
 }
 
 // Create a new seed phrase mnemonic with 12 words and BIP39 standard
 pub fn create_random() -> Result<Mnemonic, Error> {
-    let mnemonic = Mnemonic::generate_in(Language::English, 12)
+    create_random_with_word_count(WordCount::default())
+}
+
+pub fn create_random_with_word_count(word_count: WordCount) -> Result<Mnemonic, Error> {
+    let mnemonic = Mnemonic::generate_in(Language::English, word_count as usize)
         .map_err(|e| Error::GenerateMnemonic(e.to_string()))?;
     Ok(mnemonic)
 }
 
+/// Parse and validate a user provided seed phrase
+///
+/// The errors are specific enough to be shown to a user restoring its wallet,
+/// so that a typo is not mistaken for a wallet with no funds.
 pub fn create_from_str(s: &str) -> Result<Mnemonic, Error> {
-    let mnemonic = Mnemonic::parse_in_normalized(Language::English, s)
-        .map_err(|e| Error::GenerateMnemonic(e.to_string()))?;
+    WordCount::try_from(s.split_whitespace().count())?;
+
+    let mnemonic = Mnemonic::parse_in_normalized(Language::English, s).map_err(|e| match e {
+        bip39::Error::BadWordCount(n) => Error::InvalidWordCount(n),
+        bip39::Error::InvalidChecksum => Error::InvalidChecksum,
+        bip39::Error::UnknownWord(i) => Error::UnknownWord(i + 1),
+        e => Error::InvalidSeedPhrase(e.to_string()),
+    })?;
     Ok(mnemonic)
 }
 
@@ -99,4 +153,51 @@ mod tests {
             assert!(!word.is_empty(), "Word {} in seed phrase is empty", i + 1);
         }
     }
+
+    #[test]
+    fn test_create_seed_phrase_with_word_count() {
+        for n in [12, 15, 18, 21, 24] {
+            let seed_phrase =
+                create_random_with_word_count(WordCount::try_from(n).unwrap()).unwrap();
+            assert_eq!(seed_phrase.word_count(), n);
+        }
+    }
+
+    #[test]
+    fn test_parse_valid_24_words_seed_phrase() {
+        let seed_phrase = create_from_str(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art",
+        )
+        .unwrap();
+        assert_eq!(seed_phrase.word_count(), 24);
+    }
+
+    #[test]
+    fn test_parse_seed_phrase_with_broken_checksum() {
+        let res = create_from_str(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon",
+        );
+        assert!(matches!(res, Err(Error::InvalidChecksum)));
+    }
+
+    #[test]
+    fn test_parse_seed_phrase_with_unsupported_word_count() {
+        let res = create_from_str(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+        );
+        assert!(matches!(res, Err(Error::InvalidWordCount(11))));
+
+        let res = create_from_str(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+        );
+        assert!(matches!(res, Err(Error::InvalidWordCount(13))));
+    }
+
+    #[test]
+    fn test_parse_seed_phrase_with_unknown_word() {
+        let res = create_from_str(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandonn",
+        );
+        assert!(matches!(res, Err(Error::UnknownWord(12))));
+    }
 }
@@ -1,25 +1,63 @@
 use num_traits::Zero;
-use nuts::{Amount, traits::Unit};
+use nuts::{Amount, nut01::PublicKey, traits::Unit};
 use rusqlite::Connection;
 
-use crate::db;
+use crate::{
+    InputsSelection, db,
+    errors::Error,
+    types::{NodeUrl, ProofState, compact_wad::CompactWads},
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum PlanSpendingError {
     #[error("failed to iteract with the database: {0}")]
     Rusqlite(#[from] rusqlite::Error),
+    #[error("failed to select the proofs to spend: {0}")]
+    SelectInputs(#[from] Error),
     #[error("not enough funds available for unit {0}, requested: {1}, available: {2}")]
     NotEnoughFunds(String, Amount, Amount),
     #[error("duplicate node id {0} in prefered nodes ids")]
     DuplicatePreferedNodeId(u32),
 }
 
+/// How to split a payment between the nodes holding funds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SpendingStrategy {
+    /// Use the whole balance of a node before touching the next one
+    ///
+    /// Produces as few wads as possible.
+    #[default]
+    DrainFirst,
+    /// Take from each node proportionally to its balance
+    ///
+    /// Keeps the funds spread across nodes, limiting the exposure to any single one of them.
+    Balanced,
+}
+
+/// The part of a payment covered by one node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeSpending {
+    pub node_id: u32,
+    pub amount: Amount,
+    /// The proofs that would be used, if nothing changes in the db until then
+    pub inputs: InputsSelection,
+}
+
+/// Decide which nodes, and which of their proofs, to use to pay `amount_to_send`
+///
+/// `prefered_node_ids` are used first, in order.
+/// Other nodes only get involved if the prefered ones cannot cover the whole amount.
 pub fn plan_spending<U: Unit>(
     db_conn: &Connection,
     amount_to_send: Amount,
     unit: U,
     prefered_node_ids: &[u32],
-) -> Result<Vec<(u32, Amount)>, PlanSpendingError> {
+    strategy: SpendingStrategy,
+) -> Result<Vec<NodeSpending>, PlanSpendingError> {
+    if amount_to_send.is_zero() {
+        return Err(Error::ZeroAmountSend.into());
+    }
+
     // Check all prefered nodes are unique
     // Otherwise we will try to spend the same proofs twice :(
     for i in 0..prefered_node_ids.len() {
@@ -29,50 +67,555 @@ pub fn plan_spending<U: Unit>(
             ));
         }
     }
-    let mut amount_left_to_send = amount_to_send;
 
-    let mut amount_per_node_id = Vec::new();
+    let mut available_per_node = Vec::with_capacity(prefered_node_ids.len());
+    let mut total_available = Amount::ZERO;
     for node_id in prefered_node_ids {
-        let total_amount_available =
+        let node_available =
             db::proof::get_node_total_available_amount_of_unit(db_conn, *node_id, unit.as_ref())?;
-        if total_amount_available < amount_left_to_send {
-            amount_left_to_send -= total_amount_available;
-            amount_per_node_id.push((*node_id, total_amount_available));
-        } else {
-            amount_per_node_id.push((*node_id, amount_left_to_send));
-            amount_left_to_send = Amount::ZERO;
-            break;
+        available_per_node.push((*node_id, node_available));
+        total_available += node_available;
+    }
+
+    if total_available < amount_to_send {
+        let others = db::proof::get_nodes_ids_and_available_funds_ordered_desc(
+            db_conn,
+            unit.as_ref(),
+            prefered_node_ids,
+        )?;
+        for (node_id, node_available) in others {
+            available_per_node.push((node_id, node_available));
+            total_available += node_available;
         }
     }
 
-    if amount_left_to_send.is_zero() {
-        return Ok(amount_per_node_id);
+    if total_available < amount_to_send {
+        return Err(PlanSpendingError::NotEnoughFunds(
+            unit.to_string(),
+            amount_to_send,
+            total_available,
+        ));
+    }
+
+    let amount_per_node = match strategy {
+        SpendingStrategy::DrainFirst => drain_first(&available_per_node, amount_to_send),
+        SpendingStrategy::Balanced => {
+            balanced(&available_per_node, total_available, amount_to_send)
+        }
+    };
+
+    let mut plan = Vec::with_capacity(amount_per_node.len());
+    for (node_id, amount) in amount_per_node {
+        let inputs = crate::select_inputs(db_conn, node_id, amount, unit.as_ref())?
+            // Balances were read from the same db just above
+            .ok_or_else(|| {
+                PlanSpendingError::NotEnoughFunds(unit.to_string(), amount_to_send, total_available)
+            })?;
+        plan.push(NodeSpending {
+            node_id,
+            amount,
+            inputs,
+        });
     }
 
-    let ordered_nodes_and_amount = db::proof::get_nodes_ids_and_available_funds_ordered_desc(
-        db_conn,
-        unit.as_ref(),
-        prefered_node_ids,
-    )?;
+    Ok(plan)
+}
+
+fn drain_first(available_per_node: &[(u32, Amount)], amount_to_send: Amount) -> Vec<(u32, Amount)> {
+    let mut amount_left_to_send = amount_to_send;
+    let mut amount_per_node = Vec::new();
 
-    for (node_id, total_amount_available) in ordered_nodes_and_amount {
-        if total_amount_available < amount_left_to_send {
-            amount_left_to_send -= total_amount_available;
-            amount_per_node_id.push((node_id, total_amount_available));
-        } else {
-            amount_per_node_id.push((node_id, amount_left_to_send));
-            amount_left_to_send = Amount::ZERO;
+    for (node_id, node_available) in available_per_node {
+        if amount_left_to_send.is_zero() {
             break;
         }
+        if node_available.is_zero() {
+            continue;
+        }
+        let amount = (*node_available).min(amount_left_to_send);
+        amount_per_node.push((*node_id, amount));
+        amount_left_to_send -= amount;
     }
 
-    if !amount_left_to_send.is_zero() {
-        return Err(PlanSpendingError::NotEnoughFunds(
-            unit.to_string(),
-            amount_to_send,
-            amount_to_send - amount_left_to_send,
+    amount_per_node
+}
+
+fn balanced(
+    available_per_node: &[(u32, Amount)],
+    total_available: Amount,
+    amount_to_send: Amount,
+) -> Vec<(u32, Amount)> {
+    let total_available = u128::from(u64::from(total_available));
+    let amount_to_send_u128 = u128::from(u64::from(amount_to_send));
+
+    // Rounding down guarantees no node is asked more than it holds
+    let mut amount_per_node: Vec<(u32, Amount, Amount)> = available_per_node
+        .iter()
+        .map(|(node_id, node_available)| {
+            let share =
+                amount_to_send_u128 * u128::from(u64::from(*node_available)) / total_available;
+            (*node_id, Amount::from(share as u64), *node_available)
+        })
+        .collect();
+
+    // Rounding leaves at most one unit per node to distribute
+    let mut rest = amount_to_send
+        - amount_per_node
+            .iter()
+            .fold(Amount::ZERO, |acc, (_, share, _)| acc + *share);
+    for (_, share, node_available) in amount_per_node.iter_mut() {
+        if rest.is_zero() {
+            break;
+        }
+        let extra = (*node_available - *share).min(rest);
+        *share += extra;
+        rest -= extra;
+    }
+
+    amount_per_node
+        .into_iter()
+        .filter(|(_, share, _)| !share.is_zero())
+        .map(|(node_id, share, _)| (node_id, share))
+        .collect()
+}
+
+/// What sending an amount from a node would consume, computed without reserving any proof
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendEstimate {
+    /// Number of proofs that will be part of the wad
+    pub n_inputs: usize,
+    /// Fee charged by the node for all the inputs spent, including the swap one if any
+    pub input_fee: Amount,
+    /// Whether a proof has to be swapped first to produce the exact amount
+    pub needs_swap: bool,
+}
+
+/// Run the same selection as `fetch_inputs_ids_from_db_or_node` as a dry-run
+///
+/// Returns `None` if the node doesn't hold enough funds.
+pub fn estimate_send(
+    db_conn: &Connection,
+    node_id: u32,
+    amount: Amount,
+    unit: &str,
+    input_fee_ppk: u64,
+) -> Result<Option<SendEstimate>, Error> {
+    let InputsSelection {
+        proofs_ids,
+        proof_to_swap,
+        remaining_amount,
+        ..
+    } = match crate::select_inputs(db_conn, node_id, amount, unit)? {
+        Some(selection) => selection,
+        None => return Ok(None),
+    };
+
+    // The swap outputs are split in powers of two,
+    // each one of those making up the remaining amount becomes an input of the wad
+    let n_inputs = proofs_ids.len() + remaining_amount.split().count();
+    let needs_swap = proof_to_swap.is_some();
+    let n_spent = n_inputs + usize::from(needs_swap);
+
+    Ok(Some(SendEstimate {
+        n_inputs,
+        input_fee: compute_input_fee(n_spent, input_fee_ppk),
+        needs_swap,
+    }))
+}
+
+/// NUT-02 fee: the sum of the per-proof fees, expressed in thousandths, rounded up
+pub(crate) fn compute_input_fee(n_inputs: usize, input_fee_ppk: u64) -> Amount {
+    fee_from_total_ppk(n_inputs as u64 * input_fee_ppk)
+}
+
+/// Same as [`compute_input_fee`], for inputs spread over keysets with different fees
+///
+/// The rounding applies once to the whole sum, not per keyset.
+pub(crate) fn fee_from_total_ppk(total_input_fee_ppk: u64) -> Amount {
+    Amount::from(total_input_fee_ppk.div_ceil(1000))
+}
+
+/// Reserve the proofs of every node and build the matching wads
+///
+/// Everything happens in a single db transaction.
+/// If the proofs of any node cannot be reserved, the transaction is dropped
+/// and the proofs of all the other nodes remain `Unspent`.
+pub fn create_wads_atomic<U: Unit>(
+    db_conn: &mut Connection,
+    unit: U,
+    proofs_ids_per_node: &[(NodeUrl, Vec<PublicKey>)],
+    memo: Option<String>,
+) -> Result<CompactWads<U>, Error> {
+    let tx = db_conn.transaction()?;
+
+    let mut wads = Vec::with_capacity(proofs_ids_per_node.len());
+    for (node_url, proofs_ids) in proofs_ids_per_node {
+        let states = db::proof::get_proofs_state_by_ids(&tx, proofs_ids)?;
+        if states.len() != proofs_ids.len()
+            || states.iter().any(|state| *state != ProofState::Unspent)
+        {
+            return Err(Error::ProofNotAvailable);
+        }
+
+        let proofs = crate::load_tokens_from_db(&tx, proofs_ids)?;
+        wads.push(crate::wad::create_from_parts(
+            node_url.clone(),
+            unit,
+            memo.clone(),
+            proofs,
+        )?);
+    }
+
+    tx.commit()?;
+
+    Ok(CompactWads::new(wads))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db::test_utils, types::compact_wad::tests::TestUnit};
+
+    const UNIT: &str = "sat";
+
+    fn add_node_with_proofs(conn: &Connection, amounts: &[u64]) -> u32 {
+        let node_id = test_utils::insert_node(conn, UNIT);
+        for amount in amounts {
+            test_utils::insert_proof(conn, node_id, *amount, ProofState::Unspent);
+        }
+
+        node_id
+    }
+
+    fn setup_node_with_proofs(amounts: &[u64]) -> (Connection, u32) {
+        let conn = test_utils::setup_db();
+        let node_id = add_node_with_proofs(&conn, amounts);
+        (conn, node_id)
+    }
+
+    fn count_reserved(conn: &Connection) -> usize {
+        conn.query_row(
+            "SELECT COUNT(*) FROM proof WHERE state = ?1;",
+            [ProofState::Reserved],
+            |r| r.get(0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn insufficient_funds_reports_shortfall() {
+        let (conn, node_id) = setup_node_with_proofs(&[1, 2, 8, 16]);
+
+        let res =
+            crate::select_inputs_or_insufficient_funds(&conn, node_id, Amount::from(42u64), UNIT);
+
+        assert!(matches!(
+            res,
+            Err(Error::InsufficientFunds { required, available })
+                if required == Amount::from(42u64) && available == Amount::from(27u64)
+        ));
+        assert!(
+            crate::select_inputs_or_insufficient_funds(&conn, node_id, Amount::from(27u64), UNIT)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn estimate_matches_inputs_consumed_by_send() {
+        let (conn, node_id) = setup_node_with_proofs(&[1, 2, 8, 16, 32]);
+
+        let estimate = estimate_send(&conn, node_id, Amount::from(42u64), UNIT, 100)
+            .unwrap()
+            .unwrap();
+        assert_eq!(count_reserved(&conn), 0);
+
+        let selection = crate::select_inputs(&conn, node_id, Amount::from(42u64), UNIT)
+            .unwrap()
+            .unwrap();
+        crate::load_tokens_from_db(&conn, &selection.proofs_ids).unwrap();
+
+        assert_eq!(
+            estimate,
+            SendEstimate {
+                n_inputs: 3,
+                input_fee: Amount::from(1u64),
+                needs_swap: false,
+            }
+        );
+        // 32 + 8 + 2
+        assert_eq!(count_reserved(&conn), 3);
+    }
+
+    #[test]
+    fn input_fee_is_rounded_up_per_send() {
+        for (n_inputs, input_fee_ppk, expected_fee) in [
+            (0, 100, 0),
+            (1, 0, 0),
+            (1, 1, 1),
+            (1, 100, 1),
+            (10, 100, 1),
+            (11, 100, 2),
+            (3, 333, 1),
+            (3, 334, 2),
+            (3, 1000, 3),
+            (1001, 1, 2),
+        ] {
+            assert_eq!(
+                compute_input_fee(n_inputs, input_fee_ppk),
+                Amount::from(expected_fee as u64),
+                "{n_inputs} inputs at {input_fee_ppk} ppk"
+            );
+        }
+    }
+
+    #[test]
+    fn estimate_fee_depends_on_the_keyset_fee() {
+        // 42 is sent with the 32, 8 and 2 proofs, no swap needed
+        let (conn, node_id) = setup_node_with_proofs(&[1, 2, 8, 16, 32]);
+
+        for (input_fee_ppk, expected_fee) in [(0, 0), (1, 1), (333, 1), (334, 2), (1000, 3)] {
+            let estimate = estimate_send(&conn, node_id, Amount::from(42u64), UNIT, input_fee_ppk)
+                .unwrap()
+                .unwrap();
+
+            assert_eq!(estimate.n_inputs, 3);
+            assert_eq!(
+                estimate.input_fee,
+                Amount::from(expected_fee as u64),
+                "at {input_fee_ppk} ppk"
+            );
+        }
+    }
+
+    #[test]
+    fn estimate_requires_swap() {
+        let (conn, node_id) = setup_node_with_proofs(&[32]);
+
+        let estimate = estimate_send(&conn, node_id, Amount::from(5u64), UNIT, 500)
+            .unwrap()
+            .unwrap();
+
+        // 4 + 1 produced by swapping the 32 proof
+        assert_eq!(
+            estimate,
+            SendEstimate {
+                n_inputs: 2,
+                input_fee: Amount::from(2u64),
+                needs_swap: true,
+            }
+        );
+        assert_eq!(count_reserved(&conn), 0);
+    }
+
+    #[test]
+    fn estimate_not_enough_funds() {
+        let (conn, node_id) = setup_node_with_proofs(&[1, 2]);
+
+        assert!(
+            estimate_send(&conn, node_id, Amount::from(4u64), UNIT, 0)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    fn amounts_per_node(plan: &[NodeSpending]) -> Vec<(u32, u64)> {
+        plan.iter()
+            .map(|s| (s.node_id, u64::from(s.amount)))
+            .collect()
+    }
+
+    #[test]
+    fn drain_first_empties_nodes_in_order() {
+        let conn = test_utils::setup_db();
+        let node_a = add_node_with_proofs(&conn, &[4, 2]);
+        let node_b = add_node_with_proofs(&conn, &[16, 8]);
+        let node_c = add_node_with_proofs(&conn, &[1]);
+
+        let plan = plan_spending(
+            &conn,
+            Amount::from(10u64),
+            TestUnit::Sat,
+            &[node_a],
+            SpendingStrategy::DrainFirst,
+        )
+        .unwrap();
+
+        // Prefered node first, then the others by descending balance
+        assert_eq!(amounts_per_node(&plan), vec![(node_a, 6), (node_b, 4)]);
+        assert!(plan.iter().all(|s| s.node_id != node_c));
+        assert_eq!(plan[0].inputs.proofs_ids.len(), 2);
+        assert!(plan[0].inputs.proof_to_swap.is_none());
+        // 4 must be obtained by swapping the 8 proof of node b
+        assert!(plan[1].inputs.proofs_ids.is_empty());
+        assert_eq!(plan[1].inputs.proof_to_swap.unwrap().1, Amount::from(8u64));
+    }
+
+    #[test]
+    fn balanced_splits_proportionally_to_balances() {
+        let conn = test_utils::setup_db();
+        let node_a = add_node_with_proofs(&conn, &[8, 4]);
+        let node_b = add_node_with_proofs(&conn, &[16, 8, 4]);
+
+        let plan = plan_spending(
+            &conn,
+            Amount::from(16u64),
+            TestUnit::Sat,
+            &[],
+            SpendingStrategy::Balanced,
+        )
+        .unwrap();
+
+        let mut amounts = amounts_per_node(&plan);
+        amounts.sort();
+        // The rounding remainder goes to the node with the highest balance
+        assert_eq!(amounts, vec![(node_a, 4), (node_b, 12)]);
+        let total = plan.iter().fold(Amount::ZERO, |acc, s| acc + s.amount);
+        assert_eq!(total, Amount::from(16u64));
+    }
+
+    #[test]
+    fn balanced_never_exceeds_node_balance() {
+        let conn = test_utils::setup_db();
+        let node_a = add_node_with_proofs(&conn, &[1]);
+        let node_b = add_node_with_proofs(&conn, &[1]);
+        let node_c = add_node_with_proofs(&conn, &[1]);
+
+        let plan = plan_spending(
+            &conn,
+            Amount::from(3u64),
+            TestUnit::Sat,
+            &[node_a, node_b, node_c],
+            SpendingStrategy::Balanced,
+        )
+        .unwrap();
+
+        assert_eq!(
+            amounts_per_node(&plan),
+            vec![(node_a, 1), (node_b, 1), (node_c, 1)]
+        );
+    }
+
+    #[test]
+    fn plan_not_enough_funds() {
+        let conn = test_utils::setup_db();
+        add_node_with_proofs(&conn, &[4]);
+        add_node_with_proofs(&conn, &[2]);
+
+        for strategy in [SpendingStrategy::DrainFirst, SpendingStrategy::Balanced] {
+            let res = plan_spending(&conn, Amount::from(8u64), TestUnit::Sat, &[], strategy);
+            assert!(
+                matches!(res, Err(PlanSpendingError::NotEnoughFunds(_, _, available)) if available == Amount::from(6u64))
+            );
+        }
+    }
+
+    fn proofs_ids_and_states(conn: &Connection, node_id: u32) -> Vec<(PublicKey, ProofState)> {
+        let mut stmt = conn
+            .prepare("SELECT y, state FROM proof WHERE node_id = ?1;")
+            .unwrap();
+        stmt.query_map([node_id], |r| Ok((r.get(0)?, r.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn create_wads_atomic_reserves_all_proofs() {
+        let mut conn = test_utils::setup_db();
+        let node_a = add_node_with_proofs(&conn, &[4, 2]);
+        let node_b = add_node_with_proofs(&conn, &[8]);
+        let plan: Vec<_> = [node_a, node_b]
+            .into_iter()
+            .map(|node_id| {
+                let node_url = db::node::get_url_by_id(&conn, node_id).unwrap().unwrap();
+                let ys = proofs_ids_and_states(&conn, node_id)
+                    .into_iter()
+                    .map(|(y, _)| y)
+                    .collect();
+                (node_url, ys)
+            })
+            .collect();
+
+        let wads = create_wads_atomic(&mut conn, TestUnit::Sat, &plan, None).unwrap();
+
+        assert_eq!(wads.0.len(), 2);
+        assert_eq!(wads.0[0].proofs().len(), 2);
+        assert_eq!(wads.0[1].proofs().len(), 1);
+        assert_eq!(count_reserved(&conn), 3);
+    }
+
+    #[test]
+    fn zero_amount_send_is_rejected() {
+        let (conn, node_id) = setup_node_with_proofs(&[1, 2]);
+
+        let res = plan_spending(
+            &conn,
+            Amount::ZERO,
+            TestUnit::Sat,
+            &[node_id],
+            SpendingStrategy::DrainFirst,
+        );
+        assert!(matches!(
+            res,
+            Err(PlanSpendingError::SelectInputs(Error::ZeroAmountSend))
         ));
+        let res = crate::select_inputs_or_insufficient_funds(&conn, node_id, Amount::ZERO, UNIT);
+        assert!(matches!(res, Err(Error::ZeroAmountSend)));
+        assert_eq!(count_reserved(&conn), 0);
     }
 
-    Ok(amount_per_node_id)
+    #[test]
+    fn create_wads_atomic_rejects_empty_proof_list() {
+        let mut conn = test_utils::setup_db();
+        let node_id = add_node_with_proofs(&conn, &[4]);
+        let node_url = db::node::get_url_by_id(&conn, node_id).unwrap().unwrap();
+
+        let res = create_wads_atomic(&mut conn, TestUnit::Sat, &[(node_url, vec![])], None);
+
+        assert!(matches!(res, Err(Error::EmptyWad)));
+    }
+
+    #[test]
+    fn create_wads_atomic_reverts_all_nodes_on_failure() {
+        let mut conn = test_utils::setup_db();
+        let node_a = add_node_with_proofs(&conn, &[4, 2]);
+        let node_b = add_node_with_proofs(&conn, &[8, 1]);
+
+        // One of the second node proofs has already been spent
+        let node_b_ys: Vec<_> = proofs_ids_and_states(&conn, node_b)
+            .into_iter()
+            .map(|(y, _)| y)
+            .collect();
+        db::proof::set_proofs_to_state(&conn, &node_b_ys[..1], ProofState::Spent).unwrap();
+
+        let plan = vec![
+            (
+                db::node::get_url_by_id(&conn, node_a).unwrap().unwrap(),
+                proofs_ids_and_states(&conn, node_a)
+                    .into_iter()
+                    .map(|(y, _)| y)
+                    .collect(),
+            ),
+            (
+                db::node::get_url_by_id(&conn, node_b).unwrap().unwrap(),
+                node_b_ys.clone(),
+            ),
+        ];
+
+        let res = create_wads_atomic(&mut conn, TestUnit::Sat, &plan, None);
+
+        assert!(matches!(res, Err(Error::ProofNotAvailable)));
+        assert!(
+            proofs_ids_and_states(&conn, node_a)
+                .iter()
+                .all(|(_, state)| *state == ProofState::Unspent)
+        );
+        assert_eq!(
+            proofs_ids_and_states(&conn, node_b),
+            vec![
+                (node_b_ys[0], ProofState::Spent),
+                (node_b_ys[1], ProofState::Unspent)
+            ]
+        );
+    }
 }
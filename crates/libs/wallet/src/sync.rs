@@ -1,15 +1,20 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use node_client::{NodeClient, QuoteStateRequest};
-use nuts::{nut04::MintQuoteState, nut05::MeltQuoteState};
+use node_client::{CheckStateRequest, NodeClient, QuoteStateRequest};
+use nuts::{nut01::PublicKey, nut04::MintQuoteState, nut05::MeltQuoteState};
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::Connection;
 use tonic::transport::Channel;
 use uuid::Uuid;
 
 use crate::{
     db::{self, wad::SyncData},
     errors::Error,
+    types::{NodeUrl, ProofState},
 };
 
 pub async fn mint_quote(
@@ -115,13 +120,140 @@ pub async fn melt_quote(
     }
 }
 
+/// Changes made to the local proofs by [`unsettled_proofs`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProofsSyncResult {
+    /// Proofs the node has seen spent
+    pub spent: usize,
+    /// Pending proofs the node never received, available again
+    pub released: usize,
+}
+
+/// Reconcile the Pending and Reserved proofs of `node_id` with their state on the node (NUT-07)
+///
+/// An interrupted swap or melt leaves its inputs Pending. Once the node reports them unspent,
+/// the operation never reached it and they can be used again.
+/// Reserved proofs belong to a wad that may still be received, they stay reserved until spent.
+pub async fn unsettled_proofs(
+    pool: Pool<SqliteConnectionManager>,
+    node_client: &mut NodeClient<Channel>,
+    node_id: u32,
+) -> Result<ProofsSyncResult, Error> {
+    let local_proofs = {
+        let db_conn = pool.get()?;
+        db::proof::get_unsettled_proofs(&db_conn, node_id)?
+    };
+    if local_proofs.is_empty() {
+        return Ok(ProofsSyncResult::default());
+    }
+
+    let response = node_client
+        .check_state(CheckStateRequest {
+            ys: local_proofs
+                .iter()
+                .map(|(y, _)| y.to_bytes().to_vec())
+                .collect(),
+        })
+        .await?
+        .into_inner();
+    let node_states = response
+        .states
+        .into_iter()
+        .map(|s| -> Result<_, Error> {
+            let y = PublicKey::from_slice(&s.y)?;
+            let state = node_client::ProofState::try_from(s.state).map_err(|_| {
+                Error::UnexpectedProofState(format!("Invalid proof state: {}", s.state))
+            })?;
+            Ok((y, state))
+        })
+        .collect::<Result<HashMap<_, _>, _>>()?;
+
+    let mut db_conn = pool.get()?;
+    let tx = db_conn.transaction()?;
+    let result = apply_node_proof_states(&tx, &local_proofs, &node_states)?;
+    tx.commit()?;
+
+    Ok(result)
+}
+
+fn apply_node_proof_states(
+    conn: &Connection,
+    local_proofs: &[(PublicKey, ProofState)],
+    node_states: &HashMap<PublicKey, node_client::ProofState>,
+) -> Result<ProofsSyncResult, Error> {
+    let mut spent = Vec::new();
+    let mut released = Vec::new();
+    for (y, local_state) in local_proofs {
+        match (local_state, node_states.get(y)) {
+            (_, Some(node_client::ProofState::PsSpent)) => spent.push(*y),
+            (ProofState::Pending, Some(node_client::ProofState::PsUnspent)) => released.push(*y),
+            _ => {}
+        }
+    }
+
+    db::proof::set_proofs_to_state(conn, &spent, ProofState::Spent)?;
+    db::proof::set_proofs_to_state(conn, &released, ProofState::Unspent)?;
+
+    Ok(ProofsSyncResult {
+        spent: spent.len(),
+        released: released.len(),
+    })
+}
+
+/// The nodes to sync, all of them when `node_ids` is `None`
+///
+/// Errors if any of `node_ids` is unknown, rather than silently syncing less than asked.
+pub fn select_nodes(
+    conn: &Connection,
+    node_ids: Option<&[u32]>,
+) -> Result<Vec<(u32, NodeUrl)>, Error> {
+    let nodes = db::node::fetch_all(conn)?;
+    let Some(node_ids) = node_ids else {
+        return Ok(nodes);
+    };
+
+    if let Some(unknown) = node_ids
+        .iter()
+        .find(|id| !nodes.iter().any(|(node_id, _)| node_id == *id))
+    {
+        return Err(Error::UnknownNode(*unknown));
+    }
+
+    Ok(nodes
+        .into_iter()
+        .filter(|(node_id, _)| node_ids.contains(node_id))
+        .collect())
+}
+
+fn get_pending_wads_of_nodes(
+    conn: &Connection,
+    node_ids: Option<&[u32]>,
+) -> Result<Vec<SyncData>, Error> {
+    let pending_wads = db::wad::get_pending_wads(conn)?;
+    if node_ids.is_none() {
+        return Ok(pending_wads);
+    }
+
+    let node_urls: Vec<NodeUrl> = select_nodes(conn, node_ids)?
+        .into_iter()
+        .map(|(_, url)| url)
+        .collect();
+
+    Ok(pending_wads
+        .into_iter()
+        .filter(|wad| node_urls.contains(&wad.node_url))
+        .collect())
+}
+
+/// Sync the pending wads, restricted to those of `node_ids` when set
 pub async fn pending_wads(
     pool: Pool<SqliteConnectionManager>,
     root_ca_certificate: Option<tonic::transport::Certificate>,
+    node_ids: Option<&[u32]>,
 ) -> Result<Vec<WadSyncResult>, Error> {
     let pending_wads = {
         let db_conn = pool.get()?;
-        db::wad::get_pending_wads(&db_conn)?
+        get_pending_wads_of_nodes(&db_conn, node_ids)?
     };
 
     let mut results = Vec::with_capacity(pending_wads.len());
@@ -143,7 +275,7 @@ async fn sync_single_wad(
     sync_info: SyncData,
     root_ca_certificate: Option<tonic::transport::Certificate>,
 ) -> Result<Option<db::wad::WadStatus>, Error> {
-    use node_client::{CheckStateRequest, ProofState};
+    use node_client::ProofState;
 
     let SyncData {
         id: wad_id,
@@ -200,3 +332,78 @@ pub struct WadSyncResult {
     pub wad_id: Uuid,
     pub result: Result<Option<db::wad::WadStatus>, String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::db::test_utils;
+
+    fn insert_proof(conn: &Connection, node_id: u32, state: ProofState) -> PublicKey {
+        test_utils::insert_proof(conn, node_id, 1, state)
+    }
+
+    #[test]
+    fn unsettled_proofs_follow_the_node_state() {
+        let (conn, node_id) = test_utils::setup_node("sat");
+
+        let pending_spent = insert_proof(&conn, node_id, ProofState::Pending);
+        let pending_unspent = insert_proof(&conn, node_id, ProofState::Pending);
+        let pending_unknown = insert_proof(&conn, node_id, ProofState::Pending);
+        let reserved_spent = insert_proof(&conn, node_id, ProofState::Reserved);
+        let reserved_unspent = insert_proof(&conn, node_id, ProofState::Reserved);
+        let unspent = insert_proof(&conn, node_id, ProofState::Unspent);
+        let node_states = HashMap::from([
+            (pending_spent, node_client::ProofState::PsSpent),
+            (pending_unspent, node_client::ProofState::PsUnspent),
+            (reserved_spent, node_client::ProofState::PsSpent),
+            (reserved_unspent, node_client::ProofState::PsUnspent),
+        ]);
+
+        let local_proofs = db::proof::get_unsettled_proofs(&conn, node_id).unwrap();
+        assert_eq!(local_proofs.len(), 5);
+        let result = apply_node_proof_states(&conn, &local_proofs, &node_states).unwrap();
+
+        assert_eq!(
+            result,
+            ProofsSyncResult {
+                spent: 2,
+                released: 1
+            }
+        );
+        let state_of = |y| db::proof::get_proofs_state_by_ids(&conn, &[y]).unwrap()[0];
+        assert_eq!(state_of(pending_spent), ProofState::Spent);
+        assert_eq!(state_of(pending_unspent), ProofState::Unspent);
+        assert_eq!(state_of(pending_unknown), ProofState::Pending);
+        assert_eq!(state_of(reserved_spent), ProofState::Spent);
+        assert_eq!(state_of(reserved_unspent), ProofState::Reserved);
+        assert_eq!(state_of(unspent), ProofState::Unspent);
+    }
+
+    #[test]
+    fn filtered_sync_only_selects_the_given_nodes() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        db::create_tables(&mut conn).unwrap();
+        let first_url = NodeUrl::from_str("https://first.node:10003").unwrap();
+        let second_url = NodeUrl::from_str("https://second.node:10003").unwrap();
+        let first = db::node::insert(&conn, &first_url).unwrap();
+        let second = db::node::insert(&conn, &second_url).unwrap();
+        db::wad::register_wad(&conn, db::wad::WadType::OUT, &first_url, &None, &[]).unwrap();
+        let second_wad =
+            db::wad::register_wad(&conn, db::wad::WadType::OUT, &second_url, &None, &[]).unwrap();
+
+        let nodes = select_nodes(&conn, Some(&[second])).unwrap();
+        let wads = get_pending_wads_of_nodes(&conn, Some(&[second])).unwrap();
+
+        assert_eq!(nodes, vec![(second, second_url)]);
+        assert_eq!(wads.len(), 1);
+        assert_eq!(wads[0].id, second_wad);
+        assert_eq!(select_nodes(&conn, None).unwrap().len(), 2);
+        assert_eq!(get_pending_wads_of_nodes(&conn, None).unwrap().len(), 2);
+        assert!(matches!(
+            select_nodes(&conn, Some(&[first, 42])),
+            Err(Error::UnknownNode(42))
+        ));
+    }
+}
@@ -1,4 +1,5 @@
 use std::fmt;
+use std::io::{Read, Write};
 use std::str::FromStr;
 
 use num_traits::CheckedAdd;
@@ -22,18 +23,95 @@ use bitcoin::base64::{Engine as _, alphabet};
 pub enum Error {
     #[error("the total amount of this wad is to big")]
     WadValueOverflow,
-    #[error("unsuported wad format. Should start with {CASHU_PREFIX}")]
+    #[error("unsuported wad format. Should start with {CASHU_PREFIX} or {DEFLATE_PREFIX}")]
     UnsupportedWadFormat,
     #[error("failed to decode the base64 wad representation: {0}")]
     InvalidBase64(#[from] bitcoin::base64::DecodeError),
     #[error("failed to deserialize the CBOR wad representation: {0}")]
     InvalidCbor(#[from] ciborium::de::Error<std::io::Error>),
+    #[error("failed to serialize the wad to CBOR: {0}")]
+    SerializeCbor(#[from] ciborium::ser::Error<std::io::Error>),
+    #[error("failed to compress the wad: {0}")]
+    Compression(std::io::Error),
+    #[error("failed to decompress the wad: {0}")]
+    Decompression(std::io::Error),
+    #[error("decompressed wad is bigger than {MAX_DECOMPRESSED_WAD_SIZE} bytes")]
+    DecompressedWadTooBig,
+    #[error("unsupported binary wad version: {0:?}")]
+    UnsupportedBinaryVersion(Option<u8>),
 }
 
 impl<U: Unit> CompactWads<U> {
     pub fn new(wads: Vec<CompactWad<U>>) -> Self {
         Self(wads)
     }
+
+    /// Value held by each `(node_url, unit)` pair, in order of first appearance
+    pub fn summary(&self) -> Result<Vec<WadSummary<U>>, Error> {
+        let mut summaries: Vec<WadSummary<U>> = Vec::new();
+
+        for wad in self.0.iter() {
+            let value = wad.value()?;
+            let proof_count = wad.proofs.iter().map(|p| p.proofs.len()).sum();
+
+            match summaries
+                .iter_mut()
+                .find(|s| s.node_url == wad.node_url && s.unit == wad.unit)
+            {
+                Some(summary) => {
+                    summary.total = summary
+                        .total
+                        .checked_add(&value)
+                        .ok_or(Error::WadValueOverflow)?;
+                    summary.proof_count += proof_count;
+                }
+                None => summaries.push(WadSummary {
+                    node_url: wad.node_url.clone(),
+                    unit: wad.unit,
+                    total: value,
+                    proof_count,
+                }),
+            }
+        }
+
+        Ok(summaries)
+    }
+
+    /// Value of all the wads together
+    ///
+    /// Amounts of different units are added as is, use [`Self::total_by_unit`] when they may differ.
+    pub fn value(&self) -> Result<Amount, Error> {
+        self.0.iter().try_fold(Amount::ZERO, |acc, wad| {
+            acc.checked_add(&wad.value()?)
+                .ok_or(Error::WadValueOverflow)
+        })
+    }
+
+    /// Value held for each unit, all nodes together, in order of first appearance
+    pub fn total_by_unit(&self) -> Result<Vec<(U, Amount)>, Error> {
+        let mut totals: Vec<(U, Amount)> = Vec::new();
+
+        for summary in self.summary()? {
+            match totals.iter_mut().find(|(unit, _)| *unit == summary.unit) {
+                Some((_, total)) => {
+                    *total = total
+                        .checked_add(&summary.total)
+                        .ok_or(Error::WadValueOverflow)?;
+                }
+                None => totals.push((summary.unit, summary.total)),
+            }
+        }
+
+        Ok(totals)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WadSummary<U: Unit> {
+    pub node_url: NodeUrl,
+    pub unit: U,
+    pub total: Amount,
+    pub proof_count: usize,
 }
 
 impl<U: Unit + Serialize> fmt::Display for CompactWads<U> {
@@ -50,6 +128,19 @@ impl<U: Unit + Serialize> fmt::Display for CompactWads<U> {
     }
 }
 
+impl<U: Unit + Serialize> CompactWads<U> {
+    /// Same as `to_string` but each wad is compressed, see `CompactWad::to_compressed_string`
+    pub fn to_compressed_string(&self) -> Result<String, Error> {
+        let wads = self
+            .0
+            .iter()
+            .map(CompactWad::to_compressed_string)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(wads.join(":"))
+    }
+}
+
 impl<U: Unit + DeserializeOwned> FromStr for CompactWads<U> {
     type Err = Error;
 
@@ -97,18 +188,37 @@ impl<U: Unit> CompactWad<U> {
     }
 
     /// Value
+    ///
+    /// Wads are built by their sender, the amounts are not trusted to fit in a `u64`.
     #[inline]
     pub fn value(&self) -> Result<Amount, Error> {
-        let mut sum = Amount::ZERO;
+        Amount::try_sum(
+            self.proofs
+                .iter()
+                .flat_map(|token| token.proofs.iter().map(|p| p.amount)),
+        )
+        .map_err(|_| Error::WadValueOverflow)
+    }
+
+    /// Value held by each keyset, in the wad order
+    pub fn value_by_keyset(&self) -> Result<Vec<(KeysetId, Amount)>, Error> {
+        let mut subtotals: Vec<(KeysetId, Amount)> = Vec::with_capacity(self.proofs.len());
+
         for token in self.proofs.iter() {
-            for proof in token.proofs.iter() {
-                sum = sum
-                    .checked_add(&proof.amount)
-                    .ok_or(Error::WadValueOverflow)?;
+            let value = Amount::try_sum(token.proofs.iter().map(|p| p.amount))
+                .map_err(|_| Error::WadValueOverflow)?;
+            // The same keyset may appear in several entries of a wad crafted by hand
+            match subtotals.iter_mut().find(|(id, _)| *id == token.keyset_id) {
+                Some((_, subtotal)) => {
+                    *subtotal = subtotal
+                        .checked_add(&value)
+                        .ok_or(Error::WadValueOverflow)?;
+                }
+                None => subtotals.push((token.keyset_id, value)),
             }
         }
 
-        Ok(sum)
+        Ok(subtotals)
     }
 
     /// Memo
@@ -125,6 +235,57 @@ impl<U: Unit> CompactWad<U> {
 }
 
 pub const CASHU_PREFIX: &str = "cashuB";
+/// Same CBOR payload as `CASHU_PREFIX`, deflate compressed before being base64 encoded
+pub const DEFLATE_PREFIX: &str = "paynetD";
+/// First byte of the `to_bytes` representation
+pub const BINARY_WAD_VERSION: u8 = 1;
+/// Protects against decompression bombs, a few thousands proofs fit in it
+const MAX_DECOMPRESSED_WAD_SIZE: u64 = 4 * 1024 * 1024;
+
+impl<U: Unit + Serialize> CompactWad<U> {
+    /// Compressed alternative to the `Display` representation
+    ///
+    /// The hex secrets and keyset ids repeated in each proof compress well,
+    /// making wads with many proofs easier to share through QR codes or messaging apps.
+    /// Wallets that don't know this format will fail to parse it,
+    /// so the uncompressed format remains the default.
+    pub fn to_compressed_string(&self) -> Result<String, Error> {
+        let mut data = Vec::new();
+        ciborium::into_writer(self, &mut data)?;
+
+        let mut encoder =
+            flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(&data).map_err(Error::Compression)?;
+        let compressed = encoder.finish().map_err(Error::Compression)?;
+
+        let encoded = general_purpose::URL_SAFE.encode(compressed);
+        Ok(format!("{}{}", DEFLATE_PREFIX, encoded))
+    }
+}
+
+impl<U: Unit + Serialize> CompactWad<U> {
+    /// Binary representation, for transports that don't need a printable string (NFC, BLE)
+    ///
+    /// The CBOR payload is the same as the string formats, prefixed by `BINARY_WAD_VERSION`
+    /// so that the encoding can evolve without breaking wallets reading older wads.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut data = vec![BINARY_WAD_VERSION];
+        ciborium::into_writer(self, &mut data)?;
+
+        Ok(data)
+    }
+}
+
+impl<U: Unit + DeserializeOwned> CompactWad<U> {
+    /// Parse the output of `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        match bytes.split_first() {
+            Some((&BINARY_WAD_VERSION, cbor)) => Ok(ciborium::from_reader(cbor)?),
+            Some((&version, _)) => Err(Error::UnsupportedBinaryVersion(Some(version))),
+            None => Err(Error::UnsupportedBinaryVersion(None)),
+        }
+    }
+}
 
 impl<U: Unit + Serialize> fmt::Display for CompactWad<U> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -136,18 +297,41 @@ impl<U: Unit + Serialize> fmt::Display for CompactWad<U> {
     }
 }
 
+fn decode_base64(s: &str) -> Result<Vec<u8>, Error> {
+    let decode_config = general_purpose::GeneralPurposeConfig::new()
+        .with_decode_padding_mode(bitcoin::base64::engine::DecodePaddingMode::Indifferent);
+    let decoded = GeneralPurpose::new(&alphabet::URL_SAFE, decode_config).decode(s)?;
+
+    Ok(decoded)
+}
+
+fn decompress(compressed: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut decoder =
+        flate2::read::DeflateDecoder::new(compressed).take(MAX_DECOMPRESSED_WAD_SIZE + 1);
+    let mut decompressed = Vec::new();
+    decoder
+        .read_to_end(&mut decompressed)
+        .map_err(Error::Decompression)?;
+    if decompressed.len() as u64 > MAX_DECOMPRESSED_WAD_SIZE {
+        return Err(Error::DecompressedWadTooBig);
+    }
+
+    Ok(decompressed)
+}
+
 impl<U: Unit + DeserializeOwned> FromStr for CompactWad<U> {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let s = s
-            .strip_prefix(CASHU_PREFIX)
-            .ok_or(Error::UnsupportedWadFormat)?;
-
-        let decode_config = general_purpose::GeneralPurposeConfig::new()
-            .with_decode_padding_mode(bitcoin::base64::engine::DecodePaddingMode::Indifferent);
-        let decoded = GeneralPurpose::new(&alphabet::URL_SAFE, decode_config).decode(s)?;
-        let token = ciborium::from_reader(&decoded[..])?;
+        let cbor = if let Some(s) = s.strip_prefix(CASHU_PREFIX) {
+            decode_base64(s)?
+        } else if let Some(s) = s.strip_prefix(DEFLATE_PREFIX) {
+            decompress(&decode_base64(s)?)?
+        } else {
+            return Err(Error::UnsupportedWadFormat);
+        };
+
+        let token = ciborium::from_reader(&cbor[..])?;
         Ok(token)
     }
 }
@@ -231,7 +415,7 @@ where
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use nuts::nut00::secret::Secret;
     use nuts::nut01::PublicKey;
@@ -245,17 +429,21 @@ mod tests {
     #[serde(rename_all = "lowercase")]
     pub enum TestUnit {
         Sat,
+        Msat,
     }
 
     impl std::fmt::Display for TestUnit {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-            write!(f, "sat")
+            write!(f, "{}", self.as_ref())
         }
     }
 
     impl From<TestUnit> for u32 {
-        fn from(_: TestUnit) -> Self {
-            0
+        fn from(value: TestUnit) -> Self {
+            match value {
+                TestUnit::Sat => 0,
+                TestUnit::Msat => 1,
+            }
         }
     }
 
@@ -265,6 +453,7 @@ mod tests {
         fn from_str(s: &str) -> Result<Self, Self::Err> {
             match s {
                 "sat" => Ok(TestUnit::Sat),
+                "msat" => Ok(TestUnit::Msat),
                 _ => Err("invalid unit"),
             }
         }
@@ -302,14 +491,17 @@ mod tests {
 
         fn matching_asset(&self) -> Self::Asset {
             match self {
-                TestUnit::Sat => TestAsset::Btc,
+                TestUnit::Sat | TestUnit::Msat => TestAsset::Btc,
             }
         }
     }
 
     impl AsRef<str> for TestUnit {
         fn as_ref(&self) -> &str {
-            "sat"
+            match self {
+                TestUnit::Sat => "sat",
+                TestUnit::Msat => "msat",
+            }
         }
     }
 
@@ -502,8 +694,188 @@ mod tests {
         assert_eq!(wads, deserialized);
     }
 
+    #[test]
+    fn test_summary_groups_by_node_and_unit() {
+        let wad1 = create_test_compact_wad_multiple_proofs("mint1.example.com", &[1, 2]);
+        let mut wad2 = create_test_compact_wad_single_proof("mint1.example.com", 8);
+        wad2.unit = TestUnit::Msat;
+        let wad3 = create_test_compact_wad_multiple_proofs("mint2.example.com", &[4, 16]);
+        let wad4 = create_test_compact_wad_single_proof("mint1.example.com", 32);
+        let wads = CompactWads::new(vec![wad1, wad2, wad3, wad4]);
+
+        let summary = wads.summary().unwrap();
+
+        let mint1 = NodeUrl::from_str("https://mint1.example.com").unwrap();
+        let mint2 = NodeUrl::from_str("https://mint2.example.com").unwrap();
+        assert_eq!(
+            summary,
+            vec![
+                WadSummary {
+                    node_url: mint1.clone(),
+                    unit: TestUnit::Sat,
+                    total: Amount::from(35u64),
+                    proof_count: 3,
+                },
+                WadSummary {
+                    node_url: mint1,
+                    unit: TestUnit::Msat,
+                    total: Amount::from(8u64),
+                    proof_count: 1,
+                },
+                WadSummary {
+                    node_url: mint2,
+                    unit: TestUnit::Sat,
+                    total: Amount::from(20u64),
+                    proof_count: 2,
+                },
+            ]
+        );
+        assert_eq!(
+            wads.total_by_unit().unwrap(),
+            vec![
+                (TestUnit::Sat, Amount::from(55u64)),
+                (TestUnit::Msat, Amount::from(8u64))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_summary_overflow() {
+        let wad1 = create_test_compact_wad_single_proof("mint.example.com", u64::MAX);
+        let wad2 = create_test_compact_wad_single_proof("mint.example.com", 1);
+        let wads = CompactWads::new(vec![wad1, wad2]);
+
+        assert!(matches!(wads.summary(), Err(Error::WadValueOverflow)));
+        assert!(matches!(wads.total_by_unit(), Err(Error::WadValueOverflow)));
+        assert!(matches!(wads.value(), Err(Error::WadValueOverflow)));
+    }
+
+    #[test]
+    fn test_value_overflow() {
+        let wad =
+            create_test_compact_wad_multiple_proofs("mint.example.com", &[u64::MAX - 1, 1, 1]);
+
+        assert!(matches!(wad.value(), Err(Error::WadValueOverflow)));
+        assert!(matches!(
+            wad.value_by_keyset(),
+            Err(Error::WadValueOverflow)
+        ));
+    }
+
+    #[test]
+    fn test_value_by_keyset() {
+        let mut wad = create_test_compact_wad_multiple_proofs("mint.example.com", &[1, 2, 4]);
+        let first_keyset = wad.proofs[0].keyset_id;
+        let other_keyset = KeysetId::from_bytes(&[0, 7, 6, 5, 4, 3, 2, 1]).unwrap();
+        let mut other_proofs = wad.proofs[0].clone();
+        other_proofs.keyset_id = other_keyset;
+        other_proofs.proofs.truncate(1);
+        wad.proofs.push(other_proofs);
+        // A second entry for the first keyset is merged into its subtotal
+        wad.proofs.push(wad.proofs[0].clone());
+
+        assert_eq!(
+            wad.value_by_keyset().unwrap(),
+            vec![
+                (first_keyset, Amount::from(14u64)),
+                (other_keyset, Amount::from(1u64))
+            ]
+        );
+        assert_eq!(wad.value().unwrap(), Amount::from(15u64));
+
+        let wads = CompactWads::new(vec![
+            wad,
+            create_test_compact_wad_single_proof("mint.example.com", 5),
+        ]);
+        assert_eq!(wads.value().unwrap(), Amount::from(20u64));
+    }
+
+    #[test]
+    fn test_compressed_roundtrip() {
+        let amounts: Vec<u64> = (0..64).map(|i| 1 << (i % 32)).collect();
+        let wad = create_test_compact_wad_multiple_proofs("mint.example.com", &amounts);
+
+        let uncompressed = wad.to_string();
+        let compressed = wad.to_compressed_string().unwrap();
+        assert!(compressed.starts_with(DEFLATE_PREFIX));
+        assert!(compressed.len() < uncompressed.len());
+
+        let decoded = CompactWad::<TestUnit>::from_str(&compressed).unwrap();
+        assert_eq!(decoded, wad);
+        assert_eq!(decoded.proofs(), wad.proofs());
+        // The uncompressed format is still accepted
+        assert_eq!(
+            CompactWad::<TestUnit>::from_str(&uncompressed).unwrap(),
+            wad
+        );
+    }
+
+    #[test]
+    fn test_compressed_wads_roundtrip() {
+        let token1 = create_test_compact_wad_single_proof("mint1.example.com", 100);
+        let token2 = create_test_compact_wad_multiple_proofs("mint2.example.com", &[200, 300]);
+        let wads = CompactWads::new(vec![token1, token2]);
+
+        // Both formats can be mixed in the same string
+        let mixed = format!(
+            "{}:{}",
+            wads.0[0].to_compressed_string().unwrap(),
+            wads.0[1]
+        );
+        assert_eq!(CompactWads::<TestUnit>::from_str(&mixed).unwrap(), wads);
+
+        let compressed = wads.to_compressed_string().unwrap();
+        assert_eq!(compressed.matches(DEFLATE_PREFIX).count(), 2);
+        assert_eq!(
+            CompactWads::<TestUnit>::from_str(&compressed).unwrap(),
+            wads
+        );
+    }
+
+    #[test]
+    fn test_bytes_roundtrip() {
+        let mut wad = create_test_compact_wad_multiple_proofs("mint.example.com", &[1, 2, 4, 8]);
+        wad.memo = Some("lunch".to_string());
+
+        let bytes = wad.to_bytes().unwrap();
+        assert_eq!(bytes[0], BINARY_WAD_VERSION);
+
+        let decoded = CompactWad::<TestUnit>::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, wad);
+        assert_eq!(decoded.proofs(), wad.proofs());
+    }
+
+    #[test]
+    fn test_bytes_smaller_than_json() {
+        let amounts: Vec<u64> = (0..16).map(|i| 1 << i).collect();
+        let wad = create_test_compact_wad_multiple_proofs("mint.example.com", &amounts);
+
+        let bytes = wad.to_bytes().unwrap();
+        let json = serde_json::to_string(&wad).unwrap();
+
+        // At least a third smaller
+        assert!(bytes.len() * 3 < json.len() * 2);
+        assert!(bytes.len() < wad.to_string().len());
+    }
+
     // KO tests
 
+    #[test]
+    fn test_bytes_with_unknown_version() {
+        let wad = create_test_compact_wad_single_proof("mint.example.com", 100);
+        let mut bytes = wad.to_bytes().unwrap();
+        bytes[0] = BINARY_WAD_VERSION + 1;
+
+        assert!(matches!(
+            CompactWad::<TestUnit>::from_bytes(&bytes),
+            Err(Error::UnsupportedBinaryVersion(Some(2)))
+        ));
+        assert!(matches!(
+            CompactWad::<TestUnit>::from_bytes(&[]),
+            Err(Error::UnsupportedBinaryVersion(None))
+        ));
+    }
+
     #[test]
     fn test_wad_string_two_tokens_not_separated_by_colon() {
         // wad string of two tokens not separated by :
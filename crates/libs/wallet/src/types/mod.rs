@@ -20,12 +20,15 @@ use crate::{
 mod node_url;
 pub use node_url::{Error as NodeUrlError, NodeUrl};
 pub mod compact_wad;
+pub mod qr_chunks;
 
 #[derive(Debug)]
 pub struct BlindingData {
     xpriv: Xpriv,
     keyset_id: KeysetId,
     keyset_counter: u32,
+    /// `None` if the keyset keys have not been stored yet
+    max_denomination: Option<u64>,
 }
 
 impl BlindingData {
@@ -36,14 +39,48 @@ impl BlindingData {
         unit: &str,
     ) -> Result<Self, Error> {
         let (id, counter) = get_active_keyset_for_unit(db_conn, node_id, unit)?;
+        let max_denomination = db::proof::get_max_denomination_for_keyset(db_conn, id)?;
         let pk = crate::wallet::get_private_key(seed_phrase_manager)?;
 
         Ok(Self {
             xpriv: pk,
             keyset_id: id,
             keyset_counter: counter,
+            max_denomination,
         })
     }
+
+    /// The keyset new outputs will be signed with
+    pub fn keyset_id(&self) -> KeysetId {
+        self.keyset_id
+    }
+}
+
+/// Split `total_amount` as asked by `split_target`, in denominations the keyset can sign
+///
+/// The node would refuse to sign an output bigger than the keyset max denomination.
+fn split_for_keyset(
+    total_amount: Amount,
+    split_target: &SplitTarget,
+    max_denomination: Option<u64>,
+) -> Result<Vec<Amount>, Error> {
+    let parts = total_amount.split_targeted(split_target).map_err(|e| {
+        Error::InvalidSplitTarget(format!(
+            "{:?} for amount {}: {}",
+            split_target, total_amount, e
+        ))
+    })?;
+
+    if let Some(max_denomination) = max_denomination {
+        if let Some(part) = parts.iter().find(|p| u64::from(**p) > max_denomination) {
+            return Err(Error::InvalidSplitTarget(format!(
+                "{:?} for amount {} requires a {} output, above the keyset max denomination {}",
+                split_target, total_amount, part, max_denomination
+            )));
+        }
+    }
+
+    Ok(parts)
 }
 
 #[derive(Debug, Clone)]
@@ -66,34 +103,35 @@ impl PreMints {
         split_target: &SplitTarget,
         blinding_data: BlindingData,
     ) -> Result<Self, Error> {
-        let pre_mints = total_amount
-            .split_targeted(split_target)?
-            .into_iter()
-            .enumerate()
-            .map(|(i, amount)| -> Result<_, Error> {
-                let secret = Secret::from_xpriv(
-                    blinding_data.xpriv,
-                    blinding_data.keyset_id,
-                    blinding_data.keyset_counter + i as u32,
-                )?;
-                let blinding_factor = SecretKey::from_xpriv(
-                    blinding_data.xpriv,
-                    blinding_data.keyset_id,
-                    blinding_data.keyset_counter + i as u32,
-                )?;
-
-                let (blinded_secret, r) = blind_message(&secret.to_bytes(), Some(blinding_factor))?;
-
-                let pm = PreMint {
-                    amount,
-                    blinded_secret,
-                    secret,
-                    r,
-                };
-
-                Ok(pm)
-            })
-            .collect::<Result<Vec<PreMint>, _>>()?;
+        let pre_mints =
+            split_for_keyset(total_amount, split_target, blinding_data.max_denomination)?
+                .into_iter()
+                .enumerate()
+                .map(|(i, amount)| -> Result<_, Error> {
+                    let secret = Secret::from_xpriv(
+                        blinding_data.xpriv,
+                        blinding_data.keyset_id,
+                        blinding_data.keyset_counter + i as u32,
+                    )?;
+                    let blinding_factor = SecretKey::from_xpriv(
+                        blinding_data.xpriv,
+                        blinding_data.keyset_id,
+                        blinding_data.keyset_counter + i as u32,
+                    )?;
+
+                    let (blinded_secret, r) =
+                        blind_message(&secret.to_bytes(), Some(blinding_factor))?;
+
+                    let pm = PreMint {
+                        amount,
+                        blinded_secret,
+                        secret,
+                        r,
+                    };
+
+                    Ok(pm)
+                })
+                .collect::<Result<Vec<PreMint>, _>>()?;
 
         Ok(PreMints {
             keyset_id: blinding_data.keyset_id,
@@ -177,3 +215,48 @@ pub struct Wad {
     pub node_url: NodeUrl,
     pub proofs: Vec<nut00::Proof>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_target_above_amount_is_rejected() {
+        let res = split_for_keyset(
+            Amount::from(10u64),
+            &SplitTarget::Value(Amount::from(16u64)),
+            Some(8),
+        );
+
+        assert!(matches!(res, Err(Error::InvalidSplitTarget(_))));
+    }
+
+    #[test]
+    fn split_target_needing_an_output_above_max_denomination_is_rejected() {
+        let res = split_for_keyset(
+            Amount::from(40u64),
+            &SplitTarget::Value(Amount::from(32u64)),
+            Some(16),
+        );
+
+        assert!(matches!(res, Err(Error::InvalidSplitTarget(_))));
+    }
+
+    #[test]
+    fn valid_split_target_is_honored() {
+        let parts = split_for_keyset(
+            Amount::from(13u64),
+            &SplitTarget::Value(Amount::from(5u64)),
+            Some(8),
+        )
+        .unwrap();
+
+        assert_eq!(
+            Amount::try_sum(parts.iter().copied()).unwrap(),
+            Amount::from(13u64)
+        );
+        assert!(parts.iter().all(|p| u64::from(*p) <= 8));
+        // 5 = 1 + 4 can be taken from the outputs
+        assert!(parts.contains(&Amount::from(1u64)) && parts.contains(&Amount::from(4u64)));
+    }
+}
@@ -29,23 +29,48 @@ pub enum Error {
 }
 
 /// MintUrl Url
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+///
+/// Always normalized, so that it can be used as the node identity:
+/// lowercase host without trailing dot, no default port, no trailing slash, no query nor fragment.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
 #[repr(transparent)]
 #[serde(transparent)]
 pub struct NodeUrl(pub(crate) Url);
 
 fn parse_node_url(url_string: &str) -> Result<Url, Error> {
-    let url_string = url_string.trim_end_matches('/');
+    let url_string = url_string.trim().trim_end_matches('/');
 
-    let url = Url::parse(url_string)?;
+    // Lowercases the host and drops the scheme default port
+    let mut url = Url::parse(url_string)?;
 
     if url.scheme() != "https" && url.scheme() != "http" {
         return Err(Error::InvalidScheme(url.scheme().to_string()));
     }
 
+    url.set_query(None);
+    url.set_fragment(None);
+    if let Some(host) = url
+        .host_str()
+        .filter(|h| h.ends_with('.'))
+        .map(str::to_string)
+    {
+        url.set_host(Some(host.trim_end_matches('.')))?;
+    }
+
     Ok(url)
 }
 
+// Wads coming from other wallets must go through the same normalization
+impl<'de> Deserialize<'de> for NodeUrl {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        NodeUrl::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
 impl FromStr for NodeUrl {
     type Err = Error;
 
@@ -114,4 +139,35 @@ mod tests {
             cased_url_with_path_formatted.to_string()
         );
     }
+
+    #[test]
+    fn test_surface_forms_are_normalized() {
+        let expected = NodeUrl::from_str("https://node.example").unwrap();
+
+        for surface_form in [
+            "https://node.example/",
+            "https://Node.Example",
+            "https://NODE.EXAMPLE:443/",
+            "https://node.example./",
+            "  https://node.example?foo=bar#baz",
+        ] {
+            assert_eq!(
+                NodeUrl::from_str(surface_form).unwrap(),
+                expected,
+                "{surface_form}"
+            );
+        }
+
+        // Non default ports are a different node
+        assert_ne!(
+            NodeUrl::from_str("https://node.example:8443").unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_deserialize_normalizes() {
+        let url: NodeUrl = serde_json::from_str(r#""https://Node.Example/path/""#).unwrap();
+        assert_eq!(url.to_string(), "https://node.example/path");
+    }
 }
@@ -0,0 +1,296 @@
+//! Split a wad in several strings, each small enough to fit in a QR code
+//!
+//! Chunk format: `paynetQ:<transfer id>:<k>/<n>:<data>`, `k` starting at 1.
+//! The transfer id is derived from the hash of the whole wad string,
+//! it lets the scanner reject chunks from another transfer and detect corruption once reassembled.
+
+use std::str::FromStr;
+
+use bitcoin::hashes::{Hash, sha256};
+use nuts::traits::Unit;
+use serde::{Serialize, de::DeserializeOwned};
+
+use super::compact_wad::{self, CompactWad};
+
+pub const QR_CHUNK_PREFIX: &str = "paynetQ";
+const TRANSFER_ID_LEN: usize = 8;
+/// Way more than any wad needs, the scanner allocates a slot per announced chunk
+const MAX_CHUNKS: usize = 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Wad(#[from] compact_wad::Error),
+    #[error("max chunk size of {0} bytes is too small to hold any data")]
+    ChunkSizeTooSmall(usize),
+    #[error("no chunk provided")]
+    NoChunks,
+    #[error("invalid chunk: {0}")]
+    InvalidChunk(String),
+    #[error("chunk {0} was provided more than once")]
+    DuplicateChunk(usize),
+    #[error("chunk {0} does not belong to the same transfer as the others")]
+    ForeignChunk(usize),
+    #[error("missing chunks: {0:?}")]
+    MissingChunks(Vec<usize>),
+    #[error("reassembled wad does not match its transfer id")]
+    CorruptedTransfer,
+}
+
+struct Chunk<'a> {
+    transfer_id: &'a str,
+    index: usize,
+    total: usize,
+    data: &'a str,
+}
+
+impl<'a> Chunk<'a> {
+    fn parse(s: &'a str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidChunk(s.to_string());
+
+        let mut parts = s.splitn(4, ':');
+        if parts.next() != Some(QR_CHUNK_PREFIX) {
+            return Err(invalid());
+        }
+        let transfer_id = parts.next().ok_or_else(invalid)?;
+        let (index, total) = parts
+            .next()
+            .and_then(|p| p.split_once('/'))
+            .ok_or_else(invalid)?;
+        let index = usize::from_str(index).map_err(|_| invalid())?;
+        let total = usize::from_str(total).map_err(|_| invalid())?;
+        let data = parts.next().ok_or_else(invalid)?;
+
+        if transfer_id.len() != TRANSFER_ID_LEN || index == 0 || index > total || total > MAX_CHUNKS
+        {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            transfer_id,
+            index,
+            total,
+            data,
+        })
+    }
+}
+
+fn compute_transfer_id(wad_string: &str) -> String {
+    let hash = sha256::Hash::hash(wad_string.as_bytes());
+    hash.to_string()[..TRANSFER_ID_LEN].to_string()
+}
+
+fn header_len(transfer_id: &str, total: usize) -> usize {
+    let digits = total.to_string().len();
+    // prefix:id:k/n:
+    QR_CHUNK_PREFIX.len() + 1 + transfer_id.len() + 1 + 2 * digits + 1 + 1
+}
+
+impl<U: Unit + Serialize> CompactWad<U> {
+    /// Split the compressed representation of this wad in chunks of at most `max_chunk_bytes`
+    pub fn to_qr_chunks(&self, max_chunk_bytes: usize) -> Result<Vec<String>, Error> {
+        let wad_string = self.to_compressed_string()?;
+        let transfer_id = compute_transfer_id(&wad_string);
+
+        // The header grows with the number of chunks, iterate until it stabilizes
+        let mut total = 1;
+        let data_len = loop {
+            let data_len = max_chunk_bytes
+                .checked_sub(header_len(&transfer_id, total))
+                .filter(|l| *l > 0)
+                .ok_or(Error::ChunkSizeTooSmall(max_chunk_bytes))?;
+            let needed = wad_string.len().div_ceil(data_len);
+            if needed > MAX_CHUNKS {
+                return Err(Error::ChunkSizeTooSmall(max_chunk_bytes));
+            }
+            if needed <= total {
+                break data_len;
+            }
+            total = needed;
+        };
+
+        // The wad string is base64 with an ascii prefix, it can be split at any byte
+        let chunks = wad_string
+            .as_bytes()
+            .chunks(data_len)
+            .enumerate()
+            .map(|(i, data)| {
+                format!(
+                    "{}:{}:{}/{}:{}",
+                    QR_CHUNK_PREFIX,
+                    transfer_id,
+                    i + 1,
+                    total,
+                    String::from_utf8_lossy(data)
+                )
+            })
+            .collect();
+
+        Ok(chunks)
+    }
+}
+
+impl<U: Unit + DeserializeOwned> CompactWad<U> {
+    /// Reassemble a wad from its chunks, in any order
+    pub fn from_qr_chunks<S: AsRef<str>>(chunks: &[S]) -> Result<Self, Error> {
+        let chunks = chunks
+            .iter()
+            .map(|c| Chunk::parse(c.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let first = chunks.first().ok_or(Error::NoChunks)?;
+        let (transfer_id, total) = (first.transfer_id, first.total);
+
+        let mut datas: Vec<Option<&str>> = vec![None; total];
+        for chunk in chunks.iter() {
+            if chunk.transfer_id != transfer_id || chunk.total != total {
+                return Err(Error::ForeignChunk(chunk.index));
+            }
+            let slot = &mut datas[chunk.index - 1];
+            if slot.is_some() {
+                return Err(Error::DuplicateChunk(chunk.index));
+            }
+            *slot = Some(chunk.data);
+        }
+
+        let missing: Vec<usize> = datas
+            .iter()
+            .enumerate()
+            .filter(|(_, d)| d.is_none())
+            .map(|(i, _)| i + 1)
+            .collect();
+        if !missing.is_empty() {
+            return Err(Error::MissingChunks(missing));
+        }
+
+        let wad_string: String = datas.into_iter().flatten().collect();
+        if compute_transfer_id(&wad_string) != transfer_id {
+            return Err(Error::CorruptedTransfer);
+        }
+
+        Ok(CompactWad::from_str(&wad_string)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nuts::{Amount, nut00::secret::Secret, nut01::PublicKey, nut02::KeysetId};
+
+    use super::*;
+    use crate::types::{
+        NodeUrl,
+        compact_wad::{CompactKeysetProofs, CompactProof, tests::TestUnit},
+    };
+
+    fn create_wad(n_proofs: u64) -> CompactWad<TestUnit> {
+        let keyset_id = KeysetId::from_bytes(&[0, 1, 2, 3, 4, 5, 6, 7]).unwrap();
+        let pubkey = PublicKey::from_slice(&[
+            3, 23, 183, 225, 206, 31, 159, 148, 195, 42, 67, 115, 146, 41, 248, 140, 11, 3, 51, 41,
+            111, 180, 110, 143, 114, 179, 192, 72, 147, 222, 233, 25, 52,
+        ])
+        .unwrap();
+        let proofs = (0..n_proofs)
+            .map(|i| CompactProof {
+                amount: Amount::from(1u64 << (i % 32)),
+                // Distinct, hard to compress secrets
+                secret: Secret::from_str(&sha256::Hash::hash(&i.to_be_bytes()).to_string())
+                    .unwrap(),
+                c: pubkey,
+            })
+            .collect();
+
+        CompactWad {
+            node_url: NodeUrl::from_str("https://mint.example.com").unwrap(),
+            unit: TestUnit::Sat,
+            memo: Some("qr".to_string()),
+            proofs: vec![CompactKeysetProofs { keyset_id, proofs }],
+        }
+    }
+
+    #[test]
+    fn chunks_roundtrip() {
+        let wad = create_wad(20);
+
+        let chunks = wad.to_qr_chunks(200).unwrap();
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.len() <= 200));
+        let n = chunks.len();
+        assert!(chunks[0].contains(&format!(":1/{n}:")));
+        assert!(chunks[n - 1].contains(&format!(":{n}/{n}:")));
+
+        // Scan order doesn't matter
+        let mut shuffled = chunks.clone();
+        shuffled.reverse();
+        assert_eq!(
+            CompactWad::<TestUnit>::from_qr_chunks(&shuffled).unwrap(),
+            wad
+        );
+    }
+
+    #[test]
+    fn single_chunk() {
+        let wad = create_wad(1);
+
+        let chunks = wad.to_qr_chunks(4096).unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert!(chunks[0].contains(":1/1:"));
+        assert_eq!(
+            CompactWad::<TestUnit>::from_qr_chunks(&chunks).unwrap(),
+            wad
+        );
+    }
+
+    #[test]
+    fn missing_chunk() {
+        let chunks = create_wad(20).to_qr_chunks(200).unwrap();
+        assert!(chunks.len() > 2);
+
+        let mut incomplete = chunks.clone();
+        incomplete.remove(1);
+
+        assert!(matches!(
+            CompactWad::<TestUnit>::from_qr_chunks(&incomplete),
+            Err(Error::MissingChunks(missing)) if missing == vec![2]
+        ));
+    }
+
+    #[test]
+    fn duplicate_chunk() {
+        let mut chunks = create_wad(20).to_qr_chunks(200).unwrap();
+        chunks.push(chunks[0].clone());
+
+        assert!(matches!(
+            CompactWad::<TestUnit>::from_qr_chunks(&chunks),
+            Err(Error::DuplicateChunk(1))
+        ));
+    }
+
+    #[test]
+    fn foreign_chunk() {
+        let mut chunks = create_wad(20).to_qr_chunks(200).unwrap();
+        let other_chunks = create_wad(21).to_qr_chunks(200).unwrap();
+        chunks[1] = other_chunks[1].clone();
+
+        assert!(matches!(
+            CompactWad::<TestUnit>::from_qr_chunks(&chunks),
+            Err(Error::ForeignChunk(2))
+        ));
+    }
+
+    #[test]
+    fn chunk_size_too_small() {
+        assert!(matches!(
+            create_wad(1).to_qr_chunks(10),
+            Err(Error::ChunkSizeTooSmall(10))
+        ));
+    }
+
+    #[test]
+    fn oversized_total_is_rejected() {
+        let chunk = "paynetQ:0123abcd:1/18446744073709551615:data";
+
+        assert!(matches!(
+            CompactWad::<TestUnit>::from_qr_chunks(&[chunk]),
+            Err(Error::InvalidChunk(c)) if c == chunk
+        ));
+    }
+}
@@ -1,17 +1,25 @@
 use itertools::Itertools;
 use nuts::{nut00::Proof, traits::Unit};
 
-use crate::types::{
-    NodeUrl,
-    compact_wad::{CompactKeysetProofs, CompactProof, CompactWad},
+use crate::{
+    errors::Error,
+    types::{
+        NodeUrl,
+        compact_wad::{CompactKeysetProofs, CompactProof, CompactWad},
+    },
 };
 
+/// Fails with `EmptyWad` if `proofs` is empty, such a wad is worth nothing to its receiver
 pub fn create_from_parts<U: Unit>(
     node_url: NodeUrl,
     unit: U,
     memo: Option<String>,
     proofs: Vec<Proof>,
-) -> CompactWad<U> {
+) -> Result<CompactWad<U>, Error> {
+    if proofs.is_empty() {
+        return Err(Error::EmptyWad);
+    }
+
     let compact_proofs = proofs
         .into_iter()
         .chunk_by(|p| p.keyset_id)
@@ -28,10 +36,10 @@ pub fn create_from_parts<U: Unit>(
         })
         .collect();
 
-    CompactWad {
+    Ok(CompactWad {
         node_url,
         unit,
         memo,
         proofs: compact_proofs,
-    }
+    })
 }
@@ -0,0 +1,189 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use argon2::Argon2;
+use bip39::Mnemonic;
+use chacha20poly1305::{
+    ChaCha20Poly1305, Key, Nonce,
+    aead::{Aead, KeyInit},
+};
+use rand::RngCore;
+use zeroize::Zeroizing;
+
+use crate::seed_phrase;
+
+const FILE_FORMAT_VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = 1 + SALT_LEN + NONCE_LEN;
+
+/// Stores the seed phrase in a file, encrypted with a key derived from a user passphrase
+///
+/// Meant for environments where no OS keyring is available (headless linux, CI).
+/// The file layout is `version (1) | argon2 salt (16) | nonce (12) | ciphertext`.
+#[derive(Clone)]
+pub struct SeedPhraseManager {
+    path: PathBuf,
+    passphrase: Zeroizing<String>,
+}
+
+impl std::fmt::Debug for SeedPhraseManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SeedPhraseManager")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to access seed phrase file: {0}")]
+    Io(#[from] io::Error),
+    #[error("failed to derive encryption key from passphrase: {0}")]
+    KeyDerivation(String),
+    #[error("failed to encrypt seed phrase")]
+    Encryption,
+    #[error("failed to decrypt seed phrase file, wrong passphrase or corrupted file")]
+    Decryption,
+    #[error("unsupported seed phrase file format")]
+    InvalidFormat,
+    #[error("failed to parse stored value: {0}")]
+    SeedPhrase(#[from] crate::seed_phrase::Error),
+}
+
+impl SeedPhraseManager {
+    pub fn new(path: PathBuf, passphrase: String) -> Self {
+        Self {
+            path,
+            passphrase: Zeroizing::new(passphrase),
+        }
+    }
+
+    fn cipher(&self, salt: &[u8]) -> Result<ChaCha20Poly1305, Error> {
+        let mut key = Zeroizing::new([0u8; 32]);
+        Argon2::default()
+            .hash_password_into(self.passphrase.as_bytes(), salt, key.as_mut())
+            .map_err(|e| Error::KeyDerivation(e.to_string()))?;
+
+        Ok(ChaCha20Poly1305::new(Key::from_slice(key.as_ref())))
+    }
+
+    fn write_file(&self, content: &[u8]) -> Result<(), io::Error> {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+
+        let mut file = options.open(&self.path)?;
+        file.write_all(content)?;
+        file.sync_all()
+    }
+}
+
+impl super::SeedPhraseManager for SeedPhraseManager {
+    type Error = Error;
+
+    fn store_seed_phrase(&self, seed_phrase: &Mnemonic) -> Result<(), Self::Error> {
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::rng().fill_bytes(&mut salt);
+        rand::rng().fill_bytes(&mut nonce);
+
+        let plaintext = Zeroizing::new(seed_phrase.to_string());
+        let ciphertext = self
+            .cipher(&salt)?
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+            .map_err(|_| Error::Encryption)?;
+
+        let mut content = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+        content.push(FILE_FORMAT_VERSION);
+        content.extend_from_slice(&salt);
+        content.extend_from_slice(&nonce);
+        content.extend_from_slice(&ciphertext);
+        self.write_file(&content)?;
+
+        Ok(())
+    }
+
+    fn get_seed_phrase(&self) -> Result<Option<Mnemonic>, Self::Error> {
+        let content = match fs::read(&self.path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::Io(e)),
+        };
+
+        if content.len() <= HEADER_LEN || content[0] != FILE_FORMAT_VERSION {
+            return Err(Error::InvalidFormat);
+        }
+        let salt = &content[1..1 + SALT_LEN];
+        let nonce = &content[1 + SALT_LEN..HEADER_LEN];
+        let ciphertext = &content[HEADER_LEN..];
+
+        let plaintext = Zeroizing::new(
+            self.cipher(salt)?
+                .decrypt(Nonce::from_slice(nonce), ciphertext)
+                .map_err(|_| Error::Decryption)?,
+        );
+        let seed_phrase_str = std::str::from_utf8(&plaintext).map_err(|_| Error::InvalidFormat)?;
+
+        let mnemonic = seed_phrase::create_from_str(seed_phrase_str)?;
+
+        Ok(Some(mnemonic))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::wallet::SeedPhraseManager as _;
+
+    pub(crate) fn temp_file_path(name: &str) -> PathBuf {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!("{name}-{}-{nanos}.seed", std::process::id()))
+    }
+
+    #[test]
+    fn roundtrip() {
+        let path = temp_file_path("roundtrip");
+        let manager = SeedPhraseManager::new(path.clone(), "passphrase".to_string());
+        let seed_phrase = seed_phrase::create_random().unwrap();
+
+        assert!(manager.get_seed_phrase().unwrap().is_none());
+        manager.store_seed_phrase(&seed_phrase).unwrap();
+        assert_eq!(
+            manager.get_seed_phrase().unwrap(),
+            Some(seed_phrase.clone())
+        );
+
+        // The phrase must not be readable from the file
+        let content = fs::read(&path).unwrap();
+        let first_word = seed_phrase.words().next().unwrap();
+        assert!(!String::from_utf8_lossy(&content).contains(first_word));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn wrong_passphrase() {
+        let path = temp_file_path("wrong-passphrase");
+        let seed_phrase = seed_phrase::create_random().unwrap();
+        SeedPhraseManager::new(path.clone(), "passphrase".to_string())
+            .store_seed_phrase(&seed_phrase)
+            .unwrap();
+
+        let res = SeedPhraseManager::new(path.clone(), "not the passphrase".to_string())
+            .get_seed_phrase();
+        assert!(matches!(res, Err(Error::Decryption)));
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,140 @@
+use bip39::Mnemonic;
+
+/// Uses `primary` and switches to `fallback` when it fails
+///
+/// OS keyrings are not available everywhere (headless servers, containers, some linux desktops).
+/// Rather than making the wallet unusable there, the seed phrase goes to the fallback storage.
+/// Reads check both, so a seed stored while the keyring was down is still found once it is back.
+#[derive(Debug, Clone)]
+pub struct SeedPhraseManager<P, F> {
+    primary: P,
+    fallback: Option<F>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error<PE, FE>
+where
+    PE: std::error::Error,
+    FE: std::error::Error,
+{
+    #[error("primary seed phrase storage failed: {0}")]
+    Primary(PE),
+    #[error("fallback seed phrase storage failed: {0}")]
+    Fallback(FE),
+    #[error("failed to parse stored value: {0}")]
+    SeedPhrase(#[from] crate::seed_phrase::Error),
+}
+
+impl<P, F> SeedPhraseManager<P, F> {
+    pub fn new(primary: P, fallback: Option<F>) -> Self {
+        Self { primary, fallback }
+    }
+}
+
+impl<P, F> super::SeedPhraseManager for SeedPhraseManager<P, F>
+where
+    P: super::SeedPhraseManager,
+    F: super::SeedPhraseManager,
+{
+    type Error = Error<P::Error, F::Error>;
+
+    fn store_seed_phrase(&self, seed_phrase: &Mnemonic) -> Result<(), Self::Error> {
+        match self.primary.store_seed_phrase(seed_phrase) {
+            Ok(()) => {
+                log::info!("seed phrase stored using the primary storage");
+                Ok(())
+            }
+            Err(e) => match &self.fallback {
+                Some(fallback) => {
+                    log::warn!("primary seed phrase storage failed, using fallback: {e}");
+                    fallback
+                        .store_seed_phrase(seed_phrase)
+                        .map_err(Error::Fallback)?;
+                    log::info!("seed phrase stored using the fallback storage");
+                    Ok(())
+                }
+                None => Err(Error::Primary(e)),
+            },
+        }
+    }
+
+    fn get_seed_phrase(&self) -> Result<Option<Mnemonic>, Self::Error> {
+        let primary_res = self.primary.get_seed_phrase();
+        if let Ok(Some(seed_phrase)) = primary_res {
+            return Ok(Some(seed_phrase));
+        }
+
+        match (&self.fallback, primary_res) {
+            (Some(fallback), primary_res) => {
+                if let Err(e) = primary_res {
+                    log::warn!("primary seed phrase storage failed, using fallback: {e}");
+                }
+                fallback.get_seed_phrase().map_err(Error::Fallback)
+            }
+            (None, primary_res) => primary_res.map_err(Error::Primary),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "encrypted-file-seed-phrase"))]
+mod tests {
+    use super::*;
+    use crate::{
+        seed_phrase,
+        wallet::{SeedPhraseManager as _, encrypted_file},
+    };
+
+    #[derive(Debug, thiserror::Error)]
+    enum UnavailableError {
+        #[error("storage unavailable")]
+        Unavailable,
+        #[error(transparent)]
+        SeedPhrase(#[from] seed_phrase::Error),
+    }
+
+    struct UnavailableStorage;
+
+    impl crate::wallet::SeedPhraseManager for UnavailableStorage {
+        type Error = UnavailableError;
+
+        fn store_seed_phrase(&self, _seed_phrase: &Mnemonic) -> Result<(), Self::Error> {
+            Err(UnavailableError::Unavailable)
+        }
+
+        fn get_seed_phrase(&self) -> Result<Option<Mnemonic>, Self::Error> {
+            Err(UnavailableError::Unavailable)
+        }
+    }
+
+    #[test]
+    fn uses_fallback_when_primary_is_unavailable() {
+        let path = encrypted_file::tests::temp_file_path("fallback");
+        let manager = SeedPhraseManager::new(
+            UnavailableStorage,
+            Some(encrypted_file::SeedPhraseManager::new(
+                path.clone(),
+                "passphrase".to_string(),
+            )),
+        );
+        let seed_phrase = seed_phrase::create_random().unwrap();
+
+        manager.store_seed_phrase(&seed_phrase).unwrap();
+        assert_eq!(manager.get_seed_phrase().unwrap(), Some(seed_phrase));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn fails_without_fallback() {
+        let manager = SeedPhraseManager::<_, encrypted_file::SeedPhraseManager>::new(
+            UnavailableStorage,
+            None,
+        );
+        let seed_phrase = seed_phrase::create_random().unwrap();
+
+        assert!(matches!(
+            manager.store_seed_phrase(&seed_phrase),
+            Err(Error::Primary(UnavailableError::Unavailable))
+        ));
+    }
+}
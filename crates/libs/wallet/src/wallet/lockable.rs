@@ -0,0 +1,272 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use bip39::{Language, Mnemonic};
+use zeroize::Zeroize;
+
+use super::Error;
+
+// Largest bip39 entropy, used by 24 words phrases
+const MAX_ENTROPY_LEN: usize = 32;
+
+#[derive(Default)]
+struct KeyMaterial {
+    entropy: [u8; MAX_ENTROPY_LEN],
+    len: usize,
+}
+
+impl KeyMaterial {
+    fn is_unlocked(&self) -> bool {
+        self.len != 0
+    }
+}
+
+impl Drop for KeyMaterial {
+    fn drop(&mut self) {
+        self.entropy.zeroize();
+    }
+}
+
+/// Keeps the seed in memory only while the wallet is unlocked
+///
+/// Other seed phrase managers read the seed from their storage on each call,
+/// leaving copies in memory for as long as the process lives.
+/// Locking wipes the single copy held here, every operation needing a key then fails with
+/// `Error::WalletLocked` until `unlock` is called again.
+/// Clones share the same state, so locking one locks them all.
+#[derive(Clone, Default)]
+pub struct Wallet {
+    key_material: Arc<Mutex<KeyMaterial>>,
+}
+
+impl std::fmt::Debug for Wallet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Wallet")
+            .field("is_locked", &self.is_locked())
+            .finish()
+    }
+}
+
+impl Wallet {
+    /// Create a locked wallet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn key_material(&self) -> MutexGuard<'_, KeyMaterial> {
+        // The guarded data is plain bytes, it cannot be left in an inconsistent state
+        self.key_material
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn is_locked(&self) -> bool {
+        !self.key_material().is_unlocked()
+    }
+
+    pub fn lock(&self) {
+        let mut key_material = self.key_material();
+        key_material.entropy.zeroize();
+        key_material.len = 0;
+    }
+
+    pub fn unlock(&self, seed_phrase: &Mnemonic) {
+        let (entropy, len) = seed_phrase.to_entropy_array();
+        let mut entropy = zeroize::Zeroizing::new(entropy);
+
+        let mut key_material = self.key_material();
+        key_material.entropy.zeroize();
+        key_material.entropy[..len].copy_from_slice(&entropy[..len]);
+        key_material.len = len;
+        entropy.zeroize();
+    }
+}
+
+impl super::SeedPhraseManager for Wallet {
+    type Error = Error;
+
+    /// Unlock the wallet, the seed phrase is not persisted anywhere
+    fn store_seed_phrase(&self, seed_phrase: &Mnemonic) -> Result<(), Self::Error> {
+        self.unlock(seed_phrase);
+
+        Ok(())
+    }
+
+    /// The returned copy is zeroized when dropped
+    fn get_seed_phrase(&self) -> Result<Option<Mnemonic>, Self::Error> {
+        let key_material = self.key_material();
+        if !key_material.is_unlocked() {
+            return Err(Error::WalletLocked);
+        }
+
+        let mnemonic =
+            Mnemonic::from_entropy_in(Language::English, &key_material.entropy[..key_material.len])
+                .map_err(|e| crate::seed_phrase::Error::InvalidSeedPhrase(e.to_string()))?;
+
+        Ok(Some(mnemonic))
+    }
+}
+
+/// Keeps the seed persisted by `storage` in a lockable [`Wallet`]
+///
+/// Keys are only ever derived from the wallet copy, `storage` is read again when unlocking.
+/// Meant for apps that lock when they go to the background and unlock when they come back.
+#[derive(Debug, Clone)]
+pub struct SeedPhraseManager<S> {
+    storage: S,
+    wallet: Wallet,
+}
+
+impl<S> SeedPhraseManager<S> {
+    /// Create a locked manager, see [`Self::unlock`]
+    pub fn new(storage: S) -> Self {
+        Self {
+            storage,
+            wallet: Wallet::new(),
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.wallet.is_locked()
+    }
+
+    pub fn lock(&self) {
+        self.wallet.lock();
+    }
+}
+
+impl<S: super::SeedPhraseManager> SeedPhraseManager<S> {
+    /// Load the seed phrase from `storage`
+    ///
+    /// Stays locked if none has been stored yet.
+    pub fn unlock(&self) -> Result<(), Error> {
+        let seed_phrase = self
+            .storage
+            .get_seed_phrase()
+            .map_err(|e| Error::SeedPhraseManager(Box::new(e)))?;
+        if let Some(seed_phrase) = seed_phrase {
+            self.wallet.unlock(&seed_phrase);
+        }
+
+        Ok(())
+    }
+}
+
+impl<S: super::SeedPhraseManager> super::SeedPhraseManager for SeedPhraseManager<S> {
+    type Error = Error;
+
+    /// Persist the seed phrase in `storage` and unlock with it
+    fn store_seed_phrase(&self, seed_phrase: &Mnemonic) -> Result<(), Self::Error> {
+        self.storage
+            .store_seed_phrase(seed_phrase)
+            .map_err(|e| Error::SeedPhraseManager(Box::new(e)))?;
+        self.wallet.unlock(seed_phrase);
+
+        Ok(())
+    }
+
+    fn get_seed_phrase(&self) -> Result<Option<Mnemonic>, Self::Error> {
+        self.wallet.get_seed_phrase()
+    }
+
+    /// Checks `storage` while locked, so that a wallet can be created before the first unlock
+    fn has_seed_phrase(&self) -> Result<bool, Self::Error> {
+        if !self.wallet.is_locked() {
+            return Ok(true);
+        }
+
+        self.storage
+            .has_seed_phrase()
+            .map_err(|e| Error::SeedPhraseManager(Box::new(e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{seed_phrase, wallet::SeedPhraseManager as _};
+
+    #[derive(Default)]
+    struct MemoryStorage(Mutex<Option<Mnemonic>>);
+
+    impl crate::wallet::SeedPhraseManager for MemoryStorage {
+        type Error = Error;
+
+        fn store_seed_phrase(&self, seed_phrase: &Mnemonic) -> Result<(), Self::Error> {
+            *self.0.lock().unwrap() = Some(seed_phrase.clone());
+            Ok(())
+        }
+
+        fn get_seed_phrase(&self) -> Result<Option<Mnemonic>, Self::Error> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+    }
+
+    #[test]
+    fn operations_fail_while_locked() {
+        let wallet = Wallet::new();
+        let seed_phrase = seed_phrase::create_random().unwrap();
+
+        assert!(wallet.is_locked());
+        assert!(matches!(wallet.get_private_key(), Err(Error::WalletLocked)));
+
+        wallet.unlock(&seed_phrase);
+        assert!(!wallet.is_locked());
+        assert_eq!(wallet.get_seed_phrase().unwrap(), Some(seed_phrase.clone()));
+        assert_eq!(
+            wallet.get_private_key().unwrap(),
+            Some(seed_phrase::derive_private_key(&seed_phrase).unwrap())
+        );
+
+        wallet.lock();
+        assert!(wallet.is_locked());
+        assert!(matches!(wallet.get_seed_phrase(), Err(Error::WalletLocked)));
+        assert!(matches!(wallet.get_private_key(), Err(Error::WalletLocked)));
+    }
+
+    #[test]
+    fn lock_zeroizes_key_material() {
+        let wallet = Wallet::new();
+        let seed_phrase =
+            seed_phrase::create_from_str(&format!("{} vote", ["zoo"; 23].join(" "))).unwrap();
+
+        // Clones share the same key material
+        wallet.clone().unlock(&seed_phrase);
+        assert_ne!(wallet.key_material().entropy, [0; MAX_ENTROPY_LEN]);
+
+        wallet.lock();
+        let key_material = wallet.key_material();
+        assert_eq!(key_material.entropy, [0; MAX_ENTROPY_LEN]);
+        assert_eq!(key_material.len, 0);
+    }
+
+    #[test]
+    fn manager_reads_storage_when_unlocking() {
+        let manager = SeedPhraseManager::new(MemoryStorage::default());
+        manager.unlock().unwrap();
+        assert!(manager.is_locked());
+        assert!(!manager.has_seed_phrase().unwrap());
+
+        let seed_phrase = seed_phrase::create_random().unwrap();
+        manager.store_seed_phrase(&seed_phrase).unwrap();
+        assert_eq!(
+            manager.get_seed_phrase().unwrap(),
+            Some(seed_phrase.clone())
+        );
+
+        manager.lock();
+        assert!(manager.has_seed_phrase().unwrap());
+        assert!(matches!(
+            manager.get_seed_phrase(),
+            Err(Error::WalletLocked)
+        ));
+
+        manager.unlock().unwrap();
+        assert_eq!(manager.get_seed_phrase().unwrap(), Some(seed_phrase));
+    }
+
+    #[test]
+    fn seed_phrase_copies_are_zeroized_on_drop() {
+        fn assert_zeroize_on_drop<T: zeroize::ZeroizeOnDrop>() {}
+        assert_zeroize_on_drop::<Mnemonic>();
+    }
+}
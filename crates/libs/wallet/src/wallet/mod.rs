@@ -6,7 +6,11 @@ use thiserror::Error;
 
 use crate::db;
 
+#[cfg(feature = "encrypted-file-seed-phrase")]
+pub mod encrypted_file;
+pub mod fallback;
 pub mod keyring;
+pub mod lockable;
 #[cfg(feature = "sqlite-seed-phrase")]
 pub mod sqlite;
 
@@ -20,6 +24,10 @@ pub enum Error {
     WalletAlreadyExists,
     #[error("seed phrase manager error:")]
     SeedPhraseManager(Box<dyn std::error::Error + Send + Sync + 'static>),
+    #[error("wallet is locked")]
+    WalletLocked,
+    #[error(transparent)]
+    SeedPhrase(#[from] crate::seed_phrase::Error),
 }
 
 pub trait SeedPhraseManager {
@@ -42,6 +50,19 @@ pub trait SeedPhraseManager {
     }
 }
 
+/// Lets a single manager be lent to the several steps of an operation
+impl<T: SeedPhraseManager> SeedPhraseManager for &T {
+    type Error = T::Error;
+
+    fn store_seed_phrase(&self, seed_phrase: &Mnemonic) -> Result<(), Self::Error> {
+        (**self).store_seed_phrase(seed_phrase)
+    }
+
+    fn get_seed_phrase(&self) -> Result<Option<Mnemonic>, Self::Error> {
+        (**self).get_seed_phrase()
+    }
+}
+
 /// Restore a wallet from an existing seed phrase
 /// This function stores the seed phrase in the keyring and creates a wallet record in the database
 pub fn restore(
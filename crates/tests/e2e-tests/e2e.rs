@@ -1,4 +1,4 @@
-use std::str::FromStr;
+use std::{collections::HashMap, str::FromStr};
 
 use anyhow::Result;
 use e2e_tests::{db_connection, read_env_variables};
@@ -66,9 +66,36 @@ pub async fn run_e2e() -> Result<()> {
     let wallet_ops = WalletOps::new(db_pool.clone(), node_id, node_client);
 
     assert!(wallet_ops.balance()?.is_empty());
-    wallet_ops.restore(seed_phrase).await?;
+    let restore_progress = wallet_ops.restore(seed_phrase).await?;
     let post_restore_balances = wallet_ops.balance()?;
     assert_eq!(pre_restore_balances, post_restore_balances);
 
+    // Keysets are restored concurrently, but each one reports all its batches in order
+    let mut progress_by_keyset: HashMap<_, Vec<_>> = HashMap::new();
+    for progress in restore_progress {
+        progress_by_keyset
+            .entry(progress.keyset_id)
+            .or_default()
+            .push(progress);
+    }
+    assert!(!progress_by_keyset.is_empty());
+    for progress in progress_by_keyset.values() {
+        let batch_indexes: Vec<_> = progress.iter().map(|p| p.batch_index).collect();
+        assert_eq!(
+            batch_indexes,
+            (0..progress.len() as u32).collect::<Vec<_>>()
+        );
+        assert!(
+            progress
+                .windows(2)
+                .all(|w| w[0].recovered_count <= w[1].recovered_count)
+        );
+    }
+    assert!(
+        progress_by_keyset
+            .values()
+            .any(|progress| progress[0].recovered_count > 0)
+    );
+
     Ok(())
 }
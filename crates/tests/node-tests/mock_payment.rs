@@ -0,0 +1,98 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use liquidity_source::DepositInterface;
+use node_client::{
+    BlindedMessage, GetKeysetsRequest, MintQuoteState, MintRequest, QuoteStateRequest,
+};
+use node_tests::init_node_client;
+use nuts::{Amount, dhke::blind_message, nut00::secret::Secret};
+use sqlx::PgPool;
+use starknet_liquidity_source::StarknetLiquiditySource;
+use starknet_types::Unit;
+use uuid::Uuid;
+
+// Quotes created through the node are paid right away by the mock.
+// This one is inserted directly in db, so that it stays unpaid until the test pays it.
+//
+// - insert an unpaid quote
+// - pay half of it, check it is still unpaid
+// - pay the rest, check it is paid
+// - mint it
+#[tokio::test]
+async fn mint_after_marking_invoice_paid() -> Result<()> {
+    let mut client = init_node_client().await?;
+    let pg_pool = PgPool::connect(&std::env::var("PG_URL")?).await?;
+    let liquidity_source = StarknetLiquiditySource::new(pg_pool.clone());
+    let amount = Amount::from_i64_repr(32);
+
+    let quote_id = Uuid::new_v4();
+    let expiry = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() + 600;
+    let (invoice_id, request) = liquidity_source.depositer.generate_deposit_payload(
+        quote_id,
+        Unit::MilliStrk,
+        amount,
+        expiry,
+    )?;
+    db_node::mint_quote::insert_new(
+        &mut *pg_pool.acquire().await?,
+        quote_id,
+        invoice_id.clone().into(),
+        Unit::MilliStrk,
+        amount,
+        &request,
+        expiry,
+    )
+    .await?;
+
+    let quote_state_request = QuoteStateRequest {
+        method: "starknet".to_string(),
+        quote: quote_id.to_string(),
+    };
+
+    liquidity_source
+        .mark_invoice_paid(&invoice_id, Amount::from_i64_repr(16))
+        .await?;
+    let state = client
+        .mint_quote_state(quote_state_request.clone())
+        .await?
+        .into_inner()
+        .state;
+    assert_eq!(state, MintQuoteState::MnqsUnpaid as i32);
+
+    liquidity_source
+        .mark_invoice_paid(&invoice_id, Amount::from_i64_repr(16))
+        .await?;
+    let state = client
+        .mint_quote_state(quote_state_request)
+        .await?
+        .into_inner()
+        .state;
+    assert_eq!(state, MintQuoteState::MnqsPaid as i32);
+
+    let keysets = client
+        .keysets(GetKeysetsRequest {})
+        .await?
+        .into_inner()
+        .keysets;
+    let active_keyset = keysets
+        .iter()
+        .find(|ks| ks.active && ks.unit == Unit::MilliStrk.as_str())
+        .unwrap();
+    let (blinded_secret, _r) = blind_message(Secret::generate().as_bytes(), None)?;
+    let mint_response = client
+        .mint(MintRequest {
+            method: "starknet".to_string(),
+            quote: quote_id.to_string(),
+            outputs: vec![BlindedMessage {
+                amount: amount.into(),
+                keyset_id: active_keyset.id.clone(),
+                blinded_secret: blinded_secret.to_bytes().to_vec(),
+            }],
+        })
+        .await?
+        .into_inner();
+    assert_eq!(mint_response.signatures.len(), 1);
+
+    Ok(())
+}
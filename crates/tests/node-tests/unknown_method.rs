@@ -0,0 +1,54 @@
+use anyhow::{Result, anyhow};
+use node_client::{MeltQuoteRequest, MintQuoteRequest, QuoteStateRequest};
+use node_tests::init_node_client;
+use starknet_types::Unit;
+use tonic::Code;
+
+const UNKNOWN_METHOD: &str = "bitcoin";
+
+fn expect_invalid_argument<T>(res: Result<T, tonic::Status>) -> Result<()> {
+    match res {
+        Err(status) if status.code() == Code::InvalidArgument => Ok(()),
+        Err(status) => Err(anyhow!(
+            "expected InvalidArgument, got {:?}: {}",
+            status.code(),
+            status.message()
+        )),
+        Ok(_) => Err(anyhow!("request with an unknown method was accepted")),
+    }
+}
+
+#[tokio::test]
+async fn reject_unknown_method() -> Result<()> {
+    let mut client = init_node_client().await?;
+
+    expect_invalid_argument(
+        client
+            .mint_quote(MintQuoteRequest {
+                method: UNKNOWN_METHOD.to_string(),
+                amount: 32,
+                unit: Unit::MilliStrk.to_string(),
+                description: None,
+            })
+            .await,
+    )?;
+    expect_invalid_argument(
+        client
+            .melt_quote(MeltQuoteRequest {
+                method: UNKNOWN_METHOD.to_string(),
+                unit: Unit::MilliStrk.to_string(),
+                request: String::new(),
+            })
+            .await,
+    )?;
+    expect_invalid_argument(
+        client
+            .mint_quote_state(QuoteStateRequest {
+                method: UNKNOWN_METHOD.to_string(),
+                quote: String::new(),
+            })
+            .await,
+    )?;
+
+    Ok(())
+}
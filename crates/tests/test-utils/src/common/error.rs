@@ -51,6 +51,9 @@ pub enum Error {
     #[cfg(feature = "starknet")]
     #[error(transparent)]
     Provider(#[from] starknet::providers::ProviderError),
+    #[cfg(feature = "starknet-types")]
+    #[error(transparent)]
+    CallData(#[from] starknet_types::transactions::CallDataError),
     #[error(transparent)]
     Grpc(#[from] tonic::Status),
     #[error(transparent)]
@@ -109,7 +109,7 @@ pub async fn mint_same_output(
         serde_json::from_str(&mint_quote_response_iterator.next().unwrap().request)?;
     let mut c = deposit_payload
         .call_data
-        .to_starknet_calls(on_chain_constants.invoice_payment_contract_address);
+        .to_starknet_calls(on_chain_constants.invoice_payment_contract_address)?;
     c[0].calldata[1] *= Felt::from(100);
     calls.push(c[0].clone());
     calls.push(c[1].clone());
@@ -118,7 +118,7 @@ pub async fn mint_same_output(
         let deposit_payload: DepositPayload = serde_json::from_str(&quote.request)?;
         let c = deposit_payload
             .call_data
-            .to_starknet_calls(on_chain_constants.invoice_payment_contract_address);
+            .to_starknet_calls(on_chain_constants.invoice_payment_contract_address)?;
         calls.push(c[1].clone());
         i += 1;
 
@@ -385,7 +385,7 @@ pub async fn melt_same_input(
     pay_invoices(
         deposit_payload
             .call_data
-            .to_starknet_calls(on_chain_constants.invoice_payment_contract_address)
+            .to_starknet_calls(on_chain_constants.invoice_payment_contract_address)?
             .to_vec(),
         env,
     )
@@ -461,7 +461,7 @@ pub async fn melt_same_input(
 
     let method = STARKNET_STR.to_string();
     let asset = starknet_types::Asset::Strk;
-    let on_chain_amount = U256::from(32).checked_mul(asset.scale_factor()).unwrap() / 1000;
+    let on_chain_amount = asset.checked_scale(U256::from(32)).unwrap() / 1000;
     for payee in payees.iter() {
         let melt_quote_response = node_client
             .melt_quote(MeltQuoteRequest {
@@ -618,7 +618,7 @@ pub async fn melt_same_quote(
 
     let asset = starknet_types::Asset::Strk;
 
-    let on_chain_amount = U256::from(128).checked_mul(asset.scale_factor()).unwrap() / 1000;
+    let on_chain_amount = asset.checked_scale(U256::from(128)).unwrap() / 1000;
 
     let melt_quote_response = node_client
         .melt_quote(MeltQuoteRequest {
@@ -125,7 +125,7 @@ pub async fn mint_quote_and_deposit_and_wait(
     pay_invoices(
         deposit_payload
             .call_data
-            .to_starknet_calls(on_chain_constants.invoice_payment_contract_address)
+            .to_starknet_calls(on_chain_constants.invoice_payment_contract_address)?
             .to_vec(),
         env.clone(),
     )
@@ -5,12 +5,15 @@ use node_client::NodeClient;
 use nuts::{Amount, nut01::PublicKey};
 use primitive_types::U256;
 use r2d2_sqlite::SqliteConnectionManager;
-use starknet_types::{Asset, DepositPayload, STARKNET_STR, Unit, constants::ON_CHAIN_CONSTANTS};
+use starknet_types::{
+    Asset, DepositPayload, STARKNET_STR, Unit, UnitPreferences, constants::ON_CHAIN_CONSTANTS,
+};
 use starknet_types_core::felt::Felt;
 use tonic::transport::Channel;
 use wallet::{
     self,
     db::{balance::Balance, wad::delete_wad},
+    node::RestoreProgress,
     types::{
         NodeUrl,
         compact_wad::{CompactKeysetProofs, CompactProof, CompactWad},
@@ -51,7 +54,8 @@ impl WalletOps {
         Ok(seed_phrase)
     }
 
-    pub async fn restore(&self, seed_phrase: Mnemonic) -> Result<()> {
+    /// Returns the progress reported by the restoration, in the order it was sent
+    pub async fn restore(&self, seed_phrase: Mnemonic) -> Result<Vec<RestoreProgress>> {
         let seed_phrase_manager =
             wallet::wallet::sqlite::SeedPhraseManager::new(self.db_pool.clone())?;
         {
@@ -60,16 +64,23 @@ impl WalletOps {
                 .map_err(|e| Error::Wallet(e.into()))?;
         };
 
-        wallet::node::restore(
+        let (progress_sender, mut progress_receiver) = tokio::sync::mpsc::unbounded_channel();
+        wallet::node::restore_with_progress(
             seed_phrase_manager,
             self.db_pool.clone(),
             self.node_id,
             self.node_client.clone(),
+            Some(progress_sender),
         )
         .await
         .map_err(|e| Error::Wallet(e.into()))?;
 
-        Ok(())
+        let mut progress = Vec::new();
+        while let Ok(p) = progress_receiver.try_recv() {
+            progress.push(p);
+        }
+
+        Ok(progress)
     }
 
     pub fn balance(&self) -> Result<Vec<Balance>> {
@@ -80,11 +91,11 @@ impl WalletOps {
     }
 
     pub async fn mint(&mut self, amount: U256, asset: Asset, env: EnvVariables) -> Result<()> {
-        let amount = amount
-            .checked_mul(asset.scale_factor())
+        let amount = asset
+            .checked_scale(amount)
             .ok_or(anyhow!("amount too big"))?;
         let (amount, unit, _remainder) = asset
-            .convert_to_amount_and_unit(amount)
+            .convert_to_amount_and_unit(amount, &UnitPreferences::default())
             .map_err(|e| Error::Other(e.into()))?;
 
         let quote = wallet::mint::create_quote(
@@ -102,7 +113,7 @@ impl WalletOps {
         pay_invoices(
             deposit_payload
                 .call_data
-                .to_starknet_calls(on_chain_constants.invoice_payment_contract_address)
+                .to_starknet_calls(on_chain_constants.invoice_payment_contract_address)?
                 .to_vec(),
             env,
         )
@@ -113,6 +124,8 @@ impl WalletOps {
             &mut self.node_client,
             STARKNET_STR.to_string(),
             quote.quote.clone(),
+            quote.expiry,
+            wallet::mint::PollBackoff::default(),
         )
         .await?
         {
@@ -134,6 +147,7 @@ impl WalletOps {
             self.node_id,
             unit.as_str(),
             amount,
+            None,
         )
         .await?;
 
@@ -149,11 +163,11 @@ impl WalletOps {
     ) -> Result<CompactWad<Unit>> {
         let seed_phrase_manager =
             wallet::wallet::sqlite::SeedPhraseManager::new(self.db_pool.clone())?;
-        let amount = amount
-            .checked_mul(asset.scale_factor())
+        let amount = asset
+            .checked_scale(amount)
             .ok_or(anyhow!("amount too big"))?;
         let (amount, unit, _) = asset
-            .convert_to_amount_and_unit(amount)
+            .convert_to_amount_and_unit(amount, &UnitPreferences::default())
             .map_err(|e| Error::Other(e.into()))?;
         let proofs_ids = wallet::fetch_inputs_ids_from_db_or_node(
             seed_phrase_manager,
@@ -163,8 +177,7 @@ impl WalletOps {
             amount,
             unit.as_str(),
         )
-        .await?
-        .ok_or(anyhow!("not enough funds"))?;
+        .await?;
 
         let db_conn = self.db_pool.get()?;
         let proofs = wallet::load_tokens_from_db(&db_conn, &proofs_ids)?;
@@ -228,8 +241,8 @@ impl WalletOps {
             )));
         }
 
-        let amount = amount
-            .checked_mul(asset.scale_factor())
+        let amount = asset
+            .checked_scale(amount)
             .ok_or(anyhow!("amount too big"))?;
         let request = serde_json::to_string(&starknet_liquidity_source::MeltPaymentRequest {
             payee: payee_address,
@@ -279,7 +292,7 @@ impl WalletOps {
     }
 
     pub async fn sync_wads(&mut self) -> Result<()> {
-        wallet::sync::pending_wads(self.db_pool.clone(), None).await?;
+        wallet::sync::pending_wads(self.db_pool.clone(), None, None).await?;
 
         Ok(())
     }
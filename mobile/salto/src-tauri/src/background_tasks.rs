@@ -1,104 +1,92 @@
-use itertools::intersperse;
 use std::{
+    collections::HashSet,
     sync::Arc,
     time::{Duration, SystemTime},
 };
 use tauri::Emitter;
 use tokio::sync::RwLock;
 
-use crate::{PriceConfig, PriceSyncStatus};
+use crate::{PriceConfig, PriceSyncStatus, price_provider};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error(transparent)]
-    Reqwest(#[from] reqwest::Error),
+    PriceProvider(#[from] price_provider::Error),
     #[error(transparent)]
     Tauri(#[from] tauri::Error),
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 struct NewPriceResp {
     symbol: String,
     value: f64,
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct PriceProviderResponse {
-    prices: Vec<TokenPrice>,
-}
-
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct TokenPrice {
-    symbol: String,
-    price: Vec<CurrencyValue>,
-}
+/// Failures are recorded in the config status so that the front can tell them apart from "never synced"
+pub async fn fetch_and_emit_prices(
+    app: &tauri::AppHandle,
+    config: &Arc<RwLock<PriceConfig>>,
+) -> Result<(), Error> {
+    let res = inner_fetch_and_emit_prices(app, config).await;
+    record_sync_outcome(config, &res).await;
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct CurrencyValue {
-    currency: String,
-    value: f64,
+    res
 }
 
-fn pick_value(tokens: &[CurrencyValue], wanted: &str) -> Option<f64> {
-    tokens
-        .iter()
-        .find(|t| t.currency.eq_ignore_ascii_case(wanted))
-        .map(|t| t.value)
-        .or_else(|| tokens.first().map(|t| t.value))
+async fn record_sync_outcome<T>(config: &RwLock<PriceConfig>, res: &Result<T, Error>) {
+    config.write().await.status = match res {
+        Ok(_) => PriceSyncStatus::Synced(SystemTime::now()),
+        Err(err) => PriceSyncStatus::Failed {
+            at: SystemTime::now(),
+            error: err.to_string(),
+        },
+    };
 }
 
-pub async fn fetch_and_emit_prices(
+async fn inner_fetch_and_emit_prices(
     app: &tauri::AppHandle,
     config: &Arc<RwLock<PriceConfig>>,
 ) -> Result<(), Error> {
-    let url = {
-        let cfg = config.read().await;
-        let mut url = format!("{}/prices?currencies={}&assets=", cfg.url, cfg.currency);
-        url.extend(intersperse(cfg.assets.iter().map(|a| a.as_str()), ","));
-        url
-    };
-    let resp: PriceProviderResponse = reqwest::get(url).await?.error_for_status()?.json().await?;
-
-    let payload: Vec<NewPriceResp> = {
-        let currency = &config.read().await.currency;
-        resp.prices
-            .into_iter()
-            .filter_map(|p| {
-                pick_value(&p.price, currency).map(|v| NewPriceResp {
-                    symbol: p.symbol,
-                    value: v,
-                })
-            })
-            .collect()
-    };
-
+    let payload = fetch_prices(config).await?;
     app.emit("new-price", payload)?;
-    config.write().await.status = PriceSyncStatus::Synced(SystemTime::now());
 
     Ok(())
 }
 
+/// Ask the configured provider for the prices of the configured assets
+async fn fetch_prices(config: &Arc<RwLock<PriceConfig>>) -> Result<Vec<NewPriceResp>, Error> {
+    let (provider, assets, currency) = {
+        let cfg = config.read().await;
+        (
+            cfg.provider.clone(),
+            cfg.assets
+                .iter()
+                .map(|a| a.as_str().to_string())
+                .collect::<HashSet<_>>(),
+            cfg.currency.clone(),
+        )
+    };
+    let prices = provider.fetch_prices(&assets, &currency).await?;
+
+    Ok(prices
+        .into_iter()
+        .map(|(symbol, value)| NewPriceResp { symbol, value })
+        .collect())
+}
+
 // TODO: pause price fetching when app is not used (background/not-focused)
 pub async fn start_price_fetcher(config: Arc<RwLock<PriceConfig>>, app: tauri::AppHandle) {
     let mut retry_delay = 1;
     loop {
+        let previous_status = config.read().await.status.clone();
         let res = fetch_and_emit_prices(&app, &config).await;
         if let Err(err) = res {
             tracing::error!("price fetch error: {}", err);
-            match config.read().await.status {
-                crate::PriceSyncStatus::Synced(last_sync_time)
-                    if SystemTime::now()
-                        .duration_since(last_sync_time)
-                        .unwrap()
-                        .as_secs()
-                        > 60 =>
-                {
-                    if let Err(e) = app.emit("out-of-sync-price", ()) {
-                        tracing::error!("failed to signal price out of sync: {e}");
-                    }
+            if previous_status.is_out_of_sync(SystemTime::now()) {
+                if let Err(e) = app.emit("out-of-sync-price", ()) {
+                    tracing::error!("failed to signal price out of sync: {e}");
                 }
-                _ => {}
-            };
+            }
 
             tokio::time::sleep(Duration::from_secs(retry_delay)).await;
             retry_delay = std::cmp::min(60, retry_delay * 2);
@@ -108,3 +96,134 @@ pub async fn start_price_fetcher(config: Arc<RwLock<PriceConfig>>, app: tauri::A
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{
+            Mutex,
+            atomic::{AtomicBool, Ordering},
+        },
+    };
+
+    use starknet_types::Asset;
+
+    use super::*;
+    use crate::price_provider::PriceProvider;
+
+    /// Returns fixed prices, or an error while `failing` is set
+    #[derive(Debug, Default)]
+    struct MockPriceProvider {
+        failing: AtomicBool,
+        requests: Mutex<Vec<(HashSet<String>, String)>>,
+    }
+
+    #[async_trait::async_trait]
+    impl PriceProvider for MockPriceProvider {
+        async fn fetch_prices(
+            &self,
+            assets: &HashSet<String>,
+            currency: &str,
+        ) -> Result<HashMap<String, f64>, price_provider::Error> {
+            self.requests
+                .lock()
+                .unwrap()
+                .push((assets.clone(), currency.to_string()));
+            if self.failing.load(Ordering::SeqCst) {
+                // Building a request to an invalid url is the simplest way to get a reqwest error
+                return Err(reqwest::Client::new()
+                    .get("not a url")
+                    .build()
+                    .unwrap_err()
+                    .into());
+            }
+
+            Ok(HashMap::from([
+                (Asset::Strk.as_str().to_string(), 0.12),
+                (Asset::Eth.as_str().to_string(), 2500.0),
+            ]))
+        }
+
+        async fn fetch_currencies(&self) -> Result<Vec<String>, price_provider::Error> {
+            Ok(vec!["usd".to_string()])
+        }
+    }
+
+    fn price_config(provider: Arc<MockPriceProvider>) -> Arc<RwLock<PriceConfig>> {
+        Arc::new(RwLock::new(PriceConfig {
+            currency: "usd".to_string(),
+            assets: HashSet::from([Asset::Strk, Asset::Eth]),
+            provider,
+            status: PriceSyncStatus::NotSynced,
+        }))
+    }
+
+    #[tokio::test]
+    async fn provider_prices_are_converted_for_the_front() {
+        let provider = Arc::new(MockPriceProvider::default());
+        let config = price_config(provider.clone());
+
+        let mut payload = fetch_prices(&config).await.unwrap();
+        payload.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+
+        assert_eq!(
+            payload,
+            vec![
+                NewPriceResp {
+                    symbol: Asset::Eth.as_str().to_string(),
+                    value: 2500.0,
+                },
+                NewPriceResp {
+                    symbol: Asset::Strk.as_str().to_string(),
+                    value: 0.12,
+                },
+            ]
+        );
+        assert_eq!(
+            *provider.requests.lock().unwrap(),
+            vec![(
+                HashSet::from([
+                    Asset::Strk.as_str().to_string(),
+                    Asset::Eth.as_str().to_string(),
+                ]),
+                "usd".to_string(),
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn provider_error_is_returned() {
+        let provider = Arc::new(MockPriceProvider::default());
+        provider.failing.store(true, Ordering::SeqCst);
+        let config = price_config(provider);
+
+        assert!(matches!(
+            fetch_prices(&config).await,
+            Err(Error::PriceProvider(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn failed_sync_is_recorded_until_the_provider_recovers() {
+        let provider = Arc::new(MockPriceProvider::default());
+        provider.failing.store(true, Ordering::SeqCst);
+        let config = price_config(provider.clone());
+
+        let res = fetch_prices(&config).await;
+        record_sync_outcome(&config, &res).await;
+        let status = config.read().await.status.clone();
+        assert!(
+            matches!(&status, PriceSyncStatus::Failed { error, .. } if !error.is_empty()),
+            "{status:?}"
+        );
+        assert!(status.is_out_of_sync(SystemTime::now()));
+
+        provider.failing.store(false, Ordering::SeqCst);
+        let res = fetch_prices(&config).await;
+        record_sync_outcome(&config, &res).await;
+        let status = config.read().await.status.clone();
+        assert!(matches!(status, PriceSyncStatus::Synced(_)), "{status:?}");
+        assert!(!status.is_out_of_sync(SystemTime::now()));
+    }
+}
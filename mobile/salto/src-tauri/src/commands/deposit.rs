@@ -131,7 +131,7 @@ pub async fn redeem_quote(
     }
 
     wallet::mint::redeem_quote(
-        crate::SEED_PHRASE_MANAGER,
+        &*crate::SEED_PHRASE_MANAGER,
         state.pool.clone(),
         &mut node_client,
         STARKNET_STR.to_string(),
@@ -139,6 +139,7 @@ pub async fn redeem_quote(
         node_id,
         &mint_quote.unit,
         mint_quote.amount,
+        None,
     )
     .await?;
 
@@ -7,9 +7,9 @@ mod wallet;
 
 pub use deposit::{create_mint_quote, redeem_quote};
 pub use get_nodes_balance::get_nodes_balance;
-pub use node::{add_node, refresh_node_keysets};
-pub use prices_provider::{get_currencies, set_price_provider_currency};
-pub use wad::{create_wads, get_wad_history, receive_wads, sync_wads};
+pub use node::{add_node, refresh_node_keysets, remove_node};
+pub use prices_provider::{get_currencies, get_price_sync_status, set_price_provider_currency};
+pub use wad::{create_wads, estimate_send, get_wad_history, receive_wads, sync_wads};
 
 pub use wallet::{check_wallet_exists, init_wallet, restore_wallet};
 
@@ -1,8 +1,6 @@
 use std::str::FromStr;
 
-use nuts::traits::Unit as UnitT;
-use starknet_types::Asset;
-use tauri::State;
+use tauri::{Emitter, State};
 use wallet::{db::balance::Balance, types::NodeUrl};
 
 use crate::AppState;
@@ -38,8 +36,18 @@ impl serde::Serialize for AddNodeError {
     }
 }
 
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RestoreProgressEvent {
+    node_id: u32,
+    keyset_id: String,
+    batch_index: u32,
+    recovered_count: usize,
+}
+
 #[tauri::command]
 pub async fn add_node(
+    app: tauri::AppHandle,
     state: State<'_, AppState>,
     node_url: String,
 ) -> Result<(u32, Vec<Balance>), AddNodeError> {
@@ -50,16 +58,38 @@ pub async fn add_node(
     let wallet = wallet::db::wallet::get(&*state.pool.get()?)?.unwrap();
 
     if wallet.is_restored {
-        wallet::node::restore(crate::SEED_PHRASE_MANAGER, state.pool.clone(), id, client).await?;
+        let (progress_sender, mut progress_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let forward_progress = tauri::async_runtime::spawn(async move {
+            while let Some(progress) = progress_receiver.recv().await {
+                let event = RestoreProgressEvent {
+                    node_id: id,
+                    keyset_id: progress.keyset_id.to_string(),
+                    batch_index: progress.batch_index,
+                    recovered_count: progress.recovered_count,
+                };
+                if let Err(e) = app.emit("restore-progress", event) {
+                    tracing::error!("failed to emit restore progress: {e}");
+                }
+            }
+        });
+        let res = wallet::node::restore_with_progress(
+            &*crate::SEED_PHRASE_MANAGER,
+            state.pool.clone(),
+            id,
+            client,
+            Some(progress_sender),
+        )
+        .await;
+        // The sender was dropped at the end of `restore_with_progress`, which ends the forwarding loop
+        let _ = forward_progress.await;
+        res?;
     }
 
     let balances = wallet::db::balance::get_for_node(&*state.pool.get()?, id)?;
     let new_assets = balances
         .clone()
         .into_iter()
-        .map(|b| -> Result<Asset, _> {
-            starknet_types::Unit::from_str(&b.unit).map(|u| u.matching_asset())
-        })
+        .map(|b| starknet_types::unit_str_to_asset(&b.unit))
         .collect::<Result<Vec<_>, _>>()?;
     state
         .get_prices_config
@@ -111,3 +141,28 @@ pub async fn refresh_node_keysets(
 
     Ok(())
 }
+
+#[derive(Debug, thiserror::Error)]
+pub enum RemoveNodeError {
+    #[error(transparent)]
+    R2D2(#[from] r2d2::Error),
+    #[error("failed to remove node: {0}")]
+    DeleteNode(#[from] wallet::db::node::DeleteNodeError),
+}
+
+impl serde::Serialize for RemoveNodeError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.to_string().as_ref())
+    }
+}
+
+#[tauri::command]
+pub async fn remove_node(state: State<'_, AppState>, node_id: u32) -> Result<(), RemoveNodeError> {
+    let mut db_conn = state.pool.get()?;
+    wallet::db::node::delete(&mut db_conn, node_id)?;
+
+    Ok(())
+}
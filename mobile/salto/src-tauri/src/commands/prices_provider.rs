@@ -1,13 +1,10 @@
-use crate::AppState;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use crate::background_tasks::fetch_and_emit_prices;
+use crate::price_provider;
+use crate::{AppState, PriceSyncStatus};
 use tauri::Emitter;
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct CurrenciesResponce {
-    currencies: Vec<String>,
-}
-
 #[derive(Debug, thiserror::Error)]
 #[repr(transparent)]
 #[error(transparent)]
@@ -47,7 +44,7 @@ pub async fn set_price_provider_currency(
 #[derive(Debug, thiserror::Error)]
 pub enum GetCurrenciesError {
     #[error(transparent)]
-    Reqwest(#[from] reqwest::Error),
+    PriceProvider(#[from] price_provider::Error),
 }
 
 impl serde::Serialize for GetCurrenciesError {
@@ -63,11 +60,46 @@ impl serde::Serialize for GetCurrenciesError {
 pub async fn get_currencies(
     state: tauri::State<'_, AppState>,
 ) -> Result<Vec<String>, GetCurrenciesError> {
-    let cfg = state.get_prices_config.read().await;
-    let host = cfg.url.clone();
-    let resp: CurrenciesResponce = reqwest::get(host + "/currencies")
-        .await?
-        .json::<CurrenciesResponce>()
-        .await?;
-    Ok(resp.currencies)
+    let provider = state.get_prices_config.read().await.provider.clone();
+    let currencies = provider.fetch_currencies().await?;
+
+    Ok(currencies)
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum PriceSyncStatusResponse {
+    NotSynced,
+    /// `at` is a unix timestamp in milliseconds
+    Synced {
+        at: u64,
+    },
+    Failed {
+        at: u64,
+        error: String,
+    },
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[tauri::command]
+pub async fn get_price_sync_status(
+    state: tauri::State<'_, AppState>,
+) -> Result<PriceSyncStatusResponse, ()> {
+    let status = match &state.get_prices_config.read().await.status {
+        PriceSyncStatus::NotSynced => PriceSyncStatusResponse::NotSynced,
+        PriceSyncStatus::Synced(at) => PriceSyncStatusResponse::Synced {
+            at: unix_millis(*at),
+        },
+        PriceSyncStatus::Failed { at, error } => PriceSyncStatusResponse::Failed {
+            at: unix_millis(*at),
+            error: error.clone(),
+        },
+    };
+
+    Ok(status)
 }
@@ -3,7 +3,7 @@ use std::{cmp::Ordering, str::FromStr};
 use nuts::Amount;
 use starknet_types::{Asset, AssetFromStrError, AssetToUnitConversionError};
 use tauri::{AppHandle, Emitter, State};
-use wallet::types::compact_wad::CompactWads;
+use wallet::types::NodeUrl;
 
 use crate::{AppState, commands::BalanceChange};
 use parse_asset_amount::{ParseAmountStringError, parse_asset_amount};
@@ -43,6 +43,85 @@ impl serde::Serialize for CreateWadsError {
     }
 }
 
+/// Split `amount` across nodes, taking as much as possible from the first one
+fn plan_amount_per_node(
+    db_conn: &rusqlite::Connection,
+    unit: starknet_types::Unit,
+    amount: Amount,
+) -> Result<Vec<(u32, NodeUrl, Amount)>, CreateWadsError> {
+    let balances = wallet::db::balance::get_for_all_nodes_by_unit(db_conn, unit)?;
+
+    let mut used_node = vec![];
+    let mut rem_amount = amount;
+    for balance in balances {
+        match rem_amount.cmp(&balance.amount) {
+            Ordering::Less | Ordering::Equal => {
+                used_node.push((balance.id, balance.url, rem_amount));
+                rem_amount = Amount::ZERO;
+                break;
+            }
+            Ordering::Greater => {
+                rem_amount -= balance.amount;
+                used_node.push((balance.id, balance.url, balance.amount));
+            }
+        }
+    }
+
+    if rem_amount != Amount::ZERO {
+        return Err(CreateWadsError::NotEnoughFunds(amount, rem_amount));
+    }
+
+    Ok(used_node)
+}
+
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SendEstimate {
+    n_inputs: usize,
+    input_fee: u64,
+    needs_swap: bool,
+}
+
+/// Dry-run of `create_wads`, no proof is reserved
+#[tauri::command]
+pub async fn estimate_send(
+    state: State<'_, AppState>,
+    amount: String,
+    asset: String,
+) -> Result<SendEstimate, CreateWadsError> {
+    let asset = Asset::from_str(&asset)?;
+    let unit = asset.find_best_unit();
+    let amount = parse_asset_amount(&amount, asset, unit)?;
+
+    let db_conn = state.pool.get()?;
+    let mut total = SendEstimate {
+        n_inputs: 0,
+        input_fee: 0,
+        needs_swap: false,
+    };
+    for (node_id, _, amount_to_use) in plan_amount_per_node(&db_conn, unit, amount)? {
+        // The proofs sent are taken, or swapped into, the node's active keyset
+        let (active_keyset_id, _) =
+            wallet::get_active_keyset_for_unit(&db_conn, node_id, unit.as_str())?;
+        let input_fee_ppk = wallet::db::keyset::get_input_fee_ppk(&db_conn, active_keyset_id)?
+            .ok_or(wallet::errors::Error::NoMatchingKeyset)?;
+        let estimate = wallet::send::estimate_send(
+            &db_conn,
+            node_id,
+            amount_to_use,
+            unit.as_str(),
+            input_fee_ppk,
+        )?
+        .ok_or(CreateWadsError::NotEnoughFundsInNode(node_id))?;
+
+        total.n_inputs += estimate.n_inputs;
+        total.input_fee += u64::from(estimate.input_fee);
+        total.needs_swap |= estimate.needs_swap;
+    }
+
+    Ok(total)
+}
+
 #[tauri::command]
 pub async fn create_wads(
     app: AppHandle,
@@ -56,61 +135,35 @@ pub async fn create_wads(
 
     let amount_to_use_per_node = {
         let db_conn = state.pool.get()?;
-        let balances = wallet::db::balance::get_for_all_nodes_by_unit(&db_conn, unit)?;
-
-        let mut used_node = vec![];
-        let mut rem_amount = amount;
-        for balance in balances {
-            match rem_amount.cmp(&balance.amount) {
-                Ordering::Less | Ordering::Equal => {
-                    used_node.push((balance.id, balance.url, rem_amount));
-                    rem_amount = Amount::ZERO;
-                    break;
-                }
-                Ordering::Greater => {
-                    rem_amount -= balance.amount;
-                    used_node.push((balance.id, balance.url, balance.amount));
-                }
-            }
-        }
-
-        if rem_amount != Amount::ZERO {
-            return Err(CreateWadsError::NotEnoughFunds(amount, rem_amount));
-        }
-
-        used_node
+        plan_amount_per_node(&db_conn, unit, amount)?
     };
 
-    let mut wads = Vec::with_capacity(amount_to_use_per_node.len());
+    let mut proofs_ids_per_node = Vec::with_capacity(amount_to_use_per_node.len());
     let mut balance_decrease_events = Vec::with_capacity(amount_to_use_per_node.len());
-    let mut ys_per_node = Vec::with_capacity(amount_to_use_per_node.len());
     for (node_id, node_url, amount_to_use) in amount_to_use_per_node {
         let mut node_client = wallet::connect_to_node(&node_url, state.opt_root_ca_cert()).await?;
 
         let proofs_ids = wallet::fetch_inputs_ids_from_db_or_node(
-            crate::SEED_PHRASE_MANAGER,
+            &*crate::SEED_PHRASE_MANAGER,
             state.pool.clone(),
             &mut node_client,
             node_id,
             amount_to_use,
             unit.as_str(),
         )
-        .await?
-        .ok_or(CreateWadsError::NotEnoughFundsInNode(node_id))?;
+        .await?;
 
-        let db_conn = state.pool.get()?;
-        let proofs = wallet::load_tokens_from_db(&db_conn, &proofs_ids)?;
-        let wad = wallet::wad::create_from_parts(node_url, unit, None, proofs);
-        wads.push(wad);
-        ys_per_node.push(proofs_ids);
+        proofs_ids_per_node.push((node_url, proofs_ids));
         balance_decrease_events.push(BalanceChange {
             node_id,
             unit: unit.as_str().to_string(),
             amount: amount_to_use.into(),
         });
     }
-    let db_conn = state.pool.get()?;
-    for (wad, ys) in wads.iter().zip(ys_per_node) {
+
+    let mut db_conn = state.pool.get()?;
+    let wads = wallet::send::create_wads_atomic(&mut db_conn, unit, &proofs_ids_per_node, None)?;
+    for (wad, (_, ys)) in wads.0.iter().zip(proofs_ids_per_node) {
         wallet::db::wad::register_wad(
             &db_conn,
             wallet::db::wad::WadType::OUT,
@@ -123,5 +176,5 @@ pub async fn create_wads(
         app.emit("balance-decrease", event)?;
     }
 
-    Ok(CompactWads(wads).to_string())
+    Ok(wads.to_string())
 }
@@ -98,9 +98,17 @@ impl serde::Serialize for SyncWadsError {
 }
 
 #[tauri::command]
-pub async fn sync_wads(app: AppHandle, state: State<'_, AppState>) -> Result<(), SyncWadsError> {
-    let wad_results =
-        wallet::sync::pending_wads(state.pool.clone(), state.opt_root_ca_cert()).await?;
+pub async fn sync_wads(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    node_ids: Option<Vec<u32>>,
+) -> Result<(), SyncWadsError> {
+    let wad_results = wallet::sync::pending_wads(
+        state.pool.clone(),
+        state.opt_root_ca_cert(),
+        node_ids.as_deref(),
+    )
+    .await?;
 
     for result in wad_results {
         match result.result {
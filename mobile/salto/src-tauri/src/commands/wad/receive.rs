@@ -61,10 +61,10 @@ pub async fn receive_wads(
         } = wad;
         let mut node_client = wallet::connect_to_node(&node_url, state.opt_root_ca_cert()).await?;
         let node_id =
-            wallet::node::register(state.pool.clone(), &mut node_client, &node_url).await?;
+            wallet::node::get_or_register(state.pool.clone(), &mut node_client, &node_url).await?;
 
         let amount_received = wallet::receive_wad(
-            crate::SEED_PHRASE_MANAGER,
+            &*crate::SEED_PHRASE_MANAGER,
             state.pool.clone(),
             &mut node_client,
             node_id,
@@ -69,11 +69,18 @@ pub struct InitWalletResponse {
 }
 
 #[tauri::command]
-pub fn init_wallet(state: State<'_, AppState>) -> Result<InitWalletResponse, InitWalletError> {
+pub fn init_wallet(
+    state: State<'_, AppState>,
+    word_count: Option<usize>,
+) -> Result<InitWalletResponse, InitWalletError> {
     let db_conn = state.pool.get()?;
 
-    let seed_phrase = seed_phrase::create_random()?;
-    wallet::wallet::init(crate::SEED_PHRASE_MANAGER, &db_conn, &seed_phrase)?;
+    let word_count = match word_count {
+        Some(n) => seed_phrase::WordCount::try_from(n)?,
+        None => seed_phrase::WordCount::default(),
+    };
+    let seed_phrase = seed_phrase::create_random_with_word_count(word_count)?;
+    wallet::wallet::init(&*crate::SEED_PHRASE_MANAGER, &db_conn, &seed_phrase)?;
 
     Ok(InitWalletResponse {
         seed_phrase: seed_phrase.to_string(),
@@ -88,7 +95,7 @@ pub fn restore_wallet(
     let db_conn = state.pool.get()?;
 
     let seed_phrase = seed_phrase::create_from_str(&seed_phrase)?;
-    wallet::wallet::restore(crate::SEED_PHRASE_MANAGER, &db_conn, seed_phrase)?;
+    wallet::wallet::restore(&*crate::SEED_PHRASE_MANAGER, &db_conn, seed_phrase)?;
 
     Ok(())
 }
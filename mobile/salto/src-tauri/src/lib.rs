@@ -2,26 +2,41 @@ mod background_tasks;
 mod commands;
 mod errors;
 mod migrations;
+mod price_provider;
 
 use commands::{
-    add_node, check_wallet_exists, create_mint_quote, create_wads, get_currencies,
-    get_nodes_balance, get_wad_history, init_wallet, receive_wads, redeem_quote,
-    refresh_node_keysets, restore_wallet, set_price_provider_currency, sync_wads,
+    add_node, check_wallet_exists, create_mint_quote, create_wads, estimate_send, get_currencies,
+    get_nodes_balance, get_price_sync_status, get_wad_history, init_wallet, receive_wads,
+    redeem_quote, refresh_node_keysets, remove_node, restore_wallet, set_price_provider_currency,
+    sync_wads,
 };
-use nuts::traits::Unit as UnitT;
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
 use starknet_types::Asset;
-use std::{collections::HashSet, env, str::FromStr, sync::Arc, time::SystemTime};
-use tauri::{Listener, Manager, async_runtime};
+use std::{
+    collections::HashSet,
+    env,
+    sync::{Arc, LazyLock},
+    time::SystemTime,
+};
+use tauri::{Listener, Manager, RunEvent, WindowEvent, async_runtime};
 use tokio::sync::RwLock;
 use tonic::transport::Certificate;
+use wallet::wallet::{keyring, lockable};
 
-use crate::background_tasks::start_price_fetcher;
+use crate::{
+    background_tasks::start_price_fetcher,
+    price_provider::{HttpPriceProvider, PriceProvider},
+};
 
-// Value must be the same as the one configurated in tauri.conf.json["identifier"]
-const SEED_PHRASE_MANAGER: wallet::wallet::keyring::SeedPhraseManager =
-    wallet::wallet::keyring::SeedPhraseManager::new("com.salto.app");
+/// The seed phrase is persisted in the keyring
+///
+/// It is only kept in memory while the app is in the foreground.
+static SEED_PHRASE_MANAGER: LazyLock<lockable::SeedPhraseManager<keyring::SeedPhraseManager>> =
+    LazyLock::new(|| {
+        // Value must be the same as the one configurated in tauri.conf.json["identifier"]
+        lockable::SeedPhraseManager::new(keyring::SeedPhraseManager::new("com.salto.app"))
+    });
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -48,6 +63,9 @@ pub fn run() {
                         dp
                     })
                     .expect("dirs::data_dir should map to a valid path on this machine");
+                if let Err(e) = SEED_PHRASE_MANAGER.unlock() {
+                    log::error!("failed to unlock the wallet: {e}");
+                }
                 let manager = SqliteConnectionManager::file(db_path);
                 let pool = r2d2::Pool::new(manager)?;
                 let host = env!("PRICE_PROVIDER_URL");
@@ -56,8 +74,7 @@ pub fn run() {
                     if let Ok(nodes_balances) = wallet::db::balance::get_for_all_nodes(&conn) {
                         for nb in nodes_balances {
                             for b in nb.balances {
-                                let unit = starknet_types::Unit::from_str(&b.unit)?;
-                                initial_assets.insert(unit.matching_asset());
+                                initial_assets.insert(starknet_types::unit_str_to_asset(&b.unit)?);
                             }
                         }
                     }
@@ -67,7 +84,7 @@ pub fn run() {
                     get_prices_config: Arc::new(RwLock::new(PriceConfig {
                         currency: "usd".to_string(),
                         assets: initial_assets,
-                        url: host.to_string(),
+                        provider: Arc::new(HttpPriceProvider::new(host.to_string())),
                         status: Default::default(),
                     })),
                     #[cfg(feature = "tls-local-mkcert")]
@@ -92,25 +109,48 @@ pub fn run() {
                 get_nodes_balance,
                 add_node,
                 refresh_node_keysets,
+                remove_node,
                 create_mint_quote,
                 redeem_quote,
                 create_wads,
+                estimate_send,
                 receive_wads,
                 get_currencies,
                 check_wallet_exists,
                 init_wallet,
                 restore_wallet,
                 set_price_provider_currency,
+                get_price_sync_status,
                 get_wad_history,
                 sync_wads,
             ])
     };
 
-    if let Err(e) = app.run(tauri::generate_context!()) {
-        // Use grep "tauri-app-run-error" to filter the startup error in logs
-        log::error!("tauri-app-run-error: {e}");
-        panic!("error while running tauri application: {e}");
-    }
+    let app = match app.build(tauri::generate_context!()) {
+        Ok(app) => app,
+        Err(e) => {
+            // Use grep "tauri-app-run-error" to filter the startup error in logs
+            log::error!("tauri-app-run-error: {e}");
+            panic!("error while running tauri application: {e}");
+        }
+    };
+
+    app.run(|_, event| {
+        // The window loses focus when the app goes to the background
+        if let RunEvent::WindowEvent {
+            event: WindowEvent::Focused(focused),
+            ..
+        } = event
+        {
+            if focused {
+                if let Err(e) = SEED_PHRASE_MANAGER.unlock() {
+                    log::error!("failed to unlock the wallet: {e}");
+                }
+            } else {
+                SEED_PHRASE_MANAGER.lock();
+            }
+        }
+    });
 }
 
 #[derive(Debug)]
@@ -125,7 +165,7 @@ struct AppState {
 pub struct PriceConfig {
     pub currency: String,
     pub assets: HashSet<Asset>,
-    pub url: String,
+    pub provider: Arc<dyn PriceProvider>,
     pub status: PriceSyncStatus,
 }
 
@@ -134,6 +174,26 @@ pub enum PriceSyncStatus {
     #[default]
     NotSynced,
     Synced(SystemTime),
+    Failed {
+        at: SystemTime,
+        error: String,
+    },
+}
+
+impl PriceSyncStatus {
+    /// Whether the prices the front currently displays should no longer be trusted
+    ///
+    /// A single failure right after a successful sync is tolerated,
+    /// repeated ones mean the provider is down.
+    pub fn is_out_of_sync(&self, now: SystemTime) -> bool {
+        match self {
+            PriceSyncStatus::NotSynced => false,
+            PriceSyncStatus::Synced(last_sync_time) => now
+                .duration_since(*last_sync_time)
+                .is_ok_and(|elapsed| elapsed.as_secs() > 60),
+            PriceSyncStatus::Failed { .. } => true,
+        }
+    }
 }
 
 impl AppState {
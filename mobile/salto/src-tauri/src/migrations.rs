@@ -1,7 +1,11 @@
 use tauri_plugin_sql::{Migration, MigrationKind};
+use wallet::db::migrations::MIGRATIONS;
+
+/// Version of the first of the wallet `MIGRATIONS`, following the table creations
+const FIRST_WALLET_MIGRATION_VERSION: i64 = 10;
 
 pub fn migrations() -> Vec<Migration> {
-    vec![
+    let table_creations = vec![
         Migration {
             version: 1,
             description: "create_table_node",
@@ -56,5 +60,21 @@ pub fn migrations() -> Vec<Migration> {
             sql: wallet::db::wad::CREATE_TABLE_WAD_PROOF,
             kind: MigrationKind::Up,
         },
-    ]
+    ];
+
+    // Generated, so that a migration added to the wallet is never forgotten here
+    let wallet_migrations = MIGRATIONS
+        .iter()
+        .enumerate()
+        .map(|(i, migration)| Migration {
+            version: FIRST_WALLET_MIGRATION_VERSION + i as i64,
+            description: migration.description,
+            sql: migration.sql,
+            kind: MigrationKind::Up,
+        });
+
+    table_creations
+        .into_iter()
+        .chain(wallet_migrations)
+        .collect()
 }
@@ -0,0 +1,99 @@
+use std::collections::{HashMap, HashSet};
+
+use itertools::intersperse;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Reqwest(#[from] reqwest::Error),
+}
+
+/// Source of the assets fiat prices displayed by the app
+///
+/// Kept behind a trait so that the price API can be swapped without touching the background fetcher.
+#[async_trait::async_trait]
+pub trait PriceProvider: std::fmt::Debug + Send + Sync {
+    /// Returns the price of each asset in `currency`, keyed by asset symbol
+    ///
+    /// Assets the provider doesn't know about are omitted from the result.
+    async fn fetch_prices(
+        &self,
+        assets: &HashSet<String>,
+        currency: &str,
+    ) -> Result<HashMap<String, f64>, Error>;
+
+    async fn fetch_currencies(&self) -> Result<Vec<String>, Error>;
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct PriceProviderResponse {
+    prices: Vec<TokenPrice>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct TokenPrice {
+    symbol: String,
+    price: Vec<CurrencyValue>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct CurrencyValue {
+    currency: String,
+    value: f64,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CurrenciesResponce {
+    currencies: Vec<String>,
+}
+
+fn pick_value(tokens: &[CurrencyValue], wanted: &str) -> Option<f64> {
+    tokens
+        .iter()
+        .find(|t| t.currency.eq_ignore_ascii_case(wanted))
+        .map(|t| t.value)
+        .or_else(|| tokens.first().map(|t| t.value))
+}
+
+/// Client of our own price provider service
+#[derive(Debug, Clone)]
+pub struct HttpPriceProvider {
+    url: String,
+}
+
+impl HttpPriceProvider {
+    pub fn new(url: String) -> Self {
+        Self { url }
+    }
+}
+
+#[async_trait::async_trait]
+impl PriceProvider for HttpPriceProvider {
+    async fn fetch_prices(
+        &self,
+        assets: &HashSet<String>,
+        currency: &str,
+    ) -> Result<HashMap<String, f64>, Error> {
+        let mut url = format!("{}/prices?currencies={}&assets=", self.url, currency);
+        url.extend(intersperse(assets.iter().map(|a| a.as_str()), ","));
+
+        let resp: PriceProviderResponse =
+            reqwest::get(url).await?.error_for_status()?.json().await?;
+
+        Ok(resp
+            .prices
+            .into_iter()
+            .filter_map(|p| pick_value(&p.price, currency).map(|v| (p.symbol, v)))
+            .collect())
+    }
+
+    async fn fetch_currencies(&self) -> Result<Vec<String>, Error> {
+        let resp: CurrenciesResponce = reqwest::get(format!("{}/currencies", self.url))
+            .await?
+            .json()
+            .await?;
+
+        Ok(resp.currencies)
+    }
+}
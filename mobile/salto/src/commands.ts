@@ -22,6 +22,18 @@ export async function getNodesBalance() {
       .catch((error) => console.error(error));
   }
 
+export type PriceSyncStatus =
+  | { status: "notSynced" }
+  | { status: "synced"; at: number }
+  | { status: "failed"; at: number; error: string };
+
+export async function getPriceSyncStatus() {
+  let res = await invoke("get_price_sync_status")
+    .then((message) => message as PriceSyncStatus)
+    .catch((error) => console.error(error));
+  return res;
+}
+
 export async function getTokensPrices() {
   let res = await invoke("get_tokens_prices")
     .then((message) => message as {})
@@ -39,6 +51,17 @@ export async function addNode(nodeUrl: string) {
       return res;
 }
 
+export async function removeNode(nodeId: NodeId) {
+  const res = await invoke("remove_node", { nodeId })
+    .then(() => true)
+    .catch((error) => {
+      console.error(`failed to remove node with id '${nodeId}':`, error);
+      return false;
+    });
+
+  return res;
+}
+
 export type CreateMintQuoteResponse = {
   quoteId: QuoteId,
   paymentRequest: string,
@@ -74,6 +97,19 @@ export async function create_wads(amount: string, asset: string) {
   
 } 
 
+export type SendEstimate = {
+  nInputs: number;
+  inputFee: number;
+  needsSwap: boolean;
+};
+
+export async function estimateSend(amount: string, asset: string) {
+  const res = await invoke("estimate_send", { amount, asset })
+    .then((message) => message as SendEstimate)
+    .catch((error) => console.error(`failed to estimate send:`, error));
+  return res;
+}
+
 export async function receive_wads(wads: string) {
       const res = await invoke("receive_wads", {wads})
       .catch((error) => {
@@ -128,8 +164,8 @@ export async function get_wad_history(limit?: number): Promise<WadHistoryItem[]
       return res;
 } 
 
-export async function sync_wads(): Promise<void> {
-      await invoke("sync_wads")
+export async function sync_wads(nodeIds?: NodeId[]): Promise<void> {
+      await invoke("sync_wads", { nodeIds })
       .catch((error) => {
         console.error("failed to sync wads:", error);
       });